@@ -0,0 +1,30 @@
+-- Disable the enforcement of foreign-keys constraints
+PRAGMA foreign_keys = off;
+-- Create "new_playlist_tracks" table
+CREATE TABLE `new_playlist_tracks` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `playlist_id` integer NOT NULL,
+  `track_id` integer NOT NULL,
+  `position` integer NOT NULL,
+  `created_at` timestamp_text NOT NULL,
+  `updated_at` timestamp_text NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Copy rows from old table "playlist_tracks" to new temporary table "new_playlist_tracks",
+-- numbering the existing entries of each playlist in the order they were added
+INSERT INTO `new_playlist_tracks` (`playlist_id`, `track_id`, `position`, `created_at`, `updated_at`)
+SELECT `playlist_id`, `track_id`, ROW_NUMBER() OVER (PARTITION BY `playlist_id` ORDER BY `created_at`, `track_id`) - 1, `created_at`, `updated_at`
+FROM `playlist_tracks`;
+-- Drop "playlist_tracks" table after copying rows
+DROP TABLE `playlist_tracks`;
+-- Rename temporary table "new_playlist_tracks" to "playlist_tracks"
+ALTER TABLE `new_playlist_tracks` RENAME TO `playlist_tracks`;
+-- Create index "idx_playlist_tracks_playlist_id_position" to table: "playlist_tracks"
+CREATE INDEX `idx_playlist_tracks_playlist_id_position` ON `playlist_tracks` (`playlist_id`, `position`);
+-- Create index "idx_playlist_tracks_track_id" to table: "playlist_tracks"
+CREATE INDEX `idx_playlist_tracks_track_id` ON `playlist_tracks` (`track_id`);
+-- Add column "position" to table: "spotify_track_playlist"
+ALTER TABLE `spotify_track_playlist` ADD COLUMN `position` integer NOT NULL DEFAULT 0;
+-- Enable back the enforcement of foreign-keys constraints
+PRAGMA foreign_keys = on;
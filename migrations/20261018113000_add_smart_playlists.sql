@@ -0,0 +1,11 @@
+-- Create "smart_playlist" table
+CREATE TABLE `smart_playlist` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `playlist_id` integer NOT NULL,
+  `rules` text NOT NULL,
+  `created_at` timestamp_text NOT NULL,
+  `updated_at` timestamp_text NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "smart_playlist_playlist_id" to table: "smart_playlist"
+CREATE UNIQUE INDEX `smart_playlist_playlist_id` ON `smart_playlist` (`playlist_id`);
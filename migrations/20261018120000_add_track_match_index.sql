@@ -0,0 +1,25 @@
+-- Create "track_match_index" table
+CREATE TABLE `track_match_index` (
+  `track_id` integer NOT NULL,
+  `title` varchar NOT NULL,
+  `original_title` varchar NOT NULL,
+  `primary_artist` varchar NOT NULL,
+  `artists` text NOT NULL,
+  `album` varchar NOT NULL,
+  `duration_ms` integer NOT NULL,
+  `version_indicator` varchar NULL,
+  `indexed_at` integer NOT NULL,
+  PRIMARY KEY (`track_id`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "idx_track_match_index_duration_ms" to table: "track_match_index"
+CREATE INDEX `idx_track_match_index_duration_ms` ON `track_match_index` (`duration_ms`);
+-- Create "track_match_key" table
+CREATE TABLE `track_match_key` (
+  `track_id` integer NOT NULL,
+  `key` varchar NOT NULL,
+  PRIMARY KEY (`track_id`, `key`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "idx_track_match_key_key" to table: "track_match_key"
+CREATE INDEX `idx_track_match_key_key` ON `track_match_key` (`key`);
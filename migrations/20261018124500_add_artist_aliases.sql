@@ -0,0 +1,12 @@
+-- Create "artist_alias" table
+CREATE TABLE `artist_alias` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `artist_id` integer NOT NULL,
+  `name` varchar NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`artist_id`) REFERENCES `artist` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "artist_alias_artist_id_name" to table: "artist_alias"
+CREATE UNIQUE INDEX `artist_alias_artist_id_name` ON `artist_alias` (`artist_id`, `name`);
+-- The matcher now uses track artists and aliases, rebuild the index on the next matching run
+DELETE FROM `track_match_key`;
+DELETE FROM `track_match_index`;
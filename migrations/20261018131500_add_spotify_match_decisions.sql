@@ -0,0 +1,20 @@
+-- Create "spotify_match_decision" table
+CREATE TABLE `spotify_match_decision` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `spotify_track_id` varchar NOT NULL,
+  `action` varchar NOT NULL,
+  `actor` varchar NOT NULL,
+  `candidate_id` integer NULL,
+  `previous_candidate_status` varchar NULL,
+  `previous_local_track_id` integer NULL,
+  `local_track_id` integer NULL,
+  `dismissed_candidate_ids` text NOT NULL,
+  `reverts_decision_id` integer NULL,
+  `reverted_at` integer NULL,
+  `created_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`spotify_track_id`) REFERENCES `spotify_track` (`spotify_track_id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_spotify_match_decision_spotify_track" to table: "spotify_match_decision"
+CREATE INDEX `idx_spotify_match_decision_spotify_track` ON `spotify_match_decision` (`spotify_track_id`);
+-- Create index "idx_spotify_match_decision_created_at" to table: "spotify_match_decision"
+CREATE INDEX `idx_spotify_match_decision_created_at` ON `spotify_match_decision` (`created_at`);
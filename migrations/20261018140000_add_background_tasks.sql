@@ -0,0 +1,34 @@
+-- Create "background_task" table
+CREATE TABLE `background_task` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `kind` varchar NOT NULL,
+  `status` varchar NOT NULL,
+  `payload` text NOT NULL,
+  `checkpoint` text NULL,
+  `progress_current` integer NOT NULL DEFAULT 0,
+  `progress_total` integer NULL,
+  `error_message` text NULL,
+  `created_at` integer NOT NULL,
+  `updated_at` integer NOT NULL,
+  `started_at` integer NULL,
+  `finished_at` integer NULL
+);
+-- Create index "idx_background_task_status" to table: "background_task"
+CREATE INDEX `idx_background_task_status` ON `background_task` (`status`);
+-- Create index "idx_background_task_kind" to table: "background_task"
+CREATE INDEX `idx_background_task_kind` ON `background_task` (`kind`);
+-- Keep the history of matcher tasks. They were never resumable, so unfinished ones are cancelled.
+INSERT INTO `background_task` (`kind`, `status`, `payload`, `progress_current`, `progress_total`, `error_message`, `created_at`, `updated_at`, `finished_at`)
+SELECT
+  'spotify_to_local_matcher',
+  CASE `status` WHEN 'completed' THEN 'completed' WHEN 'failed' THEN 'failed' ELSE 'cancelled' END,
+  '{}',
+  `matched_tracks` + `failed_tracks`,
+  `total_tracks`,
+  `error_message`,
+  CASE typeof(`created_at`) WHEN 'integer' THEN `created_at` ELSE CAST(strftime('%s', substr(`created_at`, 1, 19)) AS INTEGER) END,
+  CASE typeof(`updated_at`) WHEN 'integer' THEN `updated_at` ELSE CAST(strftime('%s', substr(`updated_at`, 1, 19)) AS INTEGER) END,
+  CASE typeof(`updated_at`) WHEN 'integer' THEN `updated_at` ELSE CAST(strftime('%s', substr(`updated_at`, 1, 19)) AS INTEGER) END
+FROM `spotify_to_local_matcher_tasks`;
+-- Drop "spotify_to_local_matcher_tasks" table
+DROP TABLE `spotify_to_local_matcher_tasks`;
@@ -0,0 +1,14 @@
+-- Create "spotify_playlist_sync_track" table
+CREATE TABLE `spotify_playlist_sync_track` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `sync_state_id` integer NOT NULL,
+  `spotify_track_id` varchar NOT NULL,
+  `status` varchar NOT NULL DEFAULT 'pending',
+  `local_track_id` integer NULL,
+  `updated_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`local_track_id`) REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE SET NULL,
+  CONSTRAINT `1` FOREIGN KEY (`spotify_track_id`) REFERENCES `spotify_track` (`spotify_track_id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `2` FOREIGN KEY (`sync_state_id`) REFERENCES `spotify_playlist_sync_state` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_spotify_playlist_sync_track_unique" to table: "spotify_playlist_sync_track"
+CREATE UNIQUE INDEX `idx_spotify_playlist_sync_track_unique` ON `spotify_playlist_sync_track` (`sync_state_id`, `spotify_track_id`);
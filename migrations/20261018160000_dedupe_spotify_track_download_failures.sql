@@ -0,0 +1,15 @@
+-- Keep the latest failure per playlist and track, counting the duplicates as attempts
+UPDATE `spotify_track_download_failure` SET `attempts_count` = (
+  SELECT COUNT(*) FROM `spotify_track_download_failure` AS `f`
+  WHERE `f`.`spotify_playlist_id` = `spotify_track_download_failure`.`spotify_playlist_id`
+    AND `f`.`spotify_track_id` = `spotify_track_download_failure`.`spotify_track_id`
+);
+DELETE FROM `spotify_track_download_failure` WHERE `id` NOT IN (
+  SELECT MAX(`id`) FROM `spotify_track_download_failure` GROUP BY `spotify_playlist_id`, `spotify_track_id`
+);
+-- Add column "next_retry_at" to table: "spotify_track_download_failure"
+ALTER TABLE `spotify_track_download_failure` ADD COLUMN `next_retry_at` integer NULL;
+-- Add column "wont_find" to table: "spotify_track_download_failure"
+ALTER TABLE `spotify_track_download_failure` ADD COLUMN `wont_find` integer NOT NULL DEFAULT 0;
+-- Create index "idx_spotify_track_download_failure_unique" to table: "spotify_track_download_failure"
+CREATE UNIQUE INDEX `idx_spotify_track_download_failure_unique` ON `spotify_track_download_failure` (`spotify_playlist_id`, `spotify_track_id`);
@@ -0,0 +1,6 @@
+-- Add column "status" to table: "spotify_account"
+ALTER TABLE `spotify_account` ADD COLUMN `status` varchar NOT NULL DEFAULT 'active';
+-- Add column "last_error" to table: "spotify_account"
+ALTER TABLE `spotify_account` ADD COLUMN `last_error` text NULL;
+-- Add column "last_refreshed_at" to table: "spotify_account"
+ALTER TABLE `spotify_account` ADD COLUMN `last_refreshed_at` integer NULL;
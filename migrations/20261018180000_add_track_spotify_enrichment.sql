@@ -0,0 +1,12 @@
+-- Add column "spotify_id" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `spotify_id` varchar NULL;
+-- Add column "spotify_popularity" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `spotify_popularity` integer NULL;
+-- Add column "tempo" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `tempo` real NULL;
+-- Add column "musical_key" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `musical_key` integer NULL;
+-- Add column "energy" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `energy` real NULL;
+-- Add column "spotify_enriched_at" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `spotify_enriched_at` integer NULL;
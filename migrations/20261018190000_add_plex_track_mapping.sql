@@ -0,0 +1,18 @@
+-- Add column "library_updated_at" to table: "plex_servers"
+ALTER TABLE `plex_servers` ADD COLUMN `library_updated_at` integer NULL;
+-- Create "plex_track_mapping" table
+CREATE TABLE `plex_track_mapping` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `plex_server_id` integer NOT NULL,
+  `track_id` integer NOT NULL,
+  `rating_key` varchar NOT NULL,
+  `match_method` varchar NOT NULL,
+  `plex_updated_at` integer NULL,
+  `updated_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_track_mapping_track" to table: "plex_track_mapping"
+CREATE UNIQUE INDEX `idx_plex_track_mapping_track` ON `plex_track_mapping` (`plex_server_id`, `track_id`);
+-- Create index "idx_plex_track_mapping_rating_key" to table: "plex_track_mapping"
+CREATE UNIQUE INDEX `idx_plex_track_mapping_rating_key` ON `plex_track_mapping` (`plex_server_id`, `rating_key`);
@@ -0,0 +1,10 @@
+-- Add column "music_section_id" to table: "plex_servers"
+ALTER TABLE `plex_servers` ADD COLUMN `music_section_id` varchar NULL;
+-- Create "playlist_plex_server" table
+CREATE TABLE `playlist_plex_server` (
+  `playlist_id` integer NOT NULL,
+  `plex_server_id` integer NOT NULL,
+  PRIMARY KEY (`playlist_id`, `plex_server_id`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,25 @@
+-- Add column "play_count" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `play_count` integer NULL;
+-- Add column "last_played_at" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `last_played_at` integer NULL;
+-- Add column "rating" to table: "tracks"
+ALTER TABLE `tracks` ADD COLUMN `rating` real NULL;
+-- Add column "view_count" to table: "plex_track_mapping"
+ALTER TABLE `plex_track_mapping` ADD COLUMN `view_count` integer NULL;
+-- Add column "last_viewed_at" to table: "plex_track_mapping"
+ALTER TABLE `plex_track_mapping` ADD COLUMN `last_viewed_at` integer NULL;
+-- Add column "user_rating" to table: "plex_track_mapping"
+ALTER TABLE `plex_track_mapping` ADD COLUMN `user_rating` real NULL;
+-- Add column "history_imported_at" to table: "plex_servers"
+ALTER TABLE `plex_servers` ADD COLUMN `history_imported_at` integer NULL;
+-- Create "plex_playlist_import" table
+CREATE TABLE `plex_playlist_import` (
+  `playlist_id` integer NOT NULL PRIMARY KEY,
+  `plex_server_id` integer NOT NULL,
+  `plex_rating_key` varchar NOT NULL,
+  `imported_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_playlist_import_unique" to table: "plex_playlist_import"
+CREATE UNIQUE INDEX `idx_plex_playlist_import_unique` ON `plex_playlist_import` (`plex_server_id`, `plex_rating_key`);
@@ -0,0 +1,10 @@
+-- Create "plex_path_mapping" table
+CREATE TABLE `plex_path_mapping` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `plex_server_id` integer NOT NULL,
+  `local_prefix` varchar NOT NULL,
+  `plex_prefix` varchar NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_path_mapping_local_prefix" to table: "plex_path_mapping"
+CREATE UNIQUE INDEX `idx_plex_path_mapping_local_prefix` ON `plex_path_mapping` (`plex_server_id`, `local_prefix`);
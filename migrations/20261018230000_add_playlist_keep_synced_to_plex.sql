@@ -0,0 +1,2 @@
+-- Add column "keep_synced_to_plex" to table: "playlists"
+ALTER TABLE `playlists` ADD COLUMN `keep_synced_to_plex` integer NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+-- Create "plex_playlist_sync" table
+CREATE TABLE `plex_playlist_sync` (
+  `playlist_id` integer NOT NULL,
+  `plex_server_id` integer NOT NULL,
+  `plex_rating_key` varchar NOT NULL,
+  `synced_rating_keys` text NOT NULL,
+  `synced_at` integer NOT NULL,
+  PRIMARY KEY (`playlist_id`, `plex_server_id`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
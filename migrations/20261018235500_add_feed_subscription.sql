@@ -0,0 +1,13 @@
+-- Create "feed_subscription" table
+CREATE TABLE `feed_subscription` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `name` varchar NOT NULL,
+  `feed_url` varchar NOT NULL,
+  `kind` varchar NOT NULL,
+  `created_at` integer NOT NULL,
+  `updated_at` integer NOT NULL
+);
+-- Create index "feed_subscription_feed_url" to table: "feed_subscription"
+CREATE UNIQUE INDEX `feed_subscription_feed_url` ON `feed_subscription` (`feed_url`);
+-- Add column "feed_subscription_id" to table: "youtube_video"
+ALTER TABLE `youtube_video` ADD COLUMN `feed_subscription_id` integer NULL REFERENCES `feed_subscription` (`id`) ON UPDATE CASCADE ON DELETE CASCADE;
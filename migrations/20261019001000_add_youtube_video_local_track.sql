@@ -0,0 +1,2 @@
+-- Add column "local_track_id" to table: "youtube_video"
+ALTER TABLE `youtube_video` ADD COLUMN `local_track_id` integer NULL REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE SET NULL;
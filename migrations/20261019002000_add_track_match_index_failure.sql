@@ -0,0 +1,10 @@
+-- Add column "created_at" to table: "artist_alias"
+ALTER TABLE `artist_alias` ADD COLUMN `created_at` integer NOT NULL DEFAULT 0;
+-- Create "track_match_index_failure" table
+CREATE TABLE `track_match_index_failure` (
+  `track_id` integer NOT NULL,
+  `error` varchar NOT NULL,
+  `failed_at` integer NOT NULL,
+  PRIMARY KEY (`track_id`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
@@ -0,0 +1,10 @@
+-- Add column "mappings_refreshed_at" to table: "plex_servers"
+ALTER TABLE `plex_servers` ADD COLUMN `mappings_refreshed_at` integer NULL;
+-- Create "plex_unmatched_track" table
+CREATE TABLE `plex_unmatched_track` (
+  `plex_server_id` integer NOT NULL,
+  `rating_key` varchar NOT NULL,
+  `plex_track` text NOT NULL,
+  PRIMARY KEY (`plex_server_id`, `rating_key`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Prefix the IDs of feed items with their subscription, as feeds only keep them unique within themselves
+UPDATE `youtube_video` SET `youtube_id` = 'feed:' || `feed_subscription_id` || ':' || `youtube_id`
+WHERE `feed_subscription_id` IS NOT NULL;
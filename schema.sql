@@ -16,6 +16,16 @@ CREATE TABLE `artist` (
 CREATE UNIQUE INDEX `artist_musicbrainz_id` ON `artist` (`musicbrainz_id`);
 -- Create index "idx_artists_musicbrainz_id" to table: "artist"
 CREATE INDEX `idx_artists_musicbrainz_id` ON `artist` (`musicbrainz_id`);
+-- Create "artist_alias" table
+CREATE TABLE `artist_alias` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `artist_id` integer NOT NULL,
+  `name` varchar NOT NULL,
+  `created_at` integer NOT NULL DEFAULT 0,
+  CONSTRAINT `0` FOREIGN KEY (`artist_id`) REFERENCES `artist` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "artist_alias_artist_id_name" to table: "artist_alias"
+CREATE UNIQUE INDEX `artist_alias_artist_id_name` ON `artist_alias` (`artist_id`, `name`);
 -- Create "album" table
 CREATE TABLE `album` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -56,18 +66,35 @@ CREATE TABLE `playlists` (
   `name` varchar NOT NULL,
   `description` varchar NULL,
   `created_at` timestamp_text NOT NULL,
-  `updated_at` timestamp_text NOT NULL
+  `updated_at` timestamp_text NOT NULL,
+  `keep_synced_to_plex` integer NOT NULL DEFAULT 0
 );
 -- Create "playlist_tracks" table
 CREATE TABLE `playlist_tracks` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
   `playlist_id` integer NOT NULL,
   `track_id` integer NOT NULL,
+  `position` integer NOT NULL,
   `created_at` timestamp_text NOT NULL,
   `updated_at` timestamp_text NOT NULL,
-  PRIMARY KEY (`playlist_id`, `track_id`),
   CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE,
   CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
 );
+-- Create index "idx_playlist_tracks_playlist_id_position" to table: "playlist_tracks"
+CREATE INDEX `idx_playlist_tracks_playlist_id_position` ON `playlist_tracks` (`playlist_id`, `position`);
+-- Create index "idx_playlist_tracks_track_id" to table: "playlist_tracks"
+CREATE INDEX `idx_playlist_tracks_track_id` ON `playlist_tracks` (`track_id`);
+-- Create "smart_playlist" table
+CREATE TABLE `smart_playlist` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `playlist_id` integer NOT NULL,
+  `rules` text NOT NULL,
+  `created_at` timestamp_text NOT NULL,
+  `updated_at` timestamp_text NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "smart_playlist_playlist_id" to table: "smart_playlist"
+CREATE UNIQUE INDEX `smart_playlist_playlist_id` ON `smart_playlist` (`playlist_id`);
 -- Create "plex_servers" table
 CREATE TABLE `plex_servers` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -75,12 +102,83 @@ CREATE TABLE `plex_servers` (
   `server_url` varchar NOT NULL,
   `access_token` varchar NULL,
   `created_at` timestamp_text NOT NULL,
-  `updated_at` timestamp_text NOT NULL
+  `updated_at` timestamp_text NOT NULL,
+  `library_updated_at` integer NULL,
+  `music_section_id` varchar NULL,
+  `history_imported_at` integer NULL,
+  `mappings_refreshed_at` integer NULL
 );
 -- Create index "plex_servers_name" to table: "plex_servers"
 CREATE UNIQUE INDEX `plex_servers_name` ON `plex_servers` (`name`);
 -- Create index "plex_servers_server_url" to table: "plex_servers"
 CREATE UNIQUE INDEX `plex_servers_server_url` ON `plex_servers` (`server_url`);
+-- Create "playlist_plex_server" table
+CREATE TABLE `playlist_plex_server` (
+  `playlist_id` integer NOT NULL,
+  `plex_server_id` integer NOT NULL,
+  PRIMARY KEY (`playlist_id`, `plex_server_id`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create "plex_path_mapping" table
+CREATE TABLE `plex_path_mapping` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `plex_server_id` integer NOT NULL,
+  `local_prefix` varchar NOT NULL,
+  `plex_prefix` varchar NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_path_mapping_local_prefix" to table: "plex_path_mapping"
+CREATE UNIQUE INDEX `idx_plex_path_mapping_local_prefix` ON `plex_path_mapping` (`plex_server_id`, `local_prefix`);
+-- Create "plex_playlist_import" table
+CREATE TABLE `plex_playlist_import` (
+  `playlist_id` integer NOT NULL PRIMARY KEY,
+  `plex_server_id` integer NOT NULL,
+  `plex_rating_key` varchar NOT NULL,
+  `imported_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_playlist_import_unique" to table: "plex_playlist_import"
+CREATE UNIQUE INDEX `idx_plex_playlist_import_unique` ON `plex_playlist_import` (`plex_server_id`, `plex_rating_key`);
+-- Create "plex_playlist_sync" table
+CREATE TABLE `plex_playlist_sync` (
+  `playlist_id` integer NOT NULL,
+  `plex_server_id` integer NOT NULL,
+  `plex_rating_key` varchar NOT NULL,
+  `synced_rating_keys` text NOT NULL,
+  `synced_at` integer NOT NULL,
+  PRIMARY KEY (`playlist_id`, `plex_server_id`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`playlist_id`) REFERENCES `playlists` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create "plex_track_mapping" table
+CREATE TABLE `plex_track_mapping` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `plex_server_id` integer NOT NULL,
+  `track_id` integer NOT NULL,
+  `rating_key` varchar NOT NULL,
+  `match_method` varchar NOT NULL,
+  `plex_updated_at` integer NULL,
+  `updated_at` integer NOT NULL,
+  `view_count` integer NULL,
+  `last_viewed_at` integer NULL,
+  `user_rating` real NULL,
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `1` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_plex_track_mapping_track" to table: "plex_track_mapping"
+CREATE UNIQUE INDEX `idx_plex_track_mapping_track` ON `plex_track_mapping` (`plex_server_id`, `track_id`);
+-- Create index "idx_plex_track_mapping_rating_key" to table: "plex_track_mapping"
+CREATE UNIQUE INDEX `idx_plex_track_mapping_rating_key` ON `plex_track_mapping` (`plex_server_id`, `rating_key`);
+-- Create "plex_unmatched_track" table
+CREATE TABLE `plex_unmatched_track` (
+  `plex_server_id` integer NOT NULL,
+  `rating_key` varchar NOT NULL,
+  `plex_track` text NOT NULL,
+  PRIMARY KEY (`plex_server_id`, `rating_key`),
+  CONSTRAINT `0` FOREIGN KEY (`plex_server_id`) REFERENCES `plex_servers` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
 -- Create "tracks" table
 CREATE TABLE `tracks` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -95,6 +193,15 @@ CREATE TABLE `tracks` (
   `updated_at` integer NOT NULL DEFAULT (strftime('%s', 'now')),
   `isrcs` varchar NULL,
   `barcode` varchar NULL,
+  `spotify_id` varchar NULL,
+  `spotify_popularity` integer NULL,
+  `tempo` real NULL,
+  `musical_key` integer NULL,
+  `energy` real NULL,
+  `spotify_enriched_at` integer NULL,
+  `play_count` integer NULL,
+  `last_played_at` integer NULL,
+  `rating` real NULL,
   CONSTRAINT `0` FOREIGN KEY (`album_id`) REFERENCES `album` (`id`) ON UPDATE NO ACTION ON DELETE NO ACTION
 );
 -- Create index "tracks_musicbrainz_id" to table: "tracks"
@@ -114,6 +221,39 @@ CREATE TABLE `track_artist` (
   CONSTRAINT `0` FOREIGN KEY (`artist_id`) REFERENCES `artist` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE,
   CONSTRAINT `1` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
 );
+-- Create "track_match_index" table
+CREATE TABLE `track_match_index` (
+  `track_id` integer NOT NULL,
+  `title` varchar NOT NULL,
+  `original_title` varchar NOT NULL,
+  `primary_artist` varchar NOT NULL,
+  `artists` text NOT NULL,
+  `album` varchar NOT NULL,
+  `duration_ms` integer NOT NULL,
+  `version_indicator` varchar NULL,
+  `indexed_at` integer NOT NULL,
+  PRIMARY KEY (`track_id`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "idx_track_match_index_duration_ms" to table: "track_match_index"
+CREATE INDEX `idx_track_match_index_duration_ms` ON `track_match_index` (`duration_ms`);
+-- Create "track_match_key" table
+CREATE TABLE `track_match_key` (
+  `track_id` integer NOT NULL,
+  `key` varchar NOT NULL,
+  PRIMARY KEY (`track_id`, `key`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
+-- Create index "idx_track_match_key_key" to table: "track_match_key"
+CREATE INDEX `idx_track_match_key_key` ON `track_match_key` (`key`);
+-- Create "track_match_index_failure" table
+CREATE TABLE `track_match_index_failure` (
+  `track_id` integer NOT NULL,
+  `error` varchar NOT NULL,
+  `failed_at` integer NOT NULL,
+  PRIMARY KEY (`track_id`),
+  CONSTRAINT `0` FOREIGN KEY (`track_id`) REFERENCES `tracks` (`id`) ON UPDATE NO ACTION ON DELETE CASCADE
+);
 -- Create "spotify_account" table
 CREATE TABLE `spotify_account` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -123,7 +263,10 @@ CREATE TABLE `spotify_account` (
   `refresh_token` varchar NOT NULL,
   `token_expiry` integer NOT NULL,
   `created_at` integer NOT NULL,
-  `updated_at` integer NOT NULL
+  `updated_at` integer NOT NULL,
+  `status` varchar NOT NULL DEFAULT 'active',
+  `last_error` text NULL,
+  `last_refreshed_at` integer NULL
 );
 -- Create index "spotify_account_user_id" to table: "spotify_account"
 CREATE UNIQUE INDEX `spotify_account_user_id` ON `spotify_account` (`user_id`);
@@ -161,6 +304,20 @@ CREATE TABLE `spotify_playlist_sync_state` (
 );
 -- Create index "idx_spotify_playlist_sync_state_spotify_playlist_id" to table: "spotify_playlist_sync_state"
 CREATE INDEX `idx_spotify_playlist_sync_state_spotify_playlist_id` ON `spotify_playlist_sync_state` (`spotify_playlist_id`);
+-- Create "spotify_playlist_sync_track" table
+CREATE TABLE `spotify_playlist_sync_track` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `sync_state_id` integer NOT NULL,
+  `spotify_track_id` varchar NOT NULL,
+  `status` varchar NOT NULL DEFAULT 'pending',
+  `local_track_id` integer NULL,
+  `updated_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`local_track_id`) REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE SET NULL,
+  CONSTRAINT `1` FOREIGN KEY (`spotify_track_id`) REFERENCES `spotify_track` (`spotify_track_id`) ON UPDATE CASCADE ON DELETE CASCADE,
+  CONSTRAINT `2` FOREIGN KEY (`sync_state_id`) REFERENCES `spotify_playlist_sync_state` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_spotify_playlist_sync_track_unique" to table: "spotify_playlist_sync_track"
+CREATE UNIQUE INDEX `idx_spotify_playlist_sync_track_unique` ON `spotify_playlist_sync_track` (`sync_state_id`, `spotify_track_id`);
 -- Create "spotify_track_download_failure" table
 CREATE TABLE `spotify_track_download_failure` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -174,8 +331,12 @@ CREATE TABLE `spotify_track_download_failure` (
   `attempts_count` integer NOT NULL DEFAULT 1,
   `created_at` integer NOT NULL,
   `updated_at` integer NOT NULL,
+  `next_retry_at` integer NULL,
+  `wont_find` integer NOT NULL DEFAULT 0,
   CONSTRAINT `0` FOREIGN KEY (`spotify_playlist_id`) REFERENCES `spotify_playlist` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
 );
+-- Create index "idx_spotify_track_download_failure_unique" to table: "spotify_track_download_failure"
+CREATE UNIQUE INDEX `idx_spotify_track_download_failure_unique` ON `spotify_track_download_failure` (`spotify_playlist_id`, `spotify_track_id`);
 -- Create "spotify_track" table
 CREATE TABLE `spotify_track` (
   `spotify_track_id` varchar NOT NULL,
@@ -195,21 +356,30 @@ CREATE TABLE `spotify_track` (
 CREATE TABLE `spotify_track_playlist` (
   `spotify_track_id` varchar NOT NULL,
   `spotify_playlist_id` integer NOT NULL,
+  `position` integer NOT NULL DEFAULT 0,
   PRIMARY KEY (`spotify_track_id`, `spotify_playlist_id`),
   CONSTRAINT `0` FOREIGN KEY (`spotify_playlist_id`) REFERENCES `spotify_playlist` (`id`) ON UPDATE CASCADE ON DELETE CASCADE,
   CONSTRAINT `1` FOREIGN KEY (`spotify_track_id`) REFERENCES `spotify_track` (`spotify_track_id`) ON UPDATE CASCADE ON DELETE CASCADE
 );
--- Create "spotify_to_local_matcher_tasks" table
-CREATE TABLE `spotify_to_local_matcher_tasks` (
+-- Create "background_task" table
+CREATE TABLE `background_task` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `kind` varchar NOT NULL,
   `status` varchar NOT NULL,
-  `matched_tracks` integer NOT NULL,
-  `failed_tracks` integer NOT NULL,
-  `total_tracks` integer NOT NULL,
+  `payload` text NOT NULL,
+  `checkpoint` text NULL,
+  `progress_current` integer NOT NULL DEFAULT 0,
+  `progress_total` integer NULL,
   `error_message` text NULL,
   `created_at` integer NOT NULL,
-  `updated_at` integer NOT NULL
+  `updated_at` integer NOT NULL,
+  `started_at` integer NULL,
+  `finished_at` integer NULL
 );
+-- Create index "idx_background_task_status" to table: "background_task"
+CREATE INDEX `idx_background_task_status` ON `background_task` (`status`);
+-- Create index "idx_background_task_kind" to table: "background_task"
+CREATE INDEX `idx_background_task_kind` ON `background_task` (`kind`);
 
 -- Create "spotify_match_candidate" table
 CREATE TABLE `spotify_match_candidate` (
@@ -233,6 +403,27 @@ CREATE INDEX `idx_spotify_match_candidate_spotify_track` ON `spotify_match_candi
 CREATE INDEX `idx_spotify_match_candidate_status` ON `spotify_match_candidate` (`status`);
 CREATE UNIQUE INDEX `idx_spotify_match_candidate_unique` ON `spotify_match_candidate` (`spotify_track_id`, `local_track_id`);
 
+-- Create "spotify_match_decision" table
+CREATE TABLE `spotify_match_decision` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `spotify_track_id` varchar NOT NULL,
+  `action` varchar NOT NULL,
+  `actor` varchar NOT NULL,
+  `candidate_id` integer NULL,
+  `previous_candidate_status` varchar NULL,
+  `previous_local_track_id` integer NULL,
+  `local_track_id` integer NULL,
+  `dismissed_candidate_ids` text NOT NULL,
+  `reverts_decision_id` integer NULL,
+  `reverted_at` integer NULL,
+  `created_at` integer NOT NULL,
+  CONSTRAINT `0` FOREIGN KEY (`spotify_track_id`) REFERENCES `spotify_track` (`spotify_track_id`) ON UPDATE CASCADE ON DELETE CASCADE
+);
+-- Create index "idx_spotify_match_decision_spotify_track" to table: "spotify_match_decision"
+CREATE INDEX `idx_spotify_match_decision_spotify_track` ON `spotify_match_decision` (`spotify_track_id`);
+-- Create index "idx_spotify_match_decision_created_at" to table: "spotify_match_decision"
+CREATE INDEX `idx_spotify_match_decision_created_at` ON `spotify_match_decision` (`created_at`);
+
 -- Create "youtube_video" table
 CREATE TABLE `youtube_video` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -244,7 +435,11 @@ CREATE TABLE `youtube_video` (
   `video_url` varchar NOT NULL,
   `created_at` integer NOT NULL,
   `updated_at` integer NOT NULL,
-  `watched` integer NOT NULL
+  `watched` integer NOT NULL,
+  `feed_subscription_id` integer NULL,
+  `local_track_id` integer NULL,
+  CONSTRAINT `0` FOREIGN KEY (`local_track_id`) REFERENCES `tracks` (`id`) ON UPDATE CASCADE ON DELETE SET NULL,
+  CONSTRAINT `1` FOREIGN KEY (`feed_subscription_id`) REFERENCES `feed_subscription` (`id`) ON UPDATE CASCADE ON DELETE CASCADE
 );
 CREATE TABLE `youtube_subscription` (
   `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
@@ -252,4 +447,13 @@ CREATE TABLE `youtube_subscription` (
   `youtube_id` varchar NOT NULL UNIQUE,
   `created_at` integer NOT NULL,
   `updated_at` integer NOT NULL
+);
+-- Create "feed_subscription" table
+CREATE TABLE `feed_subscription` (
+  `id` integer NOT NULL PRIMARY KEY AUTOINCREMENT,
+  `name` varchar NOT NULL,
+  `feed_url` varchar NOT NULL UNIQUE,
+  `kind` varchar NOT NULL,
+  `created_at` integer NOT NULL,
+  `updated_at` integer NOT NULL
 );
\ No newline at end of file
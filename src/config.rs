@@ -3,12 +3,29 @@ use std::path::PathBuf;
 use color_eyre::{Result, eyre::Context};
 use serde::{Deserialize, Serialize};
 
+use crate::plex_rs::sync_playlist::PlexPlaylistSyncConfig;
+use crate::services::plex::auto_sync::PlexAutoSyncConfig;
+use crate::services::spotify::matching_local_tracks::MatcherConfig;
+use crate::services::spotify::sync_spotify_playlist_to_local_library::PlaylistSyncConfig;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     /// The directory to store the music
     directory: String,
     /// The path to store the sqlite database
     database_path: String,
+    /// Tuning for matching Spotify tracks against the local library
+    #[serde(default)]
+    matcher: MatcherConfig,
+    /// Concurrency limits for syncing Spotify playlists to the local library
+    #[serde(default)]
+    playlist_sync: PlaylistSyncConfig,
+    /// Plex refresh and playlist re-sync after imports
+    #[serde(default)]
+    plex_auto_sync: PlexAutoSyncConfig,
+    /// One- or two-way sync of playlists to Plex
+    #[serde(default)]
+    plex_playlist_sync: PlexPlaylistSyncConfig,
 }
 
 impl Config {
@@ -45,6 +62,10 @@ impl Config {
                     .to_str()
                     .ok_or(color_eyre::eyre::eyre!("Music directory not found"))?
                     .to_string(),
+                matcher: MatcherConfig::default(),
+                playlist_sync: PlaylistSyncConfig::default(),
+                plex_auto_sync: PlexAutoSyncConfig::default(),
+                plex_playlist_sync: PlexPlaylistSyncConfig::default(),
             })?,
         )?;
 
@@ -99,4 +120,24 @@ impl Config {
     pub fn database_path(&self) -> PathBuf {
         self.expand_path(&self.database_path)
     }
+
+    /// Get the Spotify to local track matcher settings
+    pub fn matcher(&self) -> &MatcherConfig {
+        &self.matcher
+    }
+
+    /// Get the Spotify playlist sync concurrency limits
+    pub fn playlist_sync(&self) -> &PlaylistSyncConfig {
+        &self.playlist_sync
+    }
+
+    /// Get the settings of the Plex refresh after imports
+    pub fn plex_auto_sync(&self) -> &PlexAutoSyncConfig {
+        &self.plex_auto_sync
+    }
+
+    /// Get how playlists are synced to Plex
+    pub fn plex_playlist_sync(&self) -> &PlexPlaylistSyncConfig {
+        &self.plex_playlist_sync
+    }
 }
@@ -7,16 +7,28 @@ use sea_orm::{
     DatabaseConnection, EntityTrait, QueryFilter,
 };
 use serde::Serialize;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
+use tokio::sync::broadcast;
 
 use crate::entities;
 use crate::entities::unimportable_file::UnimportableReason;
 use crate::import_track::ImportError;
 use crate::migrator::run_migrations;
 
+/// IDs per `IN` list, well within SQLite's limit of bound variables
+pub const IN_LIST_CHUNK_SIZE: usize = 500;
+
 pub struct Database {
     pub conn: DatabaseConnection,
+    events: broadcast::Sender<LibraryEvent>,
+}
+
+/// A change to the library other parts of the app react to.
+#[derive(Debug, Clone)]
+pub enum LibraryEvent {
+    /// A track was imported and moved to `file_path`
+    TrackImported { track_id: i64, file_path: PathBuf },
 }
 
 #[derive(Debug, Clone)]
@@ -86,7 +98,22 @@ impl Database {
         run_migrations(path)?;
 
         tracing::info!("Database ready at: {}", path.display());
-        Ok(Database { conn })
+        Ok(Self::from_connection(conn))
+    }
+
+    pub fn from_connection(conn: DatabaseConnection) -> Self {
+        let (events, _) = broadcast::channel(1024);
+        Database { conn, events }
+    }
+
+    /// Receive library events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<LibraryEvent> {
+        self.events.subscribe()
+    }
+
+    /// Publish a library event; nothing happens when no one is subscribed.
+    pub fn publish(&self, event: LibraryEvent) {
+        let _ = self.events.send(event);
     }
 
     pub async fn get_tracks(&self) -> Result<Vec<Track>> {
@@ -492,6 +519,15 @@ impl Database {
             // TODO: add barcode and isrcs
             barcode: ActiveValue::Set(None),
             isrcs: ActiveValue::Set(None),
+            spotify_id: ActiveValue::NotSet,
+            spotify_popularity: ActiveValue::NotSet,
+            tempo: ActiveValue::NotSet,
+            musical_key: ActiveValue::NotSet,
+            energy: ActiveValue::NotSet,
+            spotify_enriched_at: ActiveValue::NotSet,
+            play_count: ActiveValue::NotSet,
+            last_played_at: ActiveValue::NotSet,
+            rating: ActiveValue::NotSet,
         };
 
         let result = new_track
@@ -628,6 +664,48 @@ impl Database {
         Ok(())
     }
 
+    /// Record alternative names of an artist, ignoring ones already known
+    pub async fn add_artist_aliases(&self, artist_id: i64, aliases: &[String]) -> Result<()> {
+        if aliases.is_empty() {
+            return Ok(());
+        }
+
+        tracing::debug!(
+            "Adding {} aliases for artist_id={}",
+            aliases.len(),
+            artist_id
+        );
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64;
+        let models = aliases
+            .iter()
+            .map(|alias| entities::artist_alias::ActiveModel {
+                artist_id: ActiveValue::Set(artist_id),
+                name: ActiveValue::Set(alias.clone()),
+                created_at: ActiveValue::Set(now),
+                ..Default::default()
+            });
+
+        entities::artist_alias::Entity::insert_many(models)
+            .on_conflict(
+                sea_orm::sea_query::OnConflict::columns([
+                    entities::artist_alias::Column::ArtistId,
+                    entities::artist_alias::Column::Name,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .do_nothing()
+            .exec(&self.conn)
+            .await
+            .context("Failed to add artist aliases")?;
+
+        Ok(())
+    }
+
     /// Get all artists for an album
     pub async fn get_album_artists(&self, album_id: i64) -> Result<Vec<(Artist, bool)>> {
         // Load related artists through the junction table
@@ -0,0 +1,18 @@
+use sea_orm::entity::prelude::*;
+
+/// Alternative name of an artist from MusicBrainz, e.g. a romanized name
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "artist_alias")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub artist_id: i64,
+    pub name: String,
+    pub created_at: i64,
+
+    #[sea_orm(belongs_to, from = "artist_id", to = "id")]
+    pub artist: Option<super::artist::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,107 @@
+use async_graphql::Enum;
+use async_trait::async_trait;
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Enum,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumIter,
+    DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum TaskKind {
+    /// Match unmatched Spotify tracks against the local library
+    #[sea_orm(string_value = "spotify_to_local_matcher")]
+    SpotifyToLocalMatcher,
+    /// Download and import the tracks of a Spotify playlist into a local playlist
+    #[sea_orm(string_value = "spotify_playlist_sync")]
+    SpotifyPlaylistSync,
+    /// Look up local tracks on Spotify for their popularity and audio features
+    #[sea_orm(string_value = "spotify_enrichment")]
+    SpotifyEnrichment,
+    /// Find the song of a YouTube video on MusicBrainz, then download and import it
+    #[sea_orm(string_value = "youtube_song_download")]
+    YoutubeSongDownload,
+    /// Map the new tracks of a Plex server reported by its webhooks
+    #[sea_orm(string_value = "plex_mapping_refresh")]
+    PlexMappingRefresh,
+}
+
+#[derive(
+    Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum TaskStatus {
+    /// Waiting to start, e.g. for a free slot of its kind
+    #[sea_orm(string_value = "pending")]
+    Pending,
+    #[sea_orm(string_value = "running")]
+    Running,
+    #[sea_orm(string_value = "completed")]
+    Completed,
+    #[sea_orm(string_value = "failed")]
+    Failed,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Whether the task will not run again
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// A background job run by the task manager. `payload` is the job's input and
+/// `checkpoint` the progress it saved, used to resume after a restart.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "background_task")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub kind: TaskKind,
+    pub status: TaskStatus,
+    pub payload: Json,
+    pub checkpoint: Option<Json>,
+    pub progress_current: i64,
+    pub progress_total: Option<i64>,
+    pub error_message: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            status: Set(TaskStatus::Pending),
+            progress_current: Set(0),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..ActiveModelTrait::default()
+        }
+    }
+
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, sea_orm::DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert {
+            self.updated_at = Set(chrono::Utc::now().timestamp());
+        }
+        Ok(self)
+    }
+}
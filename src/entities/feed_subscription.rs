@@ -0,0 +1,61 @@
+use async_graphql::Enum;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+/// Format of a feed, which decides how it is parsed.
+#[derive(
+    Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum FeedKind {
+    /// Atom feed of a YouTube channel, with `yt:` and `media:` fields
+    #[sea_orm(string_value = "youtube")]
+    Youtube,
+    /// Atom feed, e.g. of a label or blog
+    #[sea_orm(string_value = "atom")]
+    Atom,
+    /// RSS 2.0 feed, e.g. of a podcast, a SoundCloud or Bandcamp artist or a label
+    #[sea_orm(string_value = "rss")]
+    Rss,
+}
+
+/// A subscribed RSS or Atom feed whose new items land in the video inbox
+/// (`youtube_video`), like the videos of YouTube subscriptions.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "feed_subscription")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub name: String,
+    #[sea_orm(unique)]
+    pub feed_url: String,
+    pub kind: FeedKind,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        let now = Utc::now();
+        Self {
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..ActiveModelTrait::default()
+        }
+    }
+
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, sea_orm::DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert {
+            self.updated_at = Set(Utc::now());
+        }
+        Ok(self)
+    }
+}
@@ -1,19 +1,33 @@
 pub mod album;
 pub mod album_artist;
 pub mod artist;
+pub mod artist_alias;
+pub mod background_task;
+pub mod feed_subscription;
 pub mod playlist;
+pub mod playlist_plex_server;
 pub mod playlist_track;
+pub mod plex_path_mapping;
+pub mod plex_playlist_import;
+pub mod plex_playlist_sync;
 pub mod plex_server;
+pub mod plex_track_mapping;
+pub mod plex_unmatched_track;
+pub mod smart_playlist;
 pub mod spotify_account;
 pub mod spotify_match_candidate;
+pub mod spotify_match_decision;
 pub mod spotify_playlist;
 pub mod spotify_playlist_sync_state;
-pub mod spotify_to_local_matcher_tasks;
+pub mod spotify_playlist_sync_track;
 pub mod spotify_track;
 pub mod spotify_track_download_failure;
 pub mod spotify_track_playlist;
 pub mod track;
 pub mod track_artist;
+pub mod track_match_index;
+pub mod track_match_index_failure;
+pub mod track_match_key;
 pub mod unimportable_file;
 pub mod youtube_subscription;
 pub mod youtube_video;
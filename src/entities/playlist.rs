@@ -11,6 +11,8 @@ pub struct Model {
     pub id: i64,
     pub name: String,
     pub description: Option<String>,
+    /// Re-synced to its Plex servers when imports changed the library
+    pub keep_synced_to_plex: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     #[sea_orm(has_many, via = "playlist_track")]
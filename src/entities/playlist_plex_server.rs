@@ -0,0 +1,19 @@
+use sea_orm::entity::prelude::*;
+
+/// Plex server a playlist is synced to.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "playlist_plex_server")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub playlist_id: i64,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub plex_server_id: i64,
+
+    #[sea_orm(belongs_to, from = "playlist_id", to = "id")]
+    pub playlist: Option<super::playlist::Entity>,
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
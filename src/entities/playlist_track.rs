@@ -7,10 +7,13 @@ use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "playlist_tracks")]
 pub struct Model {
-    #[sea_orm(primary_key, auto_increment = false)]
+    #[sea_orm(primary_key)]
+    pub id: i64,
     pub playlist_id: i64,
-    #[sea_orm(primary_key, auto_increment = false)]
     pub track_id: i64,
+    /// Zero-based position of the entry within its playlist. A track may
+    /// appear multiple times in the same playlist at different positions.
+    pub position: i32,
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -0,0 +1,19 @@
+use sea_orm::entity::prelude::*;
+
+/// Where a folder of the local library is mounted on a Plex server, e.g.
+/// `/music` locally is `/data/media/music` on Plex.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "plex_path_mapping")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub plex_server_id: i64,
+    pub local_prefix: String,
+    pub plex_prefix: String,
+
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
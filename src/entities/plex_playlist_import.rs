@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+
+/// Local playlist imported from a Plex playlist, replaced on re-import.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "plex_playlist_import")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub playlist_id: i64,
+    pub plex_server_id: i64,
+    pub plex_rating_key: String,
+    pub imported_at: i64,
+
+    #[sea_orm(belongs_to, from = "playlist_id", to = "id")]
+    pub playlist: Option<super::playlist::Entity>,
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
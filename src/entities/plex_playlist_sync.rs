@@ -0,0 +1,28 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The Plex playlist a local playlist was last synced to on a server, and its
+/// tracks at that point, to tell which side changed since.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "plex_playlist_sync")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub playlist_id: i64,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub plex_server_id: i64,
+    pub plex_rating_key: String,
+    pub synced_rating_keys: SyncedRatingKeys,
+    pub synced_at: i64,
+
+    #[sea_orm(belongs_to, from = "playlist_id", to = "id")]
+    pub playlist: Option<super::playlist::Entity>,
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+}
+
+/// Rating keys of the Plex playlist items, in playlist order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct SyncedRatingKeys(pub Vec<String>);
+
+impl ActiveModelBehavior for ActiveModel {}
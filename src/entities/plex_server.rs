@@ -17,6 +17,17 @@ pub struct Model {
     pub server_url: String,
     /// The server access token to use for API calls.
     pub access_token: Option<String>,
+    /// Newest Plex `updatedAt` covered by the track mappings; tracks updated
+    /// since are fetched by the next mapping refresh.
+    pub library_updated_at: Option<i64>,
+    /// Music library section used when an operation doesn't name one; the
+    /// first music section when unset.
+    pub music_section_id: Option<String>,
+    /// Newest Plex `lastViewedAt` covered by the imported play history
+    pub history_imported_at: Option<i64>,
+    /// When the track mappings were last refreshed; local tracks added since
+    /// are a reason to match the unmatched Plex tracks again.
+    pub mappings_refreshed_at: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
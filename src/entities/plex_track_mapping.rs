@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum PlexTrackMatchMethod {
+    /// Same artist/album/file name at the end of the file path
+    #[sea_orm(string_value = "path")]
+    Path,
+    /// Same MusicBrainz recording ID in the file tags
+    #[sea_orm(string_value = "musicbrainz_id")]
+    MusicBrainzId,
+    /// High confidence fuzzy match of title, artists, album and duration
+    #[sea_orm(string_value = "metadata")]
+    Metadata,
+}
+
+/// Plex track (`rating_key`) of a local track on a Plex server, so playlist
+/// syncs don't have to download and match the whole Plex library.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "plex_track_mapping")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub plex_server_id: i64,
+    pub track_id: i64,
+    pub rating_key: String,
+    pub match_method: PlexTrackMatchMethod,
+    /// Plex `updatedAt` of the track when it was matched
+    pub plex_updated_at: Option<i64>,
+    pub updated_at: i64,
+    /// Plays of the Plex track, from the imported play history
+    pub view_count: Option<i32>,
+    pub last_viewed_at: Option<i64>,
+    /// Plex user rating from 0 to 10
+    pub user_rating: Option<f64>,
+
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+    #[sea_orm(belongs_to, from = "track_id", to = "id")]
+    pub track: Option<super::track::Entity>,
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        Self {
+            updated_at: Set(chrono::Utc::now().timestamp()),
+            ..ActiveModelTrait::default()
+        }
+    }
+
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, sea_orm::DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if !insert {
+            self.updated_at = Set(chrono::Utc::now().timestamp());
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,20 @@
+use sea_orm::entity::prelude::*;
+
+/// Plex track no local track was found for, kept to match it again once local
+/// tracks were added, as incremental refreshes only fetch changed Plex tracks.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "plex_unmatched_track")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub plex_server_id: i64,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub rating_key: String,
+    /// The Plex track as last fetched
+    pub plex_track: Json,
+
+    #[sea_orm(belongs_to, from = "plex_server_id", to = "id")]
+    pub plex_server: Option<super::plex_server::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,149 @@
+use async_graphql::{Enum, InputObject, SimpleObject};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+use crate::http_server::graphql::query_builder::{SortOrder, TrackSortField};
+
+/// Saved rules for a playlist whose tracks are computed from the library
+/// instead of being stored as `playlist_tracks` entries.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "smart_playlist")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    #[sea_orm(unique)]
+    pub playlist_id: i64,
+    pub rules: SmartPlaylistRules,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+
+    #[sea_orm(belongs_to, from = "playlist_id", to = "id")]
+    pub playlist: Option<super::playlist::Entity>,
+}
+
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    FromJsonQueryResult,
+    SimpleObject,
+    InputObject,
+)]
+#[graphql(input_name = "SmartPlaylistRulesInput")]
+pub struct SmartPlaylistRules {
+    /// Whether a track must match all rules or any of them
+    pub match_mode: SmartRuleMatch,
+    pub rules: Vec<SmartRule>,
+    /// Sort applied to matching tracks; falls back to the default track sort
+    #[graphql(default)]
+    #[serde(default)]
+    pub sort: Vec<SmartRuleSort>,
+    /// Maximum number of tracks in the playlist
+    pub limit: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "SmartRuleInput")]
+pub struct SmartRule {
+    pub field: SmartRuleField,
+    pub operator: SmartRuleOperator,
+    /// Text, number, day count or playlist ID depending on the field
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, SimpleObject, InputObject)]
+#[graphql(input_name = "SmartRuleSortInput")]
+pub struct SmartRuleSort {
+    pub field: TrackSortField,
+    pub order: SortOrder,
+}
+
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartRuleMatch {
+    All,
+    Any,
+}
+
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartRuleField {
+    Title,
+    /// Name of any of the track's artists
+    Artist,
+    Album,
+    /// File extension, e.g. "flac"
+    Format,
+    /// Album release year
+    Year,
+    /// Duration in seconds
+    Duration,
+    /// When the track was imported
+    AddedAt,
+    /// Track is an entry of the playlist with this ID
+    InPlaylist,
+    /// Track shares an artist with a track of the playlist with this ID
+    ArtistInPlaylist,
+    /// Spotify popularity from 0 to 100
+    Popularity,
+    /// Beats per minute
+    Tempo,
+    /// Energy from 0.0 to 1.0
+    Energy,
+    /// Pitch class of the key, 0 = C … 11 = B
+    Key,
+    /// Plays on Plex, summed over all servers
+    PlayCount,
+    /// When the track was last played on Plex
+    LastPlayed,
+    /// Plex rating from 0 to 10
+    Rating,
+}
+
+#[derive(Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmartRuleOperator {
+    Contains,
+    NotContains,
+    Is,
+    IsNot,
+    GreaterThan,
+    LessThan,
+    InLastDays,
+    NotInLastDays,
+}
+
+#[async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        let now = Utc::now();
+
+        Self {
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..ActiveModelTrait::default()
+        }
+    }
+
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, sea_orm::DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        let now = Utc::now();
+
+        if insert {
+            self.created_at = Set(now);
+        }
+
+        self.updated_at = Set(now);
+
+        Ok(self)
+    }
+}
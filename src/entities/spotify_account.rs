@@ -1,6 +1,21 @@
+use async_graphql::Enum;
 use async_trait::async_trait;
 use sea_orm::entity::prelude::*;
 use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+#[derive(
+    Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum AccountStatus {
+    /// Tokens can be refreshed and the account can be synced
+    #[sea_orm(string_value = "active")]
+    Active,
+    /// The refresh token was revoked; the user has to authorize the account again
+    #[sea_orm(string_value = "needs_reauth")]
+    NeedsReauth,
+}
 
 #[sea_orm::model]
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -17,6 +32,10 @@ pub struct Model {
     pub token_expiry: i64,
     pub created_at: i64,
     pub updated_at: i64,
+    pub status: AccountStatus,
+    /// Error from the last failed token refresh, cleared on success
+    pub last_error: Option<String>,
+    pub last_refreshed_at: Option<i64>,
 }
 
 #[async_trait]
@@ -26,6 +45,7 @@ impl ActiveModelBehavior for ActiveModel {
         Self {
             created_at: Set(now),
             updated_at: Set(now),
+            status: Set(AccountStatus::Active),
             ..ActiveModelTrait::default()
         }
     }
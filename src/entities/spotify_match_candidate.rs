@@ -45,6 +45,9 @@ pub enum CandidateStatus {
     Accepted,
     #[sea_orm(string_value = "dismissed")]
     Dismissed,
+    /// Linked by the matcher's auto-accept policy rather than by a person
+    #[sea_orm(string_value = "auto_accepted")]
+    AutoAccepted,
 }
 
 #[sea_orm::model]
@@ -0,0 +1,77 @@
+use async_graphql::Enum;
+use sea_orm::entity::prelude::*;
+use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+use super::spotify_match_candidate::CandidateStatus;
+
+#[derive(
+    Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum MatchDecisionAction {
+    /// The Spotify track was linked to a local track
+    #[sea_orm(string_value = "link")]
+    Link,
+    /// The Spotify track's link was removed
+    #[sea_orm(string_value = "unlink")]
+    Unlink,
+    /// Pending candidates were dismissed without linking
+    #[sea_orm(string_value = "dismiss")]
+    Dismiss,
+    /// An earlier decision was undone
+    #[sea_orm(string_value = "revert")]
+    Revert,
+}
+
+#[derive(
+    Enum, Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
+pub enum MatchDecisionActor {
+    /// Made through the API, e.g. from the review UI
+    #[sea_orm(string_value = "user")]
+    User,
+    /// Made by the matcher's auto-accept policy
+    #[sea_orm(string_value = "matcher")]
+    Matcher,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, FromJsonQueryResult)]
+pub struct IdVec(pub Vec<i64>);
+
+/// Audit log entry for a change to a Spotify track's match, with enough of
+/// the previous state to undo it.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "spotify_match_decision")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i64,
+    pub spotify_track_id: String,
+    pub action: MatchDecisionAction,
+    pub actor: MatchDecisionActor,
+    /// Candidate the decision was made from, if any
+    pub candidate_id: Option<i64>,
+    pub previous_candidate_status: Option<CandidateStatus>,
+    pub previous_local_track_id: Option<i64>,
+    pub local_track_id: Option<i64>,
+    /// Pending candidates that were dismissed by the decision
+    pub dismissed_candidate_ids: IdVec,
+    pub reverts_decision_id: Option<i64>,
+    pub reverted_at: Option<i64>,
+    pub created_at: i64,
+
+    #[sea_orm(belongs_to, from = "spotify_track_id", to = "spotify_track_id")]
+    pub spotify_track: Option<super::spotify_track::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {
+    fn new() -> Self {
+        Self {
+            dismissed_candidate_ids: Set(IdVec(Vec::new())),
+            created_at: Set(chrono::Utc::now().timestamp()),
+            ..ActiveModelTrait::default()
+        }
+    }
+}
@@ -1,46 +1,46 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
 use sea_orm::entity::prelude::*;
 use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, EnumIter, DeriveActiveEnum)]
 #[sea_orm(rs_type = "String", db_type = "String(StringLen::None)")]
-pub enum MatchTaskStatus {
+pub enum SyncTrackStatus {
+    /// Not attempted yet in this sync
     #[sea_orm(string_value = "pending")]
     Pending,
-    #[sea_orm(string_value = "in_progress")]
-    InProgress,
+    /// Found in or imported into the local library
     #[sea_orm(string_value = "completed")]
     Completed,
+    /// Could not be downloaded or imported; retried when the sync resumes
     #[sea_orm(string_value = "failed")]
     Failed,
-    #[sea_orm(string_value = "killed")]
-    Killed,
 }
 
+/// Per-track progress of a Spotify playlist sync, used to skip tracks that
+/// were already synced when an interrupted sync is resumed.
 #[sea_orm::model]
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
-#[sea_orm(table_name = "spotify_to_local_matcher_tasks")]
+#[sea_orm(table_name = "spotify_playlist_sync_track")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i64,
-    pub status: MatchTaskStatus,
-    pub matched_tracks: i64,
-    pub failed_tracks: i64,
-    pub total_tracks: i64,
-    pub error_message: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
+    pub sync_state_id: i64,
+    pub spotify_track_id: String,
+    pub status: SyncTrackStatus,
+    pub local_track_id: Option<i64>,
+    pub updated_at: i64,
+
+    #[sea_orm(belongs_to, from = "sync_state_id", to = "id")]
+    pub sync_state: Option<super::spotify_playlist_sync_state::Entity>,
 }
 
 #[async_trait]
 impl ActiveModelBehavior for ActiveModel {
     fn new() -> Self {
-        let now = chrono::Utc::now();
         Self {
-            created_at: Set(now),
-            updated_at: Set(now),
+            status: Set(SyncTrackStatus::Pending),
+            updated_at: Set(chrono::Utc::now().timestamp()),
             ..ActiveModelTrait::default()
         }
     }
@@ -50,7 +50,7 @@ impl ActiveModelBehavior for ActiveModel {
         C: ConnectionTrait,
     {
         if !insert {
-            self.updated_at = Set(chrono::Utc::now());
+            self.updated_at = Set(chrono::Utc::now().timestamp());
         }
         Ok(self)
     }
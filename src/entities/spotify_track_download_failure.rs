@@ -19,6 +19,10 @@ pub struct Model {
     pub attempts_count: i32,
     pub created_at: i64,
     pub updated_at: i64,
+    /// When the track is next retried automatically, None to retry on the next sync
+    pub next_retry_at: Option<i64>,
+    /// Set by the user to stop retrying a track that can't be found
+    pub wont_find: bool,
 }
 
 #[async_trait]
@@ -29,6 +33,7 @@ impl ActiveModelBehavior for ActiveModel {
             created_at: Set(now),
             updated_at: Set(now),
             attempts_count: Set(1),
+            wont_find: Set(false),
             ..ActiveModelTrait::default()
         }
     }
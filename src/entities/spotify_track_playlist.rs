@@ -10,6 +10,8 @@ pub struct Model {
     pub spotify_track_id: String,
     #[sea_orm(primary_key, auto_increment = false)]
     pub spotify_playlist_id: i64,
+    /// Zero-based position of the track within the Spotify playlist
+    pub position: i32,
     #[sea_orm(belongs_to, from = "spotify_track_id", to = "spotify_track_id")]
     pub spotify_track: Option<super::spotify_track::Entity>,
     #[sea_orm(belongs_to, from = "spotify_playlist_id", to = "id")]
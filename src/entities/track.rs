@@ -1,7 +1,7 @@
 use sea_orm::entity::prelude::*;
 
 #[sea_orm::model]
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "tracks")]
 pub struct Model {
     #[sea_orm(primary_key)]
@@ -16,6 +16,24 @@ pub struct Model {
     pub sha256: String,
     pub isrcs: Option<String>, // JSON array of ISRCs: ["USRC11234567", ...]
     pub barcode: Option<String>, // EAN or UPC barcode
+    /// Spotify track found for this track by enrichment
+    pub spotify_id: Option<String>,
+    /// Spotify popularity from 0 to 100
+    pub spotify_popularity: Option<i32>,
+    /// Beats per minute
+    pub tempo: Option<f64>,
+    /// Pitch class of the key, 0 = C, 1 = C♯/D♭, … 11 = B
+    pub musical_key: Option<i32>,
+    /// Perceived intensity from 0.0 to 1.0
+    pub energy: Option<f64>,
+    /// When enrichment last looked this track up on Spotify, found or not
+    pub spotify_enriched_at: Option<i64>,
+    /// Plays over all Plex servers, from the imported Plex play history
+    pub play_count: Option<i32>,
+    /// Last play on any Plex server
+    pub last_played_at: Option<i64>,
+    /// Plex user rating from 0 to 10, the highest given on any server
+    pub rating: Option<f64>,
     pub created_at: i64,
     pub updated_at: i64,
 
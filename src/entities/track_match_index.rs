@@ -0,0 +1,26 @@
+use sea_orm::entity::prelude::*;
+
+use super::spotify_track::StringVec;
+
+/// Normalized matcher view of a local track, so matching does not have to
+/// rebuild and re-normalize the whole library on every run.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "track_match_index")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub track_id: i64,
+    pub title: String,
+    pub original_title: String,
+    pub primary_artist: String,
+    pub artists: StringVec,
+    pub album: String,
+    pub duration_ms: i64,
+    pub version_indicator: Option<String>,
+    pub indexed_at: i64,
+
+    #[sea_orm(belongs_to, from = "track_id", to = "id")]
+    pub track: Option<super::track::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,18 @@
+use sea_orm::entity::prelude::*;
+
+/// Local track that could not be indexed for matching, e.g. because it has no
+/// artist. It is only retried once the track or its artists change.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "track_match_index_failure")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub track_id: i64,
+    pub error: String,
+    pub failed_at: i64,
+
+    #[sea_orm(belongs_to, from = "track_id", to = "id")]
+    pub track: Option<super::track::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
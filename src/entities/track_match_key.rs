@@ -0,0 +1,18 @@
+use sea_orm::entity::prelude::*;
+
+/// Blocking key of an indexed track; tracks sharing a key with a Spotify
+/// track are the only ones scored against it.
+#[sea_orm::model]
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "track_match_key")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub track_id: i64,
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub key: String,
+
+    #[sea_orm(belongs_to, from = "track_id", to = "id")]
+    pub track: Option<super::track::Entity>,
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -9,6 +9,7 @@ use sea_orm::{ActiveModelBehavior, ActiveValue::Set};
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i64,
+    /// YouTube video ID, `feed:{feed_subscription_id}:{item id}` for feed items
     pub youtube_id: String,
     pub title: String,
     pub channel_name: String,
@@ -16,6 +17,10 @@ pub struct Model {
     pub thumbnail_url: String,
     pub video_url: String,
     pub watched: bool,
+    /// Feed the item came from, `None` for videos of YouTube subscriptions
+    pub feed_subscription_id: Option<i64>,
+    /// Local track of the song in the video, once found and downloaded
+    pub local_track_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -18,15 +18,29 @@ use crate::{
     config::Config,
     database::Database,
     http_server::{
-        cleanup_tasks::cleanup_tasks,
         graphql,
         http_routes::{
             album_art_image::get_track_album_art_image, audio_file::audio_file,
-            download_file::download_file,
+            download_file::download_file, plex_webhook::plex_webhook,
         },
         state::AppState,
     },
-    services::{background::run_background_tasks, spotify::client::SpotifyApiCredentials},
+    services::{
+        background::run_background_tasks,
+        plex::mapping_refresh::PlexMappingRefreshTaskHandler,
+        spotify::{
+            client::SpotifyApiCredentials,
+            matching_local_tracks::{
+                SpotifyEnrichmentTaskHandler, SpotifyToLocalMatcherTaskHandler,
+            },
+            sync_spotify_playlist_to_local_library::{
+                SpotifyPlaylistSyncTaskHandler, mark_stale_syncs,
+            },
+            token_manager::SpotifyTokenManager,
+        },
+        tasks::TaskManager,
+        youtube::find_song::YoutubeSongDownloadTaskHandler,
+    },
     soulseek::{SearchConfig, SoulSeekClientContext},
 };
 
@@ -85,15 +99,42 @@ pub async fn start(config: HttpServerConfig) -> color_eyre::Result<()> {
     .await
     .wrap_err("Failed to initialize SoulSeek client context")?;
 
+    let db = Arc::new(database);
+    let soulseek_context = Arc::new(soulseek_context);
+
+    let spotify_tokens = Arc::new(SpotifyTokenManager::new(
+        db.clone(),
+        spotify_credentials.clone(),
+    ));
+    let mut tasks = TaskManager::new(db.clone());
+    tasks.register(SpotifyToLocalMatcherTaskHandler);
+    tasks.register(SpotifyPlaylistSyncTaskHandler {
+        soulseek_context: soulseek_context.clone(),
+        api_key: acoustid_api_key.clone(),
+        config: config.clone(),
+    });
+    tasks.register(SpotifyEnrichmentTaskHandler {
+        spotify_tokens: spotify_tokens.clone(),
+    });
+    tasks.register(YoutubeSongDownloadTaskHandler {
+        soulseek_context: soulseek_context.clone(),
+        api_key: acoustid_api_key.clone(),
+        config: config.clone(),
+    });
+    tasks.register(PlexMappingRefreshTaskHandler);
+    let tasks = Arc::new(tasks);
+
     let app_state = Arc::new(AppState {
-        db: Arc::new(database),
-        soulseek_context: Arc::new(soulseek_context),
+        db,
+        soulseek_context,
         download_directory,
         api_key: acoustid_api_key.clone(),
         config: config.clone(),
         base_url: base_url.clone(),
         spotify_credentials,
         spotify_oauth_session: tokio::sync::Mutex::new(None),
+        tasks,
+        spotify_tokens,
     });
 
     let schema = graphql::create_schema(app_state.clone());
@@ -172,6 +213,7 @@ pub async fn start(config: HttpServerConfig) -> color_eyre::Result<()> {
         )
         .route("/audio-file/{track_id}", get(audio_file))
         .route("/download-file", post(download_file))
+        .route("/plex-webhook", post(plex_webhook))
         .layer(ServiceBuilder::new().layer(cors_layer))
         .layer(TraceLayer::new_for_http())
         .with_state(app_state.clone());
@@ -180,8 +222,16 @@ pub async fn start(config: HttpServerConfig) -> color_eyre::Result<()> {
 
     {
         tokio::task::spawn(async move {
-            if let Err(e) = cleanup_tasks(&app_state.db).await {
-                tracing::error!(error = ?e, "Error cleaning up tasks");
+            match mark_stale_syncs(&app_state.db).await {
+                Ok(0) => {}
+                Ok(marked) => {
+                    tracing::warn!("Marked {} stale playlist syncs as interrupted", marked)
+                }
+                Err(e) => tracing::error!(error = ?e, "Error marking stale playlist syncs"),
+            }
+            match app_state.tasks.resume_unfinished().await {
+                Ok(resumed) => tracing::info!("Resumed {} background tasks", resumed),
+                Err(e) => tracing::error!(error = ?e, "Error resuming background tasks"),
             }
         });
     }
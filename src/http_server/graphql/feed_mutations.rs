@@ -0,0 +1,32 @@
+use async_graphql::{Context, Object};
+
+use crate::http_server::{graphql::context::get_app_state, graphql_error::GraphqlResult};
+use crate::services::feeds::service::FeedService;
+
+#[derive(Default)]
+pub struct FeedMutation;
+
+#[Object]
+impl FeedMutation {
+    /// Subscribe to an RSS or Atom feed, named after the feed's title unless
+    /// `name` is given.
+    async fn add_feed_subscription(
+        &self,
+        ctx: &Context<'_>,
+        feed_url: String,
+        name: Option<String>,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        let service = FeedService::new(app_state.db.clone());
+        service.add_subscription(feed_url, name).await?;
+        Ok(true)
+    }
+
+    /// Unsubscribe from a feed, removing its items.
+    async fn remove_feed_subscription(&self, ctx: &Context<'_>, id: i64) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        let service = FeedService::new(app_state.db.clone());
+        service.remove_subscription(id).await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,43 @@
+use async_graphql::{Context, Object};
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+use crate::entities::feed_subscription::FeedKind;
+use crate::http_server::graphql::context::get_app_state;
+use crate::http_server::graphql_error::GraphqlResult;
+use crate::services::feeds::service::FeedService;
+
+#[derive(Default)]
+pub struct FeedQuery;
+
+#[derive(async_graphql::SimpleObject, Debug)]
+pub struct FeedSubscription {
+    pub id: i64,
+    pub name: String,
+    pub feed_url: String,
+    pub kind: FeedKind,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[Object]
+impl FeedQuery {
+    /// RSS and Atom feeds whose new items show up with the YouTube videos
+    #[instrument(skip(self, ctx))]
+    async fn feed_subscriptions(&self, ctx: &Context<'_>) -> GraphqlResult<Vec<FeedSubscription>> {
+        let app_state = get_app_state(ctx)?;
+        let service = FeedService::new(app_state.db.clone());
+        let subscriptions = service.list_subscriptions().await?;
+        Ok(subscriptions
+            .into_iter()
+            .map(|subscription| FeedSubscription {
+                id: subscription.id,
+                name: subscription.name,
+                feed_url: subscription.feed_url,
+                kind: subscription.kind,
+                created_at: subscription.created_at,
+                updated_at: subscription.updated_at,
+            })
+            .collect())
+    }
+}
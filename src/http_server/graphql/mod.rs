@@ -8,6 +8,8 @@ use async_graphql::Context;
 use chrono::{DateTime, Utc};
 use color_eyre::eyre::OptionExt;
 
+use crate::http_server::graphql::feed_mutations::FeedMutation;
+use crate::http_server::graphql::feed_queries::FeedQuery;
 use crate::http_server::graphql::plex_library_refresh_queries::PlexLibraryRefreshQuery;
 use crate::http_server::graphql::query_builder::{
     PaginationInput, SortInput, TextSearchInput, TrackSortField, TrackSortInput,
@@ -23,6 +25,8 @@ use crate::services::plex::client::PlexHttpAdapter;
 use crate::services::track::{TrackService, TrackWithRelations};
 
 mod context;
+mod feed_mutations;
+mod feed_queries;
 pub mod playlist_mutations;
 pub mod playlist_queries;
 pub mod plex_library_refresh_mutations;
@@ -35,6 +39,8 @@ pub mod plex_track_queries;
 pub mod query_builder;
 pub mod soulseek_mutations;
 mod spotify;
+pub mod task_mutations;
+pub mod task_queries;
 pub mod track_queries;
 pub mod unimportable_file_queries;
 mod youtube_mutations;
@@ -42,14 +48,16 @@ mod youtube_queries;
 
 use context::get_app_state;
 use playlist_mutations::PlaylistMutation;
-use playlist_queries::{Playlist, PlaylistsResponse};
+use playlist_queries::{Playlist, PlaylistEntriesResponse, PlaylistEntry, PlaylistsResponse};
 use plex_library_refresh_mutations::PlexLibraryRefreshMutation;
 use plex_playlist_mutations::PlexPlaylistMutation;
 use plex_playlist_queries::PlexPlaylistsResponse;
 use plex_server_mutations::PlexServerMutation;
-use plex_server_queries::PlexServer;
+use plex_server_queries::{PlexMusicSection, PlexPathMapping, PlexServer};
 use plex_track_queries::PlexTracksResult;
 use soulseek_mutations::SoulseekMutation;
+use task_mutations::TaskMutation;
+use task_queries::TaskQuery;
 use track_queries::{Album, Artist, Track, TracksResponse};
 use unimportable_file_queries::{UnimportableFile, UnimportableFilesResponse};
 
@@ -66,6 +74,17 @@ pub(crate) fn map_track_with_relations(twr: TrackWithRelations) -> color_eyre::R
         duration: twr.track.duration,
         created_at: DateTime::<Utc>::from_timestamp_secs(twr.track.created_at)
             .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
+        spotify_id: twr.track.spotify_id,
+        popularity: twr.track.spotify_popularity,
+        tempo: twr.track.tempo,
+        musical_key: twr.track.musical_key,
+        energy: twr.track.energy,
+        play_count: twr.track.play_count,
+        last_played_at: twr
+            .track
+            .last_played_at
+            .and_then(DateTime::<Utc>::from_timestamp_secs),
+        rating: twr.track.rating,
         album: Album {
             id: twr.album.id,
             title: twr.album.title,
@@ -190,6 +209,7 @@ impl LegacyQuery {
                 id: playlist_model.id,
                 name: playlist_model.name,
                 description: playlist_model.description,
+                keep_synced_to_plex: playlist_model.keep_synced_to_plex,
                 created_at: playlist_model.created_at,
                 updated_at: playlist_model.updated_at,
                 track_count: track_count as i64,
@@ -214,6 +234,7 @@ impl LegacyQuery {
             id: playlist_model.id,
             name: playlist_model.name,
             description: playlist_model.description,
+            keep_synced_to_plex: playlist_model.keep_synced_to_plex,
             created_at: playlist_model.created_at,
             updated_at: playlist_model.updated_at,
             track_count: track_count as i64,
@@ -248,49 +269,135 @@ impl LegacyQuery {
         })
     }
 
+    async fn playlist_entries(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        page: Option<i32>,
+        page_size: Option<i32>,
+    ) -> GraphqlResult<PlaylistEntriesResponse> {
+        let app_state = get_app_state(ctx)?;
+        let service = TrackService::new(app_state.db.clone());
+
+        let result = service
+            .list_playlist_entries(playlist_id, page, page_size)
+            .await?;
+
+        let entries: Vec<PlaylistEntry> = result
+            .items
+            .into_iter()
+            .map(|item| {
+                Ok(PlaylistEntry {
+                    id: item.entry_id,
+                    position: item.position,
+                    track: map_track_with_relations(item.track)?,
+                })
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+
+        Ok(PlaylistEntriesResponse {
+            entries,
+            total_count: result.total_count as i64,
+            page: result.page as i32,
+            page_size: result.page_size as i32,
+        })
+    }
+
     async fn plex_servers(&self, ctx: &Context<'_>) -> GraphqlResult<Vec<PlexServer>> {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
         let servers = service.list_servers().await?;
 
-        Ok(servers
+        Ok(servers.into_iter().map(PlexServer::from).collect())
+    }
+
+    /// Music library sections of a Plex server
+    async fn plex_music_sections(
+        &self,
+        ctx: &Context<'_>,
+        server_id: i64,
+    ) -> GraphqlResult<Vec<PlexMusicSection>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let sections = service.list_music_sections(server_id).await?;
+
+        Ok(sections
             .into_iter()
-            .map(|server| PlexServer {
-                id: server.id,
-                name: server.name,
-                server_url: server.server_url,
-                has_access_token: server.access_token.is_some(),
-                created_at: server.created_at,
-                updated_at: server.updated_at,
+            .map(|section| PlexMusicSection {
+                key: section.key,
+                title: section.title,
             })
             .collect())
     }
 
-    async fn plex_tracks(&self, ctx: &Context<'_>) -> GraphqlResult<PlexTracksResult> {
-        plex_track_queries::plex_tracks(ctx).await
+    /// Tracks of a Plex server's music section. The server may be omitted
+    /// when only one is configured, and the section to use its default.
+    async fn plex_tracks(
+        &self,
+        ctx: &Context<'_>,
+        server_id: Option<i64>,
+        section_id: Option<String>,
+    ) -> GraphqlResult<PlexTracksResult> {
+        plex_track_queries::plex_tracks(ctx, server_id, section_id).await
+    }
+
+    /// Music playlists of a Plex server. The server may be omitted when only
+    /// one is configured.
+    async fn plex_playlists(
+        &self,
+        ctx: &Context<'_>,
+        server_id: Option<i64>,
+    ) -> GraphqlResult<PlexPlaylistsResponse> {
+        plex_playlist_queries::plex_playlists(ctx, server_id).await
+    }
+
+    /// Where the local library folders are mounted on a Plex server
+    async fn plex_path_mappings(
+        &self,
+        ctx: &Context<'_>,
+        server_id: i64,
+    ) -> GraphqlResult<Vec<PlexPathMapping>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let mappings = service.path_mappings(server_id).await?;
+
+        Ok(mappings.into_iter().map(PlexPathMapping::from).collect())
     }
 
-    async fn plex_playlists(&self, ctx: &Context<'_>) -> GraphqlResult<PlexPlaylistsResponse> {
-        plex_playlist_queries::plex_playlists(ctx).await
+    /// Plex servers a playlist is synced to by default
+    async fn playlist_plex_servers(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+    ) -> GraphqlResult<Vec<PlexServer>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let servers = service.playlist_servers(playlist_id).await?;
+
+        Ok(servers.into_iter().map(PlexServer::from).collect())
     }
 }
 
 #[derive(Default, MergedObject)]
 pub struct Query(
     LegacyQuery,
+    FeedQuery,
     PlexLibraryRefreshQuery,
     SpotifyQuery,
+    TaskQuery,
     YoutubeQuery,
 );
 
 #[derive(Default, MergedObject)]
 pub struct Mutation(
+    FeedMutation,
     PlaylistMutation,
     SoulseekMutation,
     PlexServerMutation,
     PlexPlaylistMutation,
     PlexLibraryRefreshMutation,
     SpotifyMutation,
+    TaskMutation,
     YoutubeMutation,
 );
 
@@ -1,9 +1,15 @@
-use async_graphql::{Context, Object};
+use std::sync::Arc;
 
+use async_graphql::{Context, MaybeUndefined, Object};
+use color_eyre::eyre::OptionExt;
+
+use crate::database::Database;
+use crate::entities::smart_playlist::SmartPlaylistRules;
 use crate::http_server::graphql::context::get_app_state;
 use crate::http_server::graphql::playlist_queries::Playlist;
 use crate::http_server::graphql_error::GraphqlResult;
 use crate::services::playlist::PlaylistService;
+use crate::services::track::TrackService;
 
 #[derive(Default)]
 pub struct PlaylistMutation;
@@ -24,12 +30,41 @@ impl PlaylistMutation {
             id: model.id,
             name: model.name,
             description: model.description,
+            keep_synced_to_plex: model.keep_synced_to_plex,
             created_at: model.created_at,
             updated_at: model.updated_at,
             track_count: 0,
         })
     }
 
+    /// Creates a smart playlist whose tracks are computed from `rules`.
+    async fn create_smart_playlist(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        description: Option<String>,
+        rules: SmartPlaylistRules,
+    ) -> GraphqlResult<Playlist> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let model = service.create_smart(name, description, rules).await?;
+
+        playlist_with_track_count(db, model.id).await
+    }
+
+    async fn update_smart_playlist_rules(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        rules: SmartPlaylistRules,
+    ) -> GraphqlResult<Playlist> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let model = service.update_smart_rules(playlist_id, rules).await?;
+
+        playlist_with_track_count(db, model.id).await
+    }
+
     async fn add_track_to_playlist(
         &self,
         ctx: &Context<'_>,
@@ -41,4 +76,219 @@ impl PlaylistMutation {
         service.add_track(playlist_id, track_id).await?;
         Ok(true)
     }
+
+    /// Inserts a track at a zero-based position, shifting later entries down.
+    async fn insert_track_into_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        track_id: i64,
+        position: i32,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service
+            .insert_track_at(playlist_id, track_id, Some(position.max(0) as usize))
+            .await?;
+        Ok(true)
+    }
+
+    /// Moves a playlist entry to a zero-based position.
+    async fn move_playlist_entry(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        entry_id: i64,
+        position: i32,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service
+            .move_entry(playlist_id, entry_id, position.max(0) as usize)
+            .await?;
+        Ok(true)
+    }
+
+    /// Reorders a playlist. `entry_ids` must list every entry exactly once.
+    async fn reorder_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        entry_ids: Vec<i64>,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service.reorder(playlist_id, entry_ids).await?;
+        Ok(true)
+    }
+
+    /// Updates a playlist's name and/or description. Passing `description: null`
+    /// clears it; omitting it leaves it unchanged.
+    async fn update_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        name: Option<String>,
+        description: MaybeUndefined<String>,
+    ) -> GraphqlResult<Playlist> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let description = match description {
+            MaybeUndefined::Undefined => None,
+            MaybeUndefined::Null => Some(None),
+            MaybeUndefined::Value(description) => Some(Some(description)),
+        };
+        let model = service.update(playlist_id, name, description).await?;
+
+        playlist_with_track_count(db, model.id).await
+    }
+
+    async fn delete_playlist(&self, ctx: &Context<'_>, playlist_id: i64) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service.delete(playlist_id).await?;
+        Ok(true)
+    }
+
+    /// Copies a playlist and its entries. Defaults the name to "<name> (copy)".
+    async fn duplicate_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        name: Option<String>,
+    ) -> GraphqlResult<Playlist> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let model = service.duplicate(playlist_id, name).await?;
+
+        playlist_with_track_count(db, model.id).await
+    }
+
+    /// Appends the entries of the source playlists to the target playlist.
+    /// Returns the number of entries added.
+    async fn merge_playlists(
+        &self,
+        ctx: &Context<'_>,
+        target_playlist_id: i64,
+        source_playlist_ids: Vec<i64>,
+        #[graphql(default = true)] skip_existing: bool,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let added = service
+            .merge(target_playlist_id, source_playlist_ids, skip_existing)
+            .await?;
+        Ok(added as i64)
+    }
+
+    /// Removes a single entry from a playlist.
+    async fn remove_playlist_entry(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        entry_id: i64,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service.remove_entry(playlist_id, entry_id).await?;
+        Ok(true)
+    }
+
+    /// Removes every entry of a track from a playlist.
+    async fn remove_track_from_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        track_id: i64,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        service.remove_track(playlist_id, track_id).await?;
+        Ok(true)
+    }
+
+    /// Removes all entries from a playlist. Returns the number of entries removed.
+    async fn clear_playlist(&self, ctx: &Context<'_>, playlist_id: i64) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let removed = service.clear(playlist_id).await?;
+        Ok(removed as i64)
+    }
+
+    /// Appends tracks to a playlist in the given order. Returns the number of
+    /// entries added.
+    async fn add_tracks_to_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        track_ids: Vec<i64>,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let added = service.add_tracks(playlist_id, track_ids).await?;
+        Ok(added as i64)
+    }
+
+    /// Removes every entry of the given tracks from a playlist. Returns the
+    /// number of entries removed.
+    async fn remove_tracks_from_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        track_ids: Vec<i64>,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let removed = service.remove_tracks(playlist_id, track_ids).await?;
+        Ok(removed as i64)
+    }
+
+    /// Appends every track matching the track search query. Returns the
+    /// number of entries added.
+    async fn add_tracks_matching_search_to_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        search: String,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let added = service.add_tracks_matching(playlist_id, &search).await?;
+        Ok(added as i64)
+    }
+
+    /// Removes every entry whose track matches the track search query. Returns
+    /// the number of entries removed.
+    async fn remove_tracks_matching_search_from_playlist(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        search: String,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = PlaylistService::new(db.clone());
+        let removed = service.remove_tracks_matching(playlist_id, &search).await?;
+        Ok(removed as i64)
+    }
+}
+
+async fn playlist_with_track_count(
+    db: &Arc<Database>,
+    playlist_id: i64,
+) -> GraphqlResult<Playlist> {
+    let service = TrackService::new(db.clone());
+    let (model, track_count) = service
+        .get_playlist(playlist_id)
+        .await?
+        .ok_or_eyre("Playlist not found")?;
+
+    Ok(Playlist {
+        id: model.id,
+        name: model.name,
+        description: model.description,
+        keep_synced_to_plex: model.keep_synced_to_plex,
+        created_at: model.created_at,
+        updated_at: model.updated_at,
+        track_count: track_count as i64,
+    })
 }
@@ -1,16 +1,34 @@
-use async_graphql::SimpleObject;
+use async_graphql::{ComplexObject, Context, SimpleObject};
 use chrono::{DateTime, Utc};
 
+use crate::entities::smart_playlist::SmartPlaylistRules;
+use crate::http_server::graphql::context::get_app_state;
+use crate::http_server::graphql::track_queries::Track;
+use crate::http_server::graphql_error::GraphqlResult;
+use crate::services::smart_playlist;
+
 #[derive(Debug, Clone, SimpleObject)]
+#[graphql(complex)]
 pub struct Playlist {
     pub id: i64,
     pub name: String,
     pub description: Option<String>,
+    /// Re-synced to its Plex servers after imports
+    pub keep_synced_to_plex: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub track_count: i64,
 }
 
+#[ComplexObject]
+impl Playlist {
+    /// Rules of a smart playlist; `null` for a regular playlist
+    async fn smart_rules(&self, ctx: &Context<'_>) -> GraphqlResult<Option<SmartPlaylistRules>> {
+        let db = &get_app_state(ctx)?.db;
+        Ok(smart_playlist::find_rules(&db.conn, self.id).await?)
+    }
+}
+
 #[derive(Debug, Clone, SimpleObject)]
 pub struct PlaylistsResponse {
     pub playlists: Vec<Playlist>,
@@ -18,3 +36,19 @@ pub struct PlaylistsResponse {
     pub page: i32,
     pub page_size: i32,
 }
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlaylistEntry {
+    /// Entry ID used by the reorder mutations; `null` for smart playlists
+    pub id: Option<i64>,
+    pub position: i32,
+    pub track: Track,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlaylistEntriesResponse {
+    pub entries: Vec<PlaylistEntry>,
+    pub total_count: i64,
+    pub page: i32,
+    pub page_size: i32,
+}
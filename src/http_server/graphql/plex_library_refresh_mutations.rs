@@ -17,19 +17,32 @@ pub struct PlexLibraryRefreshMutation;
 
 #[Object]
 impl PlexLibraryRefreshMutation {
-    /// Trigger a refresh/rescan of the music library on a Plex server
+    /// Trigger a refresh/rescan of a music library section on a Plex server,
+    /// by default the server's music section. With `path`, only that local
+    /// folder (e.g. an album folder) is scanned, at its path on the server.
     async fn refresh_music_library(
         &self,
         ctx: &Context<'_>,
         plex_server_id: i64,
+        section_id: Option<String>,
+        path: Option<String>,
     ) -> GraphqlResult<RefreshLibraryResult> {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
-        let music_section_id = service.refresh_music_library(plex_server_id).await?;
+        let music_section_id = service
+            .refresh_music_library(plex_server_id, section_id, path.clone())
+            .await?;
+        let message = match path {
+            Some(path) => format!(
+                "Library refresh started for {} in section {}",
+                path, music_section_id
+            ),
+            None => format!("Library refresh started for section {}", music_section_id),
+        };
 
         Ok(RefreshLibraryResult {
             success: true,
-            message: format!("Library refresh started for section {}", music_section_id),
+            message,
             section_id: music_section_id,
         })
     }
@@ -18,15 +18,17 @@ pub struct PlexLibraryRefreshQuery;
 
 #[Object]
 impl PlexLibraryRefreshQuery {
-    /// Get the current scan status for the music library on a Plex server
+    /// Get the current scan status for a music library section on a Plex
+    /// server, by default the server's music section
     async fn music_library_scan_status(
         &self,
         ctx: &Context<'_>,
         plex_server_id: i64,
+        section_id: Option<String>,
     ) -> GraphqlResult<LibraryScanStatus> {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
-        let activity = service.get_scan_status(plex_server_id).await?;
+        let activity = service.get_scan_status(plex_server_id, section_id).await?;
 
         Ok(match activity {
             Some(activity) => {
@@ -1,17 +1,63 @@
 use async_graphql::{Context, Object, SimpleObject};
 
 use crate::http_server::graphql::context::get_app_state;
+use crate::http_server::graphql::plex_server_queries::PlexServer;
 use crate::http_server::graphql_error::GraphqlResult;
-use crate::plex_rs::sync_playlist::MissingTrack;
-use crate::services::plex::PlexService;
+use crate::plex_rs::import_playlist::ImportedPlexPlaylist;
+use crate::plex_rs::sync_playlist::{ConflictPolicy, MissingTrack, SyncPlaylistResult};
 use crate::services::plex::client::PlexHttpAdapter;
+use crate::services::plex::{PlexService, ServerSyncOutcome};
 
+/// Totals over every server the playlist was synced to
 #[derive(Debug, Clone, SimpleObject)]
 pub struct SyncPlaylistToPlexResult {
+    /// Tracks missing from at least one server
     pub missing_tracks: Vec<MissingTrackInfo>,
     pub tracks_added: u32,
     pub tracks_removed: u32,
+    pub tracks_moved: u32,
     pub tracks_skipped: u32,
+    /// Changes made in Plex were brought to the local playlist
+    pub local_playlist_updated: bool,
+    pub servers: Vec<PlexServerSyncResult>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlexServerSyncResult {
+    pub server_id: i64,
+    pub server_name: String,
+    /// Why the sync to this server failed
+    pub error: Option<String>,
+    pub missing_tracks: Vec<MissingTrackInfo>,
+    pub tracks_added: u32,
+    pub tracks_removed: u32,
+    pub tracks_moved: u32,
+    pub tracks_skipped: u32,
+    pub local_playlist_updated: bool,
+}
+
+impl From<ServerSyncOutcome> for PlexServerSyncResult {
+    fn from(outcome: ServerSyncOutcome) -> Self {
+        let (error, result) = match outcome.result {
+            Ok(result) => (None, result),
+            Err(e) => (Some(e.to_string()), SyncPlaylistResult::default()),
+        };
+        PlexServerSyncResult {
+            server_id: outcome.server.id,
+            server_name: outcome.server.name,
+            error,
+            missing_tracks: result
+                .missing_tracks
+                .into_iter()
+                .map(MissingTrackInfo::from)
+                .collect(),
+            tracks_added: result.tracks_added,
+            tracks_removed: result.tracks_removed,
+            tracks_moved: result.tracks_moved,
+            tracks_skipped: result.tracks_skipped,
+            local_playlist_updated: result.local_updated,
+        }
+    }
 }
 
 #[derive(Debug, Clone, SimpleObject)]
@@ -31,30 +77,138 @@ impl From<MissingTrack> for MissingTrackInfo {
     }
 }
 
+/// A Plex playlist imported into a local playlist
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ImportPlexPlaylistResult {
+    /// The local playlist, null when the import failed
+    pub playlist_id: Option<i64>,
+    pub plex_rating_key: String,
+    pub title: String,
+    pub tracks_imported: u32,
+    /// Plex tracks not found in the local library, as "Artist - Title"
+    pub missing_tracks: Vec<String>,
+    /// Why the playlist could not be imported
+    pub error: Option<String>,
+}
+
+impl From<ImportedPlexPlaylist> for ImportPlexPlaylistResult {
+    fn from(imported: ImportedPlexPlaylist) -> Self {
+        ImportPlexPlaylistResult {
+            playlist_id: imported.playlist_id,
+            plex_rating_key: imported.plex_rating_key,
+            title: imported.title,
+            tracks_imported: imported.tracks_imported,
+            missing_tracks: imported.missing_tracks,
+            error: imported.error,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct PlexPlaylistMutation;
 
 #[Object]
 impl PlexPlaylistMutation {
-    /// Sync a database playlist to Plex
+    /// Sync a database playlist to Plex servers: the given ones, otherwise the
+    /// playlist's servers, otherwise the only configured server. `two_way` and
+    /// `conflict_policy` override the configured playlist sync for this sync.
     async fn sync_playlist_to_plex(
         &self,
         ctx: &Context<'_>,
         playlist_id: i64,
+        server_ids: Option<Vec<i64>>,
+        two_way: Option<bool>,
+        conflict_policy: Option<ConflictPolicy>,
     ) -> GraphqlResult<SyncPlaylistToPlexResult> {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
-        let result = service.sync_playlist(playlist_id).await?;
+        let mut options = app_state.config.plex_playlist_sync().clone();
+        if let Some(two_way) = two_way {
+            options.two_way = two_way;
+        }
+        if let Some(conflict_policy) = conflict_policy {
+            options.conflict_policy = conflict_policy;
+        }
+        let servers: Vec<PlexServerSyncResult> = service
+            .sync_playlist(
+                playlist_id,
+                server_ids,
+                app_state.config.matcher(),
+                &options,
+            )
+            .await?
+            .into_iter()
+            .map(PlexServerSyncResult::from)
+            .collect();
+
+        let mut missing_tracks: Vec<MissingTrackInfo> = Vec::new();
+        for track in servers.iter().flat_map(|server| &server.missing_tracks) {
+            if !missing_tracks.iter().any(|t| t.track_id == track.track_id) {
+                missing_tracks.push(track.clone());
+            }
+        }
 
         Ok(SyncPlaylistToPlexResult {
-            missing_tracks: result
-                .missing_tracks
-                .into_iter()
-                .map(MissingTrackInfo::from)
-                .collect(),
-            tracks_added: result.tracks_added,
-            tracks_removed: result.tracks_removed,
-            tracks_skipped: result.tracks_skipped,
+            missing_tracks,
+            tracks_added: servers.iter().map(|server| server.tracks_added).sum(),
+            tracks_removed: servers.iter().map(|server| server.tracks_removed).sum(),
+            tracks_moved: servers.iter().map(|server| server.tracks_moved).sum(),
+            tracks_skipped: servers.iter().map(|server| server.tracks_skipped).sum(),
+            local_playlist_updated: servers.iter().any(|server| server.local_playlist_updated),
+            servers,
         })
     }
+
+    /// Import music playlists of a Plex server into local playlists: the
+    /// given ones, otherwise all of them. Re-importing a playlist replaces the
+    /// tracks of the local playlist it was imported into before.
+    async fn import_plex_playlists(
+        &self,
+        ctx: &Context<'_>,
+        server_id: Option<i64>,
+        plex_playlist_rating_keys: Option<Vec<String>>,
+    ) -> GraphqlResult<Vec<ImportPlexPlaylistResult>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let imported = service
+            .import_playlists(
+                server_id,
+                plex_playlist_rating_keys,
+                app_state.config.matcher(),
+            )
+            .await?;
+        Ok(imported
+            .into_iter()
+            .map(ImportPlexPlaylistResult::from)
+            .collect())
+    }
+
+    /// Choose the Plex servers a playlist is synced to by default
+    async fn set_playlist_plex_servers(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        server_ids: Vec<i64>,
+    ) -> GraphqlResult<Vec<PlexServer>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let servers = service
+            .set_playlist_servers(playlist_id, server_ids)
+            .await?;
+        Ok(servers.into_iter().map(PlexServer::from).collect())
+    }
+
+    /// Re-sync a playlist to its Plex servers whenever imported tracks were
+    /// scanned by Plex, or stop doing so. Returns the new setting.
+    async fn set_playlist_keep_synced_to_plex(
+        &self,
+        ctx: &Context<'_>,
+        playlist_id: i64,
+        keep_synced: bool,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let playlist = service.set_keep_synced(playlist_id, keep_synced).await?;
+        Ok(playlist.keep_synced_to_plex)
+    }
 }
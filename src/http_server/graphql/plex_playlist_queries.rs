@@ -20,11 +20,14 @@ pub struct PlexPlaylistsResponse {
     pub playlists: Vec<PlexPlaylist>,
 }
 
-/// Fetch all music playlists from Plex
-pub async fn plex_playlists(ctx: &Context<'_>) -> GraphqlResult<PlexPlaylistsResponse> {
+/// Fetch all music playlists from a Plex server
+pub async fn plex_playlists(
+    ctx: &Context<'_>,
+    server_id: Option<i64>,
+) -> GraphqlResult<PlexPlaylistsResponse> {
     let app_state = get_app_state(ctx)?;
     let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
-    let plex_playlists = service.get_playlists().await?;
+    let plex_playlists = service.get_playlists(server_id).await?;
 
     let music_playlists: Vec<PlexPlaylist> = plex_playlists
         .into_iter()
@@ -1,19 +1,30 @@
-use async_graphql::{Context, Object};
+use async_graphql::{Context, Object, SimpleObject};
 
 use crate::http_server::graphql::context::get_app_state;
-use crate::http_server::graphql::plex_server_queries::{AuthResponse, PlexServer};
+use crate::http_server::graphql::plex_server_queries::{
+    AuthResponse, PlexPathMapping, PlexPathMappingInput, PlexServer,
+};
 use crate::http_server::graphql_error::GraphqlResult;
+use crate::plex_rs::track_mapping::TrackMappingRefreshResult;
 use crate::services::plex::PlexService;
 use crate::services::plex::client::PlexHttpAdapter;
 
-fn map_server(m: crate::entities::plex_server::Model) -> PlexServer {
-    PlexServer {
-        id: m.id,
-        name: m.name,
-        server_url: m.server_url,
-        has_access_token: m.access_token.is_some(),
-        created_at: m.created_at,
-        updated_at: m.updated_at,
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlexTrackMappingRefresh {
+    pub tracks_scanned: u32,
+    pub tracks_mapped: u32,
+    pub tracks_unmatched: u32,
+    pub mappings_removed: u32,
+}
+
+impl From<TrackMappingRefreshResult> for PlexTrackMappingRefresh {
+    fn from(result: TrackMappingRefreshResult) -> Self {
+        PlexTrackMappingRefresh {
+            tracks_scanned: result.tracks_scanned,
+            tracks_mapped: result.tracks_mapped,
+            tracks_unmatched: result.tracks_unmatched,
+            mappings_removed: result.mappings_removed,
+        }
     }
 }
 
@@ -31,7 +42,7 @@ impl PlexServerMutation {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
         let server_model = service.create_server(name, server_url).await?;
-        Ok(map_server(server_model))
+        Ok(server_model.into())
     }
 
     async fn authenticate_plex_server(
@@ -56,6 +67,76 @@ impl PlexServerMutation {
         let app_state = get_app_state(ctx)?;
         let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
         let updated_server = service.complete_authentication(server_id, pin_id).await?;
-        Ok(map_server(updated_server))
+        Ok(updated_server.into())
+    }
+
+    /// Match the server's Plex tracks to local tracks for playlist syncs.
+    /// Only tracks updated since the last refresh are matched unless `full`
+    /// is set; a full refresh also forgets tracks deleted from Plex.
+    async fn refresh_plex_track_mappings(
+        &self,
+        ctx: &Context<'_>,
+        server_id: i64,
+        #[graphql(default = false)] full: bool,
+    ) -> GraphqlResult<PlexTrackMappingRefresh> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let result = service
+            .refresh_track_mappings(server_id, full, app_state.config.matcher())
+            .await?;
+        Ok(result.into())
+    }
+
+    /// Import play counts, last played times and ratings from Plex into the
+    /// local tracks. Only tracks played since the last import are fetched
+    /// unless `full` is set; a full import also picks up changed ratings.
+    async fn import_plex_play_history(
+        &self,
+        ctx: &Context<'_>,
+        server_id: Option<i64>,
+        #[graphql(default = false)] full: bool,
+    ) -> GraphqlResult<PlexTrackMappingRefresh> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let result = service
+            .import_play_history(server_id, full, app_state.config.matcher())
+            .await?;
+        Ok(result.into())
+    }
+
+    /// Replace the path mappings of a server, used wherever file paths are
+    /// compared with or sent to Plex. The next track mapping refresh is full.
+    async fn set_plex_path_mappings(
+        &self,
+        ctx: &Context<'_>,
+        server_id: i64,
+        mappings: Vec<PlexPathMappingInput>,
+    ) -> GraphqlResult<Vec<PlexPathMapping>> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let mappings = service
+            .set_path_mappings(
+                server_id,
+                mappings
+                    .into_iter()
+                    .map(|mapping| (mapping.local_prefix, mapping.plex_prefix))
+                    .collect(),
+            )
+            .await?;
+        Ok(mappings.into_iter().map(PlexPathMapping::from).collect())
+    }
+
+    /// Set the music library section a server uses when an operation doesn't
+    /// name one. Pass no section to use the first music section again.
+    async fn set_plex_server_music_section(
+        &self,
+        ctx: &Context<'_>,
+        server_id: i64,
+        section_id: Option<String>,
+    ) -> GraphqlResult<PlexServer> {
+        let app_state = get_app_state(ctx)?;
+        let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+        let server = service.set_music_section(server_id, section_id).await?;
+        Ok(server.into())
     }
 }
@@ -1,4 +1,4 @@
-use async_graphql::SimpleObject;
+use async_graphql::{InputObject, SimpleObject};
 use chrono::{DateTime, Utc};
 
 #[derive(Debug, Clone, SimpleObject)]
@@ -7,10 +7,57 @@ pub struct PlexServer {
     pub name: String,
     pub server_url: String,
     pub has_access_token: bool,
+    /// Music library section used when none is given
+    pub music_section_id: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl From<crate::entities::plex_server::Model> for PlexServer {
+    fn from(m: crate::entities::plex_server::Model) -> Self {
+        PlexServer {
+            id: m.id,
+            name: m.name,
+            server_url: m.server_url,
+            has_access_token: m.access_token.is_some(),
+            music_section_id: m.music_section_id,
+            created_at: m.created_at,
+            updated_at: m.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlexMusicSection {
+    /// Library section ID
+    pub key: String,
+    pub title: String,
+}
+
+/// Where a local library folder is mounted on a Plex server
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PlexPathMapping {
+    /// e.g. `/music`
+    pub local_prefix: String,
+    /// e.g. `/data/media/music`
+    pub plex_prefix: String,
+}
+
+impl From<crate::entities::plex_path_mapping::Model> for PlexPathMapping {
+    fn from(m: crate::entities::plex_path_mapping::Model) -> Self {
+        PlexPathMapping {
+            local_prefix: m.local_prefix,
+            plex_prefix: m.plex_prefix,
+        }
+    }
+}
+
+#[derive(Debug, Clone, InputObject)]
+pub struct PlexPathMappingInput {
+    pub local_prefix: String,
+    pub plex_prefix: String,
+}
+
 #[derive(Debug, Clone, SimpleObject)]
 pub struct AuthResponse {
     pub auth_url: String,
@@ -41,13 +41,17 @@ pub enum PlexTracksResult {
     Error(PlexTracksError),
 }
 
-/// Fetch up to 50 tracks from the configured Plex server.
+/// Fetch up to 50 tracks from a Plex server's music section.
 /// Returns a union type that can be either success or one of several error types.
-pub async fn plex_tracks(ctx: &Context<'_>) -> GraphqlResult<PlexTracksResult> {
+pub async fn plex_tracks(
+    ctx: &Context<'_>,
+    server_id: Option<i64>,
+    section_id: Option<String>,
+) -> GraphqlResult<PlexTracksResult> {
     let app_state = get_app_state(ctx)?;
     let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
 
-    let outcome = service.get_tracks().await?;
+    let outcome = service.get_tracks(server_id, section_id).await?;
 
     Ok(match outcome {
         PlexTracksOutcome::NoServer => PlexTracksResult::NoPlexServer(NoPlexServerError {
@@ -56,7 +60,7 @@ pub async fn plex_tracks(ctx: &Context<'_>) -> GraphqlResult<PlexTracksResult> {
         PlexTracksOutcome::MultipleServers(count) => {
             PlexTracksResult::MultiplePlexServers(MultiplePlexServersError {
                 message: format!(
-                    "Multiple Plex servers found ({}). Only one server is supported at a time.",
+                    "Multiple Plex servers found ({}). Please choose a server.",
                     count
                 ),
                 server_count: count as i32,
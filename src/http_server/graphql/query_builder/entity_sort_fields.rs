@@ -1,4 +1,5 @@
 use async_graphql::{Enum, InputObject};
+use serde::{Deserialize, Serialize};
 
 use super::{SortOrder, SortableField};
 use crate::entities;
@@ -7,8 +8,9 @@ use crate::entities;
 // Entity-Specific Sort Field Enums
 // ============================================================================
 
-#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 #[graphql(name = "TrackSortField")]
+#[serde(rename_all = "snake_case")]
 pub enum TrackSortField {
     Id,
     Title,
@@ -16,6 +18,14 @@ pub enum TrackSortField {
     Duration,
     CreatedAt,
     UpdatedAt,
+    /// Spotify popularity, from enrichment
+    Popularity,
+    Tempo,
+    Energy,
+    /// Plays on Plex, from the imported play history
+    PlayCount,
+    LastPlayedAt,
+    Rating,
 }
 
 #[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
@@ -86,6 +96,12 @@ impl SortableField for TrackSortField {
             TrackSortField::Duration => entities::track::Column::Duration,
             TrackSortField::CreatedAt => entities::track::Column::CreatedAt,
             TrackSortField::UpdatedAt => entities::track::Column::UpdatedAt,
+            TrackSortField::Popularity => entities::track::Column::SpotifyPopularity,
+            TrackSortField::Tempo => entities::track::Column::Tempo,
+            TrackSortField::Energy => entities::track::Column::Energy,
+            TrackSortField::PlayCount => entities::track::Column::PlayCount,
+            TrackSortField::LastPlayedAt => entities::track::Column::LastPlayedAt,
+            TrackSortField::Rating => entities::track::Column::Rating,
         }
     }
 
@@ -1,5 +1,6 @@
 use async_graphql::{Enum, InputObject};
 use sea_orm::{ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
 
 #[allow(unused_imports)] // Used in tests
 use crate::entities;
@@ -11,7 +12,8 @@ pub use entity_sort_fields::*;
 // GraphQL Input Types
 // ============================================================================
 
-#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SortOrder {
     Asc,
     Desc,
@@ -139,6 +141,12 @@ where
         return query;
     }
 
+    query.filter(text_search_condition(columns, search_term))
+}
+
+/// Build the case-insensitive substring condition used by the text search helpers,
+/// matching if the search term appears in any of the provided columns.
+pub fn text_search_condition(columns: Vec<impl ColumnTrait>, search_term: &str) -> Condition {
     let pattern = format!("%{}%", search_term);
     let mut condition = Condition::any();
 
@@ -146,7 +154,7 @@ where
         condition = condition.add(column.like(&pattern));
     }
 
-    query.filter(condition)
+    condition
 }
 
 // ============================================================================
@@ -1,16 +1,19 @@
-use super::spotify_queries::SpotifyAccount;
-use crate::entities;
+use super::spotify_queries::{SpotifyAccount, map_spotify_account};
 use crate::http_server::graphql::context::get_app_state;
 use crate::http_server::graphql::spotify::context::get_spotify_adapter;
 use crate::http_server::graphql_error::GraphqlResult;
 use crate::services::spotify::client::start_spotify_auth_flow;
-use crate::services::spotify::matching_local_tracks::match_existing_spotify_tracks_with_local_task;
-use crate::services::spotify::sync_spotify_playlist_to_local_library::sync_spotify_playlist_to_local_library_task;
+use crate::services::spotify::download_failures::{
+    RetryFilter, ScheduledRetries, retry_download_failures, set_wont_find,
+};
+use crate::services::spotify::matching_local_tracks::{
+    enrich_local_tracks_from_spotify_task, match_existing_spotify_tracks_with_local_task,
+};
+use crate::services::spotify::sync_spotify_playlist_to_local_library::{
+    resume_spotify_playlist_sync_task, sync_spotify_playlist_to_local_library_task,
+};
 use async_graphql::{Context, Object};
-use chrono::{DateTime, Utc};
 use color_eyre::eyre::OptionExt;
-use color_eyre::eyre::WrapErr;
-use sea_orm::EntityTrait;
 
 #[derive(Default)]
 pub struct SpotifyMutation;
@@ -20,6 +23,24 @@ pub struct SpotifyAuthResponse {
     pub redirect_url: String,
 }
 
+/// Failed track downloads made due for retry.
+#[derive(async_graphql::SimpleObject)]
+pub struct ScheduledDownloadRetries {
+    pub failures_scheduled: i64,
+    /// Playlist syncs resumed right away. Running syncs are resumed by the
+    /// periodic retry once they finished.
+    pub resumed_sync_state_ids: Vec<i64>,
+}
+
+impl From<ScheduledRetries> for ScheduledDownloadRetries {
+    fn from(scheduled: ScheduledRetries) -> Self {
+        Self {
+            failures_scheduled: scheduled.failures as i64,
+            resumed_sync_state_ids: scheduled.resumed_sync_state_ids,
+        }
+    }
+}
+
 #[Object]
 impl SpotifyMutation {
     /// Initiate Spotify OAuth flow
@@ -63,16 +84,10 @@ impl SpotifyMutation {
         let account_model = service
             .complete_auth(session, auth_code, csrf_state)
             .await?;
+        // Drop any client built from the account's previous tokens
+        app_state.spotify_tokens.forget(account_model.id).await;
 
-        Ok(SpotifyAccount {
-            id: account_model.id,
-            user_id: account_model.user_id,
-            display_name: account_model.display_name,
-            created_at: DateTime::<Utc>::from_timestamp(account_model.created_at, 0)
-                .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
-            updated_at: DateTime::<Utc>::from_timestamp(account_model.updated_at, 0)
-                .ok_or_eyre("Failed to convert updated_at to DateTime<Utc>")?,
-        })
+        Ok(map_spotify_account(account_model)?)
     }
 
     async fn delete_spotify_account(
@@ -84,6 +99,7 @@ impl SpotifyMutation {
         let service =
             crate::services::spotify::account::SpotifyAccountService::new(app_state.db.clone());
         service.delete_account(account_id).await?;
+        app_state.spotify_tokens.forget(account_id).await;
         Ok(true)
     }
 
@@ -94,12 +110,7 @@ impl SpotifyMutation {
     ) -> GraphqlResult<bool> {
         let app_state = get_app_state(ctx)?;
         let db = &app_state.db;
-        let spotify_account = entities::spotify_account::Entity::find_by_id(account_id)
-            .one(&db.conn)
-            .await
-            .wrap_err("Failed to fetch spotify account")?
-            .ok_or_eyre("Spotify account not found")?;
-        let adapter = get_spotify_adapter(app_state, spotify_account).await?;
+        let adapter = get_spotify_adapter(app_state, account_id).await?;
 
         let service = crate::services::spotify::sync::SpotifySyncService::new(db.clone(), adapter);
         service.sync_account_playlists(account_id).await?;
@@ -115,16 +126,10 @@ impl SpotifyMutation {
         local_playlist_name: String,
     ) -> GraphqlResult<bool> {
         let app_state = get_app_state(ctx)?;
-        let db = app_state.db.clone();
-        let soulseek_context = app_state.soulseek_context.clone();
-        let api_key = &app_state.api_key;
-        let config = &app_state.config;
 
         sync_spotify_playlist_to_local_library_task(
-            db,
-            soulseek_context,
-            api_key,
-            config,
+            &app_state.db,
+            &app_state.tasks,
             spotify_account_id,
             spotify_playlist_id,
             local_playlist_name,
@@ -134,6 +139,87 @@ impl SpotifyMutation {
         Ok(true)
     }
 
+    /// Resume an interrupted, failed or cancelled playlist sync. Tracks that
+    /// were already synced are skipped.
+    async fn resume_spotify_playlist_sync(
+        &self,
+        ctx: &Context<'_>,
+        sync_state_id: i64,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+
+        resume_spotify_playlist_sync_task(&app_state.db, &app_state.tasks, sync_state_id).await?;
+
+        Ok(true)
+    }
+
+    /// Retry a failed track download now instead of waiting for its back-off,
+    /// resuming the playlist's sync unless it is running. Returns false if the
+    /// failure is marked "won't find".
+    async fn retry_spotify_track_download_failure(
+        &self,
+        ctx: &Context<'_>,
+        failure_id: i64,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        let scheduled =
+            retry_download_failures(&app_state.db, &app_state.tasks, RetryFilter::Id(failure_id))
+                .await?;
+        Ok(scheduled.failures > 0)
+    }
+
+    /// Retry all failed track downloads, optionally only those of one playlist.
+    async fn retry_all_spotify_track_download_failures(
+        &self,
+        ctx: &Context<'_>,
+        spotify_playlist_id: Option<i64>,
+    ) -> GraphqlResult<ScheduledDownloadRetries> {
+        let app_state = get_app_state(ctx)?;
+        let scheduled = retry_download_failures(
+            &app_state.db,
+            &app_state.tasks,
+            RetryFilter::All {
+                spotify_playlist_id,
+            },
+        )
+        .await?;
+        Ok(scheduled.into())
+    }
+
+    /// Retry failed track downloads with the given reason, optionally only
+    /// those of one playlist.
+    async fn retry_spotify_track_download_failures_by_reason(
+        &self,
+        ctx: &Context<'_>,
+        reason: String,
+        spotify_playlist_id: Option<i64>,
+    ) -> GraphqlResult<ScheduledDownloadRetries> {
+        let app_state = get_app_state(ctx)?;
+        let scheduled = retry_download_failures(
+            &app_state.db,
+            &app_state.tasks,
+            RetryFilter::Reason {
+                reason,
+                spotify_playlist_id,
+            },
+        )
+        .await?;
+        Ok(scheduled.into())
+    }
+
+    /// Mark a failed track as "won't find" so it is no longer retried, or
+    /// unmark it
+    async fn set_spotify_track_download_failure_wont_find(
+        &self,
+        ctx: &Context<'_>,
+        failure_id: i64,
+        wont_find: bool,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        set_wont_find(&app_state.db, failure_id, wont_find).await?;
+        Ok(true)
+    }
+
     async fn match_existing_spotify_tracks_with_local_tracks(
         &self,
         ctx: &Context<'_>,
@@ -143,7 +229,30 @@ impl SpotifyMutation {
             crate::services::spotify::matching::SpotifyMatchingService::new(app_state.db.clone());
         let spotify_tracks = service.list_unmatched_spotify_tracks().await?;
 
-        match_existing_spotify_tracks_with_local_task(app_state.db.clone(), spotify_tracks).await?;
+        match_existing_spotify_tracks_with_local_task(
+            &app_state.tasks,
+            app_state.config.matcher().clone(),
+            spotify_tracks,
+        )
+        .await?;
+        Ok(true)
+    }
+
+    /// Look up local tracks without a Spotify link on Spotify, storing their
+    /// Spotify ID, popularity and audio features. Uses the account's token.
+    async fn enrich_local_tracks_from_spotify(
+        &self,
+        ctx: &Context<'_>,
+        spotify_account_id: i64,
+    ) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+
+        enrich_local_tracks_from_spotify_task(
+            &app_state.tasks,
+            spotify_account_id,
+            app_state.config.matcher().clone(),
+        )
+        .await?;
         Ok(true)
     }
 
@@ -185,4 +294,43 @@ impl SpotifyMutation {
             .await?;
         Ok(true)
     }
+
+    /// Unlink a wrongly matched Spotify track from its local track
+    async fn unlink_spotify_track(
+        &self,
+        ctx: &Context<'_>,
+        spotify_track_id: String,
+    ) -> GraphqlResult<bool> {
+        let db = &get_app_state(ctx)?.db;
+        let service = crate::services::spotify::matching::SpotifyMatchingService::new(db.clone());
+        service.unlink(&spotify_track_id).await?;
+        Ok(true)
+    }
+
+    /// Undo a match decision from the history, restoring the previous link and
+    /// candidate statuses. Returns the ID of the decision recording the revert.
+    async fn revert_spotify_match_decision(
+        &self,
+        ctx: &Context<'_>,
+        decision_id: i64,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = crate::services::spotify::matching::SpotifyMatchingService::new(db.clone());
+        let revert = service.revert_decision(decision_id).await?;
+        Ok(revert.id)
+    }
+
+    /// Revert auto-accepted matches and dismiss their candidates — all of
+    /// them, or only the given candidates. Returns the number of reverted
+    /// candidates.
+    async fn revert_auto_accepted_spotify_matches(
+        &self,
+        ctx: &Context<'_>,
+        candidate_ids: Option<Vec<i64>>,
+    ) -> GraphqlResult<i64> {
+        let db = &get_app_state(ctx)?.db;
+        let service = crate::services::spotify::matching::SpotifyMatchingService::new(db.clone());
+        let reverted = service.revert_auto_accepted(candidate_ids).await?;
+        Ok(reverted as i64)
+    }
 }
@@ -2,6 +2,9 @@ use async_graphql::{Context, Object};
 use chrono::{DateTime, Utc};
 use color_eyre::eyre::OptionExt;
 
+use crate::entities;
+use crate::entities::spotify_account::AccountStatus;
+use crate::entities::spotify_match_decision::{MatchDecisionAction, MatchDecisionActor};
 use crate::http_server::graphql::context::get_app_state;
 use crate::http_server::graphql::map_track_with_relations;
 use crate::http_server::graphql::track_queries::Track;
@@ -19,6 +22,34 @@ pub struct SpotifyAccount {
     pub display_name: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Whether the account can be synced or has to be authorized again
+    pub status: AccountStatus,
+    /// Error from the last failed token refresh
+    pub last_error: Option<String>,
+    pub last_refreshed_at: Option<DateTime<Utc>>,
+}
+
+pub(super) fn map_spotify_account(
+    account: entities::spotify_account::Model,
+) -> color_eyre::Result<SpotifyAccount> {
+    Ok(SpotifyAccount {
+        id: account.id,
+        user_id: account.user_id,
+        display_name: account.display_name,
+        created_at: DateTime::from_timestamp(account.created_at, 0)
+            .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
+        updated_at: DateTime::from_timestamp(account.updated_at, 0)
+            .ok_or_eyre("Failed to convert updated_at to DateTime<Utc>")?,
+        status: account.status,
+        last_error: account.last_error,
+        last_refreshed_at: account
+            .last_refreshed_at
+            .map(|last_refreshed_at| {
+                DateTime::from_timestamp(last_refreshed_at, 0)
+                    .ok_or_eyre("Failed to convert last_refreshed_at to DateTime<Utc>")
+            })
+            .transpose()?,
+    })
 }
 
 #[derive(async_graphql::SimpleObject)]
@@ -57,6 +88,9 @@ pub struct SpotifyTrackDownloadFailure {
     pub attempts_count: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the track is next retried automatically
+    pub next_retry_at: Option<DateTime<Utc>>,
+    pub wont_find: bool,
 }
 
 #[derive(async_graphql::SimpleObject)]
@@ -70,6 +104,9 @@ pub struct SpotifyMatchedTrack {
     pub spotify_created_at: DateTime<Utc>,
     pub spotify_updated_at: DateTime<Utc>,
     pub local_track: Track,
+    /// Set when the match was linked by the auto-accept policy
+    pub auto_accepted_candidate_id: Option<i64>,
+    pub auto_accepted_score: Option<f64>,
 }
 
 #[derive(async_graphql::SimpleObject)]
@@ -112,6 +149,31 @@ pub struct SpotifyUnmatchedTracksResponse {
     pub page_size: i32,
 }
 
+#[derive(async_graphql::SimpleObject)]
+pub struct SpotifyMatchDecision {
+    pub id: i64,
+    pub spotify_track_id: String,
+    pub spotify_title: String,
+    pub spotify_artists: Vec<String>,
+    pub action: MatchDecisionAction,
+    pub actor: MatchDecisionActor,
+    pub candidate_id: Option<i64>,
+    pub previous_local_track_id: Option<i64>,
+    pub local_track_id: Option<i64>,
+    pub dismissed_candidate_ids: Vec<i64>,
+    pub reverts_decision_id: Option<i64>,
+    pub reverted_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(async_graphql::SimpleObject)]
+pub struct SpotifyMatchDecisionsResponse {
+    pub decisions: Vec<SpotifyMatchDecision>,
+    pub total_count: i64,
+    pub page: i32,
+    pub page_size: i32,
+}
+
 #[derive(async_graphql::SimpleObject)]
 pub struct SearchLocalTracksResponse {
     pub tracks: Vec<Track>,
@@ -132,17 +194,7 @@ impl SpotifyQuery {
 
         accounts
             .into_iter()
-            .map(|account| {
-                Ok(SpotifyAccount {
-                    id: account.id,
-                    user_id: account.user_id,
-                    display_name: account.display_name,
-                    created_at: DateTime::from_timestamp(account.created_at, 0)
-                        .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
-                    updated_at: DateTime::from_timestamp(account.updated_at, 0)
-                        .ok_or_eyre("Failed to convert updated_at to DateTime<Utc>")?,
-                })
-            })
+            .map(|account| Ok(map_spotify_account(account)?))
             .collect::<GraphqlResult<Vec<SpotifyAccount>>>()
     }
 
@@ -223,18 +275,28 @@ impl SpotifyQuery {
                         .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
                     updated_at: DateTime::from_timestamp(failure.updated_at, 0)
                         .ok_or_eyre("Failed to convert updated_at to DateTime<Utc>")?,
+                    next_retry_at: failure
+                        .next_retry_at
+                        .map(|next_retry_at| {
+                            DateTime::from_timestamp(next_retry_at, 0)
+                                .ok_or_eyre("Failed to convert next_retry_at to DateTime<Utc>")
+                        })
+                        .transpose()?,
+                    wont_find: failure.wont_find,
                 })
             })
             .collect::<GraphqlResult<Vec<SpotifyTrackDownloadFailure>>>()
     }
 
-    /// Get matched Spotify tracks with their local track information
+    /// Get matched Spotify tracks with their local track information,
+    /// optionally only those linked by the auto-accept policy
     async fn spotify_matched_tracks(
         &self,
         ctx: &Context<'_>,
         page: Option<i32>,
         page_size: Option<i32>,
         search: Option<String>,
+        auto_accepted_only: Option<bool>,
     ) -> GraphqlResult<SpotifyMatchedTracksResponse> {
         let app_state = get_app_state(ctx)?;
         let service = SpotifyMatchingService::new(app_state.db.clone());
@@ -243,7 +305,12 @@ impl SpotifyQuery {
         let page_size = page_size.unwrap_or(25).clamp(1, 100) as usize;
 
         let result = service
-            .list_matched_tracks(search.as_deref(), page, page_size)
+            .list_matched_tracks(
+                search.as_deref(),
+                auto_accepted_only.unwrap_or(false),
+                page,
+                page_size,
+            )
             .await?;
 
         let mut matched_tracks = Vec::new();
@@ -261,6 +328,14 @@ impl SpotifyQuery {
                 spotify_updated_at: DateTime::from_timestamp(item.spotify_track.updated_at, 0)
                     .ok_or_eyre("Failed to convert spotify updated_at to DateTime<Utc>")?,
                 local_track,
+                auto_accepted_candidate_id: item
+                    .auto_accepted_candidate
+                    .as_ref()
+                    .map(|candidate| candidate.id),
+                auto_accepted_score: item
+                    .auto_accepted_candidate
+                    .as_ref()
+                    .map(|candidate| candidate.score),
             });
         }
 
@@ -337,6 +412,63 @@ impl SpotifyQuery {
         })
     }
 
+    /// Get the match decision history, most recent first, optionally for a
+    /// single Spotify track
+    async fn spotify_match_decisions(
+        &self,
+        ctx: &Context<'_>,
+        page: Option<i32>,
+        page_size: Option<i32>,
+        spotify_track_id: Option<String>,
+    ) -> GraphqlResult<SpotifyMatchDecisionsResponse> {
+        let app_state = get_app_state(ctx)?;
+        let service = SpotifyMatchingService::new(app_state.db.clone());
+
+        let page = page.unwrap_or(1).max(1) as usize;
+        let page_size = page_size.unwrap_or(25).clamp(1, 100) as usize;
+
+        let result = service
+            .list_decisions(spotify_track_id.as_deref(), page, page_size)
+            .await?;
+
+        let decisions = result
+            .items
+            .into_iter()
+            .map(|item| {
+                Ok(SpotifyMatchDecision {
+                    id: item.decision.id,
+                    spotify_track_id: item.decision.spotify_track_id,
+                    spotify_title: item.spotify_track.title,
+                    spotify_artists: item.spotify_track.artists.0,
+                    action: item.decision.action,
+                    actor: item.decision.actor,
+                    candidate_id: item.decision.candidate_id,
+                    previous_local_track_id: item.decision.previous_local_track_id,
+                    local_track_id: item.decision.local_track_id,
+                    dismissed_candidate_ids: item.decision.dismissed_candidate_ids.0,
+                    reverts_decision_id: item.decision.reverts_decision_id,
+                    reverted_at: item
+                        .decision
+                        .reverted_at
+                        .map(|reverted_at| {
+                            DateTime::from_timestamp(reverted_at, 0)
+                                .ok_or_eyre("Failed to convert reverted_at to DateTime<Utc>")
+                        })
+                        .transpose()?,
+                    created_at: DateTime::from_timestamp(item.decision.created_at, 0)
+                        .ok_or_eyre("Failed to convert created_at to DateTime<Utc>")?,
+                })
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+
+        Ok(SpotifyMatchDecisionsResponse {
+            decisions,
+            total_count: result.total_count as i64,
+            page: result.page as i32,
+            page_size: result.page_size as i32,
+        })
+    }
+
     /// Search local tracks for manual matching
     async fn search_local_tracks_for_matching(
         &self,
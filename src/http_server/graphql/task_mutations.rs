@@ -0,0 +1,17 @@
+use async_graphql::{Context, Object};
+
+use crate::http_server::graphql::context::get_app_state;
+use crate::http_server::graphql_error::GraphqlResult;
+
+#[derive(Default)]
+pub struct TaskMutation;
+
+#[Object]
+impl TaskMutation {
+    /// Cancel a pending or running background task. Returns false if the task
+    /// had already finished.
+    async fn cancel_background_task(&self, ctx: &Context<'_>, id: i64) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+        Ok(app_state.tasks.cancel(id).await?)
+    }
+}
@@ -0,0 +1,98 @@
+use async_graphql::{Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::OptionExt;
+
+use crate::entities;
+use crate::entities::background_task::{TaskKind, TaskStatus};
+use crate::http_server::graphql::context::get_app_state;
+use crate::http_server::graphql_error::GraphqlResult;
+use crate::services::tasks::{get_task, list_tasks};
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct BackgroundTask {
+    pub id: i64,
+    pub kind: TaskKind,
+    pub status: TaskStatus,
+    pub progress_current: i64,
+    pub progress_total: Option<i64>,
+    /// Last saved checkpoint as JSON, specific to the task kind
+    pub checkpoint: Option<String>,
+    pub error_message: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, SimpleObject)]
+pub struct BackgroundTasksResponse {
+    pub tasks: Vec<BackgroundTask>,
+    pub total_count: i64,
+    pub page: i32,
+    pub page_size: i32,
+}
+
+fn timestamp(secs: i64) -> color_eyre::Result<DateTime<Utc>> {
+    DateTime::<Utc>::from_timestamp_secs(secs)
+        .ok_or_eyre("Failed to convert timestamp to DateTime<Utc>")
+}
+
+pub(crate) fn map_background_task(
+    task: entities::background_task::Model,
+) -> color_eyre::Result<BackgroundTask> {
+    Ok(BackgroundTask {
+        id: task.id,
+        kind: task.kind,
+        status: task.status,
+        progress_current: task.progress_current,
+        progress_total: task.progress_total,
+        checkpoint: task.checkpoint.map(|checkpoint| checkpoint.to_string()),
+        error_message: task.error_message,
+        created_at: timestamp(task.created_at)?,
+        updated_at: timestamp(task.updated_at)?,
+        started_at: task.started_at.map(timestamp).transpose()?,
+        finished_at: task.finished_at.map(timestamp).transpose()?,
+    })
+}
+
+#[derive(Default)]
+pub struct TaskQuery;
+
+#[Object]
+impl TaskQuery {
+    /// Get background tasks, most recent first
+    async fn background_tasks(
+        &self,
+        ctx: &Context<'_>,
+        kind: Option<TaskKind>,
+        status: Option<TaskStatus>,
+        page: Option<i32>,
+        page_size: Option<i32>,
+    ) -> GraphqlResult<BackgroundTasksResponse> {
+        let app_state = get_app_state(ctx)?;
+
+        let page = page.unwrap_or(1).max(1) as usize;
+        let page_size = page_size.unwrap_or(25).clamp(1, 100) as usize;
+
+        let result = list_tasks(&app_state.db, kind, status, page, page_size).await?;
+        let tasks = result
+            .items
+            .into_iter()
+            .map(map_background_task)
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+
+        Ok(BackgroundTasksResponse {
+            tasks,
+            total_count: result.total_count as i64,
+            page: result.page as i32,
+            page_size: result.page_size as i32,
+        })
+    }
+
+    /// Get a single background task
+    async fn background_task(&self, ctx: &Context<'_>, id: i64) -> GraphqlResult<BackgroundTask> {
+        let app_state = get_app_state(ctx)?;
+        let task = get_task(&app_state.db, id).await?;
+        Ok(map_background_task(task)?)
+    }
+}
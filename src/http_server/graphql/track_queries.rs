@@ -8,6 +8,21 @@ pub struct Track {
     pub track_number: Option<i32>,
     pub duration: Option<i32>,
     pub created_at: DateTime<Utc>,
+    /// Spotify track found by enrichment
+    pub spotify_id: Option<String>,
+    /// Spotify popularity from 0 to 100
+    pub popularity: Option<i32>,
+    /// Beats per minute
+    pub tempo: Option<f64>,
+    /// Pitch class of the key, 0 = C … 11 = B
+    pub musical_key: Option<i32>,
+    /// Perceived intensity from 0.0 to 1.0
+    pub energy: Option<f64>,
+    /// Plays on Plex, summed over all servers
+    pub play_count: Option<i32>,
+    pub last_played_at: Option<DateTime<Utc>>,
+    /// Plex rating from 0 to 10
+    pub rating: Option<f64>,
     pub album: Album,
     pub artists: Vec<Artist>,
 }
@@ -1,6 +1,7 @@
 use async_graphql::{Context, Object};
 
 use crate::http_server::{graphql::context::get_app_state, graphql_error::GraphqlResult};
+use crate::services::youtube::find_song::find_youtube_video_song_task;
 use crate::services::youtube::service::YoutubeService;
 
 #[derive(Default)]
@@ -47,4 +48,15 @@ impl YoutubeMutation {
         service.set_video_watched(id, true).await?;
         Ok(true)
     }
+
+    /// Find the song of a video from its title on MusicBrainz, then download
+    /// and import it from SoulSeek like a Spotify track. Runs as a background
+    /// task; the video's local track is set once it is done.
+    async fn find_youtube_video_song(&self, ctx: &Context<'_>, id: i64) -> GraphqlResult<bool> {
+        let app_state = get_app_state(ctx)?;
+
+        find_youtube_video_song_task(&app_state.tasks, id, app_state.config.matcher().clone())
+            .await?;
+        Ok(true)
+    }
 }
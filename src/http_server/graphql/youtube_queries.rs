@@ -27,6 +27,10 @@ pub struct Video {
     pub thumbnail_url: String,
     pub video_url: String,
     pub watched: bool,
+    /// Feed subscription of an item from an RSS or Atom feed
+    pub feed_subscription_id: Option<i64>,
+    /// Local track of the song in the video, once found
+    pub local_track_id: Option<i64>,
 }
 
 #[Object]
@@ -50,7 +54,7 @@ impl YoutubeQuery {
             })
             .collect())
     }
-    /// Get all videos from subscribed channels
+    /// Get all videos from subscribed channels, and items of subscribed feeds
     /// Cache for 3 minutes
     #[graphql(cache_control(max_age = 180))]
     #[instrument(skip(self, ctx))]
@@ -74,6 +78,8 @@ impl YoutubeQuery {
                 thumbnail_url: video.thumbnail_url,
                 video_url: video.video_url,
                 watched: video.watched,
+                feed_subscription_id: video.feed_subscription_id,
+                local_track_id: video.local_track_id,
             })
             .collect())
     }
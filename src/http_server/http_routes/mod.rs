@@ -1,3 +1,4 @@
 pub mod album_art_image;
 pub mod audio_file;
 pub mod download_file;
+pub mod plex_webhook;
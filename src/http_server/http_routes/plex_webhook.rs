@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use tracing;
+
+use axum::{
+    extract::{Multipart, State},
+    http::StatusCode,
+};
+
+use crate::http_server::state::AppState;
+use crate::plex_rs::webhook::PlexWebhookPayload;
+use crate::services::plex::PlexService;
+use crate::services::plex::client::PlexHttpAdapter;
+
+/// Receives the webhooks of a Plex account, added in Plex under Settings →
+/// Webhooks as `<base url>/plex-webhook`.
+///
+/// The event is the JSON `payload` part of the multipart body. New tracks
+/// are mapped in the background, so Plex isn't kept waiting.
+pub async fn plex_webhook(
+    State(app_state): State<Arc<AppState>>,
+    mut multipart: Multipart,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let mut payload = None;
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid multipart body: {}", e),
+        )
+    })? {
+        if field.name() != Some("payload") {
+            continue;
+        }
+        let text = field.text().await.map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read webhook payload: {}", e),
+            )
+        })?;
+        payload = Some(
+            serde_json::from_str::<PlexWebhookPayload>(&text).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid webhook payload: {}", e),
+                )
+            })?,
+        );
+    }
+    let Some(payload) = payload else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Missing webhook payload".to_string(),
+        ));
+    };
+
+    let service = PlexService::new(app_state.db.clone(), PlexHttpAdapter::new());
+    service
+        .handle_webhook(payload, &app_state.tasks, app_state.config.matcher())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to handle Plex webhook: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to handle Plex webhook".to_string(),
+            )
+        })?;
+
+    Ok(StatusCode::OK)
+}
@@ -1,5 +1,4 @@
 pub mod app;
-pub mod cleanup_tasks;
 pub mod error;
 pub mod graphql;
 pub mod graphql_error;
@@ -4,6 +4,8 @@ use tokio::sync::Mutex;
 use crate::config::Config;
 use crate::database::Database;
 use crate::services::spotify::client::SpotifyApiCredentials;
+use crate::services::spotify::token_manager::SpotifyTokenManager;
+use crate::services::tasks::TaskManager;
 use crate::soulseek::SoulSeekClientContext;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -17,4 +19,6 @@ pub struct AppState {
     pub base_url: String,
     pub spotify_credentials: Option<SpotifyApiCredentials>,
     pub spotify_oauth_session: Mutex<Option<SpotifyClient<Unauthenticated, AuthCodeFlow>>>,
+    pub tasks: Arc<TaskManager>,
+    pub spotify_tokens: Arc<SpotifyTokenManager>,
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
@@ -14,7 +14,11 @@ use serde::{Deserialize, Serialize};
 use tokio::time::interval;
 use tracing::instrument;
 
-use crate::{config::Config, database::Database};
+use crate::services::spotify::matching_local_tracks::index_track;
+use crate::{
+    config::Config,
+    database::{Database, LibraryEvent},
+};
 
 pub const SUPPORTED_FILE_TYPES: &[&str] = &["mp3", "flac", "m4a", "aac", "ogg", "wav"];
 
@@ -87,6 +91,8 @@ struct TrackMetadata {
 
     // Artists (primary first) - (name, musicbrainz_id)
     track_artists: Vec<(String, Option<String>)>,
+    /// MusicBrainz aliases of the track artists, keyed by artist MBID
+    artist_aliases: HashMap<String, Vec<String>>,
     album_artists: Vec<(String, Option<String>)>,
 }
 
@@ -187,11 +193,18 @@ async fn gather_track_metadata(
         }
     })?;
 
-    // Extract track artists from MusicBrainz recording
+    // Extract track artists and their aliases from MusicBrainz recording
     let mut track_artists = Vec::new();
+    let mut artist_aliases = HashMap::new();
     if let Some(artist_credit) = &recording_from_musicbrainz.artist_credit {
         for credit in artist_credit {
             track_artists.push((credit.name.clone(), Some(credit.artist.id.clone())));
+            if let Some(aliases) = &credit.artist.aliases {
+                artist_aliases.insert(
+                    credit.artist.id.clone(),
+                    aliases.iter().map(|alias| alias.name.clone()).collect(),
+                );
+            }
         }
     }
 
@@ -273,6 +286,7 @@ async fn gather_track_metadata(
             .map(|rg| rg.id.clone()),
         album_year,
         track_artists,
+        artist_aliases,
         album_artists,
     })
 }
@@ -456,6 +470,19 @@ pub async fn import_track(
                 error_message: e.to_string(),
             })?;
         track_artist_ids.push((artist_id, idx == 0)); // First is primary
+
+        if let Some(aliases) = mbid
+            .as_ref()
+            .and_then(|mbid| metadata.artist_aliases.get(mbid))
+        {
+            database
+                .add_artist_aliases(artist_id, aliases)
+                .await
+                .map_err(|e| ImportError::DatabaseError {
+                    operation: format!("add aliases for artist: {}", name),
+                    error_message: e.to_string(),
+                })?;
+        }
     }
 
     // Upsert album
@@ -510,6 +537,12 @@ pub async fn import_track(
             })?;
     }
 
+    // Keep the matcher's index current. A failure is not fatal: tracks missing
+    // from the index are indexed again before the next matching run.
+    if let Err(e) = index_track(database, track_id).await {
+        tracing::warn!(error = ?e, track_id, "Failed to index track for matching");
+    }
+
     tracing::info!(
         "Track imported successfully: '{}' by '{}' -> {}",
         metadata.track_title,
@@ -534,6 +567,11 @@ pub async fn import_track(
             error_message: "Track not found".to_string(),
         })?;
 
+    database.publish(LibraryEvent::TrackImported {
+        track_id: track.id,
+        file_path: organized_path,
+    });
+
     Ok(track)
 }
 
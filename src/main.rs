@@ -31,7 +31,7 @@ use crate::{
     http_server::app::HttpServerConfig,
     import_track::{import_folder, import_track, watch_directory},
     logging::init_tracing,
-    services::spotify::client::SpotifyApiCredentials,
+    services::spotify::{client::SpotifyApiCredentials, matching_local_tracks::evaluate_matcher},
     soulseek::{SearchConfig, SoulSeekClientContext},
 };
 
@@ -136,6 +136,21 @@ enum Commands {
         /// Spotify client secret
         #[arg(long, env = "SPOTIFY_CLIENT_SECRET")]
         spotify_client_secret: Option<String>,
+
+        /// Spotify Web API base URL, e.g. a local stand-in for testing
+        #[arg(long, env = "SPOTIFY_API_BASE_URL")]
+        spotify_api_base_url: Option<url::Url>,
+
+        /// Spotify accounts service base URL used for token refreshes
+        #[arg(long, env = "SPOTIFY_ACCOUNTS_BASE_URL")]
+        spotify_accounts_base_url: Option<url::Url>,
+    },
+    /// Replay accepted and dismissed Spotify match candidates and report the
+    /// matcher's precision and recall
+    EvaluateMatcher {
+        /// TOML file with matcher settings to evaluate instead of the config's `[matcher]` section
+        #[arg(short, long)]
+        matcher_config: Option<PathBuf>,
     },
     #[command(subcommand)]
     Config(ConfigCommands),
@@ -215,6 +230,22 @@ async fn main() -> Result<()> {
             );
             watch_directory(&directory, &api_key, &config, &database).await?;
         }
+        Commands::EvaluateMatcher { matcher_config } => {
+            let matcher_config = match matcher_config {
+                Some(path) => {
+                    let contents = std::fs::read_to_string(&path).with_context(|| {
+                        format!("Failed to read matcher config: {}", path.display())
+                    })?;
+                    toml::from_str(&contents).with_context(|| {
+                        format!("Failed to parse matcher config: {}", path.display())
+                    })?
+                }
+                None => config.matcher().clone(),
+            };
+            tracing::debug!(?matcher_config, "Evaluating matcher");
+            let evaluation = evaluate_matcher(&database, &matcher_config).await?;
+            print!("{}", evaluation);
+        }
         Commands::Config(config_commands) => match config_commands {
             ConfigCommands::CreateDefault => {
                 tracing::debug!("Creating default config");
@@ -236,6 +267,8 @@ async fn main() -> Result<()> {
             base_url,
             spotify_client_id,
             spotify_client_secret,
+            spotify_api_base_url,
+            spotify_accounts_base_url,
         } => {
             // Set default base_url in debug mode, require it in release mode
             let base_url = if let Some(url) = base_url {
@@ -269,6 +302,16 @@ async fn main() -> Result<()> {
                 }
                 (None, None) => None,
             };
+            let spotify_credentials = match (spotify_api_base_url, spotify_accounts_base_url) {
+                (Some(api_base_url), Some(accounts_base_url)) => spotify_credentials
+                    .map(|credentials| credentials.with_base_urls(api_base_url, accounts_base_url)),
+                (Some(_), None) | (None, Some(_)) => {
+                    return Err(color_eyre::eyre::eyre!(
+                        "SPOTIFY_API_BASE_URL and SPOTIFY_ACCOUNTS_BASE_URL are required to be set together"
+                    ));
+                }
+                (None, None) => spotify_credentials,
+            };
             tracing::info!("Starting HTTP server on port: {}", port);
             http_server::app::start(HttpServerConfig {
                 port,
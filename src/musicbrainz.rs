@@ -4,10 +4,10 @@
 use backon::{ExponentialBuilder, Retryable};
 use color_eyre::Result;
 use color_eyre::eyre::{Context, OptionExt};
-use musicbrainz_rs::Fetch;
-use musicbrainz_rs::entity::recording::Recording;
+use musicbrainz_rs::entity::recording::{Recording, RecordingSearchQuery};
 use musicbrainz_rs::entity::release::Release;
 use musicbrainz_rs::entity::release_group::ReleaseGroupPrimaryType;
+use musicbrainz_rs::{Fetch, Search};
 
 /// Fetch a recording with details from MusicBrainz with exponential backoff
 /// If the request fails, it will retry with exponential backoff since MusicBrainz is flaky.
@@ -19,6 +19,8 @@ pub async fn fetch_recording_with_details(recording_id: &str) -> Result<Recordin
         let recording = Recording::fetch()
             .id(recording_id)
             .with_artists()
+            // Aliases of the credited artists, used when matching against Spotify
+            .with_aliases()
             .with_releases()
             .with_release_group_relations()
             .execute()
@@ -89,6 +91,39 @@ pub async fn fetch_release_with_details(release_id: &str) -> Result<Release> {
     result
 }
 
+/// Search MusicBrainz for recordings of a song with exponential backoff,
+/// best matches first.
+/// Please note that the musicbrainz rust library handles rate limiting.
+pub async fn search_recordings(artist: &str, title: &str) -> Result<Vec<Recording>> {
+    tracing::debug!("Searching MusicBrainz for '{}' by '{}'", title, artist);
+
+    let query = RecordingSearchQuery::query_builder()
+        .recording(title)
+        .and()
+        .artist(artist)
+        .build();
+    let result = (|| async {
+        let result = Recording::search(query.clone())
+            .execute()
+            .await
+            .wrap_err("Failed to search recordings on MusicBrainz")?;
+        Ok(result.entities)
+    })
+    .retry(ExponentialBuilder::default())
+    .await;
+
+    if let Err(e) = &result {
+        tracing::error!(
+            "Failed to search MusicBrainz for '{}' by '{}' after retries: {}",
+            title,
+            artist,
+            e
+        );
+    }
+
+    result
+}
+
 pub struct TrackInfo {
     pub artist_name: String,
     pub track_title: String,
@@ -1,9 +1,11 @@
 #![allow(dead_code)]
 use color_eyre::eyre::{OptionExt, Result, WrapErr};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use url::Url;
 
+use crate::ports::plex::PlexClient;
+
 /// The inner Plex MediaContainer payload.
 ///
 /// Notes
@@ -84,7 +86,8 @@ pub async fn get_library_sections(
 /* ---------- Tracks ---------- */
 
 /// A music track item returned from `/library/sections/{id}/all?type=10`.
-#[derive(Debug, Deserialize)]
+/// Serializes back to the same JSON, so unmatched tracks can be stored.
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PlexLibraryTrack {
     #[serde(rename = "ratingKey")]
     pub rating_key: String,
@@ -103,19 +106,44 @@ pub struct PlexLibraryTrack {
     #[serde(default)]
     pub duration: Option<u64>,
 
+    /// Unix timestamp of the last metadata change, e.g. a rescan of the file
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<i64>,
+
+    /// Play count of the token's user
+    #[serde(rename = "viewCount", default)]
+    pub view_count: Option<u32>,
+
+    #[serde(rename = "lastViewedAt", default)]
+    pub last_viewed_at: Option<i64>,
+
+    /// Rating from 0 to 10 given by the token's user
+    #[serde(rename = "userRating", default)]
+    pub user_rating: Option<f64>,
+
     #[serde(rename = "Media", default)]
     pub media: Vec<PlexMedia>,
+
+    /// External IDs, only included when requested with `includeGuids=1`
+    #[serde(rename = "Guid", default)]
+    pub guids: Vec<PlexGuid>,
+}
+
+/// External ID of an item, e.g. `mbid://<uuid>` for MusicBrainz
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PlexGuid {
+    pub id: String,
 }
 
 /// Media element containing Part information with file paths
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PlexMedia {
     #[serde(rename = "Part", default)]
     pub parts: Vec<PlexPart>,
 }
 
 /// Part element containing the actual file path
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PlexPart {
     /// Absolute path to the media file on disk
     pub file: String,
@@ -145,6 +173,13 @@ impl PlexLibraryTrack {
             ),
         }
     }
+
+    /// Returns the MusicBrainz ID Plex read from the file tags, if any.
+    pub fn musicbrainz_id(&self) -> Option<&str> {
+        self.guids
+            .iter()
+            .find_map(|guid| guid.id.strip_prefix("mbid://"))
+    }
 }
 
 /// Response type for `/library/sections/{id}/all?type=10`.
@@ -173,10 +208,59 @@ pub async fn get_tracks_page(
     start: u32,
     size: u32,
 ) -> Result<PlexMediaContainer<PlexLibraryTrack>> {
-    let url = base_url.join(&format!(
-        "library/sections/{}/all?type=10",
+    get_tracks_page_since(
+        client,
+        base_url,
+        user_token,
+        music_section_id,
+        None,
+        start,
+        size,
+    )
+    .await
+}
+
+/// Restricts a track listing to tracks changed at or after a unix timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracksSince {
+    /// Metadata changed, e.g. the file was rescanned (`updatedAt`)
+    Updated(i64),
+    /// Played (`lastViewedAt`)
+    Viewed(i64),
+}
+
+/// Fetch one page of tracks from a music section, optionally only those
+/// changed since a point in time.
+///
+/// Endpoint
+/// - `GET /library/sections/{id}/all?type=10&includeGuids=1&updatedAt>>={timestamp}`
+///
+/// Notes
+/// - Guids are included so tracks can be matched by their MusicBrainz ID.
+pub async fn get_tracks_page_since(
+    client: &Client,
+    base_url: &Url,
+    user_token: &str,
+    music_section_id: &str,
+    since: Option<TracksSince>,
+    start: u32,
+    size: u32,
+) -> Result<PlexMediaContainer<PlexLibraryTrack>> {
+    let mut url = base_url.join(&format!(
+        "library/sections/{}/all?type=10&includeGuids=1",
         music_section_id
     ))?;
+    match since {
+        Some(TracksSince::Updated(timestamp)) => {
+            url.query_pairs_mut()
+                .append_pair("updatedAt>>", &timestamp.to_string());
+        }
+        Some(TracksSince::Viewed(timestamp)) => {
+            url.query_pairs_mut()
+                .append_pair("lastViewedAt>>", &timestamp.to_string());
+        }
+        None => {}
+    }
 
     let res = client
         .get(url)
@@ -211,25 +295,47 @@ pub async fn get_tracks_page(
 /// Returns
 /// - All decoded tracks.
 pub async fn get_all_tracks_paginated(
-    client: &Client,
+    client: &dyn PlexClient,
     base_url: &Url,
     user_token: &str,
     music_section_id: &str,
     page_size: u32,
+) -> Result<Vec<PlexLibraryTrack>> {
+    get_all_tracks_since(
+        client,
+        base_url,
+        user_token,
+        music_section_id,
+        None,
+        page_size,
+    )
+    .await
+}
+
+/// Fetch all tracks from a music section changed since a point in time, or
+/// every track when `since` is `None`. Paginates like `get_all_tracks_paginated`.
+pub async fn get_all_tracks_since(
+    client: &dyn PlexClient,
+    base_url: &Url,
+    user_token: &str,
+    music_section_id: &str,
+    since: Option<TracksSince>,
+    page_size: u32,
 ) -> Result<Vec<PlexLibraryTrack>> {
     let mut start: u32 = 0;
     let mut out: Vec<PlexLibraryTrack> = Vec::new();
 
     loop {
-        let container = get_tracks_page(
-            client,
-            base_url,
-            user_token,
-            music_section_id,
-            start,
-            page_size,
-        )
-        .await?;
+        let container = client
+            .get_tracks_page(
+                base_url,
+                user_token,
+                music_section_id,
+                since,
+                start,
+                page_size,
+            )
+            .await?;
 
         // Defensive stop if server returns an empty page.
         if container.metadata.is_empty() {
@@ -251,6 +357,36 @@ pub async fn get_all_tracks_paginated(
     Ok(out)
 }
 
+/// Fetch the tracks of a playlist with their media parts and guids, so they
+/// can be matched to local tracks like library tracks.
+///
+/// Endpoint
+/// - `GET /playlists/{id}/items?type=10&includeGuids=1`
+pub async fn get_playlist_library_tracks(
+    client: &Client,
+    base_url: &Url,
+    user_token: &str,
+    playlist_id: &str,
+) -> Result<Vec<PlexLibraryTrack>> {
+    let url = base_url.join(&format!(
+        "playlists/{}/items?type=10&includeGuids=1",
+        playlist_id
+    ))?;
+
+    let res = client
+        .get(url)
+        .header("Accept", "application/json")
+        .header("X-Plex-Token", user_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<PlexLibraryTracksResponse>()
+        .await
+        .wrap_err("Failed to deserialize playlist items response")?;
+
+    Ok(res.media_container.metadata)
+}
+
 /// Convenience helper: find the first music library section id.
 ///
 /// Notes
@@ -0,0 +1,359 @@
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing;
+use url::Url;
+
+use crate::database::{Database, IN_LIST_CHUNK_SIZE};
+use crate::entities;
+use crate::plex_rs::playlist::{PlexPlaylist, is_music_playlist};
+use crate::plex_rs::track_mapping::map_plex_tracks;
+use crate::ports::plex::PlexClient;
+use crate::services::playlist::PlaylistService;
+use crate::services::spotify::matching_local_tracks::MatcherConfig;
+
+/// Result of importing a Plex playlist into a local playlist
+#[derive(Debug, Clone)]
+pub struct ImportedPlexPlaylist {
+    /// The local playlist, `None` when the import failed
+    pub playlist_id: Option<i64>,
+    pub plex_rating_key: String,
+    pub title: String,
+    pub tracks_imported: u32,
+    /// Plex tracks no local track was found for, as "Artist - Title"
+    pub missing_tracks: Vec<String>,
+    /// Why the playlist could not be imported
+    pub error: Option<String>,
+}
+
+/// Imports music playlists of a Plex server into local playlists.
+///
+/// Every Plex playlist is imported into its own local playlist, which is
+/// created on the first import and has its tracks replaced on later imports.
+/// Plex tracks are matched to local tracks like the track mappings, by file
+/// path, MusicBrainz ID or metadata. Tracks without a local match are skipped.
+///
+/// With `rating_keys`, only those playlists are imported; otherwise all music
+/// playlists of the server are. A playlist that fails to import is reported
+/// with its error and doesn't stop the others from being imported.
+///
+/// # Errors
+/// Returns an error if:
+/// - Plex server missing access token
+/// - A requested playlist is not a music playlist on the server
+/// - Failed to fetch the playlists from Plex
+pub async fn import_plex_playlists(
+    db: &Arc<Database>,
+    client: &dyn PlexClient,
+    config: &MatcherConfig,
+    server: &entities::plex_server::Model,
+    rating_keys: Option<Vec<String>>,
+) -> Result<Vec<ImportedPlexPlaylist>> {
+    let access_token = server.access_token.as_deref().ok_or_eyre(
+        "Plex server does not have an access token. Please authenticate the server first.",
+    )?;
+    let server_url = Url::parse(&server.server_url)
+        .wrap_err(format!("Invalid server URL: {}", server.server_url))?;
+
+    let music_playlists: Vec<PlexPlaylist> = client
+        .get_playlists(&server_url, access_token)
+        .await?
+        .into_iter()
+        .filter(is_music_playlist)
+        .collect();
+    let plex_playlists = match rating_keys {
+        Some(rating_keys) => rating_keys
+            .iter()
+            .map(|rating_key| {
+                music_playlists
+                    .iter()
+                    .find(|playlist| &playlist.rating_key == rating_key)
+                    .cloned()
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Music playlist with rating key {} not found on Plex server '{}'",
+                            rating_key,
+                            server.name
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?,
+        None => music_playlists,
+    };
+
+    let mut imported = Vec::with_capacity(plex_playlists.len());
+    for plex_playlist in &plex_playlists {
+        let result = import_plex_playlist(
+            db,
+            client,
+            config,
+            server,
+            &server_url,
+            access_token,
+            plex_playlist,
+        )
+        .await;
+        imported.push(result.unwrap_or_else(|e| {
+            tracing::warn!(
+                "Failed to import Plex playlist '{}': {:?}",
+                plex_playlist.title,
+                e
+            );
+            ImportedPlexPlaylist {
+                playlist_id: None,
+                plex_rating_key: plex_playlist.rating_key.clone(),
+                title: plex_playlist.title.clone(),
+                tracks_imported: 0,
+                missing_tracks: Vec::new(),
+                error: Some(format!("{:#}", e)),
+            }
+        }));
+    }
+
+    Ok(imported)
+}
+
+/// Imports one Plex playlist into its local playlist.
+async fn import_plex_playlist(
+    db: &Arc<Database>,
+    client: &dyn PlexClient,
+    config: &MatcherConfig,
+    server: &entities::plex_server::Model,
+    server_url: &Url,
+    access_token: &str,
+    plex_playlist: &PlexPlaylist,
+) -> Result<ImportedPlexPlaylist> {
+    let plex_tracks = client
+        .get_playlist_library_tracks(server_url, access_token, &plex_playlist.rating_key)
+        .await?;
+    map_plex_tracks(db, config, server.id, &plex_tracks, false).await?;
+
+    let mut track_ids_by_key: HashMap<String, i64> = HashMap::with_capacity(plex_tracks.len());
+    for chunk in plex_tracks.chunks(IN_LIST_CHUNK_SIZE) {
+        track_ids_by_key.extend(
+            entities::plex_track_mapping::Entity::find()
+                .filter(entities::plex_track_mapping::Column::PlexServerId.eq(server.id))
+                .filter(
+                    entities::plex_track_mapping::Column::RatingKey
+                        .is_in(chunk.iter().map(|track| track.rating_key.as_str())),
+                )
+                .all(&db.conn)
+                .await
+                .wrap_err("Failed to fetch Plex track mappings")?
+                .into_iter()
+                .map(|mapping| (mapping.rating_key, mapping.track_id)),
+        );
+    }
+
+    let mut track_ids = Vec::with_capacity(plex_tracks.len());
+    let mut missing_tracks = Vec::new();
+    for plex_track in &plex_tracks {
+        match track_ids_by_key.get(&plex_track.rating_key) {
+            Some(track_id) => track_ids.push(*track_id),
+            None => missing_tracks.push(format!(
+                "{} - {}",
+                plex_track.artist.as_deref().unwrap_or("Unknown Artist"),
+                plex_track.title
+            )),
+        }
+    }
+
+    let tracks_imported = track_ids.len() as u32;
+    let playlist_id = save_imported_playlist(db, server.id, plex_playlist, track_ids).await?;
+    tracing::info!(
+        "Imported Plex playlist '{}' into playlist {}: {} tracks, {} missing",
+        plex_playlist.title,
+        playlist_id,
+        tracks_imported,
+        missing_tracks.len()
+    );
+
+    Ok(ImportedPlexPlaylist {
+        playlist_id: Some(playlist_id),
+        plex_rating_key: plex_playlist.rating_key.clone(),
+        title: plex_playlist.title.clone(),
+        tracks_imported,
+        missing_tracks,
+        error: None,
+    })
+}
+
+/// Replaces the tracks of the local playlist a Plex playlist was imported
+/// into, creating it on the first import, and returns its id.
+async fn save_imported_playlist(
+    db: &Arc<Database>,
+    plex_server_id: i64,
+    plex_playlist: &PlexPlaylist,
+    track_ids: Vec<i64>,
+) -> Result<i64> {
+    let playlists = PlaylistService::new(db.clone());
+    let now = chrono::Utc::now().timestamp();
+
+    let existing = entities::plex_playlist_import::Entity::find()
+        .filter(entities::plex_playlist_import::Column::PlexServerId.eq(plex_server_id))
+        .filter(
+            entities::plex_playlist_import::Column::PlexRatingKey
+                .eq(plex_playlist.rating_key.as_str()),
+        )
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch Plex playlist import")?;
+
+    let playlist_id = match existing {
+        // The local playlist keeps its name, it may have been renamed
+        Some(import) => {
+            let playlist_id = import.playlist_id;
+            let mut import: entities::plex_playlist_import::ActiveModel = import.into();
+            import.imported_at = Set(now);
+            import
+                .update(&db.conn)
+                .await
+                .wrap_err("Failed to save Plex playlist import")?;
+            playlist_id
+        }
+        None => {
+            let description = plex_playlist
+                .summary
+                .clone()
+                .filter(|summary| !summary.is_empty());
+            let playlist = playlists
+                .create(plex_playlist.title.clone(), description)
+                .await?;
+            entities::plex_playlist_import::ActiveModel {
+                playlist_id: Set(playlist.id),
+                plex_server_id: Set(plex_server_id),
+                plex_rating_key: Set(plex_playlist.rating_key.clone()),
+                imported_at: Set(now),
+            }
+            .insert(&db.conn)
+            .await
+            .wrap_err("Failed to save Plex playlist import")?;
+            playlist.id
+        }
+    };
+
+    playlists.replace_tracks(playlist_id, track_ids).await?;
+
+    Ok(playlist_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::plex::MockPlexClient;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::ActiveModelBehavior;
+
+    #[tokio::test]
+    async fn test_reimport_replaces_tracks_of_the_same_playlist() {
+        let db = test_db().await;
+        let server_id = entities::plex_server::ActiveModel {
+            name: Set("Plex".into()),
+            server_url: Set("http://plex.local:32400/".into()),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap()
+        .id;
+        let first = insert_track(&db, TestTrack::new("First")).await.id;
+        let second = insert_track(&db, TestTrack::new("Second")).await.id;
+        let plex_playlist: PlexPlaylist = serde_json::from_value(serde_json::json!({
+            "ratingKey": "500",
+            "title": "Road Trip",
+            "playlistType": "audio",
+            "summary": "",
+        }))
+        .unwrap();
+
+        let playlist_id =
+            save_imported_playlist(&db, server_id, &plex_playlist, vec![first, second, first])
+                .await
+                .unwrap();
+        let playlist = entities::playlist::Entity::find_by_id(playlist_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(playlist.name, "Road Trip");
+        assert_eq!(playlist.description, None);
+
+        let reimported = save_imported_playlist(&db, server_id, &plex_playlist, vec![second])
+            .await
+            .unwrap();
+        assert_eq!(reimported, playlist_id);
+        let track_ids: Vec<i64> = crate::services::playlist::ordered_entries(&db.conn, playlist_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.track_id)
+            .collect();
+        assert_eq!(track_ids, vec![second]);
+        assert_eq!(
+            entities::playlist::Entity::find()
+                .all(&db.conn)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_playlist_does_not_stop_the_others() {
+        let db = test_db().await;
+        let server = entities::plex_server::ActiveModel {
+            name: Set("Plex".into()),
+            server_url: Set("http://plex.local:32400/".into()),
+            access_token: Set(Some("token".into())),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let mut client = MockPlexClient::new();
+        client.expect_get_playlists().returning(|_, _| {
+            Ok(["500", "501", "502"]
+                .iter()
+                .map(|rating_key| {
+                    serde_json::from_value(serde_json::json!({
+                        "ratingKey": rating_key,
+                        "title": format!("Playlist {}", rating_key),
+                        "playlistType": "audio",
+                    }))
+                    .unwrap()
+                })
+                .collect())
+        });
+        client.expect_get_playlist_library_tracks().returning(
+            |_, _, playlist_id| match playlist_id {
+                "501" => Err(eyre!("connection reset")),
+                _ => Ok(Vec::new()),
+            },
+        );
+
+        let imported =
+            import_plex_playlists(&db, &client, &MatcherConfig::default(), &server, None)
+                .await
+                .unwrap();
+
+        let outcomes: Vec<_> = imported
+            .iter()
+            .map(|playlist| {
+                (
+                    playlist.plex_rating_key.as_str(),
+                    playlist.playlist_id.is_some(),
+                )
+            })
+            .collect();
+        assert_eq!(outcomes, vec![("500", true), ("501", false), ("502", true)]);
+        assert!(
+            imported[1]
+                .error
+                .as_deref()
+                .unwrap()
+                .contains("connection reset")
+        );
+    }
+}
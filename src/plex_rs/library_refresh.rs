@@ -31,6 +31,34 @@ pub async fn refresh_library_section(
     Ok(())
 }
 
+/// Trigger a scan of one folder of a Plex library section, e.g. a newly
+/// imported album, instead of the whole section
+///
+/// Endpoint: `GET /library/sections/{section_id}/refresh?path={path}`
+///
+/// `path` is the folder as seen by the Plex server.
+pub async fn refresh_library_path(
+    client: &Client,
+    base_url: &Url,
+    user_token: &str,
+    section_id: &str,
+    path: &str,
+) -> Result<()> {
+    let mut url = base_url.join(&format!("library/sections/{}/refresh", section_id))?;
+    url.query_pairs_mut().append_pair("path", path);
+
+    client
+        .get(url)
+        .header("Accept", "application/json")
+        .header("X-Plex-Token", user_token)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Failed to refresh library folder")?;
+
+    Ok(())
+}
+
 /// Response type for `/activities` endpoint
 #[derive(Debug, Clone, Deserialize)]
 pub struct PlexActivitiesResponse {
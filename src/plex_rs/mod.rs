@@ -1,5 +1,9 @@
 pub mod all_tracks;
 pub mod auth;
+pub mod import_playlist;
 pub mod library_refresh;
+pub mod path_mapping;
 pub mod playlist;
 pub mod sync_playlist;
+pub mod track_mapping;
+pub mod webhook;
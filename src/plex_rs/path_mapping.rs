@@ -0,0 +1,126 @@
+use color_eyre::eyre::{Result, WrapErr};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use crate::database::Database;
+use crate::entities;
+
+/// Translates file paths between the local library and a Plex server that
+/// mounts it at a different root.
+///
+/// Paths outside every mapped prefix are the same on both sides.
+#[derive(Debug, Clone, Default)]
+pub struct PathMappings {
+    /// (local prefix, Plex prefix)
+    prefixes: Vec<(String, String)>,
+}
+
+impl PathMappings {
+    pub fn new(prefixes: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self {
+            prefixes: prefixes
+                .into_iter()
+                .map(|(local, plex)| (normalize_prefix(&local), normalize_prefix(&plex)))
+                .collect(),
+        }
+    }
+
+    /// Path mappings of a Plex server.
+    pub async fn load(db: &Database, plex_server_id: i64) -> Result<Self> {
+        let mappings = entities::plex_path_mapping::Entity::find()
+            .filter(entities::plex_path_mapping::Column::PlexServerId.eq(plex_server_id))
+            .all(&db.conn)
+            .await
+            .wrap_err("Failed to fetch Plex path mappings")?;
+        Ok(Self::new(mappings.into_iter().map(|mapping| {
+            (mapping.local_prefix, mapping.plex_prefix)
+        })))
+    }
+
+    /// The path of a local file or folder on the Plex server.
+    pub fn to_plex(&self, local_path: &str) -> String {
+        translate(
+            local_path,
+            self.prefixes
+                .iter()
+                .map(|(local, plex)| (local.as_str(), plex.as_str())),
+        )
+    }
+
+    /// The local path of a file or folder on the Plex server.
+    pub fn to_local(&self, plex_path: &str) -> String {
+        translate(
+            plex_path,
+            self.prefixes
+                .iter()
+                .map(|(local, plex)| (plex.as_str(), local.as_str())),
+        )
+    }
+}
+
+/// Replaces the longest matching `from` prefix of `path` with its `to`.
+fn translate<'a>(path: &str, prefixes: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    prefixes
+        .filter_map(|(from, to)| Some((from.len(), replace_prefix(path, from, to)?)))
+        .max_by_key(|(len, _)| *len)
+        .map_or_else(|| path.to_string(), |(_, path)| path)
+}
+
+/// Drops trailing separators, so `/music/` and `/music` are the same prefix.
+pub fn normalize_prefix(prefix: &str) -> String {
+    let trimmed = prefix.trim().trim_end_matches(['/', '\\']);
+    if trimmed.is_empty() {
+        // The root itself
+        prefix.trim().chars().take(1).collect()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Replaces `from` at the start of `path` with `to`, only when it ends at a
+/// path component boundary: `/music` is a prefix of `/music/a` but not of
+/// `/music2/a`.
+fn replace_prefix(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(from)?;
+    if rest.is_empty() || rest.starts_with(['/', '\\']) || from.ends_with(['/', '\\']) {
+        Some(format!("{}{}", to, rest))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mappings() -> PathMappings {
+        PathMappings::new([
+            ("/music/".to_string(), "/data/media/music".to_string()),
+            (
+                "/music/classical".to_string(),
+                "/mnt/nas/classical".to_string(),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_translates_both_ways() {
+        let mappings = mappings();
+        let local = "/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac";
+        let plex = "/data/media/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac";
+        assert_eq!(mappings.to_plex(local), plex);
+        assert_eq!(mappings.to_local(plex), local);
+        assert_eq!(mappings.to_plex("/music"), "/data/media/music");
+    }
+
+    #[test]
+    fn test_longest_prefix_wins_on_component_boundaries() {
+        let mappings = mappings();
+        assert_eq!(
+            mappings.to_plex("/music/classical/Bach/Mass in B minor/CD1/01 Kyrie.flac"),
+            "/mnt/nas/classical/Bach/Mass in B minor/CD1/01 Kyrie.flac"
+        );
+        // Not below a mapped folder, so unchanged
+        assert_eq!(mappings.to_plex("/music2/a.flac"), "/music2/a.flac");
+        assert_eq!(mappings.to_local("/elsewhere/a.flac"), "/elsewhere/a.flac");
+    }
+}
@@ -287,7 +287,7 @@ pub async fn get_playlist_tracks(
     Ok(res.media_container.metadata)
 }
 
-/* ---------- Add and remove ---------- */
+/* ---------- Add, move and remove ---------- */
 
 pub async fn add_track_to_playlist(
     client: &Client,
@@ -339,6 +339,36 @@ pub async fn remove_track_from_playlist(
     Ok(())
 }
 
+/// Move a playlist item right after another item, or to the top without
+/// `after_item_id`.
+pub async fn move_playlist_item(
+    client: &Client,
+    base_url: &Url,
+    user_token: &str,
+    playlist_id: &str,
+    playlist_item_id: u64,
+    after_item_id: Option<u64>,
+) -> Result<()> {
+    let mut url = base_url.join(&format!(
+        "playlists/{}/items/{}/move",
+        playlist_id, playlist_item_id
+    ))?;
+    if let Some(after_item_id) = after_item_id {
+        url.query_pairs_mut()
+            .append_pair("after", &after_item_id.to_string());
+    }
+
+    client
+        .put(url)
+        .header("X-Plex-Token", user_token)
+        .send()
+        .await?
+        .error_for_status()
+        .wrap_err("Failed to move playlist item")?;
+
+    Ok(())
+}
+
 pub async fn clear_playlist(
     client: &Client,
     base_url: &Url,
@@ -1,20 +1,48 @@
+use async_graphql::Enum;
 use color_eyre::eyre::{OptionExt, Result, WrapErr};
-use reqwest::Client;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::sync::Arc;
 use tracing;
 use url::Url;
 
-use crate::database::Database;
+use crate::database::{Database, IN_LIST_CHUNK_SIZE};
 use crate::entities;
-use crate::plex_rs::all_tracks::{
-    find_music_section_id, get_all_tracks_paginated, get_library_sections,
-};
-use crate::plex_rs::playlist::{
-    add_track_to_playlist, create_music_playlist, get_machine_identifier, get_playlist_tracks,
-    get_playlists, is_music_playlist, remove_track_from_playlist,
-};
+use crate::entities::plex_playlist_sync::SyncedRatingKeys;
+use crate::plex_rs::playlist::{PlexTrack, is_music_playlist};
+use crate::plex_rs::track_mapping::{rating_keys_by_track, refresh_track_mappings};
+use crate::ports::plex::PlexClient;
+use crate::services::playlist::{PlaylistService, playlist_track_ids};
+use crate::services::smart_playlist::find_rules;
+use crate::services::spotify::matching_local_tracks::MatcherConfig;
+
+/// How playlists are synced to Plex.
+///
+/// Loaded from the `[plex_playlist_sync]` section of the config file. Every
+/// field is optional and falls back to the default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlexPlaylistSyncConfig {
+    /// Also bring changes made to a playlist in Plex back to the local playlist
+    pub two_way: bool,
+    /// Which side wins when a playlist changed on both since the last sync
+    pub conflict_policy: ConflictPolicy,
+}
+
+/// Which side of a two-way sync wins when the playlist changed on both.
+#[derive(Enum, Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep the local playlist, dropping the changes made in Plex
+    #[default]
+    LocalWins,
+    /// Keep the Plex playlist, dropping the local changes
+    PlexWins,
+    /// Keep the tracks of both: the local playlist, then the tracks only in Plex
+    Union,
+}
 
 /// Represents a track that exists in the database playlist but not in the Plex library
 #[derive(Debug, Clone)]
@@ -24,81 +52,64 @@ pub struct MissingTrack {
     pub title: String,
 }
 
-/// Extracts and normalizes the last 3 path components (artist/album/track) for matching.
-///
-/// The path structure is: `.../ArtistName/AlbumName/TrackNumber - TrackName.ext`
-/// This function extracts Artist/Album/Track and normalizes them by:
-/// - Converting to lowercase
-/// - Removing track number prefix (e.g., "01 ")
-/// - Removing file extension
-///
-/// Returns None if the path doesn't have at least 3 components.
-fn normalize_path_key(file_path: &str) -> Option<String> {
-    let path = Path::new(file_path);
-    let components: Vec<_> = path.iter().filter_map(|c| c.to_str()).collect();
-
-    if components.len() < 3 {
-        return None;
-    }
-
-    // Get last 3 components: artist, album, track_filename
-    let artist = components[components.len() - 3].to_lowercase();
-    let album = components[components.len() - 2].to_lowercase();
-    let track_filename = components[components.len() - 1].to_lowercase();
-
-    // Remove file extension
-    let track_name = Path::new(&track_filename)
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or(&track_filename);
-
-    // Remove track number prefix (e.g., "01 ", "1 ", etc.)
-    // Pattern: starts with digits followed by space or dash
-    let track_name = track_name
-        .trim_start_matches(char::is_numeric)
-        .trim_start_matches(' ')
-        .trim_start_matches('-')
-        .trim_start_matches(' ');
-
-    Some(format!("{}/{}/{}", artist, album, track_name))
-}
-
 /// Result of syncing a playlist to Plex
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct SyncPlaylistResult {
     pub missing_tracks: Vec<MissingTrack>,
     pub tracks_added: u32,
     pub tracks_removed: u32,
+    /// Plex playlist items moved to match the playlist order
+    pub tracks_moved: u32,
     pub tracks_skipped: u32,
+    /// Changes made in Plex were brought to the local playlist
+    pub local_updated: bool,
 }
 
-/// Syncs a database playlist to Plex by matching tracks via file paths.
+/// Syncs a database playlist to a Plex server using the local track ↔ Plex
+/// track mappings.
 ///
 /// This function:
-/// - Finds or creates a Plex playlist with the same name
-/// - Matches tracks between database and Plex using file paths
-/// - Adds missing tracks incrementally (never clears entire playlist)
-/// - Removes extra tracks incrementally
+/// - Refreshes the track mappings with the Plex tracks updated since the last sync
+/// - Finds the Plex playlist synced before, else one with the same name, else
+///   creates it
+/// - In two-way mode, compares both sides with the tracks of the last sync to
+///   tell which one changed, settling changes on both with the conflict policy,
+///   and brings changes made in Plex to the local playlist
+/// - Adds missing tracks and removes extra tracks incrementally (never clears
+///   entire playlist), then moves items into the playlist order
+/// - Treats duplicate entries as distinct, so a track listed twice locally is
+///   listed twice in Plex
+/// - Remembers the synced Plex tracks for the next sync
 /// - Returns statistics about the sync operation
 ///
+/// Smart playlists are always synced one way, their tracks come from rules.
+///
 /// # Arguments
 /// * `db` - Database connection
-/// * `client` - HTTP client for Plex API requests
+/// * `client` - Plex API client
+/// * `config` - Matcher tuning for tracks matched by metadata
+/// * `options` - One- or two-way sync and its conflict policy
 /// * `playlist_id` - Database playlist ID to sync
+/// * `server` - Plex server to sync the playlist to
 ///
 /// # Errors
 /// Returns an error if:
 /// - Playlist not found in database
-/// - No Plex server configured
-/// - Multiple Plex servers configured (only one supported)
 /// - Plex server missing access token
 /// - Failed to fetch Plex library tracks
 pub async fn sync_playlist_to_plex(
-    db: &Database,
-    client: &Client,
+    db: &Arc<Database>,
+    client: &dyn PlexClient,
+    config: &MatcherConfig,
+    options: &PlexPlaylistSyncConfig,
     playlist_id: i64,
+    server: &entities::plex_server::Model,
 ) -> Result<SyncPlaylistResult> {
-    tracing::info!("Starting sync of playlist ID {} to Plex", playlist_id);
+    tracing::info!(
+        "Starting sync of playlist ID {} to Plex server '{}'",
+        playlist_id,
+        server.name
+    );
 
     // Step 1: Get Database Playlist
     let playlist = entities::playlist::Entity::find_by_id(playlist_id)
@@ -109,44 +120,37 @@ pub async fn sync_playlist_to_plex(
 
     tracing::info!("Found playlist: '{}' (ID: {})", playlist.name, playlist_id);
 
-    // Step 2: Get Database Playlist Tracks
-    let playlist_track_models = entities::playlist_track::Entity::find()
-        .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
-        .all(&db.conn)
-        .await
-        .wrap_err("Failed to fetch playlist tracks")?;
-
-    let track_ids: Vec<i64> = playlist_track_models.iter().map(|pt| pt.track_id).collect();
+    // Step 2: Get Database Playlist Tracks (smart playlists are materialized here)
+    let track_ids = playlist_track_ids(&db.conn, playlist_id).await?;
     tracing::info!("Found {} tracks in database playlist", track_ids.len());
 
-    let track_models = entities::track::Entity::find()
-        .filter(entities::track::Column::Id.is_in(track_ids.clone()))
-        .all(&db.conn)
-        .await
-        .wrap_err("Failed to fetch track details")?;
-
-    // Step 3: Get Plex Server Configuration
-    let servers = entities::plex_server::Entity::find()
-        .all(&db.conn)
-        .await
-        .wrap_err("Failed to fetch Plex servers")?;
-
-    if servers.is_empty() {
-        return Err(color_eyre::eyre::eyre!(
-            "No Plex server configured. Please add a Plex server first."
-        ));
-    }
-
-    if servers.len() > 1 {
-        return Err(color_eyre::eyre::eyre!(
-            "Multiple Plex servers found ({}). Only one server is supported at a time.",
-            servers.len()
-        ));
+    let unique_track_ids: Vec<i64> = track_ids
+        .iter()
+        .copied()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let mut tracks_by_id: HashMap<i64, entities::track::Model> =
+        HashMap::with_capacity(unique_track_ids.len());
+    for chunk in unique_track_ids.chunks(IN_LIST_CHUNK_SIZE) {
+        tracks_by_id.extend(
+            entities::track::Entity::find()
+                .filter(entities::track::Column::Id.is_in(chunk.iter().copied()))
+                .all(&db.conn)
+                .await
+                .wrap_err("Failed to fetch track details")?
+                .into_iter()
+                .map(|track| (track.id, track)),
+        );
     }
 
-    let server = servers.into_iter().next().unwrap();
-    tracing::info!("Using Plex server: '{}'", server.name);
+    // Tracks in playlist order, including repeated entries
+    let ordered_tracks: Vec<&entities::track::Model> = track_ids
+        .iter()
+        .filter_map(|track_id| tracks_by_id.get(track_id))
+        .collect();
 
+    // Step 3: Get Plex Server Connection
     let access_token = server.access_token.as_ref().ok_or_eyre(
         "Plex server does not have an access token. Please authenticate the server first.",
     )?;
@@ -154,67 +158,51 @@ pub async fn sync_playlist_to_plex(
     let server_url = Url::parse(&server.server_url)
         .wrap_err(format!("Invalid server URL: {}", server.server_url))?;
 
-    // Step 4: Build Plex Track Lookup Map
-    tracing::info!("Fetching all Plex library tracks...");
-    let sections = get_library_sections(client, &server_url, access_token).await?;
-    let music_section_id = find_music_section_id(&sections)
-        .ok_or_eyre("No music library section found on Plex server")?;
-
-    let plex_tracks =
-        get_all_tracks_paginated(client, &server_url, access_token, music_section_id, 1000).await?;
-    tracing::info!("Found {} tracks in Plex library", plex_tracks.len());
-
-    // Build normalized path key -> rating_key lookup map
-    // Use normalized path (last 3 components: artist/album/track) for matching
-    let mut plex_lookup: HashMap<String, String> = HashMap::new();
-    for track in &plex_tracks {
-        match track.file_path() {
-            Ok(file_path) => {
-                if let Some(normalized_key) = normalize_path_key(file_path) {
-                    plex_lookup.insert(normalized_key, track.rating_key.clone());
-                } else {
-                    tracing::warn!(
-                        "Failed to normalize path for track '{}': {}",
-                        track.title,
-                        file_path
-                    );
-                }
-            }
-            Err(e) => {
-                tracing::warn!("Failed to get file path for track '{}': {}", track.title, e);
-            }
-        }
-    }
+    // Step 4: Look Up Plex Rating Keys
+    refresh_track_mappings(db, client, config, server, false).await?;
+    let rating_keys = rating_keys_by_track(db, server.id, track_ids.clone()).await?;
     tracing::info!(
-        "Built lookup map with {} normalized paths",
-        plex_lookup.len()
+        "Found Plex tracks for {} of {} distinct playlist tracks",
+        rating_keys.len(),
+        tracks_by_id.len()
     );
 
-    // Step 5: Calculate which database tracks exist in Plex (before creating playlist)
-    // Build set of database track rating_keys (only for tracks that exist in Plex)
-    // Match using normalized path keys
-    let db_rating_keys: HashSet<String> = track_models
+    // Step 5: Build the ordered list of database track rating_keys (only for
+    // tracks that exist in Plex)
+    let db_rating_keys: Vec<String> = ordered_tracks
         .iter()
-        .filter_map(|track| {
-            normalize_path_key(&track.file_path).and_then(|key| plex_lookup.get(&key).cloned())
-        })
+        .filter_map(|track| rating_keys.get(&track.id).cloned())
         .collect();
 
     // Step 6: Get Machine Identifier (needed for creating playlist with initial track)
-    let machine_identifier = get_machine_identifier(client, &server_url, access_token).await?;
+    let machine_identifier = client
+        .get_machine_identifier(&server_url, access_token)
+        .await?;
     tracing::debug!("Using machine identifier: {}", machine_identifier);
 
     // Step 7: Find or Create Plex Playlist
-    let plex_playlists = get_playlists(client, &server_url, access_token).await?;
+    let last_sync = entities::plex_playlist_sync::Entity::find_by_id((playlist_id, server.id))
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch last Plex playlist sync")?;
+
+    let plex_playlists = client.get_playlists(&server_url, access_token).await?;
     let music_playlists: Vec<_> = plex_playlists
         .into_iter()
         .filter(is_music_playlist)
         .collect();
 
-    let playlist_existed = music_playlists.iter().any(|p| p.title == playlist.name);
-    let first_rating_key = db_rating_keys.iter().next().cloned();
+    // The playlist synced before may have been renamed on either side
+    let existing_playlist = last_sync
+        .as_ref()
+        .and_then(|sync| {
+            music_playlists
+                .iter()
+                .find(|p| p.rating_key == sync.plex_rating_key)
+        })
+        .or_else(|| music_playlists.iter().find(|p| p.title == playlist.name));
 
-    let plex_playlist = match music_playlists.iter().find(|p| p.title == playlist.name) {
+    let plex_playlist = match existing_playlist {
         Some(p) => {
             tracing::info!(
                 "Found existing Plex playlist: '{}' (ID: {})",
@@ -225,122 +213,160 @@ pub async fn sync_playlist_to_plex(
         }
         None => {
             tracing::info!("Creating new Plex playlist: '{}'", playlist.name);
-            // Try to create playlist with first track if available (some Plex versions require it)
-            let first_track_uri = first_rating_key.as_ref().map(|rating_key| {
-                format!(
-                    "server://{}/com.plexapp.plugins.library/library/metadata/{}",
-                    machine_identifier, rating_key
-                )
-            });
-
-            if let Some(uri) = first_track_uri.as_deref() {
-                crate::plex_rs::playlist::create_music_playlist_with_uri(
-                    client,
+            // Create playlist with first track if available (some Plex versions require it)
+            client
+                .create_playlist(
                     &server_url,
                     access_token,
+                    &machine_identifier,
                     &playlist.name,
-                    Some(uri),
+                    db_rating_keys.first().cloned(),
                 )
                 .await?
-            } else {
-                create_music_playlist(client, &server_url, access_token, &playlist.name).await?
-            }
         }
     };
 
-    // Step 6: Get Current Plex Playlist Tracks
-    let current_plex_tracks =
-        get_playlist_tracks(client, &server_url, access_token, &plex_playlist.rating_key).await?;
-
-    let current_plex_rating_keys: HashSet<String> = current_plex_tracks
+    // Step 8: Get Current Plex Playlist Tracks
+    let current_plex_tracks = client
+        .get_playlist_items(&server_url, access_token, &plex_playlist.rating_key)
+        .await?;
+    let plex_rating_keys: Vec<String> = current_plex_tracks
         .iter()
-        .map(|t| t.rating_key.clone())
+        .map(|track| track.rating_key.clone())
         .collect();
 
     tracing::info!(
         "Plex playlist currently has {} tracks",
-        current_plex_rating_keys.len()
+        current_plex_tracks.len()
     );
 
-    // Step 7: Identify Missing Tracks
-    // Match tracks using normalized path keys (last 3 components)
+    // Step 9: Identify Missing Tracks
     let mut missing_tracks = Vec::new();
-    for track in &track_models {
-        let normalized_key = normalize_path_key(&track.file_path);
-        match normalized_key {
-            Some(key) => {
-                if !plex_lookup.contains_key(&key) {
-                    missing_tracks.push(MissingTrack {
-                        track_id: track.id,
-                        file_path: track.file_path.clone(),
-                        title: track.title.clone(),
-                    });
-                    tracing::warn!(
-                        "Track '{}' (ID: {}) not found in Plex library: {} (normalized: {})",
-                        track.title,
-                        track.id,
-                        track.file_path,
-                        key
-                    );
-                }
-            }
-            None => {
-                missing_tracks.push(MissingTrack {
-                    track_id: track.id,
-                    file_path: track.file_path.clone(),
-                    title: track.title.clone(),
-                });
-                tracing::warn!(
-                    "Track '{}' (ID: {}) path cannot be normalized (needs at least 3 components): {}",
-                    track.title,
-                    track.id,
-                    track.file_path
-                );
-            }
+    let mut seen_track_ids = HashSet::new();
+    for track in &ordered_tracks {
+        if !seen_track_ids.insert(track.id) || rating_keys.contains_key(&track.id) {
+            continue;
         }
+
+        missing_tracks.push(MissingTrack {
+            track_id: track.id,
+            file_path: track.file_path.clone(),
+            title: track.title.clone(),
+        });
+        tracing::warn!(
+            "Track '{}' (ID: {}) not found in Plex library: {}",
+            track.title,
+            track.id,
+            track.file_path
+        );
     }
 
-    // Step 9: Calculate Differences
-    let tracks_to_add: Vec<String> = db_rating_keys
-        .difference(&current_plex_rating_keys)
-        .cloned()
-        .collect();
+    // Step 10: Decide the Synced Tracks
+    // A new Plex playlist has nothing to bring back. Smart playlists can't be
+    // edited, their tracks come from rules.
+    let two_way = options.two_way
+        && existing_playlist.is_some()
+        && find_rules(&db.conn, playlist_id).await?.is_none();
+    let mut local_updated = false;
+    let target_rating_keys = if two_way {
+        let synced = last_sync
+            .filter(|sync| sync.plex_rating_key == plex_playlist.rating_key)
+            .map(|sync| sync.synced_rating_keys.0);
+
+        // Local tracks of the Plex playlist items
+        let mut track_ids_by_key: HashMap<String, i64> = HashMap::new();
+        for chunk in plex_rating_keys.chunks(IN_LIST_CHUNK_SIZE) {
+            track_ids_by_key.extend(
+                entities::plex_track_mapping::Entity::find()
+                    .filter(entities::plex_track_mapping::Column::PlexServerId.eq(server.id))
+                    .filter(
+                        entities::plex_track_mapping::Column::RatingKey
+                            .is_in(chunk.iter().map(String::as_str)),
+                    )
+                    .all(&db.conn)
+                    .await
+                    .wrap_err("Failed to fetch Plex track mappings")?
+                    .into_iter()
+                    .map(|mapping| (mapping.rating_key, mapping.track_id)),
+            );
+        }
+        for (track_id, rating_key) in &rating_keys {
+            track_ids_by_key.insert(rating_key.clone(), *track_id);
+        }
+
+        let target = merge_playlists(
+            &db_rating_keys,
+            &plex_rating_keys,
+            synced.as_deref(),
+            |rating_key| track_ids_by_key.contains_key(rating_key),
+            options.conflict_policy,
+        );
+
+        let new_track_ids = local_track_ids(&target, &track_ids_by_key, &track_ids, &rating_keys);
+        if new_track_ids != track_ids {
+            tracing::info!(
+                "Bringing changes made in Plex to playlist '{}'",
+                playlist.name
+            );
+            PlaylistService::new(db.clone())
+                .replace_tracks(playlist_id, new_track_ids)
+                .await?;
+            local_updated = true;
+        }
+
+        target
+    } else {
+        db_rating_keys
+    };
+
+    // Step 11: Calculate Differences
+    // Compare as multisets so repeated entries are added or removed one at a time.
+    // Tracks already in Plex (including the one used to create the playlist)
+    // satisfy the earliest matching entries.
+    let mut available: HashMap<&str, usize> = HashMap::new();
+    for track in &current_plex_tracks {
+        *available.entry(track.rating_key.as_str()).or_default() += 1;
+    }
+    let mut tracks_to_add: Vec<String> = Vec::new();
+    for rating_key in &target_rating_keys {
+        match available.get_mut(rating_key.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => tracks_to_add.push(rating_key.clone()),
+        }
+    }
 
-    let tracks_to_remove: Vec<&crate::plex_rs::playlist::PlexTrack> = current_plex_tracks
+    let mut wanted: HashMap<&str, usize> = HashMap::new();
+    for rating_key in &target_rating_keys {
+        *wanted.entry(rating_key.as_str()).or_default() += 1;
+    }
+    let tracks_to_remove: Vec<&PlexTrack> = current_plex_tracks
         .iter()
-        .filter(|t| !db_rating_keys.contains(&t.rating_key))
+        .filter(|t| match wanted.get_mut(t.rating_key.as_str()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
         .collect();
 
     tracing::info!("Tracks to add: {}", tracks_to_add.len());
     tracing::info!("Tracks to remove: {}", tracks_to_remove.len());
 
-    // Step 10: Add Missing Tracks (Incremental)
+    // Step 12: Add Missing Tracks (Incremental, appended in playlist order)
     let mut tracks_added = 0;
     let mut tracks_skipped = 0;
 
-    // If we just created the playlist with the first track, skip adding it again
-    let tracks_to_add_final: Vec<String> = if !playlist_existed
-        && first_rating_key.is_some()
-        && tracks_to_add.contains(first_rating_key.as_ref().unwrap())
-    {
-        tracks_to_add
-            .into_iter()
-            .filter(|k| k != first_rating_key.as_ref().unwrap())
-            .collect()
-    } else {
-        tracks_to_add
-    };
-
-    for rating_key in &tracks_to_add_final {
-        match add_track_to_playlist(
-            client,
-            &server_url,
-            access_token,
-            &plex_playlist.rating_key,
-            &machine_identifier,
-            rating_key,
-        )
-        .await
+    for rating_key in &tracks_to_add {
+        match client
+            .add_playlist_item(
+                &server_url,
+                access_token,
+                &plex_playlist.rating_key,
+                &machine_identifier,
+                rating_key,
+            )
+            .await
         {
             Ok(()) => {
                 tracks_added += 1;
@@ -353,7 +379,7 @@ pub async fn sync_playlist_to_plex(
         }
     }
 
-    // Step 11: Remove Extra Tracks (Incremental)
+    // Step 13: Remove Extra Tracks (Incremental)
     let mut tracks_removed = 0;
 
     for plex_track in &tracks_to_remove {
@@ -362,14 +388,14 @@ pub async fn sync_playlist_to_plex(
             plex_track.title, plex_track.rating_key
         ))?;
 
-        match remove_track_from_playlist(
-            client,
-            &server_url,
-            access_token,
-            &plex_playlist.rating_key,
-            playlist_item_id,
-        )
-        .await
+        match client
+            .remove_playlist_item(
+                &server_url,
+                access_token,
+                &plex_playlist.rating_key,
+                playlist_item_id,
+            )
+            .await
         {
             Ok(()) => {
                 tracks_removed += 1;
@@ -391,22 +417,426 @@ pub async fn sync_playlist_to_plex(
         }
     }
 
-    // Step 12: Return Result
+    // Step 14: Move Tracks Into Playlist Order
+    // Added items got new playlist item IDs, so fetch them again
+    let plex_tracks = if tracks_to_add.is_empty() && tracks_to_remove.is_empty() {
+        current_plex_tracks
+    } else {
+        client
+            .get_playlist_items(&server_url, access_token, &plex_playlist.rating_key)
+            .await?
+    };
+    let items: Vec<(u64, &str)> = plex_tracks
+        .iter()
+        .filter_map(|track| Some((track.playlist_item_id?, track.rating_key.as_str())))
+        .collect();
+    let (moves, mut synced_rating_keys) = plan_moves(&items, &target_rating_keys);
+
+    let moves_planned = moves.len() as u32;
+    let mut tracks_moved = 0;
+    for (playlist_item_id, after_item_id) in moves {
+        match client
+            .move_playlist_item(
+                &server_url,
+                access_token,
+                &plex_playlist.rating_key,
+                playlist_item_id,
+                after_item_id,
+            )
+            .await
+        {
+            Ok(()) => tracks_moved += 1,
+            Err(e) => {
+                tracks_skipped += 1;
+                tracing::error!("Failed to move playlist item {}: {}", playlist_item_id, e);
+            }
+        }
+    }
+    // A failed move leaves the Plex playlist in another order than planned
+    if tracks_moved < moves_planned {
+        synced_rating_keys = client
+            .get_playlist_items(&server_url, access_token, &plex_playlist.rating_key)
+            .await?
+            .into_iter()
+            .map(|track| track.rating_key)
+            .collect();
+    }
+
+    // Step 15: Remember the Synced Tracks
+    save_synced_tracks(
+        db,
+        playlist_id,
+        server.id,
+        &plex_playlist.rating_key,
+        synced_rating_keys,
+    )
+    .await?;
+
+    // Step 16: Return Result
     let result = SyncPlaylistResult {
         missing_tracks,
         tracks_added,
         tracks_removed,
+        tracks_moved,
         tracks_skipped,
+        local_updated,
     };
 
     tracing::info!(
-        "Sync complete for playlist '{}': {} added, {} removed, {} skipped, {} missing",
+        "Sync complete for playlist '{}': {} added, {} removed, {} moved, {} skipped, {} missing",
         playlist.name,
         result.tracks_added,
         result.tracks_removed,
+        result.tracks_moved,
         result.tracks_skipped,
         result.missing_tracks.len()
     );
 
     Ok(result)
 }
+
+/// Decides the tracks both sides should have after a two-way sync, as rating
+/// keys in playlist order.
+///
+/// `synced` are the tracks of the last sync; a side that still has them didn't
+/// change. The local playlist can only have Plex tracks with a local track,
+/// `is_local`, so only those are compared for it. Without a last sync both
+/// sides count as changed.
+fn merge_playlists(
+    local: &[String],
+    plex: &[String],
+    synced: Option<&[String]>,
+    is_local: impl Fn(&str) -> bool,
+    policy: ConflictPolicy,
+) -> Vec<String> {
+    let (local_changed, plex_changed) = match synced {
+        Some(synced) => (
+            !local
+                .iter()
+                .eq(synced.iter().filter(|rating_key| is_local(rating_key))),
+            plex != synced,
+        ),
+        None => (true, true),
+    };
+
+    match (local_changed, plex_changed) {
+        // Plex may also have tracks the local playlist can't have
+        (false, _) => plex.to_vec(),
+        (true, false) => local.to_vec(),
+        (true, true) => match policy {
+            ConflictPolicy::LocalWins => local.to_vec(),
+            ConflictPolicy::PlexWins => plex.to_vec(),
+            ConflictPolicy::Union => {
+                let mut local_counts: HashMap<&str, usize> = HashMap::new();
+                for rating_key in local {
+                    *local_counts.entry(rating_key.as_str()).or_default() += 1;
+                }
+                let mut merged = local.to_vec();
+                for rating_key in plex {
+                    match local_counts.get_mut(rating_key.as_str()) {
+                        Some(count) if *count > 0 => *count -= 1,
+                        _ => merged.push(rating_key.clone()),
+                    }
+                }
+                merged
+            }
+        },
+    }
+}
+
+/// Local playlist tracks for the synced rating keys. Local tracks that aren't
+/// in Plex keep their position, they can't have changed there.
+fn local_track_ids(
+    synced_rating_keys: &[String],
+    track_ids_by_key: &HashMap<String, i64>,
+    current_track_ids: &[i64],
+    rating_keys: &HashMap<i64, String>,
+) -> Vec<i64> {
+    let mut track_ids: Vec<i64> = synced_rating_keys
+        .iter()
+        .filter_map(|rating_key| track_ids_by_key.get(rating_key).copied())
+        .collect();
+    for (index, track_id) in current_track_ids.iter().enumerate() {
+        if !rating_keys.contains_key(track_id) {
+            track_ids.insert(index.min(track_ids.len()), *track_id);
+        }
+    }
+    track_ids
+}
+
+/// Moves that put Plex playlist items, as (playlist item ID, rating key), in
+/// the order of `target`, as (item to move, item to move it after; `None` for
+/// the top), and the rating keys in the resulting order.
+///
+/// Repeated tracks keep their relative order. Items not in `target` end up
+/// last.
+fn plan_moves(items: &[(u64, &str)], target: &[String]) -> (Vec<(u64, Option<u64>)>, Vec<String>) {
+    let mut remaining = items.to_vec();
+    let mut ordered = Vec::with_capacity(items.len());
+    for rating_key in target {
+        if let Some(index) = remaining
+            .iter()
+            .position(|(_, item_key)| *item_key == rating_key.as_str())
+        {
+            ordered.push(remaining.remove(index));
+        }
+    }
+    ordered.extend(remaining);
+
+    let mut current: Vec<u64> = items.iter().map(|(item_id, _)| *item_id).collect();
+    let mut moves = Vec::new();
+    for (index, (item_id, _)) in ordered.iter().enumerate() {
+        if current[index] == *item_id {
+            continue;
+        }
+        if let Some(from) = current.iter().position(|id| id == item_id) {
+            current.remove(from);
+            current.insert(index, *item_id);
+            moves.push((
+                *item_id,
+                index.checked_sub(1).map(|before| ordered[before].0),
+            ));
+        }
+    }
+
+    let rating_keys = ordered
+        .into_iter()
+        .map(|(_, rating_key)| rating_key.to_string())
+        .collect();
+    (moves, rating_keys)
+}
+
+/// Remembers the Plex tracks a playlist was synced to, for the next sync.
+async fn save_synced_tracks(
+    db: &Database,
+    playlist_id: i64,
+    plex_server_id: i64,
+    plex_rating_key: &str,
+    synced_rating_keys: Vec<String>,
+) -> Result<()> {
+    entities::plex_playlist_sync::Entity::insert(entities::plex_playlist_sync::ActiveModel {
+        playlist_id: Set(playlist_id),
+        plex_server_id: Set(plex_server_id),
+        plex_rating_key: Set(plex_rating_key.to_string()),
+        synced_rating_keys: Set(SyncedRatingKeys(synced_rating_keys)),
+        synced_at: Set(chrono::Utc::now().timestamp()),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            entities::plex_playlist_sync::Column::PlaylistId,
+            entities::plex_playlist_sync::Column::PlexServerId,
+        ])
+        .update_columns([
+            entities::plex_playlist_sync::Column::PlexRatingKey,
+            entities::plex_playlist_sync::Column::SyncedRatingKeys,
+            entities::plex_playlist_sync::Column::SyncedAt,
+        ])
+        .to_owned(),
+    )
+    .exec(&db.conn)
+    .await
+    .wrap_err("Failed to save Plex playlist sync")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::plex::client::PlexHttpAdapter;
+    use crate::test_utils::fake_plex::FakePlex;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait};
+
+    fn keys(rating_keys: &[&str]) -> Vec<String> {
+        rating_keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge_takes_the_side_that_changed() {
+        let synced = keys(&["1", "2", "3"]);
+        let local = keys(&["1", "2", "3", "4"]);
+        let plex = keys(&["3", "1", "2"]);
+        let is_local = |_: &str| true;
+
+        assert_eq!(
+            merge_playlists(
+                &local,
+                &synced,
+                Some(&synced),
+                is_local,
+                ConflictPolicy::PlexWins
+            ),
+            local
+        );
+        assert_eq!(
+            merge_playlists(
+                &synced,
+                &plex,
+                Some(&synced),
+                is_local,
+                ConflictPolicy::LocalWins
+            ),
+            plex
+        );
+        // Plex tracks without a local track don't count as a local change
+        let plex = keys(&["1", "9", "2", "3"]);
+        assert_eq!(
+            merge_playlists(
+                &synced,
+                &plex,
+                Some(&plex),
+                |key: &str| key != "9",
+                ConflictPolicy::LocalWins
+            ),
+            plex
+        );
+    }
+
+    #[test]
+    fn test_merge_conflict_policies() {
+        let synced = keys(&["1", "2", "3"]);
+        let local = keys(&["1", "3", "4"]);
+        let plex = keys(&["5", "1", "2", "3", "1"]);
+        let merge = |policy| merge_playlists(&local, &plex, Some(&synced), |_: &str| true, policy);
+
+        assert_eq!(merge(ConflictPolicy::LocalWins), local);
+        assert_eq!(merge(ConflictPolicy::PlexWins), plex);
+        assert_eq!(
+            merge(ConflictPolicy::Union),
+            keys(&["1", "3", "4", "5", "2", "1"])
+        );
+    }
+
+    #[test]
+    fn test_local_tracks_missing_in_plex_keep_their_position() {
+        let track_ids_by_key = HashMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+        let rating_keys = HashMap::from([(1, "a".to_string()), (2, "b".to_string())]);
+        assert_eq!(
+            local_track_ids(
+                &keys(&["b", "a"]),
+                &track_ids_by_key,
+                &[1, 7, 2],
+                &rating_keys
+            ),
+            vec![2, 7, 1]
+        );
+    }
+
+    #[test]
+    fn test_plan_moves_restores_order() {
+        let items = [(10, "a"), (11, "b"), (12, "c"), (13, "a")];
+        let (moves, order) = plan_moves(&items, &keys(&["c", "a", "a", "b"]));
+        assert_eq!(moves, vec![(12, None), (13, Some(10))]);
+        assert_eq!(order, keys(&["c", "a", "a", "b"]));
+
+        let (moves, order) = plan_moves(&items, &keys(&["a", "b", "c", "a"]));
+        assert!(moves.is_empty());
+        assert_eq!(order, keys(&["a", "b", "c", "a"]));
+    }
+
+    #[tokio::test]
+    async fn test_sync_with_fake_plex_keeps_order_and_brings_back_plex_edits() {
+        let fake = FakePlex::start().await;
+        let client = PlexHttpAdapter::new();
+        let db = test_db().await;
+        let server = entities::plex_server::ActiveModel {
+            name: Set("Fake".into()),
+            server_url: Set(fake.url().to_string()),
+            access_token: Set(Some(fake.token())),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let album = "/music/Queen/A Night at the Opera";
+        let death = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/01 Death on Two Legs.flac")),
+                ..TestTrack::new("Death on Two Legs")
+            },
+        )
+        .await
+        .id;
+        let lazing = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/02 Lazing on a Sunday Afternoon.flac")),
+                ..TestTrack::new("Lazing on a Sunday Afternoon")
+            },
+        )
+        .await
+        .id;
+        insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/04 You're My Best Friend.flac")),
+                ..TestTrack::new("You're My Best Friend")
+            },
+        )
+        .await;
+        let rhapsody = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/11 Bohemian Rhapsody.flac")),
+                ..TestTrack::new("Bohemian Rhapsody")
+            },
+        )
+        .await
+        .id;
+        let playlists = PlaylistService::new(db.clone());
+        let playlist = playlists.create("Road Trip".into(), None).await.unwrap();
+        playlists
+            .replace_tracks(playlist.id, vec![rhapsody, lazing, death])
+            .await
+            .unwrap();
+        let config = MatcherConfig::default();
+        let one_way = PlexPlaylistSyncConfig::default();
+
+        // The fixture's "Road Trip" is 101, 104, 111
+        let result = sync_playlist_to_plex(&db, &client, &config, &one_way, playlist.id, &server)
+            .await
+            .unwrap();
+        assert_eq!(
+            fake.playlist_tracks("Road Trip").unwrap(),
+            keys(&["111", "102", "101"])
+        );
+        assert_eq!(result.tracks_added, 1);
+        assert_eq!(result.tracks_removed, 1);
+        assert!(result.tracks_moved >= 1);
+        assert!(!result.local_updated);
+
+        // Edited in Plex: reordered, 102 removed and 103, without a local track, added
+        fake.set_playlist_tracks("Road Trip", &["101", "111", "103"]);
+        let two_way = PlexPlaylistSyncConfig {
+            two_way: true,
+            conflict_policy: ConflictPolicy::LocalWins,
+        };
+        let result = sync_playlist_to_plex(&db, &client, &config, &two_way, playlist.id, &server)
+            .await
+            .unwrap();
+        assert!(result.local_updated);
+        assert_eq!(
+            playlist_track_ids(&db.conn, playlist.id).await.unwrap(),
+            vec![death, rhapsody]
+        );
+        assert_eq!(
+            fake.playlist_tracks("Road Trip").unwrap(),
+            keys(&["101", "111", "103"])
+        );
+
+        let result = sync_playlist_to_plex(&db, &client, &config, &two_way, playlist.id, &server)
+            .await
+            .unwrap();
+        assert!(!result.local_updated);
+        assert_eq!(
+            (
+                result.tracks_added,
+                result.tracks_removed,
+                result.tracks_moved
+            ),
+            (0, 0, 0)
+        );
+    }
+}
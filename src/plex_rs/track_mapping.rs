@@ -0,0 +1,1256 @@
+use color_eyre::eyre::{OptionExt, Result, WrapErr};
+use sea_orm::prelude::Expr;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait,
+    PaginatorTrait, QueryFilter, QuerySelect, Set, TransactionTrait,
+};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+use tracing;
+use url::Url;
+
+use crate::database::{Database, IN_LIST_CHUNK_SIZE};
+use crate::entities;
+use crate::entities::plex_track_mapping::PlexTrackMatchMethod;
+use crate::plex_rs::all_tracks::{PlexLibraryTrack, TracksSince, get_all_tracks_since};
+use crate::plex_rs::path_mapping::PathMappings;
+use crate::ports::plex::PlexClient;
+use crate::services::spotify::matching_local_tracks::{
+    MatcherConfig, Track, find_local_track, refresh_index,
+};
+
+type RefreshLock = Arc<tokio::sync::Mutex<()>>;
+
+/// One lock per Plex server, held while refreshing its mappings, as
+/// concurrent refreshes would race on them
+static REFRESH_LOCKS: LazyLock<Mutex<HashMap<i64, RefreshLock>>> = LazyLock::new(Default::default);
+
+fn refresh_lock(plex_server_id: i64) -> RefreshLock {
+    REFRESH_LOCKS
+        .lock()
+        .expect("refresh lock registry poisoned")
+        .entry(plex_server_id)
+        .or_default()
+        .clone()
+}
+
+/// Extracts and normalizes the last 3 path components (artist/album/track) for matching.
+///
+/// The path structure is: `.../ArtistName/AlbumName/TrackNumber - TrackName.ext`
+/// This function extracts Artist/Album/Track and normalizes them by:
+/// - Converting to lowercase
+/// - Removing track number prefix (e.g., "01 ")
+/// - Removing file extension
+///
+/// Returns None if the path doesn't have at least 3 components.
+fn normalize_path_key(file_path: &str) -> Option<String> {
+    let path = Path::new(file_path);
+    let components: Vec<_> = path.iter().filter_map(|c| c.to_str()).collect();
+
+    if components.len() < 3 {
+        return None;
+    }
+
+    // Get last 3 components: artist, album, track_filename
+    let artist = components[components.len() - 3].to_lowercase();
+    let album = components[components.len() - 2].to_lowercase();
+    let track_filename = components[components.len() - 1].to_lowercase();
+
+    // Remove file extension
+    let track_name = Path::new(&track_filename)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(&track_filename);
+
+    // Remove track number prefix (e.g., "01 ", "1 ", etc.)
+    // Pattern: starts with digits followed by space or dash
+    let track_name = track_name
+        .trim_start_matches(char::is_numeric)
+        .trim_start_matches(' ')
+        .trim_start_matches('-')
+        .trim_start_matches(' ');
+
+    Some(format!("{}/{}/{}", artist, album, track_name))
+}
+
+/// Result of refreshing the track mappings of a Plex server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackMappingRefreshResult {
+    /// Plex tracks matched, i.e. updated since the last refresh or left
+    /// unmatched by an earlier one
+    pub tracks_scanned: u32,
+    pub tracks_mapped: u32,
+    /// Plex tracks no local track was found for
+    pub tracks_unmatched: u32,
+    /// Mappings removed because their Plex track is gone or changed
+    pub mappings_removed: u32,
+}
+
+/// Local tracks by the keys Plex tracks are matched on first.
+struct LocalTrackLookup {
+    by_path: HashMap<String, i64>,
+    by_path_key: HashMap<String, i64>,
+    by_musicbrainz_id: HashMap<String, i64>,
+}
+
+impl LocalTrackLookup {
+    async fn load(db: &Database) -> Result<Self> {
+        let tracks = entities::track::Entity::find()
+            .select_only()
+            .column(entities::track::Column::Id)
+            .column(entities::track::Column::FilePath)
+            .column(entities::track::Column::MusicbrainzId)
+            .into_tuple::<(i64, String, Option<String>)>()
+            .all(&db.conn)
+            .await
+            .wrap_err("Failed to fetch local tracks")?;
+
+        let mut lookup = Self {
+            by_path: HashMap::new(),
+            by_path_key: HashMap::new(),
+            by_musicbrainz_id: HashMap::new(),
+        };
+        for (track_id, file_path, musicbrainz_id) in tracks {
+            if let Some(key) = normalize_path_key(&file_path) {
+                lookup.by_path_key.insert(key, track_id);
+            }
+            lookup.by_path.insert(file_path, track_id);
+            if let Some(musicbrainz_id) = musicbrainz_id {
+                lookup
+                    .by_musicbrainz_id
+                    .insert(musicbrainz_id.to_lowercase(), track_id);
+            }
+        }
+        Ok(lookup)
+    }
+}
+
+fn plex_track_to_track(track: &PlexLibraryTrack) -> Option<Track> {
+    Some(Track {
+        title: track.title.clone(),
+        primary_artist: track.artist.clone()?,
+        secondary_artists: Vec::new(),
+        album: track.album.clone().unwrap_or_default(),
+        duration_ms: track.duration? as u32,
+    })
+}
+
+/// Finds the local track of a Plex track: by file path, then by MusicBrainz
+/// ID, then by a high confidence metadata match.
+async fn match_local_track(
+    db: &Database,
+    config: &MatcherConfig,
+    lookup: &LocalTrackLookup,
+    path_mappings: &PathMappings,
+    track: &PlexLibraryTrack,
+) -> Result<Option<(i64, PlexTrackMatchMethod)>> {
+    if let Ok(file_path) = track.file_path() {
+        if let Some(track_id) = lookup.by_path.get(&path_mappings.to_local(file_path)) {
+            return Ok(Some((*track_id, PlexTrackMatchMethod::Path)));
+        }
+        // Path keys survive unmapped mount points, but not renamed files or
+        // layouts deeper than artist/album/track
+        if let Some(track_id) =
+            normalize_path_key(file_path).and_then(|key| lookup.by_path_key.get(&key))
+        {
+            return Ok(Some((*track_id, PlexTrackMatchMethod::Path)));
+        }
+    }
+
+    if let Some(track_id) = track
+        .musicbrainz_id()
+        .and_then(|id| lookup.by_musicbrainz_id.get(&id.to_lowercase()))
+    {
+        return Ok(Some((*track_id, PlexTrackMatchMethod::MusicBrainzId)));
+    }
+
+    let Some(plex_track) = plex_track_to_track(track) else {
+        return Ok(None);
+    };
+    Ok(find_local_track(db, &plex_track, config)
+        .await?
+        .map(|track_id| (track_id, PlexTrackMatchMethod::Metadata)))
+}
+
+/// Updates the mappings of a Plex server from the given Plex tracks.
+///
+/// A Plex track replaces any mapping of its rating key or of the local track
+/// it matched, so every rating key and local track is mapped at most once.
+/// Plex tracks without a local track are stored as unmatched, like those
+/// whose local track another Plex track took. The play history
+/// of the Plex tracks is stored on their mappings and rolled up into the local
+/// tracks. With `complete`, `plex_tracks` is the whole library and mappings
+/// and unmatched tracks of Plex tracks not in it are removed.
+pub async fn map_plex_tracks(
+    db: &Database,
+    config: &MatcherConfig,
+    plex_server_id: i64,
+    plex_tracks: &[PlexLibraryTrack],
+    complete: bool,
+) -> Result<TrackMappingRefreshResult> {
+    let mut result = TrackMappingRefreshResult {
+        tracks_scanned: plex_tracks.len() as u32,
+        ..Default::default()
+    };
+    if plex_tracks.is_empty() && !complete {
+        return Ok(result);
+    }
+
+    refresh_index(db).await?;
+    let lookup = LocalTrackLookup::load(db).await?;
+    let path_mappings = PathMappings::load(db, plex_server_id).await?;
+    // Local tracks whose play history has to be rolled up again
+    let mut changed_track_ids = HashSet::new();
+
+    for plex_track in plex_tracks {
+        let matched = match match_local_track(db, config, &lookup, &path_mappings, plex_track).await
+        {
+            Ok(matched) => matched,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to match Plex track '{}' (rating_key: {}): {:?}",
+                    plex_track.title,
+                    plex_track.rating_key,
+                    e
+                );
+                continue;
+            }
+        };
+
+        let mut stale = Condition::any().add(
+            entities::plex_track_mapping::Column::RatingKey.eq(plex_track.rating_key.as_str()),
+        );
+        if let Some((track_id, _)) = &matched {
+            stale = stale.add(entities::plex_track_mapping::Column::TrackId.eq(*track_id));
+        }
+        let txn = db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to start transaction")?;
+        let stale = entities::plex_track_mapping::Entity::find()
+            .filter(entities::plex_track_mapping::Column::PlexServerId.eq(plex_server_id))
+            .filter(stale)
+            .all(&txn)
+            .await
+            .wrap_err("Failed to fetch old Plex track mappings")?;
+        changed_track_ids.extend(stale.iter().map(|mapping| mapping.track_id));
+        let removed = stale.len();
+        if !stale.is_empty() {
+            entities::plex_track_mapping::Entity::delete_many()
+                .filter(
+                    entities::plex_track_mapping::Column::Id
+                        .is_in(stale.iter().map(|mapping| mapping.id)),
+                )
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to remove old Plex track mappings")?;
+        }
+        // Plex tracks whose local track went to this one are unmatched now
+        for displaced in stale
+            .iter()
+            .filter(|mapping| mapping.rating_key != plex_track.rating_key)
+        {
+            match plex_tracks
+                .iter()
+                .find(|track| track.rating_key == displaced.rating_key)
+            {
+                Some(track) => save_unmatched_track(&txn, plex_server_id, track).await?,
+                None => {
+                    save_unmatched_track(&txn, plex_server_id, &displaced_track(displaced)).await?
+                }
+            }
+        }
+
+        let Some((track_id, match_method)) = matched else {
+            save_unmatched_track(&txn, plex_server_id, plex_track).await?;
+            txn.commit()
+                .await
+                .wrap_err("Failed to save unmatched Plex track")?;
+            result.mappings_removed += removed as u32;
+            result.tracks_unmatched += 1;
+            tracing::debug!(
+                "No local track found for Plex track '{}' (rating_key: {})",
+                plex_track.title,
+                plex_track.rating_key
+            );
+            continue;
+        };
+
+        entities::plex_track_mapping::ActiveModel {
+            plex_server_id: Set(plex_server_id),
+            track_id: Set(track_id),
+            rating_key: Set(plex_track.rating_key.clone()),
+            match_method: Set(match_method),
+            plex_updated_at: Set(plex_track.updated_at),
+            view_count: Set(plex_track.view_count.map(|count| count as i32)),
+            last_viewed_at: Set(plex_track.last_viewed_at),
+            user_rating: Set(plex_track.user_rating),
+            ..entities::plex_track_mapping::ActiveModel::new()
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to save Plex track mapping")?;
+        entities::plex_unmatched_track::Entity::delete_many()
+            .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(plex_server_id))
+            .filter(
+                entities::plex_unmatched_track::Column::RatingKey
+                    .eq(plex_track.rating_key.as_str()),
+            )
+            .exec(&txn)
+            .await
+            .wrap_err("Failed to remove unmatched Plex track")?;
+        txn.commit()
+            .await
+            .wrap_err("Failed to save Plex track mapping")?;
+        changed_track_ids.insert(track_id);
+        result.tracks_mapped += 1;
+    }
+
+    if complete {
+        let rating_keys: HashSet<&str> = plex_tracks
+            .iter()
+            .map(|track| track.rating_key.as_str())
+            .collect();
+        result.mappings_removed +=
+            remove_missing_tracks(db, plex_server_id, &rating_keys, &mut changed_track_ids).await?;
+    }
+
+    update_play_history(db, changed_track_ids).await?;
+
+    Ok(result)
+}
+
+/// What is known of a Plex track that wasn't fetched from Plex, from its old
+/// mapping. It is stored until a refresh fetches the track itself.
+fn displaced_track(mapping: &entities::plex_track_mapping::Model) -> PlexLibraryTrack {
+    PlexLibraryTrack {
+        rating_key: mapping.rating_key.clone(),
+        title: String::new(),
+        artist: None,
+        album: None,
+        track_number: None,
+        duration: None,
+        updated_at: mapping.plex_updated_at,
+        view_count: mapping.view_count.map(|count| count as u32),
+        last_viewed_at: mapping.last_viewed_at,
+        user_rating: mapping.user_rating,
+        media: Vec::new(),
+        guids: Vec::new(),
+    }
+}
+
+/// Stores a Plex track no local track was found for, replacing what was
+/// stored for its rating key.
+async fn save_unmatched_track(
+    conn: &impl ConnectionTrait,
+    plex_server_id: i64,
+    plex_track: &PlexLibraryTrack,
+) -> Result<()> {
+    let stored = serde_json::to_value(plex_track).wrap_err("Failed to serialize Plex track")?;
+    entities::plex_unmatched_track::Entity::insert(entities::plex_unmatched_track::ActiveModel {
+        plex_server_id: Set(plex_server_id),
+        rating_key: Set(plex_track.rating_key.clone()),
+        plex_track: Set(stored),
+    })
+    .on_conflict(
+        OnConflict::columns([
+            entities::plex_unmatched_track::Column::PlexServerId,
+            entities::plex_unmatched_track::Column::RatingKey,
+        ])
+        .update_column(entities::plex_unmatched_track::Column::PlexTrack)
+        .to_owned(),
+    )
+    .exec(conn)
+    .await
+    .wrap_err("Failed to save unmatched Plex track")?;
+    Ok(())
+}
+
+/// The Plex tracks of a server that no local track was found for.
+async fn unmatched_tracks(db: &Database, plex_server_id: i64) -> Result<Vec<PlexLibraryTrack>> {
+    entities::plex_unmatched_track::Entity::find()
+        .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(plex_server_id))
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch unmatched Plex tracks")?
+        .into_iter()
+        .map(|unmatched| {
+            serde_json::from_value(unmatched.plex_track)
+                .wrap_err("Failed to parse unmatched Plex track")
+        })
+        .collect()
+}
+
+/// Removes the mappings and unmatched tracks of a Plex server whose rating
+/// keys aren't in `rating_keys`, i.e. that were deleted from Plex. Returns
+/// the number of mappings removed.
+async fn remove_missing_tracks(
+    db: &Database,
+    plex_server_id: i64,
+    rating_keys: &HashSet<&str>,
+    changed_track_ids: &mut HashSet<i64>,
+) -> Result<u32> {
+    let gone: Vec<i64> = entities::plex_track_mapping::Entity::find()
+        .filter(entities::plex_track_mapping::Column::PlexServerId.eq(plex_server_id))
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch Plex track mappings")?
+        .into_iter()
+        .filter(|mapping| !rating_keys.contains(mapping.rating_key.as_str()))
+        .map(|mapping| {
+            changed_track_ids.insert(mapping.track_id);
+            mapping.id
+        })
+        .collect();
+    let mut removed = 0;
+    for chunk in gone.chunks(IN_LIST_CHUNK_SIZE) {
+        removed += entities::plex_track_mapping::Entity::delete_many()
+            .filter(entities::plex_track_mapping::Column::Id.is_in(chunk.iter().copied()))
+            .exec(&db.conn)
+            .await
+            .wrap_err("Failed to remove Plex track mappings")?
+            .rows_affected as u32;
+    }
+
+    let gone_unmatched: Vec<String> = entities::plex_unmatched_track::Entity::find()
+        .select_only()
+        .column(entities::plex_unmatched_track::Column::RatingKey)
+        .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(plex_server_id))
+        .into_tuple::<String>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch unmatched Plex tracks")?
+        .into_iter()
+        .filter(|rating_key| !rating_keys.contains(rating_key.as_str()))
+        .collect();
+    for chunk in gone_unmatched.chunks(IN_LIST_CHUNK_SIZE) {
+        entities::plex_unmatched_track::Entity::delete_many()
+            .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(plex_server_id))
+            .filter(entities::plex_unmatched_track::Column::RatingKey.is_in(chunk.iter().cloned()))
+            .exec(&db.conn)
+            .await
+            .wrap_err("Failed to remove unmatched Plex tracks")?;
+    }
+
+    Ok(removed)
+}
+
+/// Removes the mappings and unmatched tracks of Plex tracks deleted from the
+/// library, which incremental refreshes don't fetch. The rating keys of the
+/// whole library are only listed when Plex has fewer tracks than are known.
+/// Returns the number of mappings removed.
+async fn prune_deleted_tracks(
+    db: &Database,
+    client: &dyn PlexClient,
+    server_url: &Url,
+    access_token: &str,
+    music_section_id: &str,
+    plex_server_id: i64,
+) -> Result<u32> {
+    // An empty page still reports the size of the library
+    let Some(library_size) = client
+        .get_tracks_page(server_url, access_token, music_section_id, None, 0, 0)
+        .await?
+        .total_size
+    else {
+        return Ok(0);
+    };
+    let mapped = entities::plex_track_mapping::Entity::find()
+        .filter(entities::plex_track_mapping::Column::PlexServerId.eq(plex_server_id))
+        .count(&db.conn)
+        .await
+        .wrap_err("Failed to count Plex track mappings")?;
+    let unmatched = entities::plex_unmatched_track::Entity::find()
+        .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(plex_server_id))
+        .count(&db.conn)
+        .await
+        .wrap_err("Failed to count unmatched Plex tracks")?;
+    if mapped + unmatched <= u64::from(library_size) {
+        return Ok(0);
+    }
+
+    tracing::info!(
+        "Plex library has {} tracks but {} are known, removing deleted ones",
+        library_size,
+        mapped + unmatched
+    );
+    let plex_tracks = get_all_tracks_since(
+        client,
+        server_url,
+        access_token,
+        music_section_id,
+        None,
+        1000,
+    )
+    .await?;
+    let rating_keys: HashSet<&str> = plex_tracks
+        .iter()
+        .map(|track| track.rating_key.as_str())
+        .collect();
+    let mut changed_track_ids = HashSet::new();
+    let removed =
+        remove_missing_tracks(db, plex_server_id, &rating_keys, &mut changed_track_ids).await?;
+    update_play_history(db, changed_track_ids).await?;
+    Ok(removed)
+}
+
+/// Whether local tracks were added since a point in time, always when there
+/// is none.
+async fn local_tracks_added_since(db: &Database, since: Option<i64>) -> Result<bool> {
+    let Some(since) = since else {
+        return Ok(true);
+    };
+    let added = entities::track::Entity::find()
+        .filter(entities::track::Column::CreatedAt.gte(since))
+        .count(&db.conn)
+        .await
+        .wrap_err("Failed to count new local tracks")?;
+    Ok(added > 0)
+}
+
+/// Rolls the play history of the Plex tracks mapped to the given local tracks
+/// up into the tracks: plays are summed over all servers, the last play and
+/// the rating are the most recent and highest.
+pub async fn update_play_history(db: &Database, track_ids: HashSet<i64>) -> Result<()> {
+    let track_ids: Vec<i64> = track_ids.into_iter().collect();
+    for chunk in track_ids.chunks(IN_LIST_CHUNK_SIZE) {
+        let txn = db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+        let mappings = entities::plex_track_mapping::Entity::find()
+            .filter(entities::plex_track_mapping::Column::TrackId.is_in(chunk.iter().copied()))
+            .all(&txn)
+            .await
+            .wrap_err("Failed to fetch Plex track mappings")?;
+
+        for &track_id in chunk {
+            let mut play_count = None;
+            let mut last_played_at = None;
+            let mut rating: Option<f64> = None;
+            for mapping in mappings.iter().filter(|m| m.track_id == track_id) {
+                // Plex leaves out the view count of unplayed tracks
+                play_count = Some(play_count.unwrap_or(0) + mapping.view_count.unwrap_or(0));
+                last_played_at = last_played_at.max(mapping.last_viewed_at);
+                if let Some(user_rating) = mapping.user_rating {
+                    rating = Some(rating.map_or(user_rating, |rating| rating.max(user_rating)));
+                }
+            }
+
+            // Not through the active model, so the track isn't reindexed
+            entities::track::Entity::update_many()
+                .col_expr(entities::track::Column::PlayCount, Expr::value(play_count))
+                .col_expr(
+                    entities::track::Column::LastPlayedAt,
+                    Expr::value(last_played_at),
+                )
+                .col_expr(entities::track::Column::Rating, Expr::value(rating))
+                .filter(entities::track::Column::Id.eq(track_id))
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to save track play history")?;
+        }
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit track play history")?;
+    }
+
+    Ok(())
+}
+
+/// URL, access token and default music section of a Plex server.
+async fn server_library<'a>(
+    client: &dyn PlexClient,
+    server: &'a entities::plex_server::Model,
+) -> Result<(Url, &'a str, String)> {
+    let access_token = server.access_token.as_deref().ok_or_eyre(
+        "Plex server does not have an access token. Please authenticate the server first.",
+    )?;
+    let server_url = Url::parse(&server.server_url)
+        .wrap_err(format!("Invalid server URL: {}", server.server_url))?;
+
+    let music_section_id = match &server.music_section_id {
+        Some(section_id) => section_id.clone(),
+        None => {
+            let sections = client
+                .get_library_sections(&server_url, access_token)
+                .await?;
+            client
+                .find_music_section_id(&sections)
+                .ok_or_eyre("No music library section found on Plex server")?
+        }
+    };
+
+    Ok((server_url, access_token, music_section_id))
+}
+
+/// Refreshes the local track ↔ Plex rating key mappings of a Plex server,
+/// from its default music section.
+///
+/// Only Plex tracks updated since the previous refresh are fetched and
+/// matched, unless `full` is set or the server was never refreshed. Plex
+/// tracks left unmatched are matched again when local tracks were added since,
+/// and mappings of tracks that were deleted from Plex are removed. Refreshes
+/// of the same server run one at a time.
+///
+/// # Errors
+/// Returns an error if:
+/// - Plex server missing access token
+/// - No music library section found
+/// - Failed to fetch Plex library tracks
+pub async fn refresh_track_mappings(
+    db: &Database,
+    client: &dyn PlexClient,
+    config: &MatcherConfig,
+    server: &entities::plex_server::Model,
+    full: bool,
+) -> Result<TrackMappingRefreshResult> {
+    let lock = refresh_lock(server.id);
+    let _refreshing = lock.lock().await;
+    // A refresh that finished while waiting moved the server's refresh times
+    let server = &entities::plex_server::Entity::find_by_id(server.id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch Plex server")?
+        .ok_or_eyre("Plex server not found")?;
+
+    let (server_url, access_token, music_section_id) = server_library(client, server).await?;
+
+    let updated_since = if full {
+        None
+    } else {
+        server.library_updated_at
+    };
+    tracing::info!(
+        "Fetching Plex tracks of server '{}' updated since {:?}",
+        server.name,
+        updated_since
+    );
+    // Tracks updated in the same second as the newest one seen are fetched
+    // again, so none are missed
+    let mut plex_tracks = get_all_tracks_since(
+        client,
+        &server_url,
+        access_token,
+        &music_section_id,
+        updated_since.map(TracksSince::Updated),
+        1000,
+    )
+    .await?;
+    let library_updated_at = plex_tracks
+        .iter()
+        .filter_map(|track| track.updated_at)
+        .chain(server.library_updated_at)
+        .max();
+    // Local tracks imported from here on are matched by the next refresh
+    let refreshed_at = chrono::Utc::now().timestamp();
+
+    let complete = updated_since.is_none();
+    if !complete && local_tracks_added_since(db, server.mappings_refreshed_at).await? {
+        let fetched: HashSet<String> = plex_tracks
+            .iter()
+            .map(|track| track.rating_key.clone())
+            .collect();
+        plex_tracks.extend(
+            unmatched_tracks(db, server.id)
+                .await?
+                .into_iter()
+                .filter(|track| !fetched.contains(&track.rating_key)),
+        );
+    }
+
+    let mut result = map_plex_tracks(db, config, server.id, &plex_tracks, complete).await?;
+    if !complete {
+        result.mappings_removed += prune_deleted_tracks(
+            db,
+            client,
+            &server_url,
+            access_token,
+            &music_section_id,
+            server.id,
+        )
+        .await?;
+    }
+
+    let mut server_model: entities::plex_server::ActiveModel = server.clone().into();
+    server_model.library_updated_at = Set(library_updated_at);
+    server_model.mappings_refreshed_at = Set(Some(refreshed_at));
+    server_model
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save Plex library refresh time")?;
+
+    tracing::info!(
+        "Refreshed Plex track mappings of server '{}': {:?}",
+        server.name,
+        result
+    );
+    Ok(result)
+}
+
+/// Imports play counts, last played times and ratings of a Plex server's
+/// default music section into the local tracks.
+///
+/// Only Plex tracks played since the previous import are fetched, unless
+/// `full` is set or the history was never imported. Ratings changed without
+/// playing the track are only picked up by a full import.
+///
+/// # Errors
+/// Returns an error if:
+/// - Plex server missing access token
+/// - No music library section found
+/// - Failed to fetch Plex library tracks
+pub async fn import_play_history(
+    db: &Database,
+    client: &dyn PlexClient,
+    config: &MatcherConfig,
+    server: &entities::plex_server::Model,
+    full: bool,
+) -> Result<TrackMappingRefreshResult> {
+    let (server_url, access_token, music_section_id) = server_library(client, server).await?;
+
+    let viewed_since = if full {
+        None
+    } else {
+        server.history_imported_at
+    };
+    tracing::info!(
+        "Fetching Plex tracks of server '{}' played since {:?}",
+        server.name,
+        viewed_since
+    );
+    let plex_tracks = get_all_tracks_since(
+        client,
+        &server_url,
+        access_token,
+        &music_section_id,
+        viewed_since.map(TracksSince::Viewed),
+        1000,
+    )
+    .await?;
+
+    let result =
+        map_plex_tracks(db, config, server.id, &plex_tracks, viewed_since.is_none()).await?;
+
+    let history_imported_at = plex_tracks
+        .iter()
+        .filter_map(|track| track.last_viewed_at)
+        .chain(server.history_imported_at)
+        .max();
+    if history_imported_at != server.history_imported_at {
+        let mut server: entities::plex_server::ActiveModel = server.clone().into();
+        server.history_imported_at = Set(history_imported_at);
+        server
+            .update(&db.conn)
+            .await
+            .wrap_err("Failed to save Plex play history import time")?;
+    }
+
+    tracing::info!(
+        "Imported Plex play history of server '{}': {:?}",
+        server.name,
+        result
+    );
+    Ok(result)
+}
+
+/// Mapped Plex rating keys of the given local tracks on a Plex server.
+pub async fn rating_keys_by_track(
+    db: &Database,
+    plex_server_id: i64,
+    track_ids: Vec<i64>,
+) -> Result<HashMap<i64, String>> {
+    let mut rating_keys = HashMap::with_capacity(track_ids.len());
+    for chunk in track_ids.chunks(IN_LIST_CHUNK_SIZE) {
+        let mappings = entities::plex_track_mapping::Entity::find()
+            .filter(entities::plex_track_mapping::Column::PlexServerId.eq(plex_server_id))
+            .filter(entities::plex_track_mapping::Column::TrackId.is_in(chunk.iter().copied()))
+            .all(&db.conn)
+            .await
+            .wrap_err("Failed to fetch Plex track mappings")?;
+        rating_keys.extend(
+            mappings
+                .into_iter()
+                .map(|mapping| (mapping.track_id, mapping.rating_key)),
+        );
+    }
+    Ok(rating_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::plex::client::PlexHttpAdapter;
+    use crate::test_utils::fake_plex::FakePlex;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+
+    async fn insert_server(db: &Database) -> i64 {
+        entities::plex_server::ActiveModel {
+            name: Set("Plex".into()),
+            server_url: Set("http://plex.local:32400/".into()),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap()
+        .id
+    }
+
+    fn local_track<'a>(
+        title: &'a str,
+        artist: &'a str,
+        file_path: &'a str,
+        musicbrainz_id: Option<&'a str>,
+    ) -> TestTrack<'a> {
+        TestTrack {
+            album: Some("A Night at the Opera"),
+            artist: Some(artist),
+            duration: Some(354),
+            file_path: Some(file_path),
+            musicbrainz_id,
+            ..TestTrack::new(title)
+        }
+    }
+
+    fn plex_track(
+        rating_key: &str,
+        title: &str,
+        artist: &str,
+        file: &str,
+        guid: Option<&str>,
+    ) -> PlexLibraryTrack {
+        serde_json::from_value(serde_json::json!({
+            "ratingKey": rating_key,
+            "title": title,
+            "grandparentTitle": artist,
+            "parentTitle": "A Night at the Opera",
+            "duration": 354_000,
+            "updatedAt": 1_700_000_000,
+            "Media": [{ "Part": [{ "file": file }] }],
+            "Guid": guid.map(|id| vec![serde_json::json!({ "id": id })]).unwrap_or_default(),
+        }))
+        .unwrap()
+    }
+
+    async fn mappings(db: &Database) -> Vec<(i64, String, PlexTrackMatchMethod)> {
+        let mut mappings: Vec<_> = entities::plex_track_mapping::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.track_id, m.rating_key, m.match_method))
+            .collect();
+        mappings.sort_by_key(|(track_id, _, _)| *track_id);
+        mappings
+    }
+
+    #[test]
+    fn test_normalize_path_key() {
+        assert_eq!(
+            normalize_path_key("/mnt/music/Queen/A Night at the Opera/11 - Bohemian Rhapsody.flac"),
+            Some("queen/a night at the opera/bohemian rhapsody".into())
+        );
+        assert_eq!(normalize_path_key("song.flac"), None);
+    }
+
+    #[tokio::test]
+    async fn test_matches_by_path_musicbrainz_id_and_metadata() {
+        let db = test_db().await;
+        let server_id = insert_server(&db).await;
+        let by_path = insert_track(
+            &db,
+            local_track(
+                "Bohemian Rhapsody",
+                "Queen",
+                "/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac",
+                None,
+            ),
+        )
+        .await
+        .id;
+        let by_mbid = insert_track(
+            &db,
+            local_track(
+                "Love of My Life",
+                "Queen",
+                "/music/Queen/A Night at the Opera/09 Love of My Life.flac",
+                Some("6d5c2a5d-6bd4-4e1b-9c35-0d0c1b9f7a6e"),
+            ),
+        )
+        .await
+        .id;
+        let by_metadata = insert_track(
+            &db,
+            local_track(
+                "You're My Best Friend",
+                "Queen",
+                "/music/Queen/A Night at the Opera/04 You're My Best Friend.flac",
+                None,
+            ),
+        )
+        .await
+        .id;
+        insert_track(
+            &db,
+            local_track(
+                "Death on Two Legs",
+                "Queen",
+                "/music/Queen/A Night at the Opera/01 Death on Two Legs.flac",
+                None,
+            ),
+        )
+        .await;
+
+        let plex_tracks = vec![
+            // Mounted elsewhere on the Plex server
+            plex_track(
+                "101",
+                "Bohemian Rhapsody",
+                "Queen",
+                "/data/media/Queen/A Night at the Opera/11 - Bohemian Rhapsody.flac",
+                None,
+            ),
+            // Renamed on the Plex server
+            plex_track(
+                "102",
+                "Love of My Life",
+                "Queen",
+                "/data/media/Queen/Opera/love.flac",
+                Some("mbid://6D5C2A5D-6BD4-4E1B-9C35-0D0C1B9F7A6E"),
+            ),
+            plex_track(
+                "103",
+                "You're My Best Friend",
+                "Queen",
+                "/data/media/Queen/Opera/friend.flac",
+                None,
+            ),
+            plex_track(
+                "104",
+                "Seaside Rendezvous",
+                "Queen",
+                "/data/media/Queen/Opera/seaside.flac",
+                None,
+            ),
+        ];
+        let result = map_plex_tracks(
+            &db,
+            &MatcherConfig::default(),
+            server_id,
+            &plex_tracks,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.tracks_scanned, 4);
+        assert_eq!(result.tracks_mapped, 3);
+        assert_eq!(result.tracks_unmatched, 1);
+        assert_eq!(
+            mappings(&db).await,
+            vec![
+                (by_path, "101".into(), PlexTrackMatchMethod::Path),
+                (by_mbid, "102".into(), PlexTrackMatchMethod::MusicBrainzId),
+                (by_metadata, "103".into(), PlexTrackMatchMethod::Metadata),
+            ]
+        );
+
+        let keys = rating_keys_by_track(&db, server_id, vec![by_path, by_metadata])
+            .await
+            .unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[&by_path], "101");
+    }
+
+    #[tokio::test]
+    async fn test_matches_deep_layouts_through_path_mappings() {
+        let db = test_db().await;
+        let server_id = insert_server(&db).await;
+        entities::plex_path_mapping::ActiveModel {
+            plex_server_id: Set(server_id),
+            local_prefix: Set("/music".into()),
+            plex_prefix: Set("/data/media/music/".into()),
+            ..Default::default()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        // Both discs have a track with the same number and title
+        let first_disc = insert_track(
+            &db,
+            local_track(
+                "Kyrie",
+                "Bach",
+                "/music/Bach/Mass in B minor/CD1/01 Kyrie.flac",
+                None,
+            ),
+        )
+        .await
+        .id;
+        let second_disc = insert_track(
+            &db,
+            local_track(
+                "Kyrie",
+                "Bach",
+                "/music/Bach/Mass in B minor/CD2/01 Kyrie.flac",
+                None,
+            ),
+        )
+        .await
+        .id;
+
+        let plex_tracks = vec![
+            plex_track(
+                "301",
+                "Kyrie",
+                "Bach",
+                "/data/media/music/Bach/Mass in B minor/CD1/01 Kyrie.flac",
+                None,
+            ),
+            plex_track(
+                "302",
+                "Kyrie",
+                "Bach",
+                "/data/media/music/Bach/Mass in B minor/CD2/01 Kyrie.flac",
+                None,
+            ),
+        ];
+        map_plex_tracks(
+            &db,
+            &MatcherConfig::default(),
+            server_id,
+            &plex_tracks,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            mappings(&db).await,
+            vec![
+                (first_disc, "301".into(), PlexTrackMatchMethod::Path),
+                (second_disc, "302".into(), PlexTrackMatchMethod::Path),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_history_is_rolled_up_over_servers() {
+        let db = test_db().await;
+        let first_server = insert_server(&db).await;
+        let second_server = insert_server(&db).await;
+        let path = "/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac";
+        let track_id = insert_track(&db, local_track("Bohemian Rhapsody", "Queen", path, None))
+            .await
+            .id;
+        let config = MatcherConfig::default();
+        let play_history = |track_id: i64| {
+            let db = db.clone();
+            async move {
+                let track = entities::track::Entity::find_by_id(track_id)
+                    .one(&db.conn)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                (track.play_count, track.last_played_at, track.rating)
+            }
+        };
+
+        let mut played = plex_track("101", "Bohemian Rhapsody", "Queen", path, None);
+        played.view_count = Some(3);
+        played.last_viewed_at = Some(1_700_000_100);
+        played.user_rating = Some(8.0);
+        map_plex_tracks(&db, &config, first_server, &[played], false)
+            .await
+            .unwrap();
+        let mut played = plex_track("7", "Bohemian Rhapsody", "Queen", path, None);
+        played.view_count = Some(2);
+        played.last_viewed_at = Some(1_700_000_500);
+        map_plex_tracks(&db, &config, second_server, &[played], false)
+            .await
+            .unwrap();
+        assert_eq!(
+            play_history(track_id).await,
+            (Some(5), Some(1_700_000_500), Some(8.0))
+        );
+
+        // The second server lost the track, and with it its plays
+        map_plex_tracks(&db, &config, second_server, &[], true)
+            .await
+            .unwrap();
+        assert_eq!(
+            play_history(track_id).await,
+            (Some(3), Some(1_700_000_100), Some(8.0))
+        );
+
+        // Unplayed tracks have no view count on Plex
+        let unplayed = plex_track("101", "Bohemian Rhapsody", "Queen", path, None);
+        map_plex_tracks(&db, &config, first_server, &[unplayed], false)
+            .await
+            .unwrap();
+        assert_eq!(play_history(track_id).await, (Some(0), None, None));
+    }
+
+    #[tokio::test]
+    async fn test_incremental_and_full_refresh_replace_stale_mappings() {
+        let db = test_db().await;
+        let server_id = insert_server(&db).await;
+        let path = "/music/Queen/A Night at the Opera/11 Bohemian Rhapsody.flac";
+        let track_id = insert_track(&db, local_track("Bohemian Rhapsody", "Queen", path, None))
+            .await
+            .id;
+        let other_id = insert_track(
+            &db,
+            local_track(
+                "Love of My Life",
+                "Queen",
+                "/music/Queen/A Night at the Opera/09 Love of My Life.flac",
+                None,
+            ),
+        )
+        .await
+        .id;
+        let config = MatcherConfig::default();
+
+        let first = vec![
+            plex_track("101", "Bohemian Rhapsody", "Queen", path, None),
+            plex_track(
+                "102",
+                "Love of My Life",
+                "Queen",
+                "/music/Queen/A Night at the Opera/09 Love of My Life.flac",
+                None,
+            ),
+        ];
+        map_plex_tracks(&db, &config, server_id, &first, true)
+            .await
+            .unwrap();
+
+        // Plex re-added the file under a new rating key
+        let updated = vec![plex_track("201", "Bohemian Rhapsody", "Queen", path, None)];
+        let result = map_plex_tracks(&db, &config, server_id, &updated, false)
+            .await
+            .unwrap();
+        assert_eq!(result.tracks_mapped, 1);
+        assert_eq!(
+            mappings(&db).await,
+            vec![
+                (track_id, "201".into(), PlexTrackMatchMethod::Path),
+                (other_id, "102".into(), PlexTrackMatchMethod::Path),
+            ]
+        );
+        // The old rating key lost its local track
+        let unmatched = unmatched_tracks(&db, server_id).await.unwrap();
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].rating_key, "101");
+        assert_eq!(unmatched[0].updated_at, Some(1_700_000_000));
+
+        // A full refresh drops tracks that are no longer in Plex
+        let result = map_plex_tracks(&db, &config, server_id, &updated, true)
+            .await
+            .unwrap();
+        assert_eq!(result.mappings_removed, 1);
+        assert_eq!(
+            mappings(&db).await,
+            vec![(track_id, "201".into(), PlexTrackMatchMethod::Path)]
+        );
+        assert!(unmatched_tracks(&db, server_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_incremental_refresh_rematches_unmatched_and_prunes_deleted_tracks() {
+        let fake = FakePlex::start().await;
+        let client = PlexHttpAdapter::new();
+        let db = test_db().await;
+        let mut server = entities::plex_server::ActiveModel {
+            name: Set("Fake".into()),
+            server_url: Set(fake.url().to_string()),
+            access_token: Set(Some(fake.token())),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let album = "/music/Queen/A Night at the Opera";
+        let death = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/01 Death on Two Legs.flac")),
+                ..TestTrack::new("Death on Two Legs")
+            },
+        )
+        .await
+        .id;
+        let config = MatcherConfig::default();
+        let reload = |id: i64| {
+            let db = db.clone();
+            async move {
+                entities::plex_server::Entity::find_by_id(id)
+                    .one(&db.conn)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+
+        let result = refresh_track_mappings(&db, &client, &config, &server, false)
+            .await
+            .unwrap();
+        assert_eq!(result.tracks_mapped, 1);
+        assert_eq!(result.tracks_unmatched, 5);
+
+        // Plex hasn't changed since, but a local track for 102 was imported
+        server = reload(server.id).await;
+        let mut newer: entities::plex_server::ActiveModel = server.clone().into();
+        newer.library_updated_at = Set(Some(1_700_000_001));
+        server = newer.update(&db.conn).await.unwrap();
+        let lazing = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some(&format!("{album}/02 Lazing on a Sunday Afternoon.flac")),
+                ..TestTrack::new("Lazing on a Sunday Afternoon")
+            },
+        )
+        .await
+        .id;
+        let result = refresh_track_mappings(&db, &client, &config, &server, false)
+            .await
+            .unwrap();
+        assert_eq!(result.tracks_scanned, 5);
+        assert_eq!(result.tracks_mapped, 1);
+        assert_eq!(
+            mappings(&db).await,
+            vec![
+                (death, "101".into(), PlexTrackMatchMethod::Path),
+                (lazing, "102".into(), PlexTrackMatchMethod::Path),
+            ]
+        );
+
+        // Deletions don't show up in what changed since
+        fake.delete_track("101");
+        fake.delete_track("201");
+        server = reload(server.id).await;
+        let result = refresh_track_mappings(&db, &client, &config, &server, false)
+            .await
+            .unwrap();
+        assert_eq!(result.mappings_removed, 1);
+        assert_eq!(
+            mappings(&db).await,
+            vec![(lazing, "102".into(), PlexTrackMatchMethod::Path)]
+        );
+        assert_eq!(
+            unmatched_tracks(&db, server.id)
+                .await
+                .unwrap()
+                .iter()
+                .map(|track| track.rating_key.as_str())
+                .collect::<HashSet<_>>(),
+            HashSet::from(["103", "104", "111"])
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use color_eyre::eyre::{Result, WrapErr};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::Deserialize;
+use std::collections::HashSet;
+use tracing;
+
+use crate::database::Database;
+use crate::entities;
+use crate::plex_rs::track_mapping::update_play_history;
+
+/* ---------- Payload ---------- */
+
+/// JSON `payload` part of a Plex webhook request.
+///
+/// Plex posts one for every event of the account's servers as
+/// `multipart/form-data`, with the artwork as an optional `thumb` part.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlexWebhookPayload {
+    /// e.g. `media.scrobble`, see [`PlexWebhookEvent`]
+    pub event: String,
+
+    /// Whether the event is from the account the webhook belongs to, rather
+    /// than another user of a server it owns
+    #[serde(default)]
+    pub user: bool,
+
+    #[serde(rename = "Server", default)]
+    pub server: Option<PlexWebhookServer>,
+
+    #[serde(rename = "Metadata", default)]
+    pub metadata: Option<PlexWebhookMetadata>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlexWebhookServer {
+    /// The server name, as seen in the Plex web interface
+    pub title: String,
+}
+
+/// The item an event is about.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlexWebhookMetadata {
+    #[serde(rename = "ratingKey")]
+    pub rating_key: String,
+
+    /// `track`, `album`, `artist`, `movie`, …
+    #[serde(rename = "type")]
+    pub item_type: String,
+
+    /// `artist` for music libraries
+    #[serde(rename = "librarySectionType", default)]
+    pub library_section_type: Option<String>,
+
+    #[serde(rename = "lastViewedAt", default)]
+    pub last_viewed_at: Option<i64>,
+
+    /// Rating from 0 to 10, missing once the rating was removed
+    #[serde(rename = "userRating", default)]
+    pub user_rating: Option<f64>,
+}
+
+impl PlexWebhookMetadata {
+    pub fn is_track(&self) -> bool {
+        self.item_type == "track"
+    }
+
+    pub fn is_music(&self) -> bool {
+        self.library_section_type.as_deref() == Some("artist")
+    }
+}
+
+/// Webhook events handled; Plex sends many more (pause, resume, devices, …).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlexWebhookEvent {
+    /// Playback started
+    Play,
+    /// Played far enough to count as a play (90%)
+    Scrobble,
+    /// Rated or rating removed
+    Rate,
+    /// New items in a library section
+    LibraryNew,
+}
+
+impl PlexWebhookEvent {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "media.play" => Some(Self::Play),
+            "media.scrobble" => Some(Self::Scrobble),
+            "media.rate" => Some(Self::Rate),
+            "library.new" => Some(Self::LibraryNew),
+            _ => None,
+        }
+    }
+}
+
+/* ---------- Recording ---------- */
+
+async fn find_mapping(
+    db: &Database,
+    plex_server_id: i64,
+    rating_key: &str,
+) -> Result<Option<entities::plex_track_mapping::Model>> {
+    entities::plex_track_mapping::Entity::find()
+        .filter(entities::plex_track_mapping::Column::PlexServerId.eq(plex_server_id))
+        .filter(entities::plex_track_mapping::Column::RatingKey.eq(rating_key))
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch Plex track mapping")
+}
+
+/// Whether a mapping refresh already tried to match a Plex track: it is
+/// mapped to a local track, or kept as unmatched.
+pub async fn is_known_track(db: &Database, plex_server_id: i64, rating_key: &str) -> Result<bool> {
+    if find_mapping(db, plex_server_id, rating_key)
+        .await?
+        .is_some()
+    {
+        return Ok(true);
+    }
+    Ok(
+        entities::plex_unmatched_track::Entity::find_by_id((
+            plex_server_id,
+            rating_key.to_string(),
+        ))
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch unmatched Plex track")?
+        .is_some(),
+    )
+}
+
+/// Counts a play of a Plex track, at `played_at`, on its mapping and rolls
+/// it up into the local track.
+///
+/// Returns the local track, `None` when the Plex track isn't mapped.
+pub async fn record_scrobble(
+    db: &Database,
+    plex_server_id: i64,
+    rating_key: &str,
+    played_at: i64,
+) -> Result<Option<i64>> {
+    let Some(mapping) = find_mapping(db, plex_server_id, rating_key).await? else {
+        tracing::debug!("Not recording play of unmapped Plex track {}", rating_key);
+        return Ok(None);
+    };
+    let track_id = mapping.track_id;
+
+    let view_count = mapping.view_count.unwrap_or(0) + 1;
+    let last_viewed_at = mapping.last_viewed_at.max(Some(played_at));
+    let mut mapping: entities::plex_track_mapping::ActiveModel = mapping.into();
+    mapping.view_count = Set(Some(view_count));
+    mapping.last_viewed_at = Set(last_viewed_at);
+    mapping
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save Plex track play")?;
+
+    update_play_history(db, HashSet::from([track_id])).await?;
+    Ok(Some(track_id))
+}
+
+/// Stores the rating of a Plex track, `None` once removed, on its mapping and
+/// rolls it up into the local track.
+///
+/// Returns the local track, `None` when the Plex track isn't mapped.
+pub async fn record_rating(
+    db: &Database,
+    plex_server_id: i64,
+    rating_key: &str,
+    user_rating: Option<f64>,
+) -> Result<Option<i64>> {
+    let Some(mapping) = find_mapping(db, plex_server_id, rating_key).await? else {
+        tracing::debug!("Not recording rating of unmapped Plex track {}", rating_key);
+        return Ok(None);
+    };
+    let track_id = mapping.track_id;
+
+    let mut mapping: entities::plex_track_mapping::ActiveModel = mapping.into();
+    mapping.user_rating = Set(user_rating);
+    mapping
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save Plex track rating")?;
+
+    update_play_history(db, HashSet::from([track_id])).await?;
+    Ok(Some(track_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::plex_track_mapping::PlexTrackMatchMethod;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::ActiveModelBehavior;
+
+    async fn insert_mapped_track(db: &Database, plex_server_id: i64, rating_key: &str) -> i64 {
+        let file_path = format!("/music/{}.flac", rating_key);
+        let track = insert_track(
+            db,
+            TestTrack {
+                file_path: Some(&file_path),
+                ..TestTrack::new("Track")
+            },
+        )
+        .await;
+        entities::plex_track_mapping::ActiveModel {
+            plex_server_id: Set(plex_server_id),
+            track_id: Set(track.id),
+            rating_key: Set(rating_key.into()),
+            match_method: Set(PlexTrackMatchMethod::Path),
+            view_count: Set(Some(2)),
+            last_viewed_at: Set(Some(1_700_000_000)),
+            ..entities::plex_track_mapping::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        track.id
+    }
+
+    #[test]
+    fn test_parses_scrobble_payload() {
+        let payload: PlexWebhookPayload = serde_json::from_str(
+            r#"{
+                "event": "media.scrobble",
+                "user": true,
+                "owner": true,
+                "Account": { "id": 1, "title": "listener" },
+                "Server": { "title": "Home", "uuid": "abc123" },
+                "Player": { "local": true, "title": "Plexamp" },
+                "Metadata": {
+                    "librarySectionType": "artist",
+                    "ratingKey": "111",
+                    "type": "track",
+                    "title": "Bohemian Rhapsody",
+                    "grandparentTitle": "Queen"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            PlexWebhookEvent::from_name(&payload.event),
+            Some(PlexWebhookEvent::Scrobble)
+        );
+        assert!(payload.user);
+        assert_eq!(payload.server.unwrap().title, "Home");
+        let metadata = payload.metadata.unwrap();
+        assert_eq!(metadata.rating_key, "111");
+        assert!(metadata.is_track() && metadata.is_music());
+        assert_eq!(PlexWebhookEvent::from_name("media.pause"), None);
+    }
+
+    #[tokio::test]
+    async fn test_records_scrobbles_and_ratings_on_mapped_tracks() {
+        let db = test_db().await;
+        let server_id = entities::plex_server::ActiveModel {
+            name: Set("Plex".into()),
+            server_url: Set("http://plex.local:32400/".into()),
+            ..entities::plex_server::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap()
+        .id;
+        let track_id = insert_mapped_track(&db, server_id, "111").await;
+
+        let recorded = record_scrobble(&db, server_id, "111", 1_700_000_500)
+            .await
+            .unwrap();
+        assert_eq!(recorded, Some(track_id));
+        record_rating(&db, server_id, "111", Some(8.0))
+            .await
+            .unwrap();
+        let track = entities::track::Entity::find_by_id(track_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(track.play_count, Some(3));
+        assert_eq!(track.last_played_at, Some(1_700_000_500));
+        assert_eq!(track.rating, Some(8.0));
+
+        record_rating(&db, server_id, "111", None).await.unwrap();
+        let track = entities::track::Entity::find_by_id(track_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(track.rating, None);
+
+        assert_eq!(
+            record_scrobble(&db, server_id, "999", 1_700_000_500)
+                .await
+                .unwrap(),
+            None
+        );
+    }
+}
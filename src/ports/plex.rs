@@ -1,14 +1,18 @@
 use color_eyre::eyre::Result;
 use url::Url;
 
-use crate::plex_rs::all_tracks::{PlexLibrarySection, PlexLibraryTrack, PlexMediaContainer};
+use crate::plex_rs::all_tracks::{
+    PlexLibrarySection, PlexLibraryTrack, PlexMediaContainer, TracksSince,
+};
 use crate::plex_rs::auth::{PlexAuthResponse, PlexPinResponse, PlexResource};
 use crate::plex_rs::library_refresh::PlexActivity;
-use crate::plex_rs::playlist::PlexPlaylist;
+use crate::plex_rs::playlist::{PlexPlaylist, PlexTrack};
 
 /// Port trait wrapping the Plex API capabilities used by business logic.
 ///
 /// Implementations live in `services::plex::client` (production) or test mocks.
+/// All Plex Media Server requests go through it, so tests can run against
+/// mocks or `test_utils::fake_plex` instead of a real server.
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
 pub trait PlexClient: Send + Sync {
@@ -28,11 +32,14 @@ pub trait PlexClient: Send + Sync {
 
     fn find_music_section_id(&self, sections: &[PlexLibrarySection]) -> Option<String>;
 
+    /// One page of tracks of a section, only those changed since `since`
+    /// when given.
     async fn get_tracks_page(
         &self,
         server_url: &Url,
         token: &str,
         section_id: &str,
+        since: Option<TracksSince>,
         start: u32,
         size: u32,
     ) -> Result<PlexMediaContainer<PlexLibraryTrack>>;
@@ -44,6 +51,15 @@ pub trait PlexClient: Send + Sync {
         section_id: &str,
     ) -> Result<()>;
 
+    /// Scan one folder of a section; `path` is the folder as seen by Plex.
+    async fn refresh_library_path(
+        &self,
+        server_url: &Url,
+        token: &str,
+        section_id: &str,
+        path: &str,
+    ) -> Result<()>;
+
     async fn get_library_scan_status(
         &self,
         server_url: &Url,
@@ -51,5 +67,63 @@ pub trait PlexClient: Send + Sync {
         section_id: &str,
     ) -> Result<Option<PlexActivity>>;
 
+    async fn get_machine_identifier(&self, server_url: &Url, token: &str) -> Result<String>;
+
     async fn get_playlists(&self, server_url: &Url, token: &str) -> Result<Vec<PlexPlaylist>>;
+
+    /// Create a music playlist, with a first track when given since some
+    /// Plex versions require one.
+    async fn create_playlist(
+        &self,
+        server_url: &Url,
+        token: &str,
+        machine_identifier: &str,
+        title: &str,
+        first_rating_key: Option<String>,
+    ) -> Result<PlexPlaylist>;
+
+    /// Items of a playlist, with their playlist item IDs.
+    async fn get_playlist_items(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+    ) -> Result<Vec<PlexTrack>>;
+
+    /// Tracks of a playlist with their media parts and guids, to match them
+    /// to local tracks.
+    async fn get_playlist_library_tracks(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+    ) -> Result<Vec<PlexLibraryTrack>>;
+
+    async fn add_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        machine_identifier: &str,
+        rating_key: &str,
+    ) -> Result<()>;
+
+    /// Move an item right after another one, or to the top without
+    /// `after_item_id`.
+    async fn move_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        playlist_item_id: u64,
+        after_item_id: Option<u64>,
+    ) -> Result<()>;
+
+    async fn remove_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        playlist_item_id: u64,
+    ) -> Result<()>;
 }
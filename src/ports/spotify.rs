@@ -20,6 +20,18 @@ pub struct SpotifyApiTrack {
     pub album_name: String,
     pub isrc: Option<String>,
     pub upc: Option<String>,
+    /// Popularity from 0 to 100, when the API includes it
+    pub popularity: Option<i32>,
+}
+
+/// Decoupled representation of a Spotify track's audio features.
+#[derive(Debug, Clone)]
+pub struct SpotifyApiAudioFeatures {
+    pub track_id: String,
+    pub tempo: f64,
+    /// Pitch class of the key, or `None` when no key was detected
+    pub key: Option<i32>,
+    pub energy: f64,
 }
 
 /// Port trait wrapping the Spotify API capabilities used by business logic.
@@ -30,4 +42,27 @@ pub struct SpotifyApiTrack {
 pub trait SpotifyClient: Send + Sync {
     async fn current_user_playlists(&self) -> Result<Vec<SpotifyApiPlaylist>>;
     async fn playlist_tracks(&self, playlist_id: &str) -> Result<Vec<SpotifyApiTrack>>;
+    /// Best matches for a search query, e.g. `isrc:USRC11234567`
+    async fn search_tracks(&self, query: &str) -> Result<Vec<SpotifyApiTrack>>;
+    /// Audio features of the tracks; tracks without features are left out
+    async fn audio_features(&self, track_ids: &[String]) -> Result<Vec<SpotifyApiAudioFeatures>>;
+}
+
+#[async_trait::async_trait]
+impl<T: SpotifyClient + ?Sized> SpotifyClient for std::sync::Arc<T> {
+    async fn current_user_playlists(&self) -> Result<Vec<SpotifyApiPlaylist>> {
+        (**self).current_user_playlists().await
+    }
+
+    async fn playlist_tracks(&self, playlist_id: &str) -> Result<Vec<SpotifyApiTrack>> {
+        (**self).playlist_tracks(playlist_id).await
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Vec<SpotifyApiTrack>> {
+        (**self).search_tracks(query).await
+    }
+
+    async fn audio_features(&self, track_ids: &[String]) -> Result<Vec<SpotifyApiAudioFeatures>> {
+        (**self).audio_features(track_ids).await
+    }
 }
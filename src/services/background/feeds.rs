@@ -0,0 +1,33 @@
+use crate::{database::Database, entities, services};
+use color_eyre::eyre::{Context, Result};
+use sea_orm::EntityTrait;
+use tracing::instrument;
+
+pub async fn add_new_items_for_subscription(
+    db: &Database,
+    subscription: &entities::feed_subscription::Model,
+) -> Result<()> {
+    let (_, feed) =
+        services::feeds::fetch_feed(&subscription.feed_url, Some(subscription.kind)).await?;
+    services::feeds::save_new_items(db, Some(subscription.id), feed).await?;
+    Ok(())
+}
+
+#[instrument(skip(db))]
+pub async fn add_new_items(db: &Database) -> Result<()> {
+    let subscriptions = entities::feed_subscription::Entity::find()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch feed subscriptions")?;
+    for subscription in subscriptions {
+        if let Err(e) = add_new_items_for_subscription(db, &subscription).await {
+            tracing::error!(
+                subscription = ?subscription,
+                error = ?e,
+                "Failed to add new items for feed subscription",
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,25 @@
-use crate::{http_server::state::AppState, import_track::watch_directory};
+use crate::{
+    http_server::state::AppState,
+    import_track::watch_directory,
+    services::{
+        plex::auto_sync::run_plex_auto_sync,
+        spotify::download_failures::retry_due_download_failures,
+    },
+};
 use std::{path::Path, sync::Arc, time::Duration};
 
+pub mod feeds;
 pub mod youtube;
 
 pub fn run_background_tasks(app_state: Arc<AppState>, watch_directory_path: &Path) {
+    // Subscribe before the watcher below can import anything
+    let import_events = app_state.db.subscribe();
+    tokio::spawn(run_plex_auto_sync(
+        app_state.db.clone(),
+        app_state.config.clone(),
+        import_events,
+    ));
+
     let watch_directory_path = watch_directory_path.to_path_buf();
     let app_state_clone = app_state.clone();
     tokio::spawn(async move {
@@ -38,4 +54,29 @@ pub fn run_background_tasks(app_state: Arc<AppState>, watch_directory_path: &Pat
             }
         }
     });
+
+    // Fetch new items of subscribed RSS and Atom feeds
+    let feeds_db = app_state.db.clone();
+    tokio::spawn(async move {
+        tracing::info!("Fetching feed items in background");
+        loop {
+            tokio::time::sleep(Duration::from_mins(15)).await;
+            if let Err(e) = feeds::add_new_items(&feeds_db).await {
+                tracing::error!("Failed to add new feed items: {}", e);
+            }
+        }
+    });
+
+    // Retry failed Spotify track downloads whose back-off has passed
+    let retry_app_state = app_state.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_mins(15)).await;
+            if let Err(e) =
+                retry_due_download_failures(&retry_app_state.db, &retry_app_state.tasks).await
+            {
+                tracing::error!("Failed to retry spotify track downloads: {}", e);
+            }
+        }
+    });
 }
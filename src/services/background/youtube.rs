@@ -1,7 +1,6 @@
 use crate::{database::Database, entities, services};
-use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Context, Result};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_orm::EntityTrait;
 use tracing::instrument;
 
 pub async fn add_new_videos_for_subscription(
@@ -9,39 +8,7 @@ pub async fn add_new_videos_for_subscription(
     subscription: &entities::youtube_subscription::Model,
 ) -> Result<()> {
     let feed = services::youtube::feed::fetch_feed(&subscription.youtube_id).await?;
-    for entry in feed.entries {
-        if entities::youtube_video::Entity::find()
-            .filter(entities::youtube_video::Column::YoutubeId.eq(&entry.id))
-            .one(&db.conn)
-            .await?
-            .is_some()
-        {
-            continue;
-        }
-
-        tracing::info!(
-            entry = ?entry,
-            "Adding new youtube video",
-        );
-
-        if let Ok(published_at) = entry.published.parse::<DateTime<Utc>>() {
-            let video = entities::youtube_video::ActiveModel {
-                youtube_id: Set(entry.id),
-                title: Set(entry.title),
-                channel_name: Set(entry.author.name),
-                published_at: Set(published_at),
-                thumbnail_url: Set(entry.media_group.thumbnail.url),
-                video_url: Set(entry.link.href),
-                watched: Set(false),
-                ..Default::default()
-            };
-            entities::youtube_video::Entity::insert(video)
-                .exec(&db.conn)
-                .await
-                .wrap_err("Failed to insert youtube video")?;
-        }
-    }
-
+    services::feeds::save_new_items(db, None, feed).await?;
     Ok(())
 }
 
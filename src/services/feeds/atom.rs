@@ -0,0 +1,170 @@
+//! Atom feeds, e.g. of labels, blogs or release trackers.
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use super::{FeedItem, FeedParser, ParsedFeed};
+
+#[derive(Debug, Deserialize)]
+struct AtomFeed {
+    title: Text,
+    #[serde(default)]
+    author: Option<Author>,
+    #[serde(rename = "entry", default)]
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    id: String,
+    title: Text,
+    #[serde(rename = "link", default)]
+    links: Vec<Link>,
+    #[serde(default)]
+    published: Option<String>,
+    #[serde(default)]
+    updated: Option<String>,
+    #[serde(default)]
+    author: Option<Author>,
+    #[serde(rename = "media:thumbnail", default)]
+    thumbnails: Vec<MediaThumbnail>,
+}
+
+/// Text construct, which may have a `type` attribute
+#[derive(Debug, Deserialize)]
+struct Text {
+    #[serde(rename = "#text", default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Author {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Link {
+    #[serde(rename = "@rel", default)]
+    rel: Option<String>,
+    #[serde(rename = "@href")]
+    href: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaThumbnail {
+    #[serde(rename = "@url")]
+    url: String,
+}
+
+pub struct AtomFeedParser;
+
+impl FeedParser for AtomFeedParser {
+    fn parse(&self, body: &str) -> Result<ParsedFeed> {
+        let feed: AtomFeed = serde_xml_rs::SerdeXml::new()
+            .overlapping_sequences(true)
+            .from_str(body)
+            .wrap_err("Failed to parse Atom feed")?;
+        let feed_author = feed.author.map(|author| author.name);
+
+        let items = feed
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                // The entry's page is its `alternate` link, the default
+                // relation, else its media file
+                let mut links = entry.links;
+                let index = links
+                    .iter()
+                    .position(|link| matches!(link.rel.as_deref(), None | Some("alternate")))
+                    .or_else(|| {
+                        links
+                            .iter()
+                            .position(|link| link.rel.as_deref() == Some("enclosure"))
+                    })?;
+                let url = links.swap_remove(index).href;
+                Some(FeedItem {
+                    id: entry.id,
+                    title: entry.title.value,
+                    author: entry
+                        .author
+                        .map(|author| author.name)
+                        .or(feed_author.clone()),
+                    published_at: entry
+                        .published
+                        .or(entry.updated)
+                        .and_then(|date| date.parse::<DateTime<Utc>>().ok()),
+                    url,
+                    thumbnail_url: entry.thumbnails.into_iter().next().map(|thumb| thumb.url),
+                })
+            })
+            .collect();
+
+        Ok(ParsedFeed {
+            title: feed.title.value,
+            items,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_entries_with_their_page() {
+        let feed = AtomFeedParser
+            .parse(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">
+  <title type="text">Label Releases</title>
+  <id>urn:uuid:60a76c80-d399-11d9-b93C-0003939e0af6</id>
+  <author><name>The Label</name></author>
+  <updated>2025-10-01T12:00:00Z</updated>
+  <entry>
+    <title type="html">Artist – Album</title>
+    <link rel="enclosure" href="https://label.example/album.mp3"/>
+    <link href="https://label.example/releases/album"/>
+    <id>urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a</id>
+    <updated>2025-10-01T12:00:00Z</updated>
+    <media:thumbnail url="https://label.example/album.jpg"/>
+  </entry>
+  <entry>
+    <title>Single</title>
+    <link rel="enclosure" href="https://label.example/single.mp3"/>
+    <id>urn:uuid:2</id>
+    <published>2025-09-01T08:30:00+02:00</published>
+  </entry>
+  <entry>
+    <title>Comments only</title>
+    <link rel="replies" href="https://label.example/comments"/>
+    <id>urn:uuid:3</id>
+  </entry>
+</feed>"#,
+            )
+            .unwrap();
+
+        assert_eq!(feed.title, "Label Releases");
+        assert_eq!(
+            feed.items,
+            vec![
+                FeedItem {
+                    id: "urn:uuid:1225c695-cfb8-4ebb-aaaa-80da344efa6a".into(),
+                    title: "Artist – Album".into(),
+                    author: Some("The Label".into()),
+                    published_at: Some("2025-10-01T12:00:00Z".parse().unwrap()),
+                    url: "https://label.example/releases/album".into(),
+                    thumbnail_url: Some("https://label.example/album.jpg".into()),
+                },
+                FeedItem {
+                    id: "urn:uuid:2".into(),
+                    title: "Single".into(),
+                    author: Some("The Label".into()),
+                    published_at: Some("2025-09-01T06:30:00Z".parse().unwrap()),
+                    url: "https://label.example/single.mp3".into(),
+                    thumbnail_url: None,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+pub mod atom;
+pub mod rss;
+pub mod service;
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use reqwest::Client;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+
+use crate::database::Database;
+use crate::entities;
+use crate::entities::feed_subscription::FeedKind;
+use crate::services::youtube::feed::YoutubeFeedParser;
+
+/// A feed parsed into inbox items.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedFeed {
+    pub title: String,
+    pub items: Vec<FeedItem>,
+}
+
+/// An entry of a feed: a video, release, episode, …
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    /// Unique ID of the entry (Atom `id`, RSS `guid`, else its link)
+    pub id: String,
+    pub title: String,
+    /// Channel, artist or show; the feed title when the entry has none
+    pub author: Option<String>,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Page of the entry, or its media file when it has no page
+    pub url: String,
+    pub thumbnail_url: Option<String>,
+}
+
+/// Parses one feed format into inbox items.
+pub trait FeedParser {
+    fn parse(&self, body: &str) -> Result<ParsedFeed>;
+}
+
+impl FeedKind {
+    /// Format of a feed document, `None` if it isn't a feed.
+    pub fn detect(body: &str) -> Option<Self> {
+        let start = body.trim_start_matches('\u{feff}').trim_start();
+        // Skip the XML declaration, comments and stylesheets
+        let root = start
+            .match_indices('<')
+            .map(|(index, _)| &start[index + 1..])
+            .find(|tag| !tag.starts_with('?') && !tag.starts_with('!'))?;
+
+        if root.starts_with("rss") {
+            Some(Self::Rss)
+        } else if root.starts_with("feed") {
+            let root_tag = &root[..root.find('>').unwrap_or(root.len())];
+            if root_tag.contains("xmlns:yt=") {
+                Some(Self::Youtube)
+            } else {
+                Some(Self::Atom)
+            }
+        } else {
+            None
+        }
+    }
+
+    pub fn parser(self) -> &'static dyn FeedParser {
+        match self {
+            Self::Youtube => &YoutubeFeedParser,
+            Self::Atom => &atom::AtomFeedParser,
+            Self::Rss => &rss::RssFeedParser,
+        }
+    }
+}
+
+/// Download a feed document.
+pub async fn fetch(url: &str) -> Result<String> {
+    let response = Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?;
+    let body = response.text().await?;
+    tracing::debug!(url, body = ?body, "Fetched feed");
+    Ok(body)
+}
+
+/// Download and parse a feed, detecting its format unless `kind` is given.
+pub async fn fetch_feed(url: &str, kind: Option<FeedKind>) -> Result<(FeedKind, ParsedFeed)> {
+    let body = fetch(url).await?;
+    let kind = match kind.or_else(|| FeedKind::detect(&body)) {
+        Some(kind) => kind,
+        None => color_eyre::eyre::bail!("{} is not an RSS or Atom feed", url),
+    };
+    let feed = kind
+        .parser()
+        .parse(&body)
+        .wrap_err_with(|| format!("Failed to parse feed {}", url))?;
+    Ok((kind, feed))
+}
+
+/// ID of a feed item in the inbox. Items of feed subscriptions are prefixed
+/// with their subscription, since feeds only keep their IDs unique within
+/// themselves, while videos of YouTube subscriptions keep their video ID.
+fn inbox_id(feed_subscription_id: Option<i64>, item_id: String) -> String {
+    match feed_subscription_id {
+        Some(feed_subscription_id) => format!("feed:{}:{}", feed_subscription_id, item_id),
+        None => item_id,
+    }
+}
+
+/// Add the items of a feed that aren't in the inbox yet. Items without a
+/// publication date are dated when first seen.
+///
+/// Returns how many items were added.
+pub async fn save_new_items(
+    db: &Database,
+    feed_subscription_id: Option<i64>,
+    feed: ParsedFeed,
+) -> Result<u32> {
+    let mut added = 0;
+    for item in feed.items {
+        let youtube_id = inbox_id(feed_subscription_id, item.id.clone());
+        if entities::youtube_video::Entity::find()
+            .filter(entities::youtube_video::Column::YoutubeId.eq(&youtube_id))
+            .one(&db.conn)
+            .await?
+            .is_some()
+        {
+            continue;
+        }
+
+        tracing::info!(item = ?item, "Adding new feed item");
+
+        let video = entities::youtube_video::ActiveModel {
+            youtube_id: Set(youtube_id),
+            title: Set(item.title),
+            channel_name: Set(item.author.unwrap_or_else(|| feed.title.clone())),
+            published_at: Set(item.published_at.unwrap_or_else(Utc::now)),
+            thumbnail_url: Set(item.thumbnail_url.unwrap_or_default()),
+            video_url: Set(item.url),
+            watched: Set(false),
+            feed_subscription_id: Set(feed_subscription_id),
+            ..Default::default()
+        };
+        entities::youtube_video::Entity::insert(video)
+            .exec(&db.conn)
+            .await
+            .wrap_err("Failed to insert feed item")?;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_db;
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait};
+
+    #[test]
+    fn test_detects_feed_kind() {
+        assert_eq!(
+            FeedKind::detect(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns:yt="http://www.youtube.com/xml/schemas/2015" xmlns="http://www.w3.org/2005/Atom">"#
+            ),
+            Some(FeedKind::Youtube)
+        );
+        assert_eq!(
+            FeedKind::detect(
+                "\u{feff}<?xml version=\"1.0\"?>\n<!-- label news -->\n<feed xmlns=\"http://www.w3.org/2005/Atom\"><entry/></feed>"
+            ),
+            Some(FeedKind::Atom)
+        );
+        assert_eq!(
+            FeedKind::detect(
+                r#"<?xml version="1.0"?><?xml-stylesheet href="feed.xsl"?><rss version="2.0">"#
+            ),
+            Some(FeedKind::Rss)
+        );
+        assert_eq!(FeedKind::detect("<!DOCTYPE html><html></html>"), None);
+    }
+
+    #[tokio::test]
+    async fn test_saves_only_new_items() {
+        let db = test_db().await;
+        let item = |id: &str| FeedItem {
+            id: id.into(),
+            title: format!("Release {}", id),
+            author: None,
+            published_at: None,
+            url: format!("https://label.example/releases/{}", id),
+            thumbnail_url: None,
+        };
+        let feed = |ids: &[&str]| ParsedFeed {
+            title: "Label".into(),
+            items: ids.iter().map(|id| item(id)).collect(),
+        };
+
+        assert_eq!(
+            save_new_items(&db, None, feed(&["1", "2"])).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            save_new_items(&db, None, feed(&["2", "3"])).await.unwrap(),
+            1
+        );
+
+        let videos = entities::youtube_video::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(videos.len(), 3);
+        assert!(videos.iter().all(|video| video.channel_name == "Label"));
+        assert!(videos.iter().all(|video| video.thumbnail_url.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_items_are_unique_per_feed() {
+        let db = test_db().await;
+        let subscribe = |name: &str| entities::feed_subscription::ActiveModel {
+            name: Set(name.into()),
+            feed_url: Set(format!("https://{}.example/feed", name)),
+            kind: Set(FeedKind::Rss),
+            ..entities::feed_subscription::ActiveModel::new()
+        };
+        let label = subscribe("label").insert(&db.conn).await.unwrap();
+        let podcast = subscribe("podcast").insert(&db.conn).await.unwrap();
+        // Both feeds number their items
+        let feed = |title: &str| ParsedFeed {
+            title: title.into(),
+            items: vec![FeedItem {
+                id: "1".into(),
+                title: format!("{} 1", title),
+                author: None,
+                published_at: None,
+                url: format!("https://{}.example/1", title),
+                thumbnail_url: None,
+            }],
+        };
+
+        assert_eq!(
+            save_new_items(&db, Some(label.id), feed("label"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            save_new_items(&db, Some(podcast.id), feed("podcast"))
+                .await
+                .unwrap(),
+            1
+        );
+
+        let videos = entities::youtube_video::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(
+            videos
+                .iter()
+                .map(|video| (video.feed_subscription_id, video.youtube_id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(label.id), format!("feed:{}:1", label.id).as_str()),
+                (Some(podcast.id), format!("feed:{}:1", podcast.id).as_str()),
+            ]
+        );
+    }
+}
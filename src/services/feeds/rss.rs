@@ -0,0 +1,187 @@
+//! RSS 2.0 feeds, including podcast (`itunes:`) and Media RSS (`media:`)
+//! extensions used by SoundCloud, Bandcamp and label release feeds.
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Context, Result};
+use serde::Deserialize;
+
+use super::{FeedItem, FeedParser, ParsedFeed};
+
+#[derive(Debug, Deserialize)]
+struct Rss {
+    channel: Channel,
+}
+
+#[derive(Debug, Deserialize)]
+struct Channel {
+    title: String,
+    #[serde(rename = "itunes:author", default)]
+    author: Option<String>,
+    #[serde(rename = "itunes:image", default)]
+    image: Option<ItunesImage>,
+    #[serde(rename = "item", default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Item {
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    link: Option<String>,
+    #[serde(default)]
+    guid: Option<Text>,
+    #[serde(rename = "pubDate", default)]
+    pub_date: Option<String>,
+    #[serde(rename = "dc:creator", default)]
+    creator: Option<String>,
+    #[serde(rename = "itunes:author", default)]
+    itunes_author: Option<String>,
+    #[serde(default)]
+    enclosure: Option<Enclosure>,
+    #[serde(rename = "itunes:image", default)]
+    image: Option<ItunesImage>,
+    #[serde(rename = "media:thumbnail", default)]
+    thumbnails: Vec<MediaThumbnail>,
+}
+
+/// Element with attributes whose text is the value, e.g. `guid`
+#[derive(Debug, Deserialize)]
+struct Text {
+    #[serde(rename = "#text", default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Enclosure {
+    #[serde(rename = "@url")]
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ItunesImage {
+    #[serde(rename = "@href")]
+    href: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaThumbnail {
+    #[serde(rename = "@url")]
+    url: String,
+}
+
+pub struct RssFeedParser;
+
+impl FeedParser for RssFeedParser {
+    fn parse(&self, body: &str) -> Result<ParsedFeed> {
+        let rss: Rss = serde_xml_rs::SerdeXml::new()
+            .overlapping_sequences(true)
+            .from_str(body)
+            .wrap_err("Failed to parse RSS feed")?;
+        let channel = rss.channel;
+        let channel_image = channel.image.map(|image| image.href);
+
+        let items = channel
+            .items
+            .into_iter()
+            .filter_map(|item| {
+                // A release without a page can still be found by its audio file
+                let url = item
+                    .link
+                    .filter(|link| !link.trim().is_empty())
+                    .or(item.enclosure.map(|enclosure| enclosure.url))?;
+                let id = item
+                    .guid
+                    .map(|guid| guid.value)
+                    .filter(|guid| !guid.trim().is_empty())
+                    .unwrap_or_else(|| url.clone());
+                Some(FeedItem {
+                    id,
+                    title: item.title.unwrap_or_default(),
+                    author: item
+                        .creator
+                        .or(item.itunes_author)
+                        .or(channel.author.clone()),
+                    published_at: item.pub_date.as_deref().and_then(parse_date),
+                    url,
+                    thumbnail_url: item
+                        .image
+                        .map(|image| image.href)
+                        .or(item.thumbnails.into_iter().next().map(|thumb| thumb.url))
+                        .or(channel_image.clone()),
+                })
+            })
+            .collect();
+
+        Ok(ParsedFeed {
+            title: channel.title,
+            items,
+        })
+    }
+}
+
+/// RSS dates are RFC 2822, but some feeds use RFC 3339.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(date.trim())
+        .or_else(|_| DateTime::parse_from_rfc3339(date.trim()))
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_podcast_and_release_items() {
+        let feed = RssFeedParser
+            .parse(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:media="http://search.yahoo.com/mrss/">
+  <channel>
+    <title>Night Drive Mixes</title>
+    <link>https://soundcloud.com/night-drive</link>
+    <itunes:author>Night Drive</itunes:author>
+    <itunes:image href="https://example.com/show.jpg"/>
+    <item>
+      <title>Mix 42</title>
+      <guid isPermaLink="false">tag:soundcloud,2010:tracks/42</guid>
+      <pubDate>Tue, 14 Oct 2025 18:00:00 +0000</pubDate>
+      <enclosure url="https://example.com/mix-42.mp3" length="1000" type="audio/mpeg"/>
+      <itunes:image href="https://example.com/mix-42.jpg"/>
+    </item>
+    <item>
+      <title>New EP out now</title>
+      <link>https://label.example/releases/ep</link>
+      <dc:creator>Some Artist</dc:creator>
+      <media:thumbnail url="https://example.com/ep.jpg"/>
+    </item>
+  </channel>
+</rss>"#,
+            )
+            .unwrap();
+
+        assert_eq!(feed.title, "Night Drive Mixes");
+        assert_eq!(
+            feed.items,
+            vec![
+                FeedItem {
+                    id: "tag:soundcloud,2010:tracks/42".into(),
+                    title: "Mix 42".into(),
+                    author: Some("Night Drive".into()),
+                    published_at: Some("2025-10-14T18:00:00Z".parse().unwrap()),
+                    url: "https://example.com/mix-42.mp3".into(),
+                    thumbnail_url: Some("https://example.com/mix-42.jpg".into()),
+                },
+                FeedItem {
+                    id: "https://label.example/releases/ep".into(),
+                    title: "New EP out now".into(),
+                    author: Some("Some Artist".into()),
+                    published_at: None,
+                    url: "https://label.example/releases/ep".into(),
+                    thumbnail_url: Some("https://example.com/ep.jpg".into()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use color_eyre::eyre::WrapErr;
+use sea_orm::{ActiveModelTrait, EntityTrait, QueryOrder, Set};
+
+use crate::database::Database;
+use crate::entities;
+use crate::services::feeds::{fetch_feed, save_new_items};
+
+pub struct FeedService {
+    db: Arc<Database>,
+}
+
+impl FeedService {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self { db }
+    }
+
+    /// Subscribe to an RSS or Atom feed, named after its title unless a name
+    /// is given, and add its items to the inbox.
+    pub async fn add_subscription(
+        &self,
+        feed_url: String,
+        name: Option<String>,
+    ) -> color_eyre::Result<entities::feed_subscription::Model> {
+        let (kind, feed) = fetch_feed(&feed_url, None).await?;
+
+        let subscription = entities::feed_subscription::ActiveModel {
+            name: Set(name
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| feed.title.clone())),
+            feed_url: Set(feed_url),
+            kind: Set(kind),
+            ..Default::default()
+        };
+        let subscription = subscription
+            .insert(&self.db.conn)
+            .await
+            .wrap_err("Failed to add feed subscription")?;
+
+        save_new_items(&self.db, Some(subscription.id), feed).await?;
+
+        Ok(subscription)
+    }
+
+    /// Unsubscribe from a feed, removing its items from the inbox.
+    pub async fn remove_subscription(&self, id: i64) -> color_eyre::Result<()> {
+        entities::feed_subscription::Entity::delete_by_id(id)
+            .exec(&self.db.conn)
+            .await
+            .wrap_err("Failed to remove feed subscription")?;
+        Ok(())
+    }
+
+    pub async fn list_subscriptions(
+        &self,
+    ) -> color_eyre::Result<Vec<entities::feed_subscription::Model>> {
+        let subscriptions = entities::feed_subscription::Entity::find()
+            .order_by_asc(entities::feed_subscription::Column::Name)
+            .all(&self.db.conn)
+            .await
+            .wrap_err("Failed to fetch feed subscriptions")?;
+        Ok(subscriptions)
+    }
+}
@@ -1,7 +1,10 @@
 pub mod background;
+pub mod feeds;
 pub mod playlist;
 pub mod plex;
+pub mod smart_playlist;
 pub mod soulseek_service;
 pub mod spotify;
+pub mod tasks;
 pub mod track;
 pub mod youtube;
@@ -1,13 +1,23 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use chrono::Utc;
-use color_eyre::eyre::{OptionExt, Result};
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter, Set, TransactionTrait,
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
 };
 
-use crate::database::Database;
+use crate::database::{Database, IN_LIST_CHUNK_SIZE};
 use crate::entities;
+use crate::entities::smart_playlist::SmartPlaylistRules;
+use crate::http_server::graphql::query_builder::{
+    SortableField, TrackSortField, apply_multi_column_text_search, apply_sort,
+};
+use crate::services::smart_playlist;
+
+/// Entries per insert, each binding five variables
+const INSERT_CHUNK_SIZE: usize = 100;
 
 pub struct PlaylistService {
     db: Arc<Database>,
@@ -37,65 +47,977 @@ impl PlaylistService {
         Ok(model)
     }
 
-    pub async fn add_track(&self, playlist_id: i64, track_id: i64) -> Result<()> {
-        let playlist = entities::playlist::Entity::find_by_id(playlist_id)
-            .one(&self.db.conn)
+    /// Creates a smart playlist whose tracks are computed from `rules`.
+    pub async fn create_smart(
+        &self,
+        name: String,
+        description: Option<String>,
+        rules: SmartPlaylistRules,
+    ) -> Result<entities::playlist::Model> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+        smart_playlist::validate_rules(&txn, &rules).await?;
+
+        let playlist = entities::playlist::ActiveModel {
+            name: Set(name),
+            description: Set(description),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to create playlist")?;
+
+        entities::smart_playlist::ActiveModel {
+            playlist_id: Set(playlist.id),
+            rules: Set(rules),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to save smart playlist rules")?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(playlist)
+    }
+
+    /// Replaces the rules of a smart playlist.
+    pub async fn update_smart_rules(
+        &self,
+        playlist_id: i64,
+        rules: SmartPlaylistRules,
+    ) -> Result<entities::playlist::Model> {
+        let txn = self
+            .db
+            .conn
+            .begin()
             .await
-            .map_err(|e| color_eyre::eyre::eyre!("Failed to find playlist: {}", e))?
-            .ok_or_eyre("Playlist not found")?;
+            .wrap_err("Failed to begin transaction")?;
+        smart_playlist::validate_rules(&txn, &rules).await?;
 
+        let playlist = find_playlist(&txn, playlist_id).await?;
+        let smart = entities::smart_playlist::Entity::find()
+            .filter(entities::smart_playlist::Column::PlaylistId.eq(playlist_id))
+            .one(&txn)
+            .await
+            .wrap_err("Failed to fetch smart playlist rules")?
+            .ok_or_eyre("Playlist is not a smart playlist")?;
+
+        let mut smart: entities::smart_playlist::ActiveModel = smart.into();
+        smart.rules = Set(rules);
+        smart
+            .update(&txn)
+            .await
+            .wrap_err("Failed to update smart playlist rules")?;
+
+        touch_playlist(&txn, playlist.clone()).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        find_playlist(&self.db.conn, playlist.id).await
+    }
+
+    /// Appends a track to the end of a playlist. The same track may be added
+    /// more than once.
+    pub async fn add_track(
+        &self,
+        playlist_id: i64,
+        track_id: i64,
+    ) -> Result<entities::playlist_track::Model> {
+        self.insert_track_at(playlist_id, track_id, None).await
+    }
+
+    /// Inserts a track at `position` (zero-based), shifting later entries down.
+    /// Positions past the end of the playlist, or `None`, append the track.
+    pub async fn insert_track_at(
+        &self,
+        playlist_id: i64,
+        track_id: i64,
+        position: Option<usize>,
+    ) -> Result<entities::playlist_track::Model> {
         entities::track::Entity::find_by_id(track_id)
             .one(&self.db.conn)
             .await
-            .map_err(|e| color_eyre::eyre::eyre!("Failed to find track: {}", e))?
+            .wrap_err("Failed to find track")?
             .ok_or_eyre("Track not found")?;
 
-        // Check if track is already in playlist
-        let existing = entities::playlist_track::Entity::find()
-            .filter(
-                Condition::all()
-                    .add(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
-                    .add(entities::playlist_track::Column::TrackId.eq(track_id)),
-            )
-            .one(&self.db.conn)
+        let txn = self
+            .db
+            .conn
+            .begin()
             .await
-            .map_err(|e| {
-                color_eyre::eyre::eyre!("Failed to check existing playlist track: {}", e)
-            })?;
+            .wrap_err("Failed to begin transaction")?;
 
-        if existing.is_some() {
-            return Ok(());
-        }
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        let mut entries = ordered_entries(&txn, playlist_id).await?;
+        let index = position.unwrap_or(entries.len()).min(entries.len());
 
-        let now = Utc::now();
-        let playlist_track = entities::playlist_track::ActiveModel {
+        let entry = entities::playlist_track::ActiveModel {
             playlist_id: Set(playlist_id),
             track_id: Set(track_id),
-            created_at: Set(now),
-            updated_at: Set(now),
-        };
+            position: Set(index as i32),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to add track to playlist")?;
+
+        entries.insert(index, entry.clone());
+        renumber_entries(&txn, &entries).await?;
+        touch_playlist(&txn, playlist).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(entry)
+    }
 
-        self.db
+    /// Moves a single playlist entry to `position` (zero-based), clamped to
+    /// the end of the playlist.
+    pub async fn move_entry(&self, playlist_id: i64, entry_id: i64, position: usize) -> Result<()> {
+        let txn = self
+            .db
             .conn
-            .transaction::<_, (), color_eyre::eyre::Report>(|txn| {
-                Box::pin(async move {
-                    playlist_track.insert(txn).await.map_err(|e| {
-                        color_eyre::eyre::eyre!("Failed to add track to playlist: {}", e)
-                    })?;
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        let mut entries = ordered_entries(&txn, playlist_id).await?;
+        let current = entries
+            .iter()
+            .position(|entry| entry.id == entry_id)
+            .ok_or_eyre("Playlist entry not found")?;
+
+        let entry = entries.remove(current);
+        let index = position.min(entries.len());
+        entries.insert(index, entry);
+
+        renumber_entries(&txn, &entries).await?;
+        touch_playlist(&txn, playlist).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(())
+    }
+
+    /// Reorders a playlist so its entries follow `entry_ids`. The list must
+    /// contain every entry of the playlist exactly once.
+    pub async fn reorder(&self, playlist_id: i64, entry_ids: Vec<i64>) -> Result<()> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        let mut entries = ordered_entries(&txn, playlist_id).await?;
+
+        if entry_ids.len() != entries.len() {
+            return Err(eyre!(
+                "Expected {} playlist entries but got {}",
+                entries.len(),
+                entry_ids.len()
+            ));
+        }
+
+        let mut reordered = Vec::with_capacity(entries.len());
+        for entry_id in entry_ids {
+            let index = entries
+                .iter()
+                .position(|entry| entry.id == entry_id)
+                .ok_or_else(|| eyre!("Playlist entry {} not found or listed twice", entry_id))?;
+            reordered.push(entries.swap_remove(index));
+        }
 
-                    let mut playlist_model: entities::playlist::ActiveModel = playlist.into();
-                    playlist_model.updated_at = Set(Utc::now());
-                    playlist_model
-                        .update(txn)
-                        .await
-                        .map_err(|e| color_eyre::eyre::eyre!("Failed to update playlist: {}", e))?;
+        renumber_entries(&txn, &reordered).await?;
+        touch_playlist(&txn, playlist).await?;
 
-                    Ok(())
-                })
-            })
+        txn.commit()
             .await
-            .map_err(|_| color_eyre::eyre::eyre!("Failed to add track to playlist"))?;
+            .wrap_err("Failed to commit transaction")?;
 
         Ok(())
     }
+
+    /// Updates a playlist's name and/or description. `None` leaves a field
+    /// unchanged; `Some(None)` clears the description.
+    pub async fn update(
+        &self,
+        playlist_id: i64,
+        name: Option<String>,
+        description: Option<Option<String>>,
+    ) -> Result<entities::playlist::Model> {
+        let playlist = find_playlist(&self.db.conn, playlist_id).await?;
+
+        let mut model: entities::playlist::ActiveModel = playlist.into();
+        if let Some(name) = name {
+            model.name = Set(name);
+        }
+        if let Some(description) = description {
+            model.description = Set(description);
+        }
+
+        model
+            .update(&self.db.conn)
+            .await
+            .wrap_err("Failed to update playlist")
+    }
+
+    /// Deletes a playlist. Its entries are removed by the foreign key cascade.
+    pub async fn delete(&self, playlist_id: i64) -> Result<()> {
+        let result = entities::playlist::Entity::delete_by_id(playlist_id)
+            .exec(&self.db.conn)
+            .await
+            .wrap_err("Failed to delete playlist")?;
+
+        if result.rows_affected == 0 {
+            return Err(eyre!("Playlist not found"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes a single entry from a playlist.
+    pub async fn remove_entry(&self, playlist_id: i64, entry_id: i64) -> Result<()> {
+        let removed = self
+            .remove_entries_where(playlist_id, |entry| entry.id == entry_id)
+            .await?;
+
+        if removed == 0 {
+            return Err(eyre!("Playlist entry not found"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes every entry of a track from a playlist.
+    pub async fn remove_track(&self, playlist_id: i64, track_id: i64) -> Result<u64> {
+        self.remove_tracks(playlist_id, vec![track_id]).await
+    }
+
+    /// Removes every entry of the given tracks from a playlist and returns the
+    /// number of entries removed.
+    pub async fn remove_tracks(&self, playlist_id: i64, track_ids: Vec<i64>) -> Result<u64> {
+        let track_ids: HashSet<i64> = track_ids.into_iter().collect();
+        self.remove_entries_where(playlist_id, |entry| track_ids.contains(&entry.track_id))
+            .await
+    }
+
+    /// Removes every entry whose track title matches `search`, using the same
+    /// matching as the track search.
+    pub async fn remove_tracks_matching(&self, playlist_id: i64, search: &str) -> Result<u64> {
+        let track_ids = self.search_track_ids(search).await?;
+        self.remove_tracks(playlist_id, track_ids).await
+    }
+
+    /// Removes all entries from a playlist.
+    pub async fn clear(&self, playlist_id: i64) -> Result<u64> {
+        self.remove_entries_where(playlist_id, |_| true).await
+    }
+
+    /// Appends tracks to a playlist in the given order and returns the number
+    /// of entries added.
+    pub async fn add_tracks(&self, playlist_id: i64, track_ids: Vec<i64>) -> Result<u64> {
+        let mut found = HashSet::with_capacity(track_ids.len());
+        for chunk in track_ids.chunks(IN_LIST_CHUNK_SIZE) {
+            found.extend(
+                entities::track::Entity::find()
+                    .select_only()
+                    .column(entities::track::Column::Id)
+                    .filter(entities::track::Column::Id.is_in(chunk.iter().copied()))
+                    .into_tuple::<i64>()
+                    .all(&self.db.conn)
+                    .await
+                    .wrap_err("Failed to find tracks")?,
+            );
+        }
+
+        if let Some(missing) = track_ids.iter().find(|id| !found.contains(id)) {
+            return Err(eyre!("Track {} not found", missing));
+        }
+
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        let added = append_entries(&txn, playlist_id, &track_ids).await?;
+        touch_playlist(&txn, playlist).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(added)
+    }
+
+    /// Replaces all entries of a playlist with tracks in the given order and
+    /// returns the number of entries.
+    pub async fn replace_tracks(&self, playlist_id: i64, track_ids: Vec<i64>) -> Result<u64> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        entities::playlist_track::Entity::delete_many()
+            .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
+            .exec(&txn)
+            .await
+            .wrap_err("Failed to remove tracks from playlist")?;
+        let added = append_entries(&txn, playlist_id, &track_ids).await?;
+        touch_playlist(&txn, playlist).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(added)
+    }
+
+    /// Appends every track whose title matches `search`, in the default track
+    /// sort order.
+    pub async fn add_tracks_matching(&self, playlist_id: i64, search: &str) -> Result<u64> {
+        let track_ids = self.search_track_ids(search).await?;
+        self.add_tracks(playlist_id, track_ids).await
+    }
+
+    /// Copies a playlist and all of its entries (or its rules, for a smart
+    /// playlist) into a new playlist. Defaults the name to "<name> (copy)".
+    pub async fn duplicate(
+        &self,
+        playlist_id: i64,
+        name: Option<String>,
+    ) -> Result<entities::playlist::Model> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let source = find_playlist(&txn, playlist_id).await?;
+        let copy = entities::playlist::ActiveModel {
+            name: Set(name.unwrap_or_else(|| format!("{} (copy)", source.name))),
+            description: Set(source.description.clone()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to create playlist")?;
+
+        if let Some(rules) = smart_playlist::find_rules(&txn, source.id).await? {
+            entities::smart_playlist::ActiveModel {
+                playlist_id: Set(copy.id),
+                rules: Set(rules),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await
+            .wrap_err("Failed to copy smart playlist rules")?;
+        } else {
+            let track_ids = playlist_track_ids(&txn, source.id).await?;
+            append_entries(&txn, copy.id, &track_ids).await?;
+        }
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(copy)
+    }
+
+    /// Appends the entries of each source playlist to the target playlist, in
+    /// order. Smart sources contribute their current matches. With
+    /// `skip_existing`, tracks already in the target (or added earlier in the
+    /// merge) are skipped. Source playlists are left unchanged.
+    pub async fn merge(
+        &self,
+        target_playlist_id: i64,
+        source_playlist_ids: Vec<i64>,
+        skip_existing: bool,
+    ) -> Result<u64> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let target = find_editable_playlist(&txn, target_playlist_id).await?;
+        let mut seen: HashSet<i64> = ordered_entries(&txn, target.id)
+            .await?
+            .into_iter()
+            .map(|entry| entry.track_id)
+            .collect();
+
+        let mut track_ids = Vec::new();
+        for source_playlist_id in source_playlist_ids {
+            if source_playlist_id == target.id {
+                return Err(eyre!("Cannot merge a playlist into itself"));
+            }
+
+            let source = find_playlist(&txn, source_playlist_id).await?;
+            for track_id in playlist_track_ids(&txn, source.id).await? {
+                if !skip_existing || seen.insert(track_id) {
+                    track_ids.push(track_id);
+                }
+            }
+        }
+
+        let added = append_entries(&txn, target.id, &track_ids).await?;
+        touch_playlist(&txn, target).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(added)
+    }
+
+    async fn remove_entries_where(
+        &self,
+        playlist_id: i64,
+        predicate: impl Fn(&entities::playlist_track::Model) -> bool,
+    ) -> Result<u64> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let playlist = find_editable_playlist(&txn, playlist_id).await?;
+        let (removed, kept): (Vec<_>, Vec<_>) = ordered_entries(&txn, playlist_id)
+            .await?
+            .into_iter()
+            .partition(|entry| predicate(entry));
+
+        if removed.is_empty() {
+            return Ok(0);
+        }
+
+        let removed_ids: Vec<i64> = removed.iter().map(|entry| entry.id).collect();
+        for chunk in removed_ids.chunks(IN_LIST_CHUNK_SIZE) {
+            entities::playlist_track::Entity::delete_many()
+                .filter(entities::playlist_track::Column::Id.is_in(chunk.iter().copied()))
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to remove tracks from playlist")?;
+        }
+
+        renumber_entries(&txn, &kept).await?;
+        touch_playlist(&txn, playlist).await?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(removed.len() as u64)
+    }
+
+    async fn search_track_ids(&self, search: &str) -> Result<Vec<i64>> {
+        if search.trim().is_empty() {
+            return Err(eyre!("Search query must not be empty"));
+        }
+
+        let query = apply_multi_column_text_search(
+            entities::track::Entity::find(),
+            vec![entities::track::Column::Title],
+            search,
+        );
+        let query = apply_sort(query, &[], Some(TrackSortField::default_sort()))?;
+
+        query
+            .select_only()
+            .column(entities::track::Column::Id)
+            .into_tuple::<i64>()
+            .all(&self.db.conn)
+            .await
+            .wrap_err("Failed to search tracks")
+    }
+}
+
+async fn find_playlist(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+) -> Result<entities::playlist::Model> {
+    entities::playlist::Entity::find_by_id(playlist_id)
+        .one(conn)
+        .await
+        .wrap_err("Failed to find playlist")?
+        .ok_or_eyre("Playlist not found")
+}
+
+/// Like `find_playlist`, but rejects smart playlists, whose tracks are
+/// computed from rules and cannot be edited directly.
+async fn find_editable_playlist(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+) -> Result<entities::playlist::Model> {
+    let playlist = find_playlist(conn, playlist_id).await?;
+
+    if smart_playlist::find_rules(conn, playlist_id)
+        .await?
+        .is_some()
+    {
+        return Err(eyre!(
+            "Playlist '{}' is a smart playlist; edit its rules instead",
+            playlist.name
+        ));
+    }
+
+    Ok(playlist)
+}
+
+/// Returns the track IDs of a playlist in order, evaluating the rules of a
+/// smart playlist.
+pub(crate) async fn playlist_track_ids(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+) -> Result<Vec<i64>> {
+    match smart_playlist::find_rules(conn, playlist_id).await? {
+        Some(rules) => smart_playlist::matching_track_ids(conn, &rules).await,
+        None => Ok(ordered_entries(conn, playlist_id)
+            .await?
+            .into_iter()
+            .map(|entry| entry.track_id)
+            .collect()),
+    }
+}
+
+async fn touch_playlist(
+    conn: &impl ConnectionTrait,
+    playlist: entities::playlist::Model,
+) -> Result<()> {
+    let mut playlist_model: entities::playlist::ActiveModel = playlist.into();
+    playlist_model.updated_at = Set(Utc::now());
+    playlist_model
+        .update(conn)
+        .await
+        .wrap_err("Failed to update playlist")?;
+
+    Ok(())
+}
+
+/// Appends entries for `track_ids` after the last entry of a playlist and
+/// returns the number of entries added.
+async fn append_entries(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+    track_ids: &[i64],
+) -> Result<u64> {
+    if track_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let next_position = entities::playlist_track::Entity::find()
+        .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
+        .count(conn)
+        .await
+        .wrap_err("Failed to count playlist tracks")? as i32;
+
+    let now = Utc::now();
+    for (chunk_index, chunk) in track_ids.chunks(INSERT_CHUNK_SIZE).enumerate() {
+        let first_position = next_position + (chunk_index * INSERT_CHUNK_SIZE) as i32;
+        let models = chunk.iter().enumerate().map(|(offset, track_id)| {
+            entities::playlist_track::ActiveModel {
+                playlist_id: Set(playlist_id),
+                track_id: Set(*track_id),
+                position: Set(first_position + offset as i32),
+                created_at: Set(now),
+                updated_at: Set(now),
+                ..Default::default()
+            }
+        });
+
+        entities::playlist_track::Entity::insert_many(models)
+            .exec(conn)
+            .await
+            .wrap_err("Failed to add tracks to playlist")?;
+    }
+
+    Ok(track_ids.len() as u64)
+}
+
+/// Returns the entries of a playlist in playlist order.
+pub(crate) async fn ordered_entries(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+) -> Result<Vec<entities::playlist_track::Model>> {
+    entities::playlist_track::Entity::find()
+        .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
+        .order_by_asc(entities::playlist_track::Column::Position)
+        .order_by_asc(entities::playlist_track::Column::Id)
+        .all(conn)
+        .await
+        .wrap_err("Failed to fetch playlist tracks")
+}
+
+/// Rewrites positions so `entries` are numbered 0..n in slice order, only
+/// touching rows whose position changed.
+pub(crate) async fn renumber_entries(
+    conn: &impl ConnectionTrait,
+    entries: &[entities::playlist_track::Model],
+) -> Result<()> {
+    for (index, entry) in entries.iter().enumerate() {
+        let index = index as i32;
+        if entry.position == index {
+            continue;
+        }
+
+        let mut model: entities::playlist_track::ActiveModel = entry.clone().into();
+        model.position = Set(index);
+        model
+            .update(conn)
+            .await
+            .wrap_err("Failed to update playlist track position")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+
+    async fn track_order(db: &Database, playlist_id: i64) -> Vec<i64> {
+        ordered_entries(&db.conn, playlist_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.track_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_add_track_appends_and_allows_duplicates() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+
+        service.add_track(playlist.id, a).await.unwrap();
+        service.add_track(playlist.id, b).await.unwrap();
+        let duplicate = service.add_track(playlist.id, a).await.unwrap();
+
+        assert_eq!(duplicate.position, 2);
+        assert_eq!(track_order(&db, playlist.id).await, vec![a, b, a]);
+    }
+
+    #[tokio::test]
+    async fn test_insert_track_at_shifts_later_entries() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+        let c = insert_track(&db, TestTrack::new("C")).await.id;
+
+        service.add_track(playlist.id, a).await.unwrap();
+        service.add_track(playlist.id, b).await.unwrap();
+        service
+            .insert_track_at(playlist.id, c, Some(1))
+            .await
+            .unwrap();
+        service
+            .insert_track_at(playlist.id, c, Some(99))
+            .await
+            .unwrap();
+
+        assert_eq!(track_order(&db, playlist.id).await, vec![a, c, b, c]);
+        let positions: Vec<i32> = ordered_entries(&db.conn, playlist.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.position)
+            .collect();
+        assert_eq!(positions, vec![0, 1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_move_entry_and_reorder() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+        let c = insert_track(&db, TestTrack::new("C")).await.id;
+
+        let entry_a = service.add_track(playlist.id, a).await.unwrap();
+        let entry_b = service.add_track(playlist.id, b).await.unwrap();
+        let entry_c = service.add_track(playlist.id, c).await.unwrap();
+
+        service
+            .move_entry(playlist.id, entry_a.id, 2)
+            .await
+            .unwrap();
+        assert_eq!(track_order(&db, playlist.id).await, vec![b, c, a]);
+
+        service
+            .reorder(playlist.id, vec![entry_c.id, entry_a.id, entry_b.id])
+            .await
+            .unwrap();
+        assert_eq!(track_order(&db, playlist.id).await, vec![c, a, b]);
+
+        // Incomplete or repeated entry lists are rejected
+        assert!(
+            service
+                .reorder(playlist.id, vec![entry_c.id, entry_a.id])
+                .await
+                .is_err()
+        );
+        assert!(
+            service
+                .reorder(playlist.id, vec![entry_c.id, entry_c.id, entry_b.id])
+                .await
+                .is_err()
+        );
+        assert_eq!(track_order(&db, playlist.id).await, vec![c, a, b]);
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service
+            .create("Mix".into(), Some("Old".into()))
+            .await
+            .unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        service.add_track(playlist.id, a).await.unwrap();
+
+        let renamed = service
+            .update(playlist.id, Some("Renamed".into()), Some(None))
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "Renamed");
+        assert_eq!(renamed.description, None);
+
+        service.delete(playlist.id).await.unwrap();
+        assert!(
+            entities::playlist::Entity::find_by_id(playlist.id)
+                .one(&db.conn)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(track_order(&db, playlist.id).await.is_empty());
+        assert!(service.delete(playlist.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_remove_and_clear() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+        let c = insert_track(&db, TestTrack::new("C")).await.id;
+
+        service
+            .add_tracks(playlist.id, vec![a, b, a, c])
+            .await
+            .unwrap();
+
+        assert_eq!(service.remove_track(playlist.id, a).await.unwrap(), 2);
+        assert_eq!(track_order(&db, playlist.id).await, vec![b, c]);
+        let positions: Vec<i32> = ordered_entries(&db.conn, playlist.id)
+            .await
+            .unwrap()
+            .iter()
+            .map(|entry| entry.position)
+            .collect();
+        assert_eq!(positions, vec![0, 1]);
+
+        let entry_c = ordered_entries(&db.conn, playlist.id).await.unwrap()[1].clone();
+        service.remove_entry(playlist.id, entry_c.id).await.unwrap();
+        assert_eq!(track_order(&db, playlist.id).await, vec![b]);
+
+        assert_eq!(service.clear(playlist.id).await.unwrap(), 1);
+        assert!(track_order(&db, playlist.id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_add_validates_tracks() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+
+        let result = service.add_tracks(playlist.id, vec![a, 9999]).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Track 9999 not found")
+        );
+        assert!(track_order(&db, playlist.id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_add_and_remove_many_tracks() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+        service.add_track(playlist.id, b).await.unwrap();
+
+        // More entries than fit in one insert or `IN` list
+        let many: Vec<i64> = (0..IN_LIST_CHUNK_SIZE + 1).map(|_| a).collect();
+        let added = service.add_tracks(playlist.id, many).await.unwrap();
+        assert_eq!(added, IN_LIST_CHUNK_SIZE as u64 + 1);
+        let positions: Vec<i32> = ordered_entries(&db.conn, playlist.id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.position)
+            .collect();
+        assert_eq!(
+            positions,
+            (0..IN_LIST_CHUNK_SIZE as i32 + 2).collect::<Vec<_>>()
+        );
+
+        let removed = service.remove_track(playlist.id, a).await.unwrap();
+        assert_eq!(removed, IN_LIST_CHUNK_SIZE as u64 + 1);
+        assert_eq!(track_order(&db, playlist.id).await, vec![b]);
+    }
+
+    #[tokio::test]
+    async fn test_add_and_remove_tracks_matching() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let playlist = service.create("Mix".into(), None).await.unwrap();
+        let love1 = insert_track(&db, TestTrack::new("Love Song")).await.id;
+        insert_track(&db, TestTrack::new("Other")).await;
+        let love2 = insert_track(&db, TestTrack::new("Lovely Day")).await.id;
+
+        assert_eq!(
+            service
+                .add_tracks_matching(playlist.id, "love")
+                .await
+                .unwrap(),
+            2
+        );
+        let mut tracks = track_order(&db, playlist.id).await;
+        tracks.sort();
+        assert_eq!(tracks, vec![love1, love2]);
+
+        assert_eq!(
+            service
+                .remove_tracks_matching(playlist.id, "Lovely")
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(track_order(&db, playlist.id).await, vec![love1]);
+        assert!(service.add_tracks_matching(playlist.id, " ").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_and_merge() {
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let first = service.create("First".into(), None).await.unwrap();
+        let second = service.create("Second".into(), None).await.unwrap();
+        let a = insert_track(&db, TestTrack::new("A")).await.id;
+        let b = insert_track(&db, TestTrack::new("B")).await.id;
+        let c = insert_track(&db, TestTrack::new("C")).await.id;
+        service.add_tracks(first.id, vec![a, b]).await.unwrap();
+        service.add_tracks(second.id, vec![b, c]).await.unwrap();
+
+        let copy = service.duplicate(first.id, None).await.unwrap();
+        assert_eq!(copy.name, "First (copy)");
+        assert_eq!(track_order(&db, copy.id).await, vec![a, b]);
+
+        let added = service
+            .merge(first.id, vec![second.id], true)
+            .await
+            .unwrap();
+        assert_eq!(added, 1);
+        assert_eq!(track_order(&db, first.id).await, vec![a, b, c]);
+
+        let added = service
+            .merge(copy.id, vec![second.id], false)
+            .await
+            .unwrap();
+        assert_eq!(added, 2);
+        assert_eq!(track_order(&db, copy.id).await, vec![a, b, b, c]);
+
+        // Sources are untouched and self-merges are rejected
+        assert_eq!(track_order(&db, second.id).await, vec![b, c]);
+        assert!(service.merge(first.id, vec![first.id], true).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_smart_playlist_is_read_only_and_duplicates_rules() {
+        use crate::entities::smart_playlist::{
+            SmartRule, SmartRuleField, SmartRuleMatch, SmartRuleOperator,
+        };
+
+        let db = test_db().await;
+        let service = PlaylistService::new(db.clone());
+        let a = insert_track(&db, TestTrack::new("Love Song")).await.id;
+        insert_track(&db, TestTrack::new("Other")).await;
+
+        let rules = SmartPlaylistRules {
+            match_mode: SmartRuleMatch::All,
+            rules: vec![SmartRule {
+                field: SmartRuleField::Title,
+                operator: SmartRuleOperator::Contains,
+                value: "love".into(),
+            }],
+            sort: Vec::new(),
+            limit: None,
+        };
+        let smart = service
+            .create_smart("Love".into(), None, rules.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            playlist_track_ids(&db.conn, smart.id).await.unwrap(),
+            vec![a]
+        );
+        assert!(service.add_track(smart.id, a).await.is_err());
+        assert!(service.clear(smart.id).await.is_err());
+
+        let copy = service.duplicate(smart.id, None).await.unwrap();
+        assert_eq!(
+            smart_playlist::find_rules(&db.conn, copy.id).await.unwrap(),
+            Some(rules)
+        );
+
+        // Smart sources are materialized when merged into a regular playlist
+        let regular = service.create("Regular".into(), None).await.unwrap();
+        assert_eq!(
+            service
+                .merge(regular.id, vec![smart.id], true)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(track_order(&db, regular.id).await, vec![a]);
+    }
 }
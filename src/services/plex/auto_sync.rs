@@ -0,0 +1,171 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::Config;
+use crate::database::{Database, LibraryEvent};
+use crate::services::plex::PlexService;
+use crate::services::plex::client::PlexHttpAdapter;
+
+/// Settings of the Plex refresh and playlist re-sync after imports.
+///
+/// Loaded from the `[plex_auto_sync]` section of the config file. Every field
+/// is optional and falls back to the default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlexAutoSyncConfig {
+    pub enabled: bool,
+    /// Seconds without imports before Plex is refreshed
+    pub debounce_secs: u64,
+    /// Seconds between checks whether Plex finished scanning
+    pub scan_poll_secs: u64,
+    /// Seconds to wait for a scan before re-syncing playlists anyway
+    pub scan_timeout_secs: u64,
+}
+
+impl Default for PlexAutoSyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            debounce_secs: 30,
+            scan_poll_secs: 5,
+            scan_timeout_secs: 600,
+        }
+    }
+}
+
+/// Imports that settled, to be scanned by Plex.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportBatch {
+    /// Album folders of the imported tracks
+    pub folders: BTreeSet<String>,
+    /// Some imports were missed, so the folders are incomplete
+    pub missed_imports: bool,
+}
+
+/// Adds an import to a batch; false when no more imports can happen.
+fn add_import(batch: &mut ImportBatch, event: Result<LibraryEvent, RecvError>) -> bool {
+    match event {
+        Ok(LibraryEvent::TrackImported { file_path, .. }) => {
+            if let Some(folder) = file_path.parent().and_then(|folder| folder.to_str()) {
+                batch.folders.insert(folder.to_string());
+            }
+            true
+        }
+        Err(RecvError::Lagged(_)) => {
+            batch.missed_imports = true;
+            true
+        }
+        Err(RecvError::Closed) => false,
+    }
+}
+
+/// Waits for the next imports and collects them until none were imported for
+/// `debounce`. Returns `None` once no more imports can happen.
+async fn next_import_batch(
+    events: &mut broadcast::Receiver<LibraryEvent>,
+    debounce: Duration,
+) -> Option<ImportBatch> {
+    let mut batch = ImportBatch::default();
+    if !add_import(&mut batch, events.recv().await) {
+        return None;
+    }
+    while let Ok(event) = tokio::time::timeout(debounce, events.recv()).await {
+        if !add_import(&mut batch, event) {
+            break;
+        }
+    }
+    Some(batch)
+}
+
+/// Rescans the folders of imported tracks on every Plex server once imports
+/// settle, then re-syncs the playlists kept synced to Plex.
+pub async fn run_plex_auto_sync(
+    db: Arc<Database>,
+    config: Config,
+    mut events: broadcast::Receiver<LibraryEvent>,
+) {
+    let settings = config.plex_auto_sync().clone();
+    if !settings.enabled {
+        return;
+    }
+
+    let debounce = Duration::from_secs(settings.debounce_secs);
+    while let Some(batch) = next_import_batch(&mut events, debounce).await {
+        tracing::info!(
+            "Imports settled, refreshing Plex for {} folders",
+            batch.folders.len()
+        );
+        let service = PlexService::new(db.clone(), PlexHttpAdapter::new());
+        let folders = (!batch.missed_imports).then_some(&batch.folders);
+        if let Err(e) = service
+            .sync_after_import(
+                folders,
+                &settings,
+                config.matcher(),
+                config.plex_playlist_sync(),
+            )
+            .await
+        {
+            tracing::error!("Failed to refresh Plex after imports: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn imported(track_id: i64, file_path: &str) -> LibraryEvent {
+        LibraryEvent::TrackImported {
+            track_id,
+            file_path: PathBuf::from(file_path),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_imports_are_batched_until_they_settle() {
+        let (sender, mut events) = broadcast::channel(16);
+        sender
+            .send(imported(1, "/music/Queen/Opera/01 Death on Two Legs.flac"))
+            .unwrap();
+        sender
+            .send(imported(2, "/music/Queen/Opera/02 Lazing on a Sunday.flac"))
+            .unwrap();
+        sender
+            .send(imported(3, "/music/Queen/Jazz/01 Mustapha.flac"))
+            .unwrap();
+
+        let batch = next_import_batch(&mut events, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(
+            batch.folders.into_iter().collect::<Vec<_>>(),
+            vec!["/music/Queen/Jazz", "/music/Queen/Opera"]
+        );
+        assert!(!batch.missed_imports);
+
+        drop(sender);
+        assert_eq!(
+            next_import_batch(&mut events, Duration::from_millis(50)).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missed_imports_are_flagged() {
+        let (sender, mut events) = broadcast::channel(1);
+        sender.send(imported(1, "/music/A/B/1.flac")).unwrap();
+        sender.send(imported(2, "/music/A/C/2.flac")).unwrap();
+
+        let batch = next_import_batch(&mut events, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(batch.missed_imports);
+    }
+}
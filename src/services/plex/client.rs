@@ -3,17 +3,21 @@ use reqwest::Client;
 use url::Url;
 
 use crate::plex_rs::all_tracks::{
-    PlexLibrarySection, PlexLibraryTrack, PlexMediaContainer, find_music_section_id,
-    get_library_sections, get_tracks_page,
+    PlexLibrarySection, PlexLibraryTrack, PlexMediaContainer, TracksSince, find_music_section_id,
+    get_library_sections, get_playlist_library_tracks, get_tracks_page_since,
 };
 use crate::plex_rs::auth::{
     PlexAuthResponse, PlexPinResponse, PlexResource, construct_auth_app_url, create_plex_pin,
     get_plex_resources, poll_for_plex_auth,
 };
 use crate::plex_rs::library_refresh::{
-    PlexActivity, get_library_scan_status, refresh_library_section,
+    PlexActivity, get_library_scan_status, refresh_library_path, refresh_library_section,
+};
+use crate::plex_rs::playlist::{
+    PlexPlaylist, PlexTrack, add_track_to_playlist, create_music_playlist_with_uri,
+    get_machine_identifier, get_playlist_tracks, get_playlists, move_playlist_item,
+    remove_track_from_playlist,
 };
-use crate::plex_rs::playlist::{PlexPlaylist, get_playlists};
 use crate::ports::plex::PlexClient;
 
 pub struct PlexHttpAdapter {
@@ -63,10 +67,20 @@ impl PlexClient for PlexHttpAdapter {
         server_url: &Url,
         token: &str,
         section_id: &str,
+        since: Option<TracksSince>,
         start: u32,
         size: u32,
     ) -> Result<PlexMediaContainer<PlexLibraryTrack>> {
-        get_tracks_page(&self.client, server_url, token, section_id, start, size).await
+        get_tracks_page_since(
+            &self.client,
+            server_url,
+            token,
+            section_id,
+            since,
+            start,
+            size,
+        )
+        .await
     }
 
     async fn refresh_library_section(
@@ -78,6 +92,16 @@ impl PlexClient for PlexHttpAdapter {
         refresh_library_section(&self.client, server_url, token, section_id).await
     }
 
+    async fn refresh_library_path(
+        &self,
+        server_url: &Url,
+        token: &str,
+        section_id: &str,
+        path: &str,
+    ) -> Result<()> {
+        refresh_library_path(&self.client, server_url, token, section_id, path).await
+    }
+
     async fn get_library_scan_status(
         &self,
         server_url: &Url,
@@ -87,7 +111,108 @@ impl PlexClient for PlexHttpAdapter {
         get_library_scan_status(&self.client, server_url, token, section_id).await
     }
 
+    async fn get_machine_identifier(&self, server_url: &Url, token: &str) -> Result<String> {
+        get_machine_identifier(&self.client, server_url, token).await
+    }
+
     async fn get_playlists(&self, server_url: &Url, token: &str) -> Result<Vec<PlexPlaylist>> {
         get_playlists(&self.client, server_url, token).await
     }
+
+    async fn create_playlist(
+        &self,
+        server_url: &Url,
+        token: &str,
+        machine_identifier: &str,
+        title: &str,
+        first_rating_key: Option<String>,
+    ) -> Result<PlexPlaylist> {
+        let first_track_uri = first_rating_key.map(|rating_key| {
+            format!(
+                "server://{}/com.plexapp.plugins.library/library/metadata/{}",
+                machine_identifier, rating_key
+            )
+        });
+        create_music_playlist_with_uri(
+            &self.client,
+            server_url,
+            token,
+            title,
+            first_track_uri.as_deref(),
+        )
+        .await
+    }
+
+    async fn get_playlist_items(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+    ) -> Result<Vec<PlexTrack>> {
+        get_playlist_tracks(&self.client, server_url, token, playlist_id).await
+    }
+
+    async fn get_playlist_library_tracks(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+    ) -> Result<Vec<PlexLibraryTrack>> {
+        get_playlist_library_tracks(&self.client, server_url, token, playlist_id).await
+    }
+
+    async fn add_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        machine_identifier: &str,
+        rating_key: &str,
+    ) -> Result<()> {
+        add_track_to_playlist(
+            &self.client,
+            server_url,
+            token,
+            playlist_id,
+            machine_identifier,
+            rating_key,
+        )
+        .await
+    }
+
+    async fn move_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        playlist_item_id: u64,
+        after_item_id: Option<u64>,
+    ) -> Result<()> {
+        move_playlist_item(
+            &self.client,
+            server_url,
+            token,
+            playlist_id,
+            playlist_item_id,
+            after_item_id,
+        )
+        .await
+    }
+
+    async fn remove_playlist_item(
+        &self,
+        server_url: &Url,
+        token: &str,
+        playlist_id: &str,
+        playlist_item_id: u64,
+    ) -> Result<()> {
+        remove_track_from_playlist(
+            &self.client,
+            server_url,
+            token,
+            playlist_id,
+            playlist_item_id,
+        )
+        .await
+    }
 }
@@ -0,0 +1,142 @@
+//! Refreshing the track mappings of a Plex server in the background when its
+//! webhooks report new or unknown tracks: at most one refresh is waiting per
+//! server, and it waits until `WEBHOOK_REFRESH_INTERVAL_SECS` passed since the
+//! last refresh, so a burst of events refreshes the mappings once.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, WrapErr};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::database::Database;
+use crate::entities;
+use crate::entities::background_task::{TaskKind, TaskStatus};
+use crate::services::plex::client::PlexHttpAdapter;
+use crate::services::plex::{PlexService, WEBHOOK_REFRESH_INTERVAL_SECS};
+use crate::services::spotify::matching_local_tracks::MatcherConfig;
+use crate::services::tasks::{TaskContext, TaskHandler, TaskManager};
+
+#[derive(Serialize, Deserialize)]
+struct MappingRefreshTaskPayload {
+    plex_server_id: i64,
+    config: MatcherConfig,
+}
+
+/// Saved once the refresh starts: events from then on may add tracks the
+/// refresh doesn't see, so they need another task.
+#[derive(Serialize, Deserialize)]
+struct MappingRefreshCheckpoint {
+    started_at: i64,
+}
+
+/// Runs [`TaskKind::PlexMappingRefresh`] tasks: waits until the mappings of
+/// the server weren't refreshed for `WEBHOOK_REFRESH_INTERVAL_SECS`, then
+/// maps the Plex tracks updated since.
+pub struct PlexMappingRefreshTaskHandler;
+
+#[async_trait]
+impl TaskHandler for PlexMappingRefreshTaskHandler {
+    fn kind(&self) -> TaskKind {
+        TaskKind::PlexMappingRefresh
+    }
+
+    /// Refreshes of other servers don't wait for a server's interval to pass
+    fn max_concurrency(&self) -> usize {
+        4
+    }
+
+    async fn run(&self, ctx: &TaskContext) -> Result<()> {
+        let db = ctx.db();
+        let payload: MappingRefreshTaskPayload = ctx.payload()?;
+
+        loop {
+            if ctx.is_cancelled() {
+                return Ok(());
+            }
+            let Some(server) = entities::plex_server::Entity::find_by_id(payload.plex_server_id)
+                .one(&db.conn)
+                .await
+                .wrap_err("Failed to fetch plex server")?
+            else {
+                tracing::info!("Plex server was deleted before refreshing its mappings");
+                return Ok(());
+            };
+            let now = chrono::Utc::now().timestamp();
+            let due_at = server
+                .mappings_refreshed_at
+                .map_or(now, |at| at + WEBHOOK_REFRESH_INTERVAL_SECS);
+            if due_at <= now {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+
+        ctx.save_checkpoint(
+            &MappingRefreshCheckpoint {
+                started_at: chrono::Utc::now().timestamp(),
+            },
+            0,
+        )
+        .await?;
+        let result = PlexService::new(db.clone(), PlexHttpAdapter::new())
+            .refresh_track_mappings(payload.plex_server_id, false, &payload.config)
+            .await?;
+        tracing::info!(
+            "Refreshed track mappings of Plex server {}: {} mapped, {} unmatched",
+            payload.plex_server_id,
+            result.tracks_mapped,
+            result.tracks_unmatched
+        );
+        Ok(())
+    }
+}
+
+/// Refresh the track mappings of a server in the background, unless a
+/// refresh of it is already waiting to start. Unauthenticated servers are
+/// skipped.
+pub async fn schedule_mapping_refresh(
+    db: &Database,
+    tasks: &Arc<TaskManager>,
+    server: &entities::plex_server::Model,
+    config: &MatcherConfig,
+) -> Result<Option<entities::background_task::Model>> {
+    if server.access_token.is_none() {
+        tracing::warn!(
+            "Not mapping new tracks of Plex server '{}', it isn't authenticated",
+            server.name
+        );
+        return Ok(None);
+    }
+
+    let waiting = entities::background_task::Entity::find()
+        .filter(entities::background_task::Column::Kind.eq(TaskKind::PlexMappingRefresh))
+        .filter(
+            entities::background_task::Column::Status
+                .is_in([TaskStatus::Pending, TaskStatus::Running]),
+        )
+        .filter(entities::background_task::Column::Checkpoint.is_null())
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch waiting mapping refreshes")?;
+    let already_waiting = waiting.iter().any(|task| {
+        serde_json::from_value::<MappingRefreshTaskPayload>(task.payload.clone())
+            .is_ok_and(|payload| payload.plex_server_id == server.id)
+    });
+    if already_waiting {
+        return Ok(None);
+    }
+
+    let task = tasks
+        .submit(
+            TaskKind::PlexMappingRefresh,
+            &MappingRefreshTaskPayload {
+                plex_server_id: server.id,
+                config: config.clone(),
+            },
+        )
+        .await?;
+    Ok(Some(task))
+}
@@ -1,17 +1,57 @@
+pub mod auto_sync;
 pub mod client;
+pub mod mapping_refresh;
 
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
 use color_eyre::eyre::OptionExt;
-use sea_orm::{ActiveModelBehavior, ActiveModelTrait, EntityTrait, Set};
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Set, TransactionTrait,
+};
+use tokio::time::Instant;
 use url::Url;
 
 use crate::database::Database;
 use crate::entities;
-use crate::plex_rs::all_tracks::{PlexLibraryTrack, PlexMediaContainer};
+use crate::plex_rs::all_tracks::{
+    PlexLibrarySection, PlexLibraryTrack, PlexMediaContainer, TracksSince, get_all_tracks_since,
+};
+use crate::plex_rs::import_playlist::{ImportedPlexPlaylist, import_plex_playlists};
 use crate::plex_rs::library_refresh::PlexActivity;
+use crate::plex_rs::path_mapping::{PathMappings, normalize_prefix};
 use crate::plex_rs::playlist::PlexPlaylist;
+use crate::plex_rs::sync_playlist::{
+    PlexPlaylistSyncConfig, SyncPlaylistResult, sync_playlist_to_plex,
+};
+use crate::plex_rs::track_mapping::{
+    TrackMappingRefreshResult, import_play_history, refresh_track_mappings, update_play_history,
+};
+use crate::plex_rs::webhook::{
+    PlexWebhookEvent, PlexWebhookMetadata, PlexWebhookPayload, is_known_track, record_rating,
+    record_scrobble,
+};
 use crate::ports::plex::PlexClient;
+use crate::services::plex::auto_sync::PlexAutoSyncConfig;
+use crate::services::plex::mapping_refresh::schedule_mapping_refresh;
+use crate::services::spotify::matching_local_tracks::MatcherConfig;
+use crate::services::tasks::TaskManager;
+
+/// Above this many imported folders the whole music section is scanned
+/// instead of each folder.
+const MAX_FOLDER_SCANS: usize = 20;
+
+/// Webhook events refresh the mappings at most this often.
+const WEBHOOK_REFRESH_INTERVAL_SECS: i64 = 60;
+
+/// Outcome of syncing a playlist to one Plex server.
+pub struct ServerSyncOutcome {
+    pub server: entities::plex_server::Model,
+    pub result: color_eyre::Result<SyncPlaylistResult>,
+}
 
 /// Outcome for the plex_tracks query, decoupled from GraphQL types.
 pub enum PlexTracksOutcome {
@@ -34,10 +74,16 @@ impl<C: PlexClient> PlexService<C> {
 
     // ---- Shared helpers ----
 
-    /// Look up the single plex server. Returns (model, parsed_url, access_token).
-    async fn resolve_server(
-        &self,
-    ) -> color_eyre::Result<(entities::plex_server::Model, Url, String)> {
+    async fn find_server(&self, id: i64) -> color_eyre::Result<entities::plex_server::Model> {
+        entities::plex_server::Entity::find_by_id(id)
+            .one(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch Plex server: {}", e))?
+            .ok_or_else(|| color_eyre::eyre::eyre!("Plex server with id {} not found", id))
+    }
+
+    /// The plex server to use when none was chosen, as long as there is only one.
+    async fn only_server(&self) -> color_eyre::Result<entities::plex_server::Model> {
         let servers = entities::plex_server::Entity::find()
             .all(&self.db.conn)
             .await
@@ -50,31 +96,24 @@ impl<C: PlexClient> PlexService<C> {
         }
         if servers.len() > 1 {
             return Err(color_eyre::eyre::eyre!(
-                "Multiple Plex servers found ({}). Only one server is supported at a time.",
+                "Multiple Plex servers found ({}). Please choose a server.",
                 servers.len()
             ));
         }
 
-        let server = servers.into_iter().next().unwrap();
-        let access_token = server.access_token.clone().ok_or_eyre(
-            "Plex server does not have an access token. Please authenticate the server first.",
-        )?;
-        let server_url = Url::parse(&server.server_url)
-            .map_err(|e| color_eyre::eyre::eyre!("Invalid server URL: {}", e))?;
-
-        Ok((server, server_url, access_token))
+        Ok(servers.into_iter().next().unwrap())
     }
 
-    /// Look up a server by ID. Returns (model, parsed_url, access_token).
-    async fn resolve_server_by_id(
+    /// Look up a server by ID, or the only server when no ID is given.
+    /// Returns (model, parsed_url, access_token).
+    async fn resolve_server(
         &self,
-        id: i64,
+        server_id: Option<i64>,
     ) -> color_eyre::Result<(entities::plex_server::Model, Url, String)> {
-        let server = entities::plex_server::Entity::find_by_id(id)
-            .one(&self.db.conn)
-            .await
-            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch Plex server: {}", e))?
-            .ok_or_eyre("Plex server not found")?;
+        let server = match server_id {
+            Some(id) => self.find_server(id).await?,
+            None => self.only_server().await?,
+        };
 
         let access_token = server.access_token.clone().ok_or_eyre(
             "Plex server does not have an access token. Please authenticate the server first.",
@@ -85,12 +124,19 @@ impl<C: PlexClient> PlexService<C> {
         Ok((server, server_url, access_token))
     }
 
-    /// Get library sections and find the music section ID.
+    /// The music section to use: the given one, else the server's default,
+    /// else the first music section of the server.
     async fn resolve_music_section(
         &self,
+        server: &entities::plex_server::Model,
         server_url: &Url,
         token: &str,
+        section_id: Option<String>,
     ) -> color_eyre::Result<String> {
+        if let Some(section_id) = section_id.or_else(|| server.music_section_id.clone()) {
+            return Ok(section_id);
+        }
+
         let sections = self.client.get_library_sections(server_url, token).await?;
         let section_id = self
             .client
@@ -228,20 +274,195 @@ impl<C: PlexClient> PlexService<C> {
 
     // ---- Library ----
 
-    pub async fn get_tracks(&self) -> color_eyre::Result<PlexTracksOutcome> {
-        let servers = entities::plex_server::Entity::find()
+    /// Music library sections of a server.
+    pub async fn list_music_sections(
+        &self,
+        server_id: i64,
+    ) -> color_eyre::Result<Vec<PlexLibrarySection>> {
+        let (_server, server_url, access_token) = self.resolve_server(Some(server_id)).await?;
+        let sections = self
+            .client
+            .get_library_sections(&server_url, &access_token)
+            .await?;
+        Ok(sections
+            .into_iter()
+            .filter(|section| section.section_type == "artist")
+            .collect())
+    }
+
+    /// Set the music section used by default for a server, or clear it to use
+    /// the first music section. Track mappings and unmatched tracks of the
+    /// previous section are dropped with its refresh and history import times,
+    /// so the next refresh and import start from scratch with the new section.
+    /// The play history the dropped mappings added to local tracks is removed.
+    pub async fn set_music_section(
+        &self,
+        server_id: i64,
+        section_id: Option<String>,
+    ) -> color_eyre::Result<entities::plex_server::Model> {
+        let server = self.find_server(server_id).await?;
+        if let Some(section_id) = &section_id {
+            let sections = self.list_music_sections(server_id).await?;
+            if !sections.iter().any(|section| &section.key == section_id) {
+                return Err(color_eyre::eyre::eyre!(
+                    "Library section {} is not a music section on Plex server '{}'",
+                    section_id,
+                    server.name
+                ));
+            }
+        }
+        if server.music_section_id == section_id {
+            return Ok(server);
+        }
+
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to begin transaction: {}", e))?;
+        let mapped_track_ids: HashSet<i64> = entities::plex_track_mapping::Entity::find()
+            .select_only()
+            .column(entities::plex_track_mapping::Column::TrackId)
+            .filter(entities::plex_track_mapping::Column::PlexServerId.eq(server_id))
+            .into_tuple::<i64>()
+            .all(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch Plex track mappings: {}", e))?
+            .into_iter()
+            .collect();
+        entities::plex_track_mapping::Entity::delete_many()
+            .filter(entities::plex_track_mapping::Column::PlexServerId.eq(server_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to remove Plex track mappings: {}", e))?;
+        entities::plex_unmatched_track::Entity::delete_many()
+            .filter(entities::plex_unmatched_track::Column::PlexServerId.eq(server_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| {
+                color_eyre::eyre::eyre!("Failed to remove unmatched Plex tracks: {}", e)
+            })?;
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.music_section_id = Set(section_id);
+        server.library_updated_at = Set(None);
+        server.mappings_refreshed_at = Set(None);
+        server.history_imported_at = Set(None);
+        let server = server
+            .update(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to update plex server: {}", e))?;
+        txn.commit()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to commit transaction: {}", e))?;
+        update_play_history(&self.db, mapped_track_ids).await?;
+
+        Ok(server)
+    }
+
+    /// Where the local library folders are mounted on a server.
+    pub async fn path_mappings(
+        &self,
+        server_id: i64,
+    ) -> color_eyre::Result<Vec<entities::plex_path_mapping::Model>> {
+        self.find_server(server_id).await?;
+        entities::plex_path_mapping::Entity::find()
+            .filter(entities::plex_path_mapping::Column::PlexServerId.eq(server_id))
+            .order_by_asc(entities::plex_path_mapping::Column::LocalPrefix)
             .all(&self.db.conn)
             .await
-            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch plex servers: {}", e))?;
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch Plex path mappings: {}", e))
+    }
 
-        if servers.is_empty() {
-            return Ok(PlexTracksOutcome::NoServer);
+    /// Replace the path mappings of a server with (local prefix, Plex prefix)
+    /// pairs. The next track mapping refresh is a full one, so tracks are
+    /// matched again with the new paths.
+    pub async fn set_path_mappings(
+        &self,
+        server_id: i64,
+        mappings: Vec<(String, String)>,
+    ) -> color_eyre::Result<Vec<entities::plex_path_mapping::Model>> {
+        let server = self.find_server(server_id).await?;
+
+        let mut local_prefixes = BTreeSet::new();
+        let mut models = Vec::with_capacity(mappings.len());
+        for (local_prefix, plex_prefix) in mappings {
+            let local_prefix = normalize_prefix(&local_prefix);
+            let plex_prefix = normalize_prefix(&plex_prefix);
+            if local_prefix.is_empty() || plex_prefix.is_empty() {
+                return Err(color_eyre::eyre::eyre!(
+                    "Path mapping prefixes must not be empty"
+                ));
+            }
+            if !local_prefixes.insert(local_prefix.clone()) {
+                return Err(color_eyre::eyre::eyre!(
+                    "Local path {} is mapped more than once",
+                    local_prefix
+                ));
+            }
+            models.push(entities::plex_path_mapping::ActiveModel {
+                plex_server_id: Set(server_id),
+                local_prefix: Set(local_prefix),
+                plex_prefix: Set(plex_prefix),
+                ..Default::default()
+            });
         }
-        if servers.len() > 1 {
-            return Ok(PlexTracksOutcome::MultipleServers(servers.len()));
+
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to begin transaction: {}", e))?;
+        entities::plex_path_mapping::Entity::delete_many()
+            .filter(entities::plex_path_mapping::Column::PlexServerId.eq(server_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to remove Plex path mappings: {}", e))?;
+        if !models.is_empty() {
+            entities::plex_path_mapping::Entity::insert_many(models)
+                .exec(&txn)
+                .await
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to save Plex path mappings: {}", e))?;
         }
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.library_updated_at = Set(None);
+        server
+            .update(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to update plex server: {}", e))?;
+        txn.commit()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to commit transaction: {}", e))?;
 
-        let server = servers.into_iter().next().unwrap();
+        self.path_mappings(server_id).await
+    }
+
+    /// Fetch up to 50 tracks of a server's music section. Without a server ID
+    /// the only configured server is used.
+    pub async fn get_tracks(
+        &self,
+        server_id: Option<i64>,
+        section_id: Option<String>,
+    ) -> color_eyre::Result<PlexTracksOutcome> {
+        let server = match server_id {
+            Some(id) => self.find_server(id).await?,
+            None => {
+                let servers = entities::plex_server::Entity::find()
+                    .all(&self.db.conn)
+                    .await
+                    .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch plex servers: {}", e))?;
+
+                if servers.is_empty() {
+                    return Ok(PlexTracksOutcome::NoServer);
+                }
+                if servers.len() > 1 {
+                    return Ok(PlexTracksOutcome::MultipleServers(servers.len()));
+                }
+
+                servers.into_iter().next().unwrap()
+            }
+        };
 
         let access_token = match &server.access_token {
             Some(token) => token.clone(),
@@ -258,32 +479,17 @@ impl<C: PlexClient> PlexService<C> {
             }
         };
 
-        let sections = match self
-            .client
-            .get_library_sections(&server_url, &access_token)
+        let music_section_id = match self
+            .resolve_music_section(&server, &server_url, &access_token, section_id)
             .await
         {
-            Ok(s) => s,
-            Err(e) => {
-                return Ok(PlexTracksOutcome::Error(format!(
-                    "Failed to fetch library sections: {}",
-                    e
-                )));
-            }
-        };
-
-        let music_section_id = match self.client.find_music_section_id(&sections) {
-            Some(id) => id,
-            None => {
-                return Ok(PlexTracksOutcome::Error(
-                    "No music library section found on Plex server.".to_string(),
-                ));
-            }
+            Ok(id) => id,
+            Err(e) => return Ok(PlexTracksOutcome::Error(e.to_string())),
         };
 
         match self
             .client
-            .get_tracks_page(&server_url, &access_token, &music_section_id, 0, 50)
+            .get_tracks_page(&server_url, &access_token, &music_section_id, None, 0, 50)
             .await
         {
             Ok(container) => Ok(PlexTracksOutcome::Success(container)),
@@ -294,15 +500,34 @@ impl<C: PlexClient> PlexService<C> {
         }
     }
 
-    pub async fn refresh_music_library(&self, server_id: i64) -> color_eyre::Result<String> {
-        let (_server, server_url, access_token) = self.resolve_server_by_id(server_id).await?;
+    /// Start a scan of a server's music section, or only of `path`, a local
+    /// folder translated with the server's path mappings.
+    pub async fn refresh_music_library(
+        &self,
+        server_id: i64,
+        section_id: Option<String>,
+        path: Option<String>,
+    ) -> color_eyre::Result<String> {
+        let (server, server_url, access_token) = self.resolve_server(Some(server_id)).await?;
         let music_section_id = self
-            .resolve_music_section(&server_url, &access_token)
+            .resolve_music_section(&server, &server_url, &access_token, section_id)
             .await?;
 
-        self.client
-            .refresh_library_section(&server_url, &access_token, &music_section_id)
-            .await?;
+        match path {
+            Some(path) => {
+                let plex_path = PathMappings::load(&self.db, server.id)
+                    .await?
+                    .to_plex(&path);
+                self.client
+                    .refresh_library_path(&server_url, &access_token, &music_section_id, &plex_path)
+                    .await?;
+            }
+            None => {
+                self.client
+                    .refresh_library_section(&server_url, &access_token, &music_section_id)
+                    .await?;
+            }
+        }
 
         Ok(music_section_id)
     }
@@ -310,10 +535,11 @@ impl<C: PlexClient> PlexService<C> {
     pub async fn get_scan_status(
         &self,
         server_id: i64,
+        section_id: Option<String>,
     ) -> color_eyre::Result<Option<PlexActivity>> {
-        let (_server, server_url, access_token) = self.resolve_server_by_id(server_id).await?;
+        let (server, server_url, access_token) = self.resolve_server(Some(server_id)).await?;
         let music_section_id = self
-            .resolve_music_section(&server_url, &access_token)
+            .resolve_music_section(&server, &server_url, &access_token, section_id)
             .await?;
 
         self.client
@@ -323,8 +549,11 @@ impl<C: PlexClient> PlexService<C> {
 
     // ---- Playlists ----
 
-    pub async fn get_playlists(&self) -> color_eyre::Result<Vec<PlexPlaylist>> {
-        let (_server, server_url, access_token) = self.resolve_server().await?;
+    pub async fn get_playlists(
+        &self,
+        server_id: Option<i64>,
+    ) -> color_eyre::Result<Vec<PlexPlaylist>> {
+        let (_server, server_url, access_token) = self.resolve_server(server_id).await?;
         let playlists = self
             .client
             .get_playlists(&server_url, &access_token)
@@ -332,22 +561,540 @@ impl<C: PlexClient> PlexService<C> {
         Ok(playlists)
     }
 
+    /// Plex servers a playlist is synced to by default.
+    pub async fn playlist_servers(
+        &self,
+        playlist_id: i64,
+    ) -> color_eyre::Result<Vec<entities::plex_server::Model>> {
+        entities::plex_server::Entity::find()
+            .filter(
+                entities::plex_server::Column::Id.in_subquery(
+                    entities::playlist_plex_server::Entity::find()
+                        .select_only()
+                        .column(entities::playlist_plex_server::Column::PlexServerId)
+                        .filter(entities::playlist_plex_server::Column::PlaylistId.eq(playlist_id))
+                        .into_query(),
+                ),
+            )
+            .order_by_asc(entities::plex_server::Column::Id)
+            .all(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch playlist Plex servers: {}", e))
+    }
+
+    /// Replace the Plex servers a playlist is synced to by default.
+    pub async fn set_playlist_servers(
+        &self,
+        playlist_id: i64,
+        server_ids: Vec<i64>,
+    ) -> color_eyre::Result<Vec<entities::plex_server::Model>> {
+        entities::playlist::Entity::find_by_id(playlist_id)
+            .one(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to find playlist: {}", e))?
+            .ok_or_else(|| color_eyre::eyre::eyre!("Playlist with ID {} not found", playlist_id))?;
+        let server_ids: BTreeSet<i64> = server_ids.into_iter().collect();
+        for server_id in &server_ids {
+            self.find_server(*server_id).await?;
+        }
+
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to begin transaction: {}", e))?;
+        entities::playlist_plex_server::Entity::delete_many()
+            .filter(entities::playlist_plex_server::Column::PlaylistId.eq(playlist_id))
+            .exec(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to clear playlist Plex servers: {}", e))?;
+        if !server_ids.is_empty() {
+            entities::playlist_plex_server::Entity::insert_many(server_ids.into_iter().map(
+                |server_id| entities::playlist_plex_server::ActiveModel {
+                    playlist_id: Set(playlist_id),
+                    plex_server_id: Set(server_id),
+                },
+            ))
+            .exec(&txn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to save playlist Plex servers: {}", e))?;
+        }
+        txn.commit()
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to commit transaction: {}", e))?;
+
+        self.playlist_servers(playlist_id).await
+    }
+
+    /// Servers to sync a playlist to: the given ones, else the playlist's
+    /// servers, else the only configured server.
+    async fn sync_targets(
+        &self,
+        playlist_id: i64,
+        server_ids: Option<Vec<i64>>,
+    ) -> color_eyre::Result<Vec<entities::plex_server::Model>> {
+        if let Some(server_ids) = server_ids.filter(|ids| !ids.is_empty()) {
+            let mut servers = Vec::with_capacity(server_ids.len());
+            for server_id in server_ids.into_iter().collect::<BTreeSet<_>>() {
+                servers.push(self.find_server(server_id).await?);
+            }
+            return Ok(servers);
+        }
+
+        let servers = self.playlist_servers(playlist_id).await?;
+        if !servers.is_empty() {
+            return Ok(servers);
+        }
+        self.only_server().await.map(|server| vec![server])
+    }
+
+    /// Sync a playlist to each of its target servers. A failure on one server
+    /// doesn't stop the sync to the others.
     pub async fn sync_playlist(
         &self,
         playlist_id: i64,
-    ) -> color_eyre::Result<crate::plex_rs::sync_playlist::SyncPlaylistResult> {
+        server_ids: Option<Vec<i64>>,
+        config: &MatcherConfig,
+        options: &PlexPlaylistSyncConfig,
+    ) -> color_eyre::Result<Vec<ServerSyncOutcome>> {
+        let servers = self.sync_targets(playlist_id, server_ids).await?;
+
         // Delegate to existing function (it mixes DB + API calls; decompose later)
-        let client = reqwest::Client::new();
-        crate::plex_rs::sync_playlist::sync_playlist_to_plex(&self.db, &client, playlist_id).await
+        let mut outcomes = Vec::with_capacity(servers.len());
+        for server in servers {
+            let result = sync_playlist_to_plex(
+                &self.db,
+                &self.client,
+                config,
+                options,
+                playlist_id,
+                &server,
+            )
+            .await;
+            if let Err(e) = &result {
+                tracing::error!(
+                    "Failed to sync playlist {} to Plex server '{}': {:?}",
+                    playlist_id,
+                    server.name,
+                    e
+                );
+            }
+            outcomes.push(ServerSyncOutcome { server, result });
+        }
+        Ok(outcomes)
+    }
+
+    /// Keep a playlist synced to its Plex servers after imports, or stop.
+    pub async fn set_keep_synced(
+        &self,
+        playlist_id: i64,
+        keep_synced: bool,
+    ) -> color_eyre::Result<entities::playlist::Model> {
+        let playlist = entities::playlist::Entity::find_by_id(playlist_id)
+            .one(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch playlist: {}", e))?
+            .ok_or_else(|| color_eyre::eyre::eyre!("Playlist with id {} not found", playlist_id))?;
+
+        let mut playlist: entities::playlist::ActiveModel = playlist.into();
+        playlist.keep_synced_to_plex = Set(keep_synced);
+        playlist
+            .update(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to update playlist: {}", e))
+    }
+
+    // ---- After imports ----
+
+    /// Let every server scan newly imported tracks, then re-sync the playlists
+    /// kept synced to it. Only `folders`, the local album folders of the
+    /// imports, are scanned, or the whole music section when they aren't
+    /// known. A failure on one server doesn't stop the others.
+    ///
+    /// The playlists are re-synced once the scan is over, or when it is still
+    /// running after `settings.scan_timeout_secs`. In that case tracks Plex
+    /// hasn't scanned yet are reported missing and only added by a later sync.
+    pub async fn sync_after_import(
+        &self,
+        folders: Option<&BTreeSet<String>>,
+        settings: &PlexAutoSyncConfig,
+        config: &MatcherConfig,
+        options: &PlexPlaylistSyncConfig,
+    ) -> color_eyre::Result<()> {
+        let servers = entities::plex_server::Entity::find()
+            .filter(entities::plex_server::Column::AccessToken.is_not_null())
+            .all(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch plex servers: {}", e))?;
+        let playlists = entities::playlist::Entity::find()
+            .filter(entities::playlist::Column::KeepSyncedToPlex.eq(true))
+            .all(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch playlists: {}", e))?;
+
+        let mut targets = Vec::with_capacity(playlists.len());
+        for playlist in playlists {
+            match self.sync_targets(playlist.id, None).await {
+                Ok(servers) => targets.push((
+                    playlist,
+                    servers
+                        .into_iter()
+                        .map(|server| server.id)
+                        .collect::<Vec<_>>(),
+                )),
+                Err(e) => {
+                    tracing::warn!("Not re-syncing playlist '{}' to Plex: {}", playlist.name, e)
+                }
+            }
+        }
+
+        for server in servers {
+            if let Err(e) = self.scan_imports(&server, folders, settings).await {
+                tracing::error!(
+                    "Failed to scan imports on Plex server '{}': {:?}",
+                    server.name,
+                    e
+                );
+                continue;
+            }
+
+            for (playlist, _) in targets
+                .iter()
+                .filter(|(_, server_ids)| server_ids.contains(&server.id))
+            {
+                match sync_playlist_to_plex(
+                    &self.db,
+                    &self.client,
+                    config,
+                    options,
+                    playlist.id,
+                    &server,
+                )
+                .await
+                {
+                    Ok(result) => tracing::info!(
+                        "Re-synced playlist '{}' to Plex server '{}': {} added, {} removed, {} missing",
+                        playlist.name,
+                        server.name,
+                        result.tracks_added,
+                        result.tracks_removed,
+                        result.missing_tracks.len()
+                    ),
+                    Err(e) => tracing::error!(
+                        "Failed to re-sync playlist '{}' to Plex server '{}': {:?}",
+                        playlist.name,
+                        server.name,
+                        e
+                    ),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Start a scan of the imported folders on a server and poll the server's
+    /// activities until the scan was reported and is over. Plex may finish a
+    /// short scan between two polls, so the scan also counts as over once
+    /// every imported folder has a track updated since the scan started.
+    ///
+    /// Returning doesn't mean the scan is done: waiting stops after
+    /// `settings.scan_timeout_secs`. Callers must not assume every imported
+    /// track is in the library yet.
+    async fn scan_imports(
+        &self,
+        server: &entities::plex_server::Model,
+        folders: Option<&BTreeSet<String>>,
+        settings: &PlexAutoSyncConfig,
+    ) -> color_eyre::Result<()> {
+        let (server, server_url, access_token) = self.resolve_server(Some(server.id)).await?;
+        let section_id = self
+            .resolve_music_section(&server, &server_url, &access_token, None)
+            .await?;
+
+        let path_mappings = PathMappings::load(&self.db, server.id).await?;
+        let scan_started_at = chrono::Utc::now().timestamp();
+        match folders {
+            Some(folders) if folders.len() <= MAX_FOLDER_SCANS => {
+                for folder in folders {
+                    self.client
+                        .refresh_library_path(
+                            &server_url,
+                            &access_token,
+                            &section_id,
+                            &path_mappings.to_plex(folder),
+                        )
+                        .await?;
+                }
+            }
+            _ => {
+                self.client
+                    .refresh_library_section(&server_url, &access_token, &section_id)
+                    .await?;
+            }
+        }
+
+        // Plex may only report the scan a moment after it was requested
+        let poll_interval = Duration::from_secs(settings.scan_poll_secs);
+        let deadline = Instant::now() + Duration::from_secs(settings.scan_timeout_secs);
+        let mut scan_seen = false;
+        loop {
+            tokio::time::sleep(poll_interval).await;
+            let scan = self
+                .client
+                .get_library_scan_status(&server_url, &access_token, &section_id)
+                .await?;
+            if scan.is_some() {
+                scan_seen = true;
+            } else if scan_seen {
+                return Ok(());
+            } else if let Some(folders) = folders {
+                let plex_folders: Vec<String> = folders
+                    .iter()
+                    .map(|folder| path_mappings.to_plex(folder))
+                    .collect();
+                if self
+                    .folders_scanned(
+                        &server_url,
+                        &access_token,
+                        &section_id,
+                        &plex_folders,
+                        scan_started_at,
+                    )
+                    .await?
+                {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                tracing::warn!(
+                    "Plex server '{}' is still scanning after {}s, continuing anyway",
+                    server.name,
+                    settings.scan_timeout_secs
+                );
+                return Ok(());
+            }
+        }
+    }
+
+    /// Whether every folder, as seen by Plex, has a track updated since
+    /// `since`.
+    async fn folders_scanned(
+        &self,
+        server_url: &Url,
+        access_token: &str,
+        section_id: &str,
+        plex_folders: &[String],
+        since: i64,
+    ) -> color_eyre::Result<bool> {
+        let tracks = get_all_tracks_since(
+            &self.client,
+            server_url,
+            access_token,
+            section_id,
+            Some(TracksSince::Updated(since)),
+            1000,
+        )
+        .await?;
+        Ok(plex_folders.iter().all(|folder| {
+            tracks.iter().any(|track| {
+                track
+                    .file_path()
+                    .is_ok_and(|path| Path::new(path).parent() == Some(Path::new(folder)))
+            })
+        }))
+    }
+
+    /// Refresh the local track ↔ Plex track mappings of a server, only with
+    /// Plex tracks updated since the last refresh unless `full` is set.
+    pub async fn refresh_track_mappings(
+        &self,
+        server_id: i64,
+        full: bool,
+        config: &MatcherConfig,
+    ) -> color_eyre::Result<TrackMappingRefreshResult> {
+        let server = self.find_server(server_id).await?;
+        refresh_track_mappings(&self.db, &self.client, config, &server, full).await
+    }
+
+    // ---- Imports ----
+
+    /// Import music playlists of a server (the only one when no ID is given)
+    /// into local playlists: the given ones, or all of them.
+    pub async fn import_playlists(
+        &self,
+        server_id: Option<i64>,
+        rating_keys: Option<Vec<String>>,
+        config: &MatcherConfig,
+    ) -> color_eyre::Result<Vec<ImportedPlexPlaylist>> {
+        let (server, _server_url, _access_token) = self.resolve_server(server_id).await?;
+        import_plex_playlists(&self.db, &self.client, config, &server, rating_keys).await
+    }
+
+    /// Import play counts, last played times and ratings of a server (the
+    /// only one when no ID is given), only of tracks played since the last
+    /// import unless `full` is set.
+    pub async fn import_play_history(
+        &self,
+        server_id: Option<i64>,
+        full: bool,
+        config: &MatcherConfig,
+    ) -> color_eyre::Result<TrackMappingRefreshResult> {
+        let (server, _server_url, _access_token) = self.resolve_server(server_id).await?;
+        import_play_history(&self.db, &self.client, config, &server, full).await
+    }
+
+    // ---- Webhooks ----
+
+    /// Record a Plex webhook event of a music library: plays and ratings of
+    /// mapped tracks by the webhook's account, while new library items and
+    /// unmapped tracks starting to play get mapped. Other events are ignored.
+    ///
+    /// Mapping happens in a background task, see [`schedule_mapping_refresh`].
+    /// A play only schedules it for a track no refresh has tried to match yet.
+    ///
+    /// The event is for the server of the same name, else the only server.
+    pub async fn handle_webhook(
+        &self,
+        payload: PlexWebhookPayload,
+        tasks: &Arc<TaskManager>,
+        config: &MatcherConfig,
+    ) -> color_eyre::Result<()> {
+        let Some(event) = PlexWebhookEvent::from_name(&payload.event) else {
+            return Ok(());
+        };
+        let Some(metadata) = payload.metadata.filter(PlexWebhookMetadata::is_music) else {
+            return Ok(());
+        };
+        let server_name = payload.server.map(|server| server.title);
+        let Some(server) = self.webhook_server(server_name.as_deref()).await? else {
+            tracing::warn!(
+                "Ignoring Plex webhook '{}' of unknown server {:?}",
+                payload.event,
+                server_name
+            );
+            return Ok(());
+        };
+        let is_own_track = payload.user && metadata.is_track();
+
+        match event {
+            // Plex only counts a play once scrobbled, but a track added since
+            // the last mapping refresh has to be mapped by then. Tracks that
+            // were tried before don't match any better on every play.
+            PlexWebhookEvent::Play if is_own_track => {
+                if !is_known_track(&self.db, server.id, &metadata.rating_key).await? {
+                    schedule_mapping_refresh(&self.db, tasks, &server, config).await?;
+                }
+            }
+            PlexWebhookEvent::Scrobble if is_own_track => {
+                let played_at = metadata
+                    .last_viewed_at
+                    .unwrap_or_else(|| chrono::Utc::now().timestamp());
+                record_scrobble(&self.db, server.id, &metadata.rating_key, played_at).await?;
+            }
+            PlexWebhookEvent::Rate if is_own_track => {
+                record_rating(
+                    &self.db,
+                    server.id,
+                    &metadata.rating_key,
+                    metadata.user_rating,
+                )
+                .await?;
+            }
+            PlexWebhookEvent::LibraryNew => {
+                schedule_mapping_refresh(&self.db, tasks, &server, config).await?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// The server a webhook event came from: the one named like the event's
+    /// server, else the only server.
+    async fn webhook_server(
+        &self,
+        name: Option<&str>,
+    ) -> color_eyre::Result<Option<entities::plex_server::Model>> {
+        let mut servers = entities::plex_server::Entity::find()
+            .all(&self.db.conn)
+            .await
+            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch plex servers: {}", e))?;
+
+        if let Some(index) =
+            name.and_then(|name| servers.iter().position(|server| server.name == name))
+        {
+            return Ok(Some(servers.swap_remove(index)));
+        }
+        Ok(if servers.len() == 1 {
+            servers.pop()
+        } else {
+            None
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::entities::background_task::{TaskKind, TaskStatus};
     use crate::plex_rs::auth::PlexPinResponse;
     use crate::ports::plex::MockPlexClient;
-    use crate::test_utils::test_db;
+    use crate::services::playlist::PlaylistService;
+    use crate::services::plex::client::PlexHttpAdapter;
+    use crate::services::plex::mapping_refresh::PlexMappingRefreshTaskHandler;
+    use crate::test_utils::fake_plex::FakePlex;
+    use crate::test_utils::{TestTrack, insert_track, test_db, wait_for_status};
+
+    fn mapping_refresh_tasks(db: &Arc<Database>) -> Arc<TaskManager> {
+        let mut tasks = TaskManager::new(db.clone());
+        tasks.register(PlexMappingRefreshTaskHandler);
+        Arc::new(tasks)
+    }
+
+    async fn mapping_refreshes(db: &Database) -> Vec<entities::background_task::Model> {
+        entities::background_task::Entity::find()
+            .filter(entities::background_task::Column::Kind.eq(TaskKind::PlexMappingRefresh))
+            .order_by_asc(entities::background_task::Column::Id)
+            .all(&db.conn)
+            .await
+            .unwrap()
+    }
+
+    async fn create_authenticated_server<C: PlexClient>(
+        service: &PlexService<C>,
+        name: &str,
+        server_url: &str,
+    ) -> entities::plex_server::Model {
+        let server = service
+            .create_server(name.into(), server_url.into())
+            .await
+            .unwrap();
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.access_token = Set(Some(format!("{}-token", name)));
+        server.update(&service.db.conn).await.unwrap()
+    }
+
+    fn sections() -> Vec<PlexLibrarySection> {
+        vec![
+            PlexLibrarySection {
+                key: "1".into(),
+                title: "Music".into(),
+                section_type: "artist".into(),
+            },
+            PlexLibrarySection {
+                key: "2".into(),
+                title: "Movies".into(),
+                section_type: "movie".into(),
+            },
+            PlexLibrarySection {
+                key: "3".into(),
+                title: "Family Music".into(),
+                section_type: "artist".into(),
+            },
+        ]
+    }
 
     #[tokio::test]
     async fn test_list_servers_empty() {
@@ -451,7 +1198,666 @@ mod tests {
         let client = MockPlexClient::new();
         let service = PlexService::new(db, client);
 
-        let result = service.get_tracks().await.unwrap();
+        let result = service.get_tracks(None, None).await.unwrap();
         assert!(matches!(result, PlexTracksOutcome::NoServer));
     }
+
+    #[tokio::test]
+    async fn test_get_tracks_multiple_servers() {
+        let db = test_db().await;
+        let mut client = MockPlexClient::new();
+        client
+            .expect_get_tracks_page()
+            .withf(|server_url, token, section_id, since, _, _| {
+                server_url.as_str() == "http://family:32400/"
+                    && token == "Family-token"
+                    && section_id == "3"
+                    && since.is_none()
+            })
+            .returning(|_, _, _, _, _, _| {
+                Ok(PlexMediaContainer {
+                    size: Some(0),
+                    total_size: Some(0),
+                    offset: Some(0),
+                    metadata: Vec::new(),
+                })
+            });
+        let service = PlexService::new(db, client);
+        create_authenticated_server(&service, "Home", "http://home:32400/").await;
+        let family = create_authenticated_server(&service, "Family", "http://family:32400/").await;
+
+        let result = service.get_tracks(None, None).await.unwrap();
+        assert!(matches!(result, PlexTracksOutcome::MultipleServers(2)));
+
+        let result = service
+            .get_tracks(Some(family.id), Some("3".into()))
+            .await
+            .unwrap();
+        assert!(matches!(result, PlexTracksOutcome::Success(_)));
+    }
+
+    #[tokio::test]
+    async fn test_set_music_section() {
+        let db = test_db().await;
+        let mut client = MockPlexClient::new();
+        client
+            .expect_get_library_sections()
+            .returning(|_, _| Ok(sections()));
+        client
+            .expect_refresh_library_section()
+            .withf(|_, _, section_id| section_id == "3")
+            .returning(|_, _, _| Ok(()));
+        let service = PlexService::new(db.clone(), client);
+        let server = create_authenticated_server(&service, "Home", "http://home:32400/").await;
+
+        let sections = service.list_music_sections(server.id).await.unwrap();
+        assert_eq!(
+            sections.iter().map(|s| s.key.as_str()).collect::<Vec<_>>(),
+            vec!["1", "3"]
+        );
+
+        let err = service
+            .set_music_section(server.id, Some("2".into()))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not a music section"));
+
+        // Mappings of the previous section are dropped with its refresh and
+        // import times, and their plays are no longer counted
+        let mut model: entities::plex_server::ActiveModel = server.clone().into();
+        model.library_updated_at = Set(Some(1_700_000_000));
+        model.mappings_refreshed_at = Set(Some(1_700_000_000));
+        model.history_imported_at = Set(Some(1_700_000_000));
+        model.update(&db.conn).await.unwrap();
+        let track = insert_track(
+            &db,
+            TestTrack {
+                file_path: Some("/music/Artist/Album/01 Track.flac"),
+                ..TestTrack::new("Track")
+            },
+        )
+        .await;
+        entities::plex_track_mapping::ActiveModel {
+            plex_server_id: Set(server.id),
+            track_id: Set(track.id),
+            rating_key: Set("101".into()),
+            match_method: Set(entities::plex_track_mapping::PlexTrackMatchMethod::Path),
+            view_count: Set(Some(3)),
+            ..entities::plex_track_mapping::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        update_play_history(&db, HashSet::from([track.id]))
+            .await
+            .unwrap();
+        entities::plex_unmatched_track::ActiveModel {
+            plex_server_id: Set(server.id),
+            rating_key: Set("102".into()),
+            plex_track: Set(serde_json::json!({ "ratingKey": "102", "title": "Other" })),
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let updated = service
+            .set_music_section(server.id, Some("3".into()))
+            .await
+            .unwrap();
+        assert_eq!(updated.music_section_id.as_deref(), Some("3"));
+        assert_eq!(updated.library_updated_at, None);
+        assert_eq!(updated.mappings_refreshed_at, None);
+        assert_eq!(updated.history_imported_at, None);
+        let mappings = entities::plex_track_mapping::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert!(mappings.is_empty());
+        let unmatched = entities::plex_unmatched_track::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert!(unmatched.is_empty());
+        let track = entities::track::Entity::find_by_id(track.id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(track.play_count, None);
+
+        // The default section is used when none is given
+        let section_id = service
+            .refresh_music_library(server.id, None, None)
+            .await
+            .unwrap();
+        assert_eq!(section_id, "3");
+    }
+
+    #[tokio::test]
+    async fn test_partial_refresh_uses_path_mappings() {
+        let db = test_db().await;
+        let mut client = MockPlexClient::new();
+        client
+            .expect_get_library_sections()
+            .returning(|_, _| Ok(sections()));
+        client
+            .expect_refresh_library_path()
+            .withf(|_, _, section_id, path| {
+                section_id == "1" && path == "/data/media/music/Queen/A Night at the Opera"
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        let service = PlexService::new(db.clone(), client);
+        let server = create_authenticated_server(&service, "Home", "http://home:32400/").await;
+
+        let err = service
+            .set_path_mappings(
+                server.id,
+                vec![
+                    ("/music".into(), "/data/media/music".into()),
+                    (" /music".into(), "/mnt/music".into()),
+                ],
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mapped more than once"));
+
+        let mappings = service
+            .set_path_mappings(
+                server.id,
+                vec![("/music/".into(), "/data/media/music/".into())],
+            )
+            .await
+            .unwrap();
+        assert_eq!(mappings.len(), 1);
+        assert_eq!(service.path_mappings(server.id).await.unwrap(), mappings);
+
+        service
+            .refresh_music_library(
+                server.id,
+                None,
+                Some("/music/Queen/A Night at the Opera".into()),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_after_import_scans_imported_folders() {
+        let db = test_db().await;
+        let mut client = MockPlexClient::new();
+        client
+            .expect_get_library_sections()
+            .returning(|_, _| Ok(sections()));
+        client
+            .expect_refresh_library_path()
+            .withf(|_, _, section_id, path| {
+                section_id == "1" && path == "/data/media/music/Queen/Jazz"
+            })
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        client
+            .expect_refresh_library_section()
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+        // Still scanning on the first check of each sync
+        let checks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let scan_checks = checks.clone();
+        client
+            .expect_get_library_scan_status()
+            .times(4)
+            .returning(move |_, _, _| {
+                let checks = &scan_checks;
+                let check = checks.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok((check % 2 == 0).then(|| PlexActivity {
+                    uuid: None,
+                    activity_type: "library.update.section".into(),
+                    title: "Scanning Music".into(),
+                    subtitle: None,
+                    progress: Some(50.0),
+                    context: None,
+                }))
+            });
+        client.expect_find_music_section_id().returning(|sections| {
+            crate::plex_rs::all_tracks::find_music_section_id(sections).map(String::from)
+        });
+        // The kept-synced playlist is re-synced after each scan
+        client
+            .expect_get_tracks_page()
+            .returning(|_, _, _, _, _, _| {
+                Ok(PlexMediaContainer {
+                    size: Some(0),
+                    total_size: Some(0),
+                    offset: Some(0),
+                    metadata: Vec::new(),
+                })
+            });
+        client
+            .expect_get_machine_identifier()
+            .times(2)
+            .returning(|_, _| Ok("machine".into()));
+        client
+            .expect_get_playlists()
+            .times(2)
+            .returning(|_, _| Ok(Vec::new()));
+        client
+            .expect_create_playlist()
+            .withf(|_, _, _, title, first_rating_key| {
+                title == "Jazz Mix" && first_rating_key.is_none()
+            })
+            .times(2)
+            .returning(move |_, _, _, title, _| {
+                // Only once the scan finished
+                assert_eq!(checks.load(std::sync::atomic::Ordering::SeqCst) % 2, 0);
+                Ok(PlexPlaylist {
+                    rating_key: "500".into(),
+                    title: title.into(),
+                    playlist_type: "audio".into(),
+                    smart: Some(false),
+                    leaf_count: Some(0),
+                    duration: None,
+                    summary: None,
+                    key: None,
+                    composite: None,
+                })
+            });
+        client
+            .expect_get_playlist_items()
+            .times(2)
+            .returning(|_, _, _| Ok(Vec::new()));
+        let service = PlexService::new(db.clone(), client);
+        let server = create_authenticated_server(&service, "Home", "http://home:32400/").await;
+        let playlist = PlaylistService::new(db.clone())
+            .create("Jazz Mix".into(), None)
+            .await
+            .unwrap();
+        service.set_keep_synced(playlist.id, true).await.unwrap();
+        service
+            .set_playlist_servers(playlist.id, vec![server.id])
+            .await
+            .unwrap();
+        // Not authenticated, so not scanned
+        service
+            .create_server("Other".into(), "http://other:32400/".into())
+            .await
+            .unwrap();
+        service
+            .set_path_mappings(
+                server.id,
+                vec![("/music".into(), "/data/media/music".into())],
+            )
+            .await
+            .unwrap();
+
+        let settings = PlexAutoSyncConfig {
+            scan_poll_secs: 0,
+            ..PlexAutoSyncConfig::default()
+        };
+        let folders = BTreeSet::from(["/music/Queen/Jazz".to_string()]);
+        service
+            .sync_after_import(
+                Some(&folders),
+                &settings,
+                &MatcherConfig::default(),
+                &PlexPlaylistSyncConfig::default(),
+            )
+            .await
+            .unwrap();
+        // Missed imports rescan the whole section
+        service
+            .sync_after_import(
+                None,
+                &settings,
+                &MatcherConfig::default(),
+                &PlexPlaylistSyncConfig::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scan_over_before_first_poll_waits_for_imported_tracks() {
+        let db = test_db().await;
+        let mut client = MockPlexClient::new();
+        client
+            .expect_get_library_sections()
+            .returning(|_, _| Ok(sections()));
+        client.expect_find_music_section_id().returning(|sections| {
+            crate::plex_rs::all_tracks::find_music_section_id(sections).map(String::from)
+        });
+        client
+            .expect_refresh_library_path()
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        // Plex never reports the scan
+        client
+            .expect_get_library_scan_status()
+            .times(2)
+            .returning(|_, _, _| Ok(None));
+        // The imported track only shows up on the second poll
+        let polls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        client
+            .expect_get_tracks_page()
+            .withf(|_, _, _, since, _, _| matches!(since, Some(TracksSince::Updated(_))))
+            .times(2)
+            .returning(move |_, _, _, _, _, _| {
+                let poll = polls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                let metadata = if poll == 0 {
+                    Vec::new()
+                } else {
+                    vec![
+                        serde_json::from_value(serde_json::json!({
+                            "ratingKey": "301",
+                            "title": "Mustapha",
+                            "Media": [{ "Part": [{ "file": "/music/Queen/Jazz/01 Mustapha.flac" }] }],
+                        }))
+                        .unwrap(),
+                    ]
+                };
+                Ok(PlexMediaContainer {
+                    size: Some(metadata.len() as u32),
+                    total_size: Some(metadata.len() as u32),
+                    offset: Some(0),
+                    metadata,
+                })
+            });
+        let service = PlexService::new(db.clone(), client);
+        create_authenticated_server(&service, "Home", "http://home:32400/").await;
+
+        let settings = PlexAutoSyncConfig {
+            scan_poll_secs: 0,
+            ..PlexAutoSyncConfig::default()
+        };
+        let folders = BTreeSet::from(["/music/Queen/Jazz".to_string()]);
+        service
+            .sync_after_import(
+                Some(&folders),
+                &settings,
+                &MatcherConfig::default(),
+                &PlexPlaylistSyncConfig::default(),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_after_import_with_fake_plex() {
+        let fake = FakePlex::start().await;
+        let db = test_db().await;
+        let service = PlexService::new(db.clone(), PlexHttpAdapter::new());
+        let server = service
+            .create_server("Fake".into(), fake.url().to_string())
+            .await
+            .unwrap();
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.access_token = Set(Some(fake.token()));
+        server.update(&db.conn).await.unwrap();
+
+        let track = insert_track(
+            &db,
+            TestTrack {
+                album: Some("Jazz"),
+                file_path: Some("/music/Queen/Jazz/01 Mustapha.flac"),
+                ..TestTrack::new("Mustapha")
+            },
+        )
+        .await;
+        let playlists = PlaylistService::new(db.clone());
+        let playlist = playlists.create("Jazz Mix".into(), None).await.unwrap();
+        playlists.add_track(playlist.id, track.id).await.unwrap();
+        service.set_keep_synced(playlist.id, true).await.unwrap();
+
+        let settings = PlexAutoSyncConfig {
+            scan_poll_secs: 0,
+            ..PlexAutoSyncConfig::default()
+        };
+        let folders = BTreeSet::from(["/music/Queen/Jazz".to_string()]);
+        service
+            .sync_after_import(
+                Some(&folders),
+                &settings,
+                &MatcherConfig::default(),
+                &PlexPlaylistSyncConfig::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            fake.refreshes(),
+            vec![("1".to_string(), Some("/music/Queen/Jazz".to_string()))]
+        );
+        // The playlist is only re-synced once the scan finished
+        assert!(!fake.is_scanning("1"));
+        assert_eq!(
+            fake.playlist_tracks("Jazz Mix"),
+            Some(vec!["201".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_webhook_maps_new_tracks_and_records_plays_and_ratings() {
+        let fake = FakePlex::start().await;
+        let db = test_db().await;
+        let service = PlexService::new(db.clone(), PlexHttpAdapter::new());
+        let server = service
+            .create_server("Fake".into(), fake.url().to_string())
+            .await
+            .unwrap();
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.access_token = Set(Some(fake.token()));
+        server.update(&db.conn).await.unwrap();
+
+        let track = insert_track(
+            &db,
+            TestTrack {
+                album: Some("Jazz"),
+                file_path: Some("/music/Queen/Jazz/01 Mustapha.flac"),
+                ..TestTrack::new("Mustapha")
+            },
+        )
+        .await;
+
+        let webhook = |event: &str, user: bool, metadata: serde_json::Value| {
+            serde_json::from_value::<PlexWebhookPayload>(serde_json::json!({
+                "event": event,
+                "user": user,
+                "Server": { "title": "Fake" },
+                "Metadata": metadata,
+            }))
+            .unwrap()
+        };
+        let track_metadata = |user_rating: Option<f64>| {
+            serde_json::json!({
+                "ratingKey": "201",
+                "type": "track",
+                "librarySectionType": "artist",
+                "lastViewedAt": 1_700_000_900,
+                "userRating": user_rating,
+            })
+        };
+        let config = MatcherConfig::default();
+        let tasks = mapping_refresh_tasks(&db);
+
+        service
+            .handle_webhook(
+                webhook(
+                    "library.new",
+                    true,
+                    serde_json::json!({
+                        "ratingKey": "200",
+                        "type": "album",
+                        "librarySectionType": "artist",
+                    }),
+                ),
+                &tasks,
+                &config,
+            )
+            .await
+            .unwrap();
+        // Mapped in the background
+        let refreshes = mapping_refreshes(&db).await;
+        assert_eq!(refreshes.len(), 1);
+        wait_for_status(&db, refreshes[0].id, TaskStatus::Completed).await;
+        service
+            .handle_webhook(
+                webhook("media.scrobble", true, track_metadata(None)),
+                &tasks,
+                &config,
+            )
+            .await
+            .unwrap();
+        // Plays of other users of the server aren't the account's history
+        service
+            .handle_webhook(
+                webhook("media.scrobble", false, track_metadata(None)),
+                &tasks,
+                &config,
+            )
+            .await
+            .unwrap();
+        service
+            .handle_webhook(
+                webhook("media.rate", true, track_metadata(Some(6.0))),
+                &tasks,
+                &config,
+            )
+            .await
+            .unwrap();
+
+        let track = entities::track::Entity::find_by_id(track.id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(track.play_count, Some(1));
+        assert_eq!(track.last_played_at, Some(1_700_000_900));
+        assert_eq!(track.rating, Some(6.0));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_play_only_refreshes_mappings_for_unknown_tracks() {
+        let fake = FakePlex::start().await;
+        let db = test_db().await;
+        let service = PlexService::new(db.clone(), PlexHttpAdapter::new());
+        let server = service
+            .create_server("Fake".into(), fake.url().to_string())
+            .await
+            .unwrap();
+        let mut server: entities::plex_server::ActiveModel = server.into();
+        server.access_token = Set(Some(fake.token()));
+        let server = server.update(&db.conn).await.unwrap();
+        let tasks = mapping_refresh_tasks(&db);
+
+        let play = |rating_key: &str| {
+            serde_json::from_value::<PlexWebhookPayload>(serde_json::json!({
+                "event": "media.play",
+                "user": true,
+                "Server": { "title": "Fake" },
+                "Metadata": {
+                    "ratingKey": rating_key,
+                    "type": "track",
+                    "librarySectionType": "artist",
+                },
+            }))
+            .unwrap()
+        };
+        let config = MatcherConfig::default();
+
+        service
+            .handle_webhook(play("101"), &tasks, &config)
+            .await
+            .unwrap();
+        let refreshes = mapping_refreshes(&db).await;
+        assert_eq!(refreshes.len(), 1);
+        wait_for_status(&db, refreshes[0].id, TaskStatus::Completed).await;
+        let unmatched =
+            entities::plex_unmatched_track::Entity::find_by_id((server.id, "101".to_string()))
+                .one(&db.conn)
+                .await
+                .unwrap();
+        assert!(unmatched.is_some());
+
+        // Tried before, without a match
+        service
+            .handle_webhook(play("101"), &tasks, &config)
+            .await
+            .unwrap();
+        assert_eq!(mapping_refreshes(&db).await.len(), 1);
+
+        // Unknown, but the mappings were just refreshed: waits for the
+        // interval, and later events don't queue another refresh
+        service
+            .handle_webhook(play("901"), &tasks, &config)
+            .await
+            .unwrap();
+        service
+            .handle_webhook(play("902"), &tasks, &config)
+            .await
+            .unwrap();
+        let refreshes = mapping_refreshes(&db).await;
+        assert_eq!(refreshes.len(), 2);
+        assert!(!refreshes[1].status.is_finished());
+        assert!(refreshes[1].checkpoint.is_none());
+
+        assert!(tasks.cancel(refreshes[1].id).await.unwrap());
+        wait_for_status(&db, refreshes[1].id, TaskStatus::Cancelled).await;
+    }
+
+    #[tokio::test]
+    async fn test_playlist_sync_targets() {
+        let db = test_db().await;
+        let client = MockPlexClient::new();
+        let service = PlexService::new(db.clone(), client);
+        let home = create_authenticated_server(&service, "Home", "http://home:32400/").await;
+        let family = create_authenticated_server(&service, "Family", "http://family:32400/").await;
+        let playlist = entities::playlist::ActiveModel {
+            name: Set("Road Trip".into()),
+            ..entities::playlist::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let err = service.sync_targets(playlist.id, None).await.unwrap_err();
+        assert!(err.to_string().contains("Multiple Plex servers found (2)"));
+
+        let servers = service
+            .set_playlist_servers(playlist.id, vec![family.id, home.id, family.id])
+            .await
+            .unwrap();
+        assert_eq!(
+            servers.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![home.id, family.id]
+        );
+        let targets = service.sync_targets(playlist.id, None).await.unwrap();
+        assert_eq!(targets.len(), 2);
+
+        // Servers given for a single sync take precedence
+        let targets = service
+            .sync_targets(playlist.id, Some(vec![family.id]))
+            .await
+            .unwrap();
+        assert_eq!(
+            targets.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![family.id]
+        );
+
+        assert!(
+            service
+                .set_playlist_servers(playlist.id, vec![home.id + 100])
+                .await
+                .is_err()
+        );
+        service
+            .set_playlist_servers(playlist.id, Vec::new())
+            .await
+            .unwrap();
+        assert!(
+            service
+                .playlist_servers(playlist.id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
@@ -0,0 +1,821 @@
+//! Compiles smart playlist rules into Sea-ORM conditions over `tracks` and
+//! evaluates them against the library.
+
+use chrono::Utc;
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use sea_orm::sea_query::LikeExpr;
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, QueryTrait, RelationTrait, Select,
+};
+
+use crate::entities;
+use crate::entities::smart_playlist::{
+    SmartPlaylistRules, SmartRule, SmartRuleField, SmartRuleMatch, SmartRuleOperator,
+};
+use crate::http_server::graphql::query_builder::{
+    SortInput, SortableField, TrackSortField, apply_sort,
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Escape character for the LIKE patterns built from rule values
+const LIKE_ESCAPE: char = '\\';
+
+/// Compiles rules into a condition on `tracks`. `now` is the epoch second used
+/// for relative date rules.
+pub fn compile_rules(rules: &SmartPlaylistRules, now: i64) -> Result<Condition> {
+    if rules.rules.is_empty() {
+        return Ok(Condition::all());
+    }
+
+    let mut condition = match rules.match_mode {
+        SmartRuleMatch::All => Condition::all(),
+        SmartRuleMatch::Any => Condition::any(),
+    };
+    for rule in &rules.rules {
+        condition = condition.add(compile_rule(rule, now)?);
+    }
+
+    Ok(condition)
+}
+
+/// Checks that every rule can be compiled, so invalid rules are rejected when
+/// saved rather than when the playlist is read.
+///
+/// Playlist rules only look at stored entries, so they can't reference another
+/// smart playlist.
+pub async fn validate_rules(conn: &impl ConnectionTrait, rules: &SmartPlaylistRules) -> Result<()> {
+    if rules.limit.is_some_and(|limit| limit < 1) {
+        return Err(eyre!("Smart playlist limit must be at least 1"));
+    }
+
+    compile_rules(rules, Utc::now().timestamp())?;
+
+    for rule in &rules.rules {
+        if !matches!(
+            rule.field,
+            SmartRuleField::InPlaylist | SmartRuleField::ArtistInPlaylist
+        ) {
+            continue;
+        }
+        let playlist_id: i64 = parse_value(rule, rule.value.trim())?;
+        if find_rules(conn, playlist_id).await?.is_some() {
+            return Err(eyre!(
+                "Rule {:?} can't reference smart playlist {}",
+                rule.field,
+                playlist_id
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the rules of a smart playlist, or `None` for a regular playlist.
+pub async fn find_rules(
+    conn: &impl ConnectionTrait,
+    playlist_id: i64,
+) -> Result<Option<SmartPlaylistRules>> {
+    let smart_playlist = entities::smart_playlist::Entity::find()
+        .filter(entities::smart_playlist::Column::PlaylistId.eq(playlist_id))
+        .one(conn)
+        .await
+        .wrap_err("Failed to fetch smart playlist rules")?;
+
+    Ok(smart_playlist.map(|model| model.rules))
+}
+
+/// Evaluates rules against the library and returns matching track IDs in
+/// playlist order.
+pub async fn matching_track_ids(
+    conn: &impl ConnectionTrait,
+    rules: &SmartPlaylistRules,
+) -> Result<Vec<i64>> {
+    let mut query = sorted_matching_tracks(rules)?;
+    if let Some(limit) = rules.limit {
+        query = query.limit(limit.max(0) as u64);
+    }
+
+    track_ids(conn, query).await
+}
+
+/// Like `matching_track_ids`, but only the `limit` tracks from `offset` on,
+/// selected by the database.
+pub async fn matching_track_ids_page(
+    conn: &impl ConnectionTrait,
+    rules: &SmartPlaylistRules,
+    offset: u64,
+    limit: u64,
+) -> Result<Vec<i64>> {
+    let limit = match rules.limit {
+        Some(rules_limit) => limit.min((rules_limit.max(0) as u64).saturating_sub(offset)),
+        None => limit,
+    };
+    if limit == 0 {
+        return Ok(Vec::new());
+    }
+
+    let query = sorted_matching_tracks(rules)?.offset(offset).limit(limit);
+    track_ids(conn, query).await
+}
+
+/// Counts the tracks matching the rules without loading them.
+pub async fn count_matching_tracks(
+    conn: &impl ConnectionTrait,
+    rules: &SmartPlaylistRules,
+) -> Result<u64> {
+    let count = matching_tracks(rules)?
+        .count(conn)
+        .await
+        .wrap_err("Failed to count smart playlist tracks")?;
+
+    Ok(match rules.limit {
+        Some(limit) => count.min(limit.max(0) as u64),
+        None => count,
+    })
+}
+
+fn matching_tracks(rules: &SmartPlaylistRules) -> Result<Select<entities::track::Entity>> {
+    let condition = compile_rules(rules, Utc::now().timestamp())?;
+    Ok(entities::track::Entity::find().filter(condition))
+}
+
+/// Matching tracks in playlist order: the rules' sort, then by ID.
+fn sorted_matching_tracks(rules: &SmartPlaylistRules) -> Result<Select<entities::track::Entity>> {
+    let sort_inputs: Vec<SortInput<TrackSortField>> = rules
+        .sort
+        .iter()
+        .map(|sort| SortInput {
+            field: sort.field,
+            order: sort.order,
+        })
+        .collect();
+
+    Ok(apply_sort(
+        matching_tracks(rules)?,
+        &sort_inputs,
+        Some(TrackSortField::default_sort()),
+    )?
+    .order_by_asc(entities::track::Column::Id))
+}
+
+async fn track_ids(
+    conn: &impl ConnectionTrait,
+    query: Select<entities::track::Entity>,
+) -> Result<Vec<i64>> {
+    query
+        .select_only()
+        .column(entities::track::Column::Id)
+        .into_tuple::<i64>()
+        .all(conn)
+        .await
+        .wrap_err("Failed to evaluate smart playlist rules")
+}
+
+fn compile_rule(rule: &SmartRule, now: i64) -> Result<Condition> {
+    let value = rule.value.trim();
+    let track_id = entities::track::Column::Id;
+
+    let condition = match rule.field {
+        SmartRuleField::Title => {
+            let (condition, negate) = text_condition(rule, entities::track::Column::Title, value)?;
+            if negate { condition.not() } else { condition }
+        }
+        SmartRuleField::Artist => {
+            let (condition, negate) = text_condition(rule, entities::artist::Column::Name, value)?;
+            let track_ids = entities::track_artist::Entity::find()
+                .select_only()
+                .column(entities::track_artist::Column::TrackId)
+                .join(
+                    JoinType::InnerJoin,
+                    entities::track_artist::Relation::Artist.def(),
+                )
+                .filter(condition)
+                .into_query();
+            Condition::all().add(if negate {
+                track_id.not_in_subquery(track_ids)
+            } else {
+                track_id.in_subquery(track_ids)
+            })
+        }
+        SmartRuleField::Album => {
+            let (condition, negate) = text_condition(rule, entities::album::Column::Title, value)?;
+            album_condition(condition, negate)
+        }
+        SmartRuleField::Format => {
+            let pattern =
+                like_pattern(&format!("%.{}", escape_like(value.trim_start_matches('.'))));
+            let file_path = entities::track::Column::FilePath;
+            match rule.operator {
+                SmartRuleOperator::Is => Condition::all().add(file_path.like(pattern)),
+                SmartRuleOperator::IsNot => Condition::all().add(file_path.not_like(pattern)),
+                _ => return Err(unsupported(rule)),
+            }
+        }
+        SmartRuleField::Year => {
+            let year: i32 = parse_value(rule, value)?;
+            let year_column = entities::album::Column::Year;
+            match rule.operator {
+                SmartRuleOperator::Is => {
+                    album_condition(Condition::all().add(year_column.eq(year)), false)
+                }
+                SmartRuleOperator::IsNot => {
+                    album_condition(Condition::all().add(year_column.eq(year)), true)
+                }
+                SmartRuleOperator::GreaterThan => {
+                    album_condition(Condition::all().add(year_column.gt(year)), false)
+                }
+                SmartRuleOperator::LessThan => {
+                    album_condition(Condition::all().add(year_column.lt(year)), false)
+                }
+                _ => return Err(unsupported(rule)),
+            }
+        }
+        SmartRuleField::Duration => {
+            let seconds: i32 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::Duration, seconds)?
+        }
+        SmartRuleField::Popularity => {
+            let popularity: i32 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::SpotifyPopularity, popularity)?
+        }
+        SmartRuleField::Tempo => {
+            let tempo: f64 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::Tempo, tempo)?
+        }
+        SmartRuleField::Energy => {
+            let energy: f64 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::Energy, energy)?
+        }
+        SmartRuleField::Key => {
+            let key: i32 = parse_value(rule, value)?;
+            let musical_key = entities::track::Column::MusicalKey;
+            Condition::all().add(match rule.operator {
+                SmartRuleOperator::Is => musical_key.eq(key),
+                SmartRuleOperator::IsNot => musical_key.ne(key),
+                _ => return Err(unsupported(rule)),
+            })
+        }
+        SmartRuleField::PlayCount => {
+            let plays: i32 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::PlayCount, plays)?
+        }
+        SmartRuleField::Rating => {
+            let rating: f64 = parse_value(rule, value)?;
+            numeric_condition(rule, entities::track::Column::Rating, rating)?
+        }
+        SmartRuleField::LastPlayed => {
+            let days: i64 = parse_value(rule, value)?;
+            let cutoff = now - days * SECONDS_PER_DAY;
+            let last_played_at = entities::track::Column::LastPlayedAt;
+            match rule.operator {
+                SmartRuleOperator::InLastDays => Condition::all().add(last_played_at.gte(cutoff)),
+                // Tracks that were never played weren't played recently either
+                SmartRuleOperator::NotInLastDays => Condition::any()
+                    .add(last_played_at.lt(cutoff))
+                    .add(last_played_at.is_null()),
+                _ => return Err(unsupported(rule)),
+            }
+        }
+        SmartRuleField::AddedAt => {
+            let days: i64 = parse_value(rule, value)?;
+            let cutoff = now - days * SECONDS_PER_DAY;
+            let created_at = entities::track::Column::CreatedAt;
+            Condition::all().add(match rule.operator {
+                SmartRuleOperator::InLastDays => created_at.gte(cutoff),
+                SmartRuleOperator::NotInLastDays => created_at.lt(cutoff),
+                _ => return Err(unsupported(rule)),
+            })
+        }
+        SmartRuleField::InPlaylist => {
+            let playlist_id: i64 = parse_value(rule, value)?;
+            let track_ids = playlist_track_ids(playlist_id);
+            Condition::all().add(match rule.operator {
+                SmartRuleOperator::Is => track_id.in_subquery(track_ids),
+                SmartRuleOperator::IsNot => track_id.not_in_subquery(track_ids),
+                _ => return Err(unsupported(rule)),
+            })
+        }
+        SmartRuleField::ArtistInPlaylist => {
+            let playlist_id: i64 = parse_value(rule, value)?;
+            let artist_ids = entities::track_artist::Entity::find()
+                .select_only()
+                .column(entities::track_artist::Column::ArtistId)
+                .filter(
+                    entities::track_artist::Column::TrackId
+                        .in_subquery(playlist_track_ids(playlist_id)),
+                )
+                .into_query();
+            let track_ids = entities::track_artist::Entity::find()
+                .select_only()
+                .column(entities::track_artist::Column::TrackId)
+                .filter(entities::track_artist::Column::ArtistId.in_subquery(artist_ids))
+                .into_query();
+            Condition::all().add(match rule.operator {
+                SmartRuleOperator::Is => track_id.in_subquery(track_ids),
+                SmartRuleOperator::IsNot => track_id.not_in_subquery(track_ids),
+                _ => return Err(unsupported(rule)),
+            })
+        }
+    };
+
+    Ok(condition)
+}
+
+/// Compares a numeric column. Tracks without a value never match.
+fn numeric_condition<V>(rule: &SmartRule, column: impl ColumnTrait, value: V) -> Result<Condition>
+where
+    V: Into<sea_orm::Value>,
+{
+    Ok(Condition::all().add(match rule.operator {
+        SmartRuleOperator::Is => column.eq(value),
+        SmartRuleOperator::IsNot => column.ne(value),
+        SmartRuleOperator::GreaterThan => column.gt(value),
+        SmartRuleOperator::LessThan => column.lt(value),
+        _ => return Err(unsupported(rule)),
+    }))
+}
+
+/// Builds the positive text condition for a rule, returning whether the
+/// caller should negate it.
+fn text_condition(
+    rule: &SmartRule,
+    column: impl ColumnTrait,
+    value: &str,
+) -> Result<(Condition, bool)> {
+    let pattern = match rule.operator {
+        SmartRuleOperator::Contains | SmartRuleOperator::NotContains => {
+            format!("%{}%", escape_like(value))
+        }
+        // LIKE without wildcards gives a case-insensitive equality check
+        SmartRuleOperator::Is | SmartRuleOperator::IsNot => escape_like(value),
+        _ => return Err(unsupported(rule)),
+    };
+    let negate = matches!(
+        rule.operator,
+        SmartRuleOperator::NotContains | SmartRuleOperator::IsNot
+    );
+
+    Ok((
+        Condition::all().add(column.like(like_pattern(&pattern))),
+        negate,
+    ))
+}
+
+/// Escapes the LIKE wildcards in a rule value so it matches literally.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '_') || c == LIKE_ESCAPE {
+            escaped.push(LIKE_ESCAPE);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn like_pattern(pattern: &str) -> LikeExpr {
+    LikeExpr::new(pattern).escape(LIKE_ESCAPE)
+}
+
+fn album_condition(condition: Condition, negate: bool) -> Condition {
+    let album_ids = entities::album::Entity::find()
+        .select_only()
+        .column(entities::album::Column::Id)
+        .filter(condition)
+        .into_query();
+    let album_id = entities::track::Column::AlbumId;
+
+    Condition::all().add(if negate {
+        album_id.not_in_subquery(album_ids)
+    } else {
+        album_id.in_subquery(album_ids)
+    })
+}
+
+fn playlist_track_ids(playlist_id: i64) -> sea_orm::sea_query::SelectStatement {
+    entities::playlist_track::Entity::find()
+        .select_only()
+        .column(entities::playlist_track::Column::TrackId)
+        .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
+        .into_query()
+}
+
+fn parse_value<T: std::str::FromStr>(rule: &SmartRule, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| eyre!("Invalid value '{}' for {:?} rule", value, rule.field))
+}
+
+fn unsupported(rule: &SmartRule) -> color_eyre::Report {
+    eyre!(
+        "Operator {:?} is not supported for {:?} rules",
+        rule.operator,
+        rule.field
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::entities::smart_playlist::SmartRuleSort;
+    use crate::http_server::graphql::query_builder::SortOrder;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::{ActiveModelTrait, Set};
+
+    fn track<'a>(
+        title: &'a str,
+        artist: &'a str,
+        file_path: &'a str,
+        created_at: i64,
+    ) -> TestTrack<'a> {
+        TestTrack {
+            artist: Some(artist),
+            file_path: Some(file_path),
+            created_at: Some(created_at),
+            ..TestTrack::new(title)
+        }
+    }
+
+    fn rule(field: SmartRuleField, operator: SmartRuleOperator, value: &str) -> SmartRule {
+        SmartRule {
+            field,
+            operator,
+            value: value.into(),
+        }
+    }
+
+    fn make_rules(match_mode: SmartRuleMatch, rules: Vec<SmartRule>) -> SmartPlaylistRules {
+        SmartPlaylistRules {
+            match_mode,
+            rules,
+            sort: vec![SmartRuleSort {
+                field: TrackSortField::Id,
+                order: SortOrder::Asc,
+            }],
+            limit: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recent_flac_by_artists_in_playlist() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        let old = now - 90 * SECONDS_PER_DAY;
+
+        let seed = insert_track(&db, track("Seed", "Band", "/m/Band/a/seed.mp3", old))
+            .await
+            .id;
+        let recent_flac = insert_track(&db, track("New", "Band", "/m/Band/b/new.FLAC", now))
+            .await
+            .id;
+        insert_track(&db, track("Old", "Band", "/m/Band/c/old.flac", old)).await;
+        insert_track(
+            &db,
+            track("Other", "Someone", "/m/Someone/d/other.flac", now),
+        )
+        .await;
+
+        let playlists = crate::services::playlist::PlaylistService::new(db.clone());
+        let source = playlists.create("Source".into(), None).await.unwrap();
+        playlists.add_track(source.id, seed).await.unwrap();
+
+        let rules = make_rules(
+            SmartRuleMatch::All,
+            vec![
+                rule(SmartRuleField::Format, SmartRuleOperator::Is, "flac"),
+                rule(SmartRuleField::AddedAt, SmartRuleOperator::InLastDays, "30"),
+                rule(
+                    SmartRuleField::ArtistInPlaylist,
+                    SmartRuleOperator::Is,
+                    &source.id.to_string(),
+                ),
+            ],
+        );
+
+        let ids = matching_track_ids(&db.conn, &rules).await.unwrap();
+        assert_eq!(ids, vec![recent_flac]);
+    }
+
+    #[tokio::test]
+    async fn test_any_match_and_limit() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        let a = insert_track(&db, track("Love Song", "A", "/m/A/x/1.mp3", now))
+            .await
+            .id;
+        let b = insert_track(&db, track("Hate Song", "B", "/m/B/x/2.mp3", now))
+            .await
+            .id;
+        insert_track(&db, track("Tune", "C", "/m/C/x/3.mp3", now)).await;
+
+        let mut rules = make_rules(
+            SmartRuleMatch::Any,
+            vec![
+                rule(SmartRuleField::Title, SmartRuleOperator::Contains, "love"),
+                rule(SmartRuleField::Artist, SmartRuleOperator::Is, "b"),
+            ],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![a, b]
+        );
+
+        rules.limit = Some(1);
+        assert_eq!(matching_track_ids(&db.conn, &rules).await.unwrap(), vec![a]);
+
+        let negated = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(
+                SmartRuleField::Title,
+                SmartRuleOperator::NotContains,
+                "song",
+            )],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &negated).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_enrichment_rules_and_sort() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        let slow = insert_track(&db, track("Slow", "A", "/m/A/x/1.mp3", now))
+            .await
+            .id;
+        let fast = insert_track(&db, track("Fast", "A", "/m/A/x/2.mp3", now))
+            .await
+            .id;
+        let loud = insert_track(&db, track("Loud", "A", "/m/A/x/3.mp3", now))
+            .await
+            .id;
+        // Not enriched, so it never matches enrichment rules
+        insert_track(&db, track("Unknown", "A", "/m/A/x/4.mp3", now)).await;
+        for (id, tempo, energy, key) in [
+            (slow, 70.5, 0.2, 9),
+            (fast, 172.0, 0.6, 9),
+            (loud, 128.0, 0.95, 2),
+        ] {
+            entities::track::ActiveModel {
+                id: Set(id),
+                tempo: Set(Some(tempo)),
+                energy: Set(Some(energy)),
+                musical_key: Set(Some(key)),
+                ..Default::default()
+            }
+            .update(&db.conn)
+            .await
+            .unwrap();
+        }
+
+        let mut rules = make_rules(
+            SmartRuleMatch::All,
+            vec![
+                rule(SmartRuleField::Tempo, SmartRuleOperator::GreaterThan, "100"),
+                rule(SmartRuleField::Energy, SmartRuleOperator::LessThan, "0.9"),
+            ],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![fast]
+        );
+
+        rules.rules = vec![rule(SmartRuleField::Key, SmartRuleOperator::Is, "9")];
+        rules.sort = vec![SmartRuleSort {
+            field: TrackSortField::Tempo,
+            order: SortOrder::Desc,
+        }];
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![fast, slow]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_play_history_rules() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        let favorite = insert_track(&db, track("Favorite", "A", "/m/A/x/1.mp3", now))
+            .await
+            .id;
+        let forgotten = insert_track(&db, track("Forgotten", "A", "/m/A/x/2.mp3", now))
+            .await
+            .id;
+        let never_played = insert_track(&db, track("Never Played", "A", "/m/A/x/3.mp3", now))
+            .await
+            .id;
+        for (id, play_count, last_played_at, rating) in [
+            (favorite, 40, Some(now - SECONDS_PER_DAY), Some(10.0)),
+            (forgotten, 12, Some(now - 400 * SECONDS_PER_DAY), Some(6.0)),
+            (never_played, 0, None, None),
+        ] {
+            entities::track::ActiveModel {
+                id: Set(id),
+                play_count: Set(Some(play_count)),
+                last_played_at: Set(last_played_at),
+                rating: Set(rating),
+                ..Default::default()
+            }
+            .update(&db.conn)
+            .await
+            .unwrap();
+        }
+
+        let mut rules = make_rules(
+            SmartRuleMatch::All,
+            vec![
+                rule(
+                    SmartRuleField::PlayCount,
+                    SmartRuleOperator::GreaterThan,
+                    "10",
+                ),
+                rule(
+                    SmartRuleField::LastPlayed,
+                    SmartRuleOperator::NotInLastDays,
+                    "365",
+                ),
+            ],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![forgotten]
+        );
+
+        rules.rules = vec![rule(
+            SmartRuleField::LastPlayed,
+            SmartRuleOperator::NotInLastDays,
+            "7",
+        )];
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![forgotten, never_played]
+        );
+
+        rules.rules = vec![rule(
+            SmartRuleField::Rating,
+            SmartRuleOperator::GreaterThan,
+            "5",
+        )];
+        rules.sort = vec![SmartRuleSort {
+            field: TrackSortField::PlayCount,
+            order: SortOrder::Desc,
+        }];
+        assert_eq!(
+            matching_track_ids(&db.conn, &rules).await.unwrap(),
+            vec![favorite, forgotten]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_rules_are_rejected() {
+        let db = test_db().await;
+        let bad_operator = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(
+                SmartRuleField::Format,
+                SmartRuleOperator::GreaterThan,
+                "flac",
+            )],
+        );
+        assert!(validate_rules(&db.conn, &bad_operator).await.is_err());
+
+        let bad_value = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(SmartRuleField::Year, SmartRuleOperator::Is, "soon")],
+        );
+        assert!(validate_rules(&db.conn, &bad_value).await.is_err());
+
+        let mut bad_limit = make_rules(SmartRuleMatch::All, Vec::new());
+        bad_limit.limit = Some(0);
+        assert!(validate_rules(&db.conn, &bad_limit).await.is_err());
+
+        // Playlist rules only see stored entries, which smart playlists don't have
+        let playlists = crate::services::playlist::PlaylistService::new(db.clone());
+        let smart = playlists
+            .create_smart(
+                "Smart".into(),
+                None,
+                make_rules(SmartRuleMatch::All, Vec::new()),
+            )
+            .await
+            .unwrap();
+        let regular = playlists.create("Regular".into(), None).await.unwrap();
+        for field in [SmartRuleField::InPlaylist, SmartRuleField::ArtistInPlaylist] {
+            let in_smart = make_rules(
+                SmartRuleMatch::All,
+                vec![rule(field, SmartRuleOperator::Is, &smart.id.to_string())],
+            );
+            assert!(validate_rules(&db.conn, &in_smart).await.is_err());
+            let in_regular = make_rules(
+                SmartRuleMatch::All,
+                vec![rule(field, SmartRuleOperator::Is, &regular.id.to_string())],
+            );
+            validate_rules(&db.conn, &in_regular).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_text_rules_match_wildcards_literally() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        let percent = insert_track(&db, track("100% Pure", "A", "/m/A/x/1.mp3", now))
+            .await
+            .id;
+        let underscore = insert_track(&db, track("snake_case", "A", "/m/A/x/2.mp3", now))
+            .await
+            .id;
+        insert_track(&db, track("1000 Pure", "A", "/m/A/x/3.mp3", now)).await;
+        insert_track(&db, track("snakeXcase", "A", "/m/A/x/4.mp3", now)).await;
+
+        let contains = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(
+                SmartRuleField::Title,
+                SmartRuleOperator::Contains,
+                "0%",
+            )],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &contains).await.unwrap(),
+            vec![percent]
+        );
+        let is = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(
+                SmartRuleField::Title,
+                SmartRuleOperator::Is,
+                "SNAKE_CASE",
+            )],
+        );
+        assert_eq!(
+            matching_track_ids(&db.conn, &is).await.unwrap(),
+            vec![underscore]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_count_matching_tracks_respects_limit() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        for (title, path) in [
+            ("A", "/m/A/x/1.mp3"),
+            ("B", "/m/A/x/2.mp3"),
+            ("C", "/m/A/x/3.mp3"),
+        ] {
+            insert_track(&db, track(title, "A", path, now)).await;
+        }
+
+        let mut rules = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(SmartRuleField::Artist, SmartRuleOperator::Is, "a")],
+        );
+        assert_eq!(count_matching_tracks(&db.conn, &rules).await.unwrap(), 3);
+        rules.limit = Some(2);
+        assert_eq!(count_matching_tracks(&db.conn, &rules).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_matching_track_ids_page_respects_limit() {
+        let db = test_db().await;
+        let now = Utc::now().timestamp();
+        for (title, path) in [
+            ("A", "/m/A/x/1.mp3"),
+            ("B", "/m/A/x/2.mp3"),
+            ("C", "/m/A/x/3.mp3"),
+        ] {
+            insert_track(&db, track(title, "A", path, now)).await;
+        }
+
+        let mut rules = make_rules(
+            SmartRuleMatch::All,
+            vec![rule(SmartRuleField::Artist, SmartRuleOperator::Is, "a")],
+        );
+        let all = matching_track_ids(&db.conn, &rules).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            matching_track_ids_page(&db.conn, &rules, 1, 5)
+                .await
+                .unwrap(),
+            all[1..]
+        );
+
+        // The page ends at the rules' limit
+        rules.limit = Some(2);
+        assert_eq!(
+            matching_track_ids_page(&db.conn, &rules, 1, 5)
+                .await
+                .unwrap(),
+            all[1..2]
+        );
+        assert!(
+            matching_track_ids_page(&db.conn, &rules, 2, 5)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
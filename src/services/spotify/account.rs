@@ -7,6 +7,7 @@ use spotify_rs::{AuthCodeFlow, Unauthenticated};
 
 use crate::database::Database;
 use crate::entities;
+use crate::entities::spotify_account::AccountStatus;
 
 pub struct SpotifyAccountService {
     db: Arc<Database>,
@@ -71,6 +72,9 @@ impl SpotifyAccountService {
             account.access_token = Set(access_token);
             account.refresh_token = Set(refresh_token);
             account.token_expiry = Set(0);
+            // Re-authorizing recovers an account whose refresh token was revoked
+            account.status = Set(AccountStatus::Active);
+            account.last_error = Set(None);
 
             account
                 .update(&self.db.conn)
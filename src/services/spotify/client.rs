@@ -1,14 +1,17 @@
-use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
 use spotify_rs::AuthCodeClient;
 use spotify_rs::AuthCodeFlow;
 use spotify_rs::RedirectUrl;
-use spotify_rs::Token;
 use spotify_rs::Unauthenticated;
-use spotify_rs::UnknownFlow;
 use spotify_rs::client::Client as SpotifyRsClient;
 use url::Url;
 
-use crate::ports::spotify::{SpotifyApiPlaylist, SpotifyApiTrack, SpotifyClient};
+use crate::ports::spotify::{
+    SpotifyApiAudioFeatures, SpotifyApiPlaylist, SpotifyApiTrack, SpotifyClient,
+};
 
 pub const SPOTIFY_SCOPES: [&str; 4] = [
     "user-read-email",
@@ -17,11 +20,21 @@ pub const SPOTIFY_SCOPES: [&str; 4] = [
     "playlist-read-collaborative",
 ];
 
+pub const DEFAULT_SPOTIFY_API_BASE_URL: &str = "https://api.spotify.com/v1/";
+pub const DEFAULT_SPOTIFY_ACCOUNTS_BASE_URL: &str = "https://accounts.spotify.com/";
+
+/// Number of results requested per track search
+const SEARCH_LIMIT: u32 = 5;
+/// Maximum number of track IDs per audio features request
+const AUDIO_FEATURES_LIMIT: usize = 100;
+
 #[derive(Debug, Clone)]
 pub struct SpotifyApiCredentials {
     client_id: String,
     client_secret: String,
     redirect_uri: RedirectUrl,
+    api_base_url: Url,
+    accounts_base_url: Url,
 }
 
 impl SpotifyApiCredentials {
@@ -30,15 +43,24 @@ impl SpotifyApiCredentials {
             client_id,
             client_secret,
             redirect_uri,
+            api_base_url: Url::parse(DEFAULT_SPOTIFY_API_BASE_URL)
+                .expect("default Spotify API URL is valid"),
+            accounts_base_url: Url::parse(DEFAULT_SPOTIFY_ACCOUNTS_BASE_URL)
+                .expect("default Spotify accounts URL is valid"),
         }
     }
 
-    pub fn client_secret(&self) -> Option<&str> {
-        Some(&self.client_secret)
+    /// Point token refreshes and API requests at another server, e.g. a local
+    /// stand-in for the Spotify Web API. The OAuth authorization flow always
+    /// uses Spotify.
+    pub fn with_base_urls(mut self, api_base_url: Url, accounts_base_url: Url) -> Self {
+        self.api_base_url = api_base_url;
+        self.accounts_base_url = accounts_base_url;
+        self
     }
 
-    pub fn client_id(&self) -> &str {
-        &self.client_id
+    pub fn api_base_url(&self) -> &Url {
+        &self.api_base_url
     }
 }
 
@@ -58,44 +80,236 @@ pub fn start_spotify_auth_flow(
     )
 }
 
-/// Adapter that implements `SpotifyClient` port using the `spotify_rs` crate.
-pub struct SpotifyRsAdapter {
-    client: SpotifyRsClient<Token, UnknownFlow>,
+/// Access token returned by a token refresh.
+#[derive(Debug, Deserialize)]
+pub struct RefreshedToken {
+    pub access_token: String,
+    /// Only set when Spotify rotated the refresh token
+    pub refresh_token: Option<String>,
+    /// Lifetime of the access token in seconds
+    pub expires_in: i64,
 }
 
-impl SpotifyRsAdapter {
-    pub async fn from_refresh_token(
-        credentials: &SpotifyApiCredentials,
-        refresh_token: String,
-    ) -> color_eyre::eyre::Result<Self> {
-        let client = SpotifyRsClient::from_refresh_token(
-            credentials.client_id(),
-            credentials.client_secret(),
-            Some(SPOTIFY_SCOPES.to_vec().into()),
-            true,
-            refresh_token,
-        )
+#[derive(Debug, Deserialize)]
+struct TokenErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// Exchanges a refresh token for a new access token at the credentials'
+/// accounts base URL.
+///
+/// Errors from Spotify keep the OAuth error code (e.g. `invalid_grant` for a
+/// revoked refresh token) in the message.
+pub async fn refresh_access_token(
+    client: &Client,
+    credentials: &SpotifyApiCredentials,
+    refresh_token: &str,
+) -> Result<RefreshedToken> {
+    let url = credentials
+        .accounts_base_url
+        .join("api/token")
+        .wrap_err("Failed to build spotify token URL")?;
+    let response = client
+        .post(url)
+        .basic_auth(&credentials.client_id, Some(&credentials.client_secret))
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+        ])
+        .send()
         .await
-        .wrap_err("Failed to create spotify client")?;
+        .wrap_err("Failed to send spotify token refresh request")?;
 
-        Ok(Self { client })
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(match serde_json::from_str::<TokenErrorResponse>(&body) {
+            Ok(error) => eyre!(
+                "Spotify token refresh failed ({}): {}: {}",
+                status,
+                error.error,
+                error.error_description.unwrap_or_default()
+            ),
+            Err(_) => eyre!("Spotify token refresh failed ({}): {}", status, body),
+        });
     }
+
+    response
+        .json()
+        .await
+        .wrap_err("Failed to parse spotify token refresh response")
 }
 
-#[async_trait::async_trait]
-impl SpotifyClient for SpotifyRsAdapter {
-    async fn current_user_playlists(&self) -> color_eyre::eyre::Result<Vec<SpotifyApiPlaylist>> {
-        let pages = spotify_rs::current_user_playlists()
-            .get(&self.client)
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiPlaylist {
+    id: String,
+    name: String,
+    description: Option<String>,
+    snapshot_id: String,
+    tracks: Option<ApiPlaylistTracksRef>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiPlaylistTracksRef {
+    total: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiPlaylistItem {
+    /// Null for items that are no longer available
+    track: Option<ApiPlayable>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ApiPlayable {
+    Track(ApiTrack),
+    /// Podcast episodes and anything else that isn't a track
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiTrack {
+    /// Null for local files added to the playlist
+    id: Option<String>,
+    name: String,
+    duration_ms: u32,
+    artists: Vec<ApiArtist>,
+    album: ApiAlbum,
+    #[serde(default)]
+    external_ids: ApiExternalIds,
+    popularity: Option<i32>,
+}
+
+impl ApiTrack {
+    /// Local files have no Spotify ID and are left out
+    fn into_port(self) -> Option<SpotifyApiTrack> {
+        Some(SpotifyApiTrack {
+            id: self.id?,
+            name: self.name,
+            duration_ms: self.duration_ms as i32,
+            artists: self.artists.into_iter().map(|a| a.name).collect(),
+            album_name: self.album.name,
+            isrc: self.external_ids.isrc,
+            upc: self.external_ids.upc,
+            popularity: self.popularity,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiArtist {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAlbum {
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiExternalIds {
+    isrc: Option<String>,
+    upc: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiSearchResponse {
+    tracks: Page<ApiTrack>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAudioFeaturesResponse {
+    /// Null for tracks without audio features
+    audio_features: Vec<Option<ApiAudioFeatures>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiAudioFeatures {
+    id: String,
+    tempo: f64,
+    /// -1 when no key was detected
+    key: i32,
+    energy: f64,
+}
+
+/// Implements the `SpotifyClient` port with plain requests to the Web API at
+/// the credentials' API base URL, Spotify's unless a stand-in server was
+/// configured.
+pub struct SpotifyWebApiClient {
+    client: Client,
+    api_base_url: Url,
+    access_token: String,
+}
+
+impl SpotifyWebApiClient {
+    pub fn new(client: Client, api_base_url: Url, access_token: String) -> Self {
+        Self {
+            client,
+            api_base_url,
+            access_token,
+        }
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        self.api_base_url
+            .join(path)
+            .wrap_err("Failed to build spotify API URL")
+    }
+
+    async fn get<T: DeserializeOwned>(&self, url: Url) -> Result<T> {
+        self.client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .send()
             .await
-            .wrap_err("Failed to fetch user spotify playlists")?
-            .get_all(&self.client)
+            .wrap_err("Failed to send spotify API request")?
+            .error_for_status()
+            .wrap_err("Spotify API request failed")?
+            .json()
             .await
-            .wrap_err("Unable to get all user spotify playlists")?;
+            .wrap_err("Failed to parse spotify API response")
+    }
+
+    /// Fetches a paginated endpoint, following `next` links until the last page.
+    async fn get_all<T: DeserializeOwned>(&self, path: &str, limit: u32) -> Result<Vec<T>> {
+        let mut url = self.url(path)?;
+        url.query_pairs_mut()
+            .append_pair("limit", &limit.to_string());
+
+        let mut items = Vec::new();
+        let mut next = Some(url);
+        while let Some(url) = next {
+            let page: Page<T> = self.get(url).await?;
+            items.extend(page.items);
+            next = page
+                .next
+                .map(|next| Url::parse(&next))
+                .transpose()
+                .wrap_err("Invalid next page URL from spotify API")?;
+        }
+        Ok(items)
+    }
+}
 
-        Ok(pages
+#[async_trait::async_trait]
+impl SpotifyClient for SpotifyWebApiClient {
+    async fn current_user_playlists(&self) -> Result<Vec<SpotifyApiPlaylist>> {
+        let playlists: Vec<ApiPlaylist> = self
+            .get_all("me/playlists", 50)
+            .await
+            .wrap_err("Failed to fetch user spotify playlists")?;
+
+        Ok(playlists
             .into_iter()
-            .flatten()
             .map(|p| SpotifyApiPlaylist {
                 id: p.id,
                 name: p.name,
@@ -106,36 +320,107 @@ impl SpotifyClient for SpotifyRsAdapter {
             .collect())
     }
 
-    async fn playlist_tracks(
-        &self,
-        playlist_id: &str,
-    ) -> color_eyre::eyre::Result<Vec<SpotifyApiTrack>> {
-        let pages = spotify_rs::playlist_items(playlist_id)
-            .get(&self.client)
+    async fn playlist_tracks(&self, playlist_id: &str) -> Result<Vec<SpotifyApiTrack>> {
+        let items: Vec<ApiPlaylistItem> = self
+            .get_all(&format!("playlists/{}/tracks", playlist_id), 100)
             .await
-            .wrap_err("Failed to fetch spotify tracks from api")?
-            .get_all(&self.client)
-            .await
-            .wrap_err("Unable to get all spotify playlist items")?;
+            .wrap_err("Failed to fetch spotify tracks from api")?;
 
-        Ok(pages
+        Ok(items
             .into_iter()
-            .flatten()
-            .filter_map(|item| {
-                if let spotify_rs::model::PlayableItem::Track(track) = item.track {
-                    Some(SpotifyApiTrack {
-                        id: track.id,
-                        name: track.name,
-                        duration_ms: track.duration_ms as i32,
-                        artists: track.artists.iter().map(|a| a.name.clone()).collect(),
-                        album_name: track.album.name,
-                        isrc: track.external_ids.isrc,
-                        upc: track.external_ids.upc,
-                    })
-                } else {
-                    None
-                }
+            .filter_map(|item| match item.track {
+                Some(ApiPlayable::Track(track)) => track.into_port(),
+                _ => None,
             })
             .collect())
     }
+
+    async fn search_tracks(&self, query: &str) -> Result<Vec<SpotifyApiTrack>> {
+        let mut url = self.url("search")?;
+        url.query_pairs_mut()
+            .append_pair("q", query)
+            .append_pair("type", "track")
+            .append_pair("limit", &SEARCH_LIMIT.to_string());
+        let response: ApiSearchResponse = self
+            .get(url)
+            .await
+            .wrap_err("Failed to search spotify tracks")?;
+
+        Ok(response
+            .tracks
+            .items
+            .into_iter()
+            .filter_map(ApiTrack::into_port)
+            .collect())
+    }
+
+    async fn audio_features(&self, track_ids: &[String]) -> Result<Vec<SpotifyApiAudioFeatures>> {
+        let mut features = Vec::with_capacity(track_ids.len());
+        for chunk in track_ids.chunks(AUDIO_FEATURES_LIMIT) {
+            let mut url = self.url("audio-features")?;
+            url.query_pairs_mut().append_pair("ids", &chunk.join(","));
+            let response: ApiAudioFeaturesResponse = self
+                .get(url)
+                .await
+                .wrap_err("Failed to fetch spotify audio features")?;
+
+            features.extend(response.audio_features.into_iter().flatten().map(|f| {
+                SpotifyApiAudioFeatures {
+                    track_id: f.id,
+                    tempo: f.tempo,
+                    key: (f.key >= 0).then_some(f.key),
+                    energy: f.energy,
+                }
+            }));
+        }
+        Ok(features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_spotify::FakeSpotify;
+
+    async fn fake_client(fake: &FakeSpotify, refresh_token: &str) -> SpotifyWebApiClient {
+        let client = Client::new();
+        let token = refresh_access_token(&client, &fake.credentials(), refresh_token)
+            .await
+            .unwrap();
+        SpotifyWebApiClient::new(client, fake.api_base_url(), token.access_token)
+    }
+
+    #[tokio::test]
+    async fn test_playlist_tracks_follows_pages_and_skips_non_tracks() {
+        let fake = FakeSpotify::start().await;
+        let client = fake_client(&fake, "refresh-household-1").await;
+
+        let playlists = client.current_user_playlists().await.unwrap();
+        let names: Vec<_> = playlists.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Road Trip", "Quiet"]);
+        assert_eq!(playlists[0].total_tracks, 6);
+
+        // Episodes, unavailable items and local files are dropped
+        let tracks = client.playlist_tracks("road-trip").await.unwrap();
+        let ids: Vec<_> = tracks.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["track-harvest-moon", "track-wild-world", "track-dreams"]
+        );
+        assert_eq!(tracks[0].artists, ["Neil Young"]);
+        assert_eq!(tracks[0].isrc.as_deref(), Some("USRE19200001"));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_access_token_reports_revoked_token() {
+        let fake = FakeSpotify::start().await;
+        fake.revoke("household-1");
+
+        let error =
+            refresh_access_token(&Client::new(), &fake.credentials(), "refresh-household-1")
+                .await
+                .err()
+                .unwrap();
+        assert!(error.to_string().contains("invalid_grant"));
+    }
 }
@@ -16,40 +16,57 @@ fn pick_best_match(search_results: &[SingleFileResult]) -> Result<Option<&Single
     Ok(search_results.first())
 }
 
-/// Downloads the best match for a spotify track to the local library.
-/// This performs a search for the track on SoulSeek.
-/// Then filters and ranks the results based on the track metadata.
-/// Finally, it downloads the best match to a temporary directory.
-pub async fn download_best_match_for_spotify_track(
+/// Searches SoulSeek for a track, then filters and ranks the results based on
+/// the track metadata. Returns the best match, if any.
+///
+/// Searches go through the SoulSeek context's rate limiter, so this can be
+/// called for several tracks at once.
+pub async fn search_best_match(
     soulseek_context: &SoulSeekClientContext,
-    spotify_track: entities::spotify_track::Model,
-) -> Result<Option<(TempDir, PathBuf)>> {
+    track: &Track,
+) -> Result<Option<SingleFileResult>> {
+    let soulseek_search_results = soulseek_context.search_for_track(track).await?;
+    Ok(pick_best_match(&soulseek_search_results)?.cloned())
+}
+
+/// Searches SoulSeek for a spotify track, see [`search_best_match`].
+pub async fn search_best_match_for_spotify_track(
+    soulseek_context: &SoulSeekClientContext,
+    spotify_track: &entities::spotify_track::Model,
+) -> Result<Option<SingleFileResult>> {
     tracing::debug!(
-        "Downloading best match for spotify track: {:?}",
-        &spotify_track
+        "Searching best match for spotify track: {:?}",
+        spotify_track
     );
 
-    let soulseek_search_results = soulseek_context
-        .search_for_track(&Track {
+    let best_match = search_best_match(
+        soulseek_context,
+        &Track {
             title: spotify_track.title.clone(),
             album: spotify_track.album.clone(),
             artists: spotify_track.artists.0.clone(),
             length: spotify_track.duration.map(|d| d as u32),
-        })
-        .await?;
-    let best_match = pick_best_match(&soulseek_search_results)?;
-    let best_match = match best_match {
-        Some(best_match) => best_match,
+        },
+    )
+    .await?;
+    match best_match {
+        Some(best_match) => {
+            tracing::debug!("Best match found for spotify track: {:?}", best_match);
+            Ok(Some(best_match))
+        }
         None => {
-            tracing::warn!(
-                "No best match found for spotify track: {:?}",
-                &spotify_track
-            );
-            return Ok(None);
+            tracing::warn!("No best match found for spotify track: {:?}", spotify_track);
+            Ok(None)
         }
-    };
-    tracing::debug!("Best match found for spotify track: {:?}", best_match);
+    }
+}
 
+/// Downloads a search result to a temporary directory. Returns the directory,
+/// which is removed when dropped, and the path of the downloaded file.
+pub async fn download_match(
+    soulseek_context: &SoulSeekClientContext,
+    best_match: &SingleFileResult,
+) -> Result<(TempDir, PathBuf)> {
     let temp_dir = tempfile::tempdir()?;
 
     // TODO: retries? backoff?
@@ -59,12 +76,12 @@ pub async fn download_best_match_for_spotify_track(
         .download_file(best_match, temp_dir.path())
         .await?;
 
-    tracing::debug!("Downloading best match for spotify track: {:?}", best_match);
+    tracing::debug!("Downloading best match: {:?}", best_match);
 
     while let Some(status) = download_receiver.recv().await {
         match status {
             soulseek_rs::DownloadStatus::Completed => {
-                tracing::debug!("Download completed for spotify track: {:?}", best_match);
+                tracing::debug!("Download completed: {:?}", best_match);
                 break;
             }
             soulseek_rs::DownloadStatus::Failed => {
@@ -79,7 +96,7 @@ pub async fn download_best_match_for_spotify_track(
                 speed_bytes_per_sec: _,
             } => {
                 tracing::debug!(
-                    "Download in progress for spotify track: {:?} ({} bytes downloaded, {} bytes total)",
+                    "Download in progress: {:?} ({} bytes downloaded, {} bytes total)",
                     best_match,
                     bytes_downloaded,
                     total_bytes
@@ -104,5 +121,5 @@ pub async fn download_best_match_for_spotify_track(
     let file = &files[0];
     let file_path = file.path();
 
-    Ok(Some((temp_dir, file_path)))
+    Ok((temp_dir, file_path))
 }
@@ -0,0 +1,530 @@
+//! Tracks that could not be downloaded while syncing a Spotify playlist.
+//!
+//! There is one failure per playlist and track. Each failed attempt bumps
+//! `attempts_count` and pushes `next_retry_at` back exponentially; syncs skip
+//! tracks that aren't due yet, and tracks marked "won't find" are never
+//! retried. Retrying from the API makes failures due immediately and resumes
+//! the playlist's sync, or leaves it to the periodic retry when the sync is
+//! still running. Failures of tracks removed from the playlist are
+//! deleted when its sync completes.
+
+use std::sync::Arc;
+
+use color_eyre::eyre::{OptionExt, Result, WrapErr};
+use sea_orm::prelude::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait,
+    Set,
+};
+
+use crate::database::Database;
+use crate::entities;
+use crate::services::spotify::sync_spotify_playlist_to_local_library::resume_latest_playlist_sync;
+use crate::services::tasks::TaskManager;
+
+/// Delay before the first automatic retry, doubled for every further attempt
+const RETRY_BASE_DELAY_SECS: i64 = 60 * 60;
+const RETRY_MAX_DELAY_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Delay before retrying a track that failed `attempts` times.
+fn retry_delay_secs(attempts: i32) -> i64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 30) as u32;
+    RETRY_BASE_DELAY_SECS
+        .saturating_mul(2_i64.saturating_pow(exponent))
+        .min(RETRY_MAX_DELAY_SECS)
+}
+
+/// Which failures to retry.
+pub enum RetryFilter {
+    /// A single failure by ID
+    Id(i64),
+    /// All failures, optionally only of one playlist
+    All { spotify_playlist_id: Option<i64> },
+    /// Failures with exactly this reason, optionally only of one playlist
+    Reason {
+        reason: String,
+        spotify_playlist_id: Option<i64>,
+    },
+}
+
+/// Failures made due by [`retry_download_failures`].
+pub struct ScheduledRetries {
+    pub failures: u64,
+    /// Syncs resumed right away; the others are resumed by the periodic retry
+    pub resumed_sync_state_ids: Vec<i64>,
+}
+
+/// Records a failed attempt to download a track, scheduling the next
+/// automatic retry.
+pub async fn record_download_failure(
+    db: &Database,
+    spotify_playlist_id: i64,
+    spotify_track: &entities::spotify_track::Model,
+    reason: String,
+) -> Result<entities::spotify_track_download_failure::Model> {
+    let now = chrono::Utc::now().timestamp();
+    let existing = entities::spotify_track_download_failure::Entity::find()
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                .eq(spotify_playlist_id),
+        )
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyTrackId
+                .eq(&spotify_track.spotify_track_id),
+        )
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch spotify track download failure")?;
+
+    match existing {
+        Some(existing) => {
+            let attempts_count = existing.attempts_count + 1;
+            let mut failure: entities::spotify_track_download_failure::ActiveModel =
+                existing.into();
+            failure.reason = Set(reason);
+            failure.attempts_count = Set(attempts_count);
+            failure.next_retry_at = Set(Some(now + retry_delay_secs(attempts_count)));
+            failure
+                .update(&db.conn)
+                .await
+                .wrap_err("Failed to update spotify track download failure")
+        }
+        None => {
+            let failure = entities::spotify_track_download_failure::ActiveModel {
+                spotify_playlist_id: Set(spotify_playlist_id),
+                spotify_track_id: Set(spotify_track.spotify_track_id.clone()),
+                track_name: Set(spotify_track.title.clone()),
+                artist_name: Set(spotify_track.artists.0.join(", ")),
+                album_name: Set(Some(spotify_track.album.clone())),
+                isrc: Set(spotify_track.isrc.clone()),
+                reason: Set(reason),
+                next_retry_at: Set(Some(now + retry_delay_secs(1))),
+                ..Default::default()
+            };
+            failure
+                .insert(&db.conn)
+                .await
+                .wrap_err("Failed to insert spotify track download failure")
+        }
+    }
+}
+
+/// Removes the failure of a track that has since been synced.
+pub async fn clear_download_failure(
+    db: &Database,
+    spotify_playlist_id: i64,
+    spotify_track_id: &str,
+) -> Result<()> {
+    entities::spotify_track_download_failure::Entity::delete_many()
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                .eq(spotify_playlist_id),
+        )
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyTrackId.eq(spotify_track_id),
+        )
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to delete spotify track download failure")?;
+    Ok(())
+}
+
+/// Deletes a playlist's failures of tracks that are no longer in the playlist,
+/// which would otherwise stay due and resume its sync forever.
+pub async fn clear_removed_track_failures(db: &Database, spotify_playlist_id: i64) -> Result<u64> {
+    let result = entities::spotify_track_download_failure::Entity::delete_many()
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                .eq(spotify_playlist_id),
+        )
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyTrackId.not_in_subquery(
+                entities::spotify_track_playlist::Entity::find()
+                    .select_only()
+                    .column(entities::spotify_track_playlist::Column::SpotifyTrackId)
+                    .filter(
+                        entities::spotify_track_playlist::Column::SpotifyPlaylistId
+                            .eq(spotify_playlist_id),
+                    )
+                    .into_query(),
+            ),
+        )
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to delete download failures of removed tracks")?;
+    Ok(result.rows_affected)
+}
+
+/// Spotify track IDs of a playlist's failures that shouldn't be retried yet,
+/// either because their back-off hasn't passed or they are marked "won't find".
+pub async fn tracks_not_due_for_retry(
+    db: &Database,
+    spotify_playlist_id: i64,
+) -> Result<Vec<String>> {
+    let now = chrono::Utc::now().timestamp();
+    entities::spotify_track_download_failure::Entity::find()
+        .select_only()
+        .column(entities::spotify_track_download_failure::Column::SpotifyTrackId)
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                .eq(spotify_playlist_id),
+        )
+        .filter(
+            entities::spotify_track_download_failure::Column::WontFind
+                .eq(true)
+                .or(entities::spotify_track_download_failure::Column::NextRetryAt.gt(now)),
+        )
+        .into_tuple()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch spotify track download failures")
+}
+
+/// Marks or unmarks a failure as "won't find". Marked tracks are skipped by
+/// syncs and never retried.
+pub async fn set_wont_find(
+    db: &Database,
+    failure_id: i64,
+    wont_find: bool,
+) -> Result<entities::spotify_track_download_failure::Model> {
+    let failure = entities::spotify_track_download_failure::Entity::find_by_id(failure_id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch spotify track download failure")?
+        .ok_or_eyre("Spotify track download failure not found")?;
+
+    let mut failure: entities::spotify_track_download_failure::ActiveModel = failure.into();
+    failure.wont_find = Set(wont_find);
+    failure
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to update spotify track download failure")
+}
+
+/// Makes the matching failures due for retry and resumes the syncs of their
+/// playlists that aren't running. Failures marked "won't find" are left
+/// alone.
+pub async fn retry_download_failures(
+    db: &Database,
+    tasks: &Arc<TaskManager>,
+    filter: RetryFilter,
+) -> Result<ScheduledRetries> {
+    let mut query = entities::spotify_track_download_failure::Entity::find()
+        .filter(entities::spotify_track_download_failure::Column::WontFind.eq(false));
+    query = match filter {
+        RetryFilter::Id(id) => {
+            query.filter(entities::spotify_track_download_failure::Column::Id.eq(id))
+        }
+        RetryFilter::All {
+            spotify_playlist_id,
+        } => match spotify_playlist_id {
+            Some(spotify_playlist_id) => query.filter(
+                entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                    .eq(spotify_playlist_id),
+            ),
+            None => query,
+        },
+        RetryFilter::Reason {
+            reason,
+            spotify_playlist_id,
+        } => {
+            let query =
+                query.filter(entities::spotify_track_download_failure::Column::Reason.eq(reason));
+            match spotify_playlist_id {
+                Some(spotify_playlist_id) => query.filter(
+                    entities::spotify_track_download_failure::Column::SpotifyPlaylistId
+                        .eq(spotify_playlist_id),
+                ),
+                None => query,
+            }
+        }
+    };
+
+    let failures = query
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch spotify track download failures")?;
+    if failures.is_empty() {
+        return Ok(ScheduledRetries {
+            failures: 0,
+            resumed_sync_state_ids: Vec::new(),
+        });
+    }
+
+    // Due now rather than cleared, so the periodic retry still picks them up
+    // when their sync can't be resumed right away
+    let failure_ids: Vec<i64> = failures.iter().map(|failure| failure.id).collect();
+    entities::spotify_track_download_failure::Entity::update_many()
+        .col_expr(
+            entities::spotify_track_download_failure::Column::NextRetryAt,
+            Expr::value(Some(chrono::Utc::now().timestamp())),
+        )
+        .filter(entities::spotify_track_download_failure::Column::Id.is_in(failure_ids))
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to schedule spotify track download retries")?;
+
+    let mut spotify_playlist_ids: Vec<i64> = failures
+        .iter()
+        .map(|failure| failure.spotify_playlist_id)
+        .collect();
+    spotify_playlist_ids.sort_unstable();
+    spotify_playlist_ids.dedup();
+    let mut resumed_sync_state_ids = Vec::new();
+    for spotify_playlist_id in spotify_playlist_ids {
+        if let Some(sync_state) =
+            resume_latest_playlist_sync(db, tasks, spotify_playlist_id).await?
+        {
+            resumed_sync_state_ids.push(sync_state.id);
+        }
+    }
+
+    Ok(ScheduledRetries {
+        failures: failures.len() as u64,
+        resumed_sync_state_ids,
+    })
+}
+
+/// Resumes the syncs of playlists with failures whose back-off has passed.
+/// Run periodically in the background.
+pub async fn retry_due_download_failures(db: &Database, tasks: &Arc<TaskManager>) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    let spotify_playlist_ids: Vec<i64> = entities::spotify_track_download_failure::Entity::find()
+        .select_only()
+        .column(entities::spotify_track_download_failure::Column::SpotifyPlaylistId)
+        .distinct()
+        .filter(entities::spotify_track_download_failure::Column::WontFind.eq(false))
+        .filter(entities::spotify_track_download_failure::Column::NextRetryAt.lte(now))
+        // Only playlists that have been synced before have a sync to resume
+        .filter(
+            entities::spotify_track_download_failure::Column::SpotifyPlaylistId.in_subquery(
+                entities::spotify_playlist_sync_state::Entity::find()
+                    .select_only()
+                    .column(entities::spotify_playlist_sync_state::Column::SpotifyPlaylistId)
+                    .into_query(),
+            ),
+        )
+        .order_by_asc(entities::spotify_track_download_failure::Column::SpotifyPlaylistId)
+        .into_tuple()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch playlists with due download retries")?;
+
+    for spotify_playlist_id in spotify_playlist_ids {
+        if let Some(sync_state) =
+            resume_latest_playlist_sync(db, tasks, spotify_playlist_id).await?
+        {
+            tracing::info!(
+                "Retrying failed downloads of spotify playlist {} in sync {}",
+                spotify_playlist_id,
+                sync_state.id
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{insert_spotify_account, test_db};
+    use sea_orm::ActiveModelBehavior;
+
+    async fn insert_playlist_and_track(
+        db: &Database,
+    ) -> (
+        entities::spotify_playlist::Model,
+        entities::spotify_track::Model,
+    ) {
+        let account = insert_spotify_account(db, "test_user", "rt").await;
+        let playlist = entities::spotify_playlist::ActiveModel {
+            account_id: Set(account.id),
+            spotify_id: Set("playlist".into()),
+            name: Set("Playlist".into()),
+            snapshot_id: Set("snap1".into()),
+            track_count: Set(1),
+            ..entities::spotify_playlist::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let track = entities::spotify_track::ActiveModel {
+            spotify_track_id: Set("sp1".into()),
+            title: Set("Song".into()),
+            artists: Set(entities::spotify_track::StringVec(vec!["Artist".into()])),
+            album: Set("Album".into()),
+            ..entities::spotify_track::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        (playlist, track)
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay_secs(1), RETRY_BASE_DELAY_SECS);
+        assert_eq!(retry_delay_secs(2), RETRY_BASE_DELAY_SECS * 2);
+        assert_eq!(retry_delay_secs(3), RETRY_BASE_DELAY_SECS * 4);
+        assert_eq!(retry_delay_secs(100), RETRY_MAX_DELAY_SECS);
+    }
+
+    #[tokio::test]
+    async fn test_record_download_failure_dedupes_and_counts_attempts() {
+        let db = test_db().await;
+        let (playlist, track) = insert_playlist_and_track(&db).await;
+
+        let first = record_download_failure(&db, playlist.id, &track, "No match found".into())
+            .await
+            .unwrap();
+        let second = record_download_failure(&db, playlist.id, &track, "Download failed".into())
+            .await
+            .unwrap();
+
+        assert_eq!(first.id, second.id);
+        assert_eq!(second.attempts_count, 2);
+        assert_eq!(second.reason, "Download failed");
+        assert!(second.next_retry_at.unwrap() > first.next_retry_at.unwrap());
+        assert_eq!(
+            entities::spotify_track_download_failure::Entity::find()
+                .all(&db.conn)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tracks_not_due_for_retry() {
+        let db = test_db().await;
+        let (playlist, track) = insert_playlist_and_track(&db).await;
+
+        let failure = record_download_failure(&db, playlist.id, &track, "No match found".into())
+            .await
+            .unwrap();
+        // Backing off after the failure
+        assert_eq!(
+            tracks_not_due_for_retry(&db, playlist.id).await.unwrap(),
+            vec!["sp1".to_string()]
+        );
+
+        let mut due: entities::spotify_track_download_failure::ActiveModel = failure.clone().into();
+        due.next_retry_at = Set(None);
+        due.update(&db.conn).await.unwrap();
+        assert!(
+            tracks_not_due_for_retry(&db, playlist.id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // Won't find is skipped regardless of the back-off
+        set_wont_find(&db, failure.id, true).await.unwrap();
+        assert_eq!(
+            tracks_not_due_for_retry(&db, playlist.id).await.unwrap(),
+            vec!["sp1".to_string()]
+        );
+
+        clear_download_failure(&db, playlist.id, "sp1")
+            .await
+            .unwrap();
+        assert!(
+            tracks_not_due_for_retry(&db, playlist.id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_while_sync_is_running_stays_due() {
+        let db = test_db().await;
+        let (playlist, track) = insert_playlist_and_track(&db).await;
+        record_download_failure(&db, playlist.id, &track, "No match found".into())
+            .await
+            .unwrap();
+        entities::spotify_playlist_sync_state::ActiveModel {
+            spotify_playlist_id: Set(playlist.id),
+            sync_status: Set("in_progress".to_string()),
+            ..entities::spotify_playlist_sync_state::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let tasks = Arc::new(TaskManager::new(db.clone()));
+
+        let scheduled = retry_download_failures(
+            &db,
+            &tasks,
+            RetryFilter::All {
+                spotify_playlist_id: None,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(scheduled.failures, 1);
+        assert!(scheduled.resumed_sync_state_ids.is_empty());
+
+        // Left for the periodic retry
+        let failure = entities::spotify_track_download_failure::Entity::find()
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(failure.next_retry_at.unwrap() <= chrono::Utc::now().timestamp());
+        assert!(
+            tracks_not_due_for_retry(&db, playlist.id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_clear_removed_track_failures() {
+        let db = test_db().await;
+        let (playlist, removed) = insert_playlist_and_track(&db).await;
+        let kept = entities::spotify_track::ActiveModel {
+            spotify_track_id: Set("sp2".into()),
+            title: Set("Other Song".into()),
+            artists: Set(entities::spotify_track::StringVec(vec!["Artist".into()])),
+            album: Set("Album".into()),
+            ..entities::spotify_track::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        entities::spotify_track_playlist::Entity::insert(
+            entities::spotify_track_playlist::ActiveModel {
+                spotify_track_id: Set(kept.spotify_track_id.clone()),
+                spotify_playlist_id: Set(playlist.id),
+                position: Set(0),
+            },
+        )
+        .exec(&db.conn)
+        .await
+        .unwrap();
+        for track in [&removed, &kept] {
+            record_download_failure(&db, playlist.id, track, "No match found".into())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            clear_removed_track_failures(&db, playlist.id)
+                .await
+                .unwrap(),
+            1
+        );
+        let remaining: Vec<String> = entities::spotify_track_download_failure::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|failure| failure.spotify_track_id)
+            .collect();
+        assert_eq!(remaining, vec!["sp2".to_string()]);
+    }
+}
@@ -1,9 +1,11 @@
 use std::sync::Arc;
 
-use color_eyre::eyre::{OptionExt, Result, WrapErr};
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
+use sea_orm::prelude::Expr;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
-    QueryOrder, QuerySelect, RelationTrait, Set,
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, EntityTrait,
+    JoinType, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, RelationTrait, Set,
+    TransactionTrait,
 };
 
 use crate::database::Database;
@@ -13,6 +15,13 @@ use crate::services::track::{PaginatedResult, TrackService, TrackWithRelations};
 pub struct MatchedTrackResult {
     pub spotify_track: entities::spotify_track::Model,
     pub local_track: TrackWithRelations,
+    /// The candidate that linked the tracks, if the link was auto-accepted
+    pub auto_accepted_candidate: Option<entities::spotify_match_candidate::Model>,
+}
+
+pub struct MatchDecisionWithTrack {
+    pub decision: entities::spotify_match_decision::Model,
+    pub spotify_track: entities::spotify_track::Model,
 }
 
 pub struct MatchCandidateWithTrack {
@@ -37,65 +46,426 @@ impl SpotifyMatchingService {
     /// Accept a match candidate: link the Spotify track to the local track,
     /// mark the candidate as accepted, and dismiss all other pending candidates.
     pub async fn accept_candidate(&self, candidate_id: i64) -> Result<()> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
         let candidate = entities::spotify_match_candidate::Entity::find_by_id(candidate_id)
-            .one(&self.db.conn)
+            .one(&txn)
             .await
             .wrap_err("Failed to fetch match candidate")?
             .ok_or_eyre("Match candidate not found")?;
 
-        self.link_spotify_to_local(&candidate.spotify_track_id, candidate.local_track_id)
-            .await?;
+        let previous_local_track_id = link_spotify_to_local(
+            &txn,
+            &candidate.spotify_track_id,
+            Some(candidate.local_track_id),
+        )
+        .await?;
 
         // Mark the accepted candidate
         let mut candidate_active: entities::spotify_match_candidate::ActiveModel =
             candidate.clone().into();
         candidate_active.status = Set(entities::spotify_match_candidate::CandidateStatus::Accepted);
         candidate_active
-            .update(&self.db.conn)
+            .update(&txn)
             .await
             .wrap_err("Failed to update match candidate")?;
 
-        self.dismiss_pending_candidates(&candidate.spotify_track_id)
-            .await
+        let dismissed = dismiss_pending_candidates(&txn, &candidate.spotify_track_id).await?;
+
+        record_decision(
+            &txn,
+            entities::spotify_match_decision::ActiveModel {
+                spotify_track_id: Set(candidate.spotify_track_id),
+                action: Set(entities::spotify_match_decision::MatchDecisionAction::Link),
+                actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                candidate_id: Set(Some(candidate.id)),
+                previous_candidate_status: Set(Some(candidate.status)),
+                previous_local_track_id: Set(previous_local_track_id),
+                local_track_id: Set(Some(candidate.local_track_id)),
+                dismissed_candidate_ids: Set(entities::spotify_match_decision::IdVec(dismissed)),
+                ..entities::spotify_match_decision::ActiveModel::new()
+            },
+        )
+        .await?;
+
+        txn.commit().await.wrap_err("Failed to commit transaction")
     }
 
     /// Manually match a Spotify track to a local track and dismiss pending candidates.
     pub async fn manually_match(&self, spotify_track_id: &str, local_track_id: i64) -> Result<()> {
-        self.link_spotify_to_local(spotify_track_id, local_track_id)
-            .await?;
-        self.dismiss_pending_candidates(spotify_track_id).await
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let previous_local_track_id =
+            link_spotify_to_local(&txn, spotify_track_id, Some(local_track_id)).await?;
+        let dismissed = dismiss_pending_candidates(&txn, spotify_track_id).await?;
+
+        record_decision(
+            &txn,
+            entities::spotify_match_decision::ActiveModel {
+                spotify_track_id: Set(spotify_track_id.to_string()),
+                action: Set(entities::spotify_match_decision::MatchDecisionAction::Link),
+                actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                previous_local_track_id: Set(previous_local_track_id),
+                local_track_id: Set(Some(local_track_id)),
+                dismissed_candidate_ids: Set(entities::spotify_match_decision::IdVec(dismissed)),
+                ..entities::spotify_match_decision::ActiveModel::new()
+            },
+        )
+        .await?;
+
+        txn.commit().await.wrap_err("Failed to commit transaction")
     }
 
     /// Dismiss all pending candidates for a Spotify track without matching.
     pub async fn dismiss_track(&self, spotify_track_id: &str) -> Result<()> {
-        self.dismiss_pending_candidates(spotify_track_id).await
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id(spotify_track_id)
+            .one(&txn)
+            .await
+            .wrap_err("Failed to fetch spotify track")?
+            .ok_or_eyre("Spotify track not found")?;
+        let dismissed = dismiss_pending_candidates(&txn, spotify_track_id).await?;
+
+        record_decision(
+            &txn,
+            entities::spotify_match_decision::ActiveModel {
+                spotify_track_id: Set(spotify_track_id.to_string()),
+                action: Set(entities::spotify_match_decision::MatchDecisionAction::Dismiss),
+                actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                previous_local_track_id: Set(spotify_track.local_track_id),
+                local_track_id: Set(spotify_track.local_track_id),
+                dismissed_candidate_ids: Set(entities::spotify_match_decision::IdVec(dismissed)),
+                ..entities::spotify_match_decision::ActiveModel::new()
+            },
+        )
+        .await?;
+
+        txn.commit().await.wrap_err("Failed to commit transaction")
+    }
+
+    /// Remove the link of a wrongly matched Spotify track. The candidate the
+    /// link came from is dismissed so the matcher does not propose it again.
+    pub async fn unlink(&self, spotify_track_id: &str) -> Result<()> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let previous_local_track_id = link_spotify_to_local(&txn, spotify_track_id, None)
+            .await?
+            .ok_or_eyre("Spotify track is not matched")?;
+
+        let candidate = entities::spotify_match_candidate::Entity::find()
+            .filter(entities::spotify_match_candidate::Column::SpotifyTrackId.eq(spotify_track_id))
+            .filter(
+                entities::spotify_match_candidate::Column::LocalTrackId.eq(previous_local_track_id),
+            )
+            .filter(entities::spotify_match_candidate::Column::Status.is_in([
+                entities::spotify_match_candidate::CandidateStatus::Accepted,
+                entities::spotify_match_candidate::CandidateStatus::AutoAccepted,
+            ]))
+            .one(&txn)
+            .await
+            .wrap_err("Failed to fetch accepted candidate")?;
+
+        let previous_candidate_status = candidate.as_ref().map(|c| c.status.clone());
+        let candidate_id = candidate.as_ref().map(|c| c.id);
+        if let Some(candidate) = candidate {
+            let mut candidate: entities::spotify_match_candidate::ActiveModel = candidate.into();
+            candidate.status = Set(entities::spotify_match_candidate::CandidateStatus::Dismissed);
+            candidate
+                .update(&txn)
+                .await
+                .wrap_err("Failed to dismiss accepted candidate")?;
+        }
+
+        record_decision(
+            &txn,
+            entities::spotify_match_decision::ActiveModel {
+                spotify_track_id: Set(spotify_track_id.to_string()),
+                action: Set(entities::spotify_match_decision::MatchDecisionAction::Unlink),
+                actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                candidate_id: Set(candidate_id),
+                previous_candidate_status: Set(previous_candidate_status),
+                previous_local_track_id: Set(Some(previous_local_track_id)),
+                local_track_id: Set(None),
+                ..entities::spotify_match_decision::ActiveModel::new()
+            },
+        )
+        .await?;
+
+        txn.commit().await.wrap_err("Failed to commit transaction")
+    }
+
+    /// Undo auto-accepted matches, or only the given candidates when
+    /// `candidate_ids` is set. Spotify tracks still linked to the auto-accepted
+    /// local track are unlinked and the candidates are dismissed, so the
+    /// matcher doesn't link them again. Other candidates stay up for review.
+    /// Returns the number of reverted candidates.
+    pub async fn revert_auto_accepted(&self, candidate_ids: Option<Vec<i64>>) -> Result<u64> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let mut query = entities::spotify_match_candidate::Entity::find().filter(
+            entities::spotify_match_candidate::Column::Status
+                .eq(entities::spotify_match_candidate::CandidateStatus::AutoAccepted),
+        );
+        if let Some(candidate_ids) = candidate_ids {
+            query =
+                query.filter(entities::spotify_match_candidate::Column::Id.is_in(candidate_ids));
+        }
+        let candidates = query
+            .all(&txn)
+            .await
+            .wrap_err("Failed to fetch auto-accepted candidates")?;
+
+        let now = chrono::Utc::now().timestamp();
+        for candidate in &candidates {
+            // A track that was re-matched by hand since keeps its new link
+            let unlinked = entities::spotify_track::Entity::update_many()
+                .col_expr(
+                    entities::spotify_track::Column::LocalTrackId,
+                    Expr::value(Option::<i64>::None),
+                )
+                .col_expr(entities::spotify_track::Column::UpdatedAt, Expr::value(now))
+                .filter(
+                    entities::spotify_track::Column::SpotifyTrackId.eq(&candidate.spotify_track_id),
+                )
+                .filter(entities::spotify_track::Column::LocalTrackId.eq(candidate.local_track_id))
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to unlink spotify track")?;
+
+            if unlinked.rows_affected > 0 {
+                record_decision(
+                    &txn,
+                    entities::spotify_match_decision::ActiveModel {
+                        spotify_track_id: Set(candidate.spotify_track_id.clone()),
+                        action: Set(entities::spotify_match_decision::MatchDecisionAction::Unlink),
+                        actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                        candidate_id: Set(Some(candidate.id)),
+                        previous_candidate_status: Set(Some(candidate.status.clone())),
+                        previous_local_track_id: Set(Some(candidate.local_track_id)),
+                        local_track_id: Set(None),
+                        ..entities::spotify_match_decision::ActiveModel::new()
+                    },
+                )
+                .await?;
+            }
+        }
+
+        let reverted = entities::spotify_match_candidate::Entity::update_many()
+            .col_expr(
+                entities::spotify_match_candidate::Column::Status,
+                Expr::value(entities::spotify_match_candidate::CandidateStatus::Dismissed),
+            )
+            .col_expr(
+                entities::spotify_match_candidate::Column::UpdatedAt,
+                Expr::value(now),
+            )
+            .filter(
+                entities::spotify_match_candidate::Column::Id
+                    .is_in(candidates.iter().map(|candidate| candidate.id)),
+            )
+            .exec(&txn)
+            .await
+            .wrap_err("Failed to revert auto-accepted candidates")?;
+
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(reverted.rows_affected)
     }
 
-    async fn link_spotify_to_local(
+    /// Match decisions, most recent first, optionally for a single Spotify track.
+    pub async fn list_decisions(
         &self,
-        spotify_track_id: &str,
-        local_track_id: i64,
-    ) -> Result<()> {
-        let spotify_track = entities::spotify_track::Entity::find()
-            .filter(entities::spotify_track::Column::SpotifyTrackId.eq(spotify_track_id))
-            .one(&self.db.conn)
+        spotify_track_id: Option<&str>,
+        page: usize,
+        page_size: usize,
+    ) -> Result<PaginatedResult<MatchDecisionWithTrack>> {
+        let mut query = entities::spotify_match_decision::Entity::find();
+        if let Some(spotify_track_id) = spotify_track_id {
+            query = query.filter(
+                entities::spotify_match_decision::Column::SpotifyTrackId.eq(spotify_track_id),
+            );
+        }
+
+        let total_count = query
+            .clone()
+            .count(&self.db.conn)
+            .await
+            .wrap_err("Failed to count match decisions")?;
+
+        let offset = (page.saturating_sub(1)) * page_size;
+        let items = query
+            .find_also_related(entities::spotify_track::Entity)
+            .order_by_desc(entities::spotify_match_decision::Column::CreatedAt)
+            .order_by_desc(entities::spotify_match_decision::Column::Id)
+            .limit(page_size as u64)
+            .offset(offset as u64)
+            .all(&self.db.conn)
+            .await
+            .wrap_err("Failed to fetch match decisions")?
+            .into_iter()
+            .map(|(decision, spotify_track)| {
+                Ok(MatchDecisionWithTrack {
+                    decision,
+                    spotify_track: spotify_track
+                        .ok_or_eyre("Spotify track not found for match decision")?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(PaginatedResult {
+            items,
+            total_count,
+            page,
+            page_size,
+        })
+    }
+
+    /// Undo a decision: restore the Spotify track's previous link and the
+    /// candidate statuses it changed. Fails if the track's link has changed
+    /// since, so a later decision is never silently overwritten. The revert is
+    /// itself recorded and returned.
+    pub async fn revert_decision(
+        &self,
+        decision_id: i64,
+    ) -> Result<entities::spotify_match_decision::Model> {
+        let txn = self
+            .db
+            .conn
+            .begin()
+            .await
+            .wrap_err("Failed to begin transaction")?;
+
+        let decision = entities::spotify_match_decision::Entity::find_by_id(decision_id)
+            .one(&txn)
+            .await
+            .wrap_err("Failed to fetch match decision")?
+            .ok_or_eyre("Match decision not found")?;
+        if decision.reverted_at.is_some() {
+            return Err(eyre!("Match decision was already reverted"));
+        }
+        if decision.action == entities::spotify_match_decision::MatchDecisionAction::Revert {
+            return Err(eyre!("A revert cannot be reverted"));
+        }
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id(&decision.spotify_track_id)
+            .one(&txn)
             .await
             .wrap_err("Failed to fetch spotify track")?
             .ok_or_eyre("Spotify track not found")?;
+        if spotify_track.local_track_id != decision.local_track_id {
+            return Err(eyre!(
+                "Spotify track's match has changed since this decision"
+            ));
+        }
+
+        link_spotify_to_local(
+            &txn,
+            &decision.spotify_track_id,
+            decision.previous_local_track_id,
+        )
+        .await?;
 
-        let mut spotify_track_active: entities::spotify_track::ActiveModel = spotify_track.into();
-        spotify_track_active.local_track_id = Set(Some(local_track_id));
-        spotify_track_active
-            .update(&self.db.conn)
+        if let (Some(candidate_id), Some(status)) =
+            (decision.candidate_id, &decision.previous_candidate_status)
+        {
+            entities::spotify_match_candidate::Entity::update_many()
+                .col_expr(
+                    entities::spotify_match_candidate::Column::Status,
+                    Expr::value(status.clone()),
+                )
+                .col_expr(
+                    entities::spotify_match_candidate::Column::UpdatedAt,
+                    Expr::value(chrono::Utc::now().timestamp()),
+                )
+                .filter(entities::spotify_match_candidate::Column::Id.eq(candidate_id))
+                .exec(&txn)
+                .await
+                .wrap_err("Failed to restore candidate status")?;
+        }
+
+        // Only candidates that are still dismissed, in case one was reviewed again
+        entities::spotify_match_candidate::Entity::update_many()
+            .col_expr(
+                entities::spotify_match_candidate::Column::Status,
+                Expr::value(entities::spotify_match_candidate::CandidateStatus::Pending),
+            )
+            .col_expr(
+                entities::spotify_match_candidate::Column::UpdatedAt,
+                Expr::value(chrono::Utc::now().timestamp()),
+            )
+            .filter(
+                entities::spotify_match_candidate::Column::Id
+                    .is_in(decision.dismissed_candidate_ids.0.clone()),
+            )
+            .filter(
+                entities::spotify_match_candidate::Column::Status
+                    .eq(entities::spotify_match_candidate::CandidateStatus::Dismissed),
+            )
+            .exec(&txn)
+            .await
+            .wrap_err("Failed to restore dismissed candidates")?;
+
+        let revert = record_decision(
+            &txn,
+            entities::spotify_match_decision::ActiveModel {
+                spotify_track_id: Set(decision.spotify_track_id.clone()),
+                action: Set(entities::spotify_match_decision::MatchDecisionAction::Revert),
+                actor: Set(entities::spotify_match_decision::MatchDecisionActor::User),
+                candidate_id: Set(decision.candidate_id),
+                previous_local_track_id: Set(decision.local_track_id),
+                local_track_id: Set(decision.previous_local_track_id),
+                reverts_decision_id: Set(Some(decision.id)),
+                ..entities::spotify_match_decision::ActiveModel::new()
+            },
+        )
+        .await?;
+
+        let mut decision: entities::spotify_match_decision::ActiveModel = decision.into();
+        decision.reverted_at = Set(Some(revert.created_at));
+        decision
+            .update(&txn)
             .await
-            .wrap_err("Failed to update spotify track")?;
+            .wrap_err("Failed to mark match decision as reverted")?;
 
-        Ok(())
+        txn.commit()
+            .await
+            .wrap_err("Failed to commit transaction")?;
+
+        Ok(revert)
     }
 
     pub async fn list_matched_tracks(
         &self,
         search: Option<&str>,
+        auto_accepted_only: bool,
         page: usize,
         page_size: usize,
     ) -> Result<PaginatedResult<MatchedTrackResult>> {
@@ -113,6 +483,21 @@ impl SpotifyMatchingService {
             base_condition = base_condition.add(search_condition);
         }
 
+        if auto_accepted_only {
+            base_condition =
+                base_condition.add(
+                    entities::spotify_track::Column::SpotifyTrackId.in_subquery(
+                        entities::spotify_match_candidate::Entity::find()
+                            .select_only()
+                            .column(entities::spotify_match_candidate::Column::SpotifyTrackId)
+                            .filter(entities::spotify_match_candidate::Column::Status.eq(
+                                entities::spotify_match_candidate::CandidateStatus::AutoAccepted,
+                            ))
+                            .into_query(),
+                    ),
+                );
+        }
+
         let total_count = entities::spotify_track::Entity::find()
             .filter(base_condition.clone())
             .count(&self.db.conn)
@@ -135,9 +520,23 @@ impl SpotifyMatchingService {
                 .local_track_id
                 .ok_or_eyre("Spotify track should have local_track_id")?;
             let local_track = track_service.get_track_by_id(local_track_id).await?;
+            let auto_accepted_candidate = entities::spotify_match_candidate::Entity::find()
+                .filter(
+                    entities::spotify_match_candidate::Column::SpotifyTrackId
+                        .eq(&spotify_track.spotify_track_id),
+                )
+                .filter(entities::spotify_match_candidate::Column::LocalTrackId.eq(local_track_id))
+                .filter(
+                    entities::spotify_match_candidate::Column::Status
+                        .eq(entities::spotify_match_candidate::CandidateStatus::AutoAccepted),
+                )
+                .one(&self.db.conn)
+                .await
+                .wrap_err("Failed to fetch auto-accepted candidate")?;
             items.push(MatchedTrackResult {
                 spotify_track,
                 local_track,
+                auto_accepted_candidate,
             });
         }
 
@@ -336,31 +735,70 @@ impl SpotifyMatchingService {
             .await
             .wrap_err("Failed to fetch unmatched spotify tracks")
     }
+}
 
-    async fn dismiss_pending_candidates(&self, spotify_track_id: &str) -> Result<()> {
-        let pending_candidates = entities::spotify_match_candidate::Entity::find()
-            .filter(entities::spotify_match_candidate::Column::SpotifyTrackId.eq(spotify_track_id))
-            .filter(
-                entities::spotify_match_candidate::Column::Status
-                    .eq(entities::spotify_match_candidate::CandidateStatus::Pending),
-            )
-            .all(&self.db.conn)
+/// Set or clear a Spotify track's local track and return the previous one.
+async fn link_spotify_to_local<C: ConnectionTrait>(
+    conn: &C,
+    spotify_track_id: &str,
+    local_track_id: Option<i64>,
+) -> Result<Option<i64>> {
+    let spotify_track = entities::spotify_track::Entity::find_by_id(spotify_track_id)
+        .one(conn)
+        .await
+        .wrap_err("Failed to fetch spotify track")?
+        .ok_or_eyre("Spotify track not found")?;
+    let previous_local_track_id = spotify_track.local_track_id;
+
+    let mut spotify_track_active: entities::spotify_track::ActiveModel = spotify_track.into();
+    spotify_track_active.local_track_id = Set(local_track_id);
+    spotify_track_active
+        .update(conn)
+        .await
+        .wrap_err("Failed to update spotify track")?;
+
+    Ok(previous_local_track_id)
+}
+
+/// Dismiss the pending candidates of a Spotify track and return their IDs.
+async fn dismiss_pending_candidates<C: ConnectionTrait>(
+    conn: &C,
+    spotify_track_id: &str,
+) -> Result<Vec<i64>> {
+    let pending_candidates = entities::spotify_match_candidate::Entity::find()
+        .filter(entities::spotify_match_candidate::Column::SpotifyTrackId.eq(spotify_track_id))
+        .filter(
+            entities::spotify_match_candidate::Column::Status
+                .eq(entities::spotify_match_candidate::CandidateStatus::Pending),
+        )
+        .all(conn)
+        .await
+        .wrap_err("Failed to fetch pending candidates")?;
+
+    let mut dismissed = Vec::with_capacity(pending_candidates.len());
+    for candidate in pending_candidates {
+        dismissed.push(candidate.id);
+        let mut candidate_active: entities::spotify_match_candidate::ActiveModel = candidate.into();
+        candidate_active.status =
+            Set(entities::spotify_match_candidate::CandidateStatus::Dismissed);
+        candidate_active
+            .update(conn)
             .await
-            .wrap_err("Failed to fetch pending candidates")?;
+            .wrap_err("Failed to dismiss candidate")?;
+    }
 
-        for candidate in pending_candidates {
-            let mut candidate_active: entities::spotify_match_candidate::ActiveModel =
-                candidate.into();
-            candidate_active.status =
-                Set(entities::spotify_match_candidate::CandidateStatus::Dismissed);
-            candidate_active
-                .update(&self.db.conn)
-                .await
-                .wrap_err("Failed to dismiss candidate")?;
-        }
+    Ok(dismissed)
+}
 
-        Ok(())
-    }
+/// Add an entry to the match decision audit log.
+pub(crate) async fn record_decision<C: ConnectionTrait>(
+    conn: &C,
+    decision: entities::spotify_match_decision::ActiveModel,
+) -> Result<entities::spotify_match_decision::Model> {
+    decision
+        .insert(conn)
+        .await
+        .wrap_err("Failed to record match decision")
 }
 
 #[cfg(test)]
@@ -561,7 +999,10 @@ mod tests {
         st.insert(&db.conn).await.unwrap();
 
         let service = SpotifyMatchingService::new(db);
-        let result = service.list_matched_tracks(None, 1, 25).await.unwrap();
+        let result = service
+            .list_matched_tracks(None, false, 1, 25)
+            .await
+            .unwrap();
 
         assert_eq!(result.total_count, 1);
         assert_eq!(result.items.len(), 1);
@@ -625,6 +1066,7 @@ mod tests {
         let link = entities::spotify_track_playlist::ActiveModel {
             spotify_track_id: Set("sp1".into()),
             spotify_playlist_id: Set(playlist.id),
+            position: Set(0),
         };
         entities::spotify_track_playlist::Entity::insert(link)
             .exec(&db.conn)
@@ -664,4 +1106,262 @@ mod tests {
         assert_eq!(result.total_count, 1);
         assert_eq!(result.items[0].track.title, "Bohemian Rhapsody");
     }
+
+    async fn insert_auto_accepted_candidate(
+        db: &Database,
+        spotify_track_id: &str,
+        local_track_id: i64,
+    ) -> i64 {
+        let id = insert_candidate(db, spotify_track_id, local_track_id, 0.95).await;
+        let candidate = entities::spotify_match_candidate::Entity::find_by_id(id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut candidate: entities::spotify_match_candidate::ActiveModel = candidate.into();
+        candidate.status = Set(entities::spotify_match_candidate::CandidateStatus::AutoAccepted);
+        candidate.update(&db.conn).await.unwrap();
+        id
+    }
+
+    async fn link(db: &Database, spotify_track_id: &str, local_track_id: i64) {
+        let spotify_track = entities::spotify_track::Entity::find_by_id(spotify_track_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut spotify_track: entities::spotify_track::ActiveModel = spotify_track.into();
+        spotify_track.local_track_id = Set(Some(local_track_id));
+        spotify_track.update(&db.conn).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_auto_accepted_matches() {
+        let db = test_db().await;
+        let auto_id = insert_local_track(&db, "Auto", "/auto.flac").await;
+        let manual_id = insert_local_track(&db, "Manual", "/manual.flac").await;
+        insert_spotify_track(&db, "sp1", "Auto Spotify").await;
+        insert_spotify_track(&db, "sp2", "Manual Spotify").await;
+        link(&db, "sp1", auto_id).await;
+        link(&db, "sp2", manual_id).await;
+        let candidate_id = insert_auto_accepted_candidate(&db, "sp1", auto_id).await;
+
+        let service = SpotifyMatchingService::new(db);
+        let all = service
+            .list_matched_tracks(None, false, 1, 25)
+            .await
+            .unwrap();
+        assert_eq!(all.total_count, 2);
+
+        let auto_accepted = service
+            .list_matched_tracks(None, true, 1, 25)
+            .await
+            .unwrap();
+        assert_eq!(auto_accepted.total_count, 1);
+        assert_eq!(auto_accepted.items[0].spotify_track.spotify_track_id, "sp1");
+        assert_eq!(
+            auto_accepted.items[0]
+                .auto_accepted_candidate
+                .as_ref()
+                .map(|candidate| candidate.id),
+            Some(candidate_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_auto_accepted() {
+        let db = test_db().await;
+        let auto_id = insert_local_track(&db, "Auto", "/auto.flac").await;
+        let manual_id = insert_local_track(&db, "Manual", "/manual.flac").await;
+        insert_spotify_track(&db, "sp1", "Auto Spotify").await;
+        insert_spotify_track(&db, "sp2", "Overridden Spotify").await;
+        link(&db, "sp1", auto_id).await;
+        // Auto-accepted, then re-matched by hand to another track
+        link(&db, "sp2", manual_id).await;
+        insert_auto_accepted_candidate(&db, "sp1", auto_id).await;
+        insert_auto_accepted_candidate(&db, "sp2", auto_id).await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        let reverted = service.revert_auto_accepted(None).await.unwrap();
+        assert_eq!(reverted, 2);
+
+        let sp1 = entities::spotify_track::Entity::find_by_id("sp1")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sp1.local_track_id, None);
+        let sp2 = entities::spotify_track::Entity::find_by_id("sp2")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sp2.local_track_id, Some(manual_id));
+
+        let candidates = entities::spotify_match_candidate::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert!(candidates.iter().all(|candidate| candidate.status
+            == entities::spotify_match_candidate::CandidateStatus::Dismissed));
+    }
+
+    #[tokio::test]
+    async fn test_revert_selected_auto_accepted() {
+        let db = test_db().await;
+        let first_id = insert_local_track(&db, "First", "/first.flac").await;
+        let second_id = insert_local_track(&db, "Second", "/second.flac").await;
+        insert_spotify_track(&db, "sp1", "First Spotify").await;
+        insert_spotify_track(&db, "sp2", "Second Spotify").await;
+        link(&db, "sp1", first_id).await;
+        link(&db, "sp2", second_id).await;
+        let first_candidate = insert_auto_accepted_candidate(&db, "sp1", first_id).await;
+        insert_auto_accepted_candidate(&db, "sp2", second_id).await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        let reverted = service
+            .revert_auto_accepted(Some(vec![first_candidate]))
+            .await
+            .unwrap();
+        assert_eq!(reverted, 1);
+
+        let sp2 = entities::spotify_track::Entity::find_by_id("sp2")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sp2.local_track_id, Some(second_id));
+    }
+
+    async fn candidate_status(
+        db: &Database,
+        candidate_id: i64,
+    ) -> entities::spotify_match_candidate::CandidateStatus {
+        entities::spotify_match_candidate::Entity::find_by_id(candidate_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap()
+            .status
+    }
+
+    async fn local_track_id(db: &Database, spotify_track_id: &str) -> Option<i64> {
+        entities::spotify_track::Entity::find_by_id(spotify_track_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap()
+            .local_track_id
+    }
+
+    #[tokio::test]
+    async fn test_unlink() {
+        let db = test_db().await;
+        let local_id = insert_local_track(&db, "Track A", "/a.flac").await;
+        insert_spotify_track(&db, "sp1", "Spotify Track").await;
+        let c1 = insert_candidate(&db, "sp1", local_id, 0.9).await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        service.accept_candidate(c1).await.unwrap();
+        service.unlink("sp1").await.unwrap();
+
+        assert_eq!(local_track_id(&db, "sp1").await, None);
+        assert_eq!(
+            candidate_status(&db, c1).await,
+            entities::spotify_match_candidate::CandidateStatus::Dismissed
+        );
+        assert!(service.unlink("sp1").await.is_err());
+
+        let decisions = service.list_decisions(Some("sp1"), 1, 25).await.unwrap();
+        assert_eq!(decisions.total_count, 2);
+        let unlink = &decisions.items[0].decision;
+        assert_eq!(
+            unlink.action,
+            entities::spotify_match_decision::MatchDecisionAction::Unlink
+        );
+        assert_eq!(unlink.previous_local_track_id, Some(local_id));
+        assert_eq!(unlink.candidate_id, Some(c1));
+        assert_eq!(decisions.items[0].spotify_track.title, "Spotify Track");
+    }
+
+    #[tokio::test]
+    async fn test_revert_accept_decision() {
+        let db = test_db().await;
+        let local_id1 = insert_local_track(&db, "Track A", "/a.flac").await;
+        let local_id2 = insert_local_track(&db, "Track B", "/b.flac").await;
+        insert_spotify_track(&db, "sp1", "Spotify Track").await;
+        let c1 = insert_candidate(&db, "sp1", local_id1, 0.95).await;
+        let c2 = insert_candidate(&db, "sp1", local_id2, 0.80).await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        service.accept_candidate(c1).await.unwrap();
+
+        let decisions = service.list_decisions(None, 1, 25).await.unwrap();
+        let accept = &decisions.items[0].decision;
+        assert_eq!(
+            accept.actor,
+            entities::spotify_match_decision::MatchDecisionActor::User
+        );
+        assert_eq!(accept.dismissed_candidate_ids.0, vec![c2]);
+
+        let revert = service.revert_decision(accept.id).await.unwrap();
+        assert_eq!(revert.reverts_decision_id, Some(accept.id));
+
+        // Back to the review queue as it was before accepting
+        assert_eq!(local_track_id(&db, "sp1").await, None);
+        assert_eq!(
+            candidate_status(&db, c1).await,
+            entities::spotify_match_candidate::CandidateStatus::Pending
+        );
+        assert_eq!(
+            candidate_status(&db, c2).await,
+            entities::spotify_match_candidate::CandidateStatus::Pending
+        );
+
+        assert!(service.revert_decision(accept.id).await.is_err());
+        assert!(service.revert_decision(revert.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_revert_stale_decision_fails() {
+        let db = test_db().await;
+        let local_id1 = insert_local_track(&db, "Track A", "/a.flac").await;
+        let local_id2 = insert_local_track(&db, "Track B", "/b.flac").await;
+        insert_spotify_track(&db, "sp1", "Spotify Track").await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        service.manually_match("sp1", local_id1).await.unwrap();
+        service.manually_match("sp1", local_id2).await.unwrap();
+
+        let decisions = service.list_decisions(Some("sp1"), 1, 25).await.unwrap();
+        let (latest, first) = (&decisions.items[0].decision, &decisions.items[1].decision);
+        assert_eq!(latest.previous_local_track_id, Some(local_id1));
+
+        // The first link was replaced by the second one
+        assert!(service.revert_decision(first.id).await.is_err());
+
+        service.revert_decision(latest.id).await.unwrap();
+        assert_eq!(local_track_id(&db, "sp1").await, Some(local_id1));
+    }
+
+    #[tokio::test]
+    async fn test_revert_dismiss_decision() {
+        let db = test_db().await;
+        let local_id = insert_local_track(&db, "Track A", "/a.flac").await;
+        insert_spotify_track(&db, "sp1", "Spotify Track").await;
+        let c1 = insert_candidate(&db, "sp1", local_id, 0.95).await;
+
+        let service = SpotifyMatchingService::new(db.clone());
+        service.dismiss_track("sp1").await.unwrap();
+
+        let decisions = service.list_decisions(None, 1, 25).await.unwrap();
+        service
+            .revert_decision(decisions.items[0].decision.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            candidate_status(&db, c1).await,
+            entities::spotify_match_candidate::CandidateStatus::Pending
+        );
+    }
 }
@@ -0,0 +1,516 @@
+//! Matching in the reverse direction: local tracks without a Spotify link are
+//! looked up on Spotify to store their Spotify ID, popularity and audio
+//! features, used for sorting and smart playlist rules.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, WrapErr};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Select, Set,
+};
+use serde::{Deserialize, Serialize};
+
+use super::matcher::{MatchConfidence, MatcherConfig, Track, find_matches, normalize_track};
+use super::similarity_filter::db_track_to_track;
+use crate::database::Database;
+use crate::entities;
+use crate::entities::background_task::TaskKind;
+use crate::ports::spotify::{SpotifyApiAudioFeatures, SpotifyApiTrack, SpotifyClient};
+use crate::services::spotify::token_manager::SpotifyTokenManager;
+use crate::services::tasks::{TaskContext, TaskHandler, TaskManager};
+
+/// Number of tracks looked up between checkpoints
+const ENRICHMENT_BATCH_SIZE: u64 = 50;
+
+/// Lookups failing in a row before a run gives up, e.g. once the account's
+/// access was revoked or Spotify is down
+const MAX_CONSECUTIVE_FAILURES: u32 = 10;
+
+/// Local tracks that were never looked up and aren't linked to a Spotify
+/// track already.
+fn unenriched_tracks() -> Select<entities::track::Entity> {
+    let linked_track_ids = entities::spotify_track::Entity::find()
+        .select_only()
+        .column(entities::spotify_track::Column::LocalTrackId)
+        .filter(entities::spotify_track::Column::LocalTrackId.is_not_null())
+        .into_query();
+
+    entities::track::Entity::find()
+        .filter(entities::track::Column::SpotifyEnrichedAt.is_null())
+        .filter(entities::track::Column::Id.not_in_subquery(linked_track_ids))
+}
+
+fn track_isrcs(track: &entities::track::Model) -> Vec<String> {
+    track
+        .isrcs
+        .as_deref()
+        .and_then(|isrcs| serde_json::from_str(isrcs).ok())
+        .unwrap_or_default()
+}
+
+fn api_track_to_track(track: &SpotifyApiTrack) -> Option<Track> {
+    let (primary_artist, secondary_artists) = track.artists.split_first()?;
+    Some(Track {
+        title: track.name.clone(),
+        primary_artist: primary_artist.clone(),
+        secondary_artists: secondary_artists.to_vec(),
+        album: track.album_name.clone(),
+        duration_ms: track.duration_ms as u32,
+    })
+}
+
+/// Finds the Spotify track of a local track: the first ISRC hit, otherwise the
+/// best artist and title search result if it is a high confidence match.
+async fn find_spotify_track(
+    db: &Database,
+    client: &impl SpotifyClient,
+    config: &MatcherConfig,
+    track: &entities::track::Model,
+) -> Result<Option<SpotifyApiTrack>> {
+    for isrc in track_isrcs(track) {
+        let results = client.search_tracks(&format!("isrc:{}", isrc)).await?;
+        if let Some(result) = results.into_iter().next() {
+            return Ok(Some(result));
+        }
+    }
+
+    let local = match db_track_to_track(&db.conn, track).await {
+        Ok(local) => local,
+        Err(e) => {
+            // Tracks without an artist or duration can't be matched by metadata
+            tracing::debug!("Skipping spotify search for track {}: {}", track.id, e);
+            return Ok(None);
+        }
+    };
+    let query = format!(
+        "track:\"{}\" artist:\"{}\"",
+        local.title.replace('"', ""),
+        local.primary_artist.replace('"', "")
+    );
+    let results: Vec<(SpotifyApiTrack, Track)> = client
+        .search_tracks(&query)
+        .await?
+        .into_iter()
+        .filter_map(|result| {
+            let track = api_track_to_track(&result)?;
+            Some((result, track))
+        })
+        .collect();
+    let normalized = results
+        .iter()
+        .map(|(_, track)| normalize_track(track))
+        .collect::<Vec<_>>();
+
+    // Search results take the place of the local candidates of the forward
+    // matcher
+    let best = find_matches(&normalize_track(&local), &normalized, config)
+        .into_iter()
+        .next()
+        .filter(|(_, result)| matches!(result.confidence, MatchConfidence::High));
+    Ok(best.and_then(|(index, _)| results.into_iter().nth(index).map(|(result, _)| result)))
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct EnrichmentCounts {
+    enriched: i64,
+    not_found: i64,
+    failed: i64,
+}
+
+/// Looks up the tracks on Spotify and stores what was found. Tracks that
+/// failed to look up are left to be retried by the next run. Audio features
+/// are optional, as Spotify refuses them to newer apps; without them the
+/// Spotify ID and popularity are still stored.
+///
+/// `consecutive_failures` carries the lookups failed in a row over batches;
+/// once it reaches `MAX_CONSECUTIVE_FAILURES` an error is returned.
+async fn enrich_tracks(
+    db: &Database,
+    client: &impl SpotifyClient,
+    config: &MatcherConfig,
+    tracks: Vec<entities::track::Model>,
+    consecutive_failures: &mut u32,
+) -> Result<EnrichmentCounts> {
+    let mut counts = EnrichmentCounts::default();
+    let mut found = Vec::new();
+    for track in tracks {
+        match find_spotify_track(db, client, config, &track).await {
+            Ok(Some(spotify_track)) => {
+                *consecutive_failures = 0;
+                found.push((track, spotify_track));
+            }
+            Ok(None) => {
+                *consecutive_failures = 0;
+                save_enrichment(db, track, None, None).await?;
+                counts.not_found += 1;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to look up track {} on spotify: {:?}", track.id, e);
+                counts.failed += 1;
+                *consecutive_failures += 1;
+                if *consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                    return Err(e.wrap_err(format!(
+                        "Giving up after {} spotify lookups failed in a row",
+                        consecutive_failures
+                    )));
+                }
+            }
+        }
+    }
+
+    let spotify_ids = found
+        .iter()
+        .map(|(_, spotify_track)| spotify_track.id.clone())
+        .collect::<Vec<_>>();
+    let mut features: HashMap<String, SpotifyApiAudioFeatures> =
+        match client.audio_features(&spotify_ids).await {
+            Ok(features) => features
+                .into_iter()
+                .map(|features| (features.track_id.clone(), features))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to fetch spotify audio features: {:?}", e);
+                HashMap::new()
+            }
+        };
+    for (track, spotify_track) in found {
+        let features = features.remove(&spotify_track.id);
+        save_enrichment(db, track, Some(spotify_track), features).await?;
+        counts.enriched += 1;
+    }
+
+    Ok(counts)
+}
+
+async fn save_enrichment(
+    db: &Database,
+    track: entities::track::Model,
+    spotify_track: Option<SpotifyApiTrack>,
+    features: Option<SpotifyApiAudioFeatures>,
+) -> Result<()> {
+    let mut track: entities::track::ActiveModel = track.into();
+    track.spotify_id = Set(spotify_track.as_ref().map(|t| t.id.clone()));
+    track.spotify_popularity = Set(spotify_track.and_then(|t| t.popularity));
+    track.tempo = Set(features.as_ref().map(|f| f.tempo));
+    track.musical_key = Set(features.as_ref().and_then(|f| f.key));
+    track.energy = Set(features.map(|f| f.energy));
+    track.spotify_enriched_at = Set(Some(chrono::Utc::now().timestamp()));
+    track
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save spotify enrichment")?;
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct EnrichmentTaskPayload {
+    /// Account whose token is used for the lookups
+    account_id: i64,
+    config: MatcherConfig,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct EnrichmentTaskCheckpoint {
+    /// Tracks up to this ID were already looked up
+    last_track_id: i64,
+    processed: i64,
+    counts: EnrichmentCounts,
+}
+
+/// Runs [`TaskKind::SpotifyEnrichment`] tasks in batches of local tracks,
+/// checkpointing after each one.
+pub struct SpotifyEnrichmentTaskHandler {
+    pub spotify_tokens: Arc<SpotifyTokenManager>,
+}
+
+#[async_trait]
+impl TaskHandler for SpotifyEnrichmentTaskHandler {
+    fn kind(&self) -> TaskKind {
+        TaskKind::SpotifyEnrichment
+    }
+
+    async fn run(&self, ctx: &TaskContext) -> Result<()> {
+        let db = ctx.db();
+        let payload: EnrichmentTaskPayload = ctx.payload()?;
+        let mut checkpoint = ctx
+            .checkpoint::<EnrichmentTaskCheckpoint>()?
+            .unwrap_or_default();
+        let client = self.spotify_tokens.client(payload.account_id).await?;
+
+        let remaining = unenriched_tracks()
+            .filter(entities::track::Column::Id.gt(checkpoint.last_track_id))
+            .count(&db.conn)
+            .await
+            .wrap_err("Failed to count tracks to enrich")? as i64;
+        let total_tracks = checkpoint.processed + remaining;
+        ctx.report_progress(checkpoint.processed, Some(total_tracks))
+            .await?;
+
+        let mut consecutive_failures = 0;
+        while !ctx.is_cancelled() {
+            let tracks = unenriched_tracks()
+                .filter(entities::track::Column::Id.gt(checkpoint.last_track_id))
+                .order_by_asc(entities::track::Column::Id)
+                .limit(ENRICHMENT_BATCH_SIZE)
+                .all(&db.conn)
+                .await
+                .wrap_err("Failed to fetch tracks to enrich")?;
+            let Some(last_track) = tracks.last() else {
+                break;
+            };
+            checkpoint.last_track_id = last_track.id;
+            checkpoint.processed += tracks.len() as i64;
+
+            let counts = enrich_tracks(
+                db,
+                &client,
+                &payload.config,
+                tracks,
+                &mut consecutive_failures,
+            )
+            .await?;
+            checkpoint.counts.enriched += counts.enriched;
+            checkpoint.counts.not_found += counts.not_found;
+            checkpoint.counts.failed += counts.failed;
+            ctx.save_checkpoint(&checkpoint, checkpoint.processed)
+                .await?;
+        }
+
+        tracing::info!(
+            enriched_tracks = checkpoint.counts.enriched,
+            not_found_tracks = checkpoint.counts.not_found,
+            failed_tracks = checkpoint.counts.failed,
+            "Enriched local tracks from spotify"
+        );
+        Ok(())
+    }
+}
+
+/// Start a background task looking up the local tracks without a Spotify link
+/// on Spotify, using the account's token.
+pub async fn enrich_local_tracks_from_spotify_task(
+    tasks: &Arc<TaskManager>,
+    account_id: i64,
+    config: MatcherConfig,
+) -> Result<entities::background_task::Model> {
+    tasks
+        .submit(
+            TaskKind::SpotifyEnrichment,
+            &EnrichmentTaskPayload { account_id, config },
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::spotify::sync::SpotifySyncService;
+    use crate::test_utils::fake_spotify::FakeSpotify;
+    use crate::test_utils::{TestTrack, insert_spotify_account, insert_track, test_db};
+
+    fn local_track<'a>(
+        title: &'a str,
+        artist: &'a str,
+        album: &'a str,
+        duration: i32,
+        isrcs: Option<&'a str>,
+    ) -> TestTrack<'a> {
+        TestTrack {
+            album: Some(album),
+            artist: Some(artist),
+            duration: Some(duration),
+            isrcs,
+            ..TestTrack::new(title)
+        }
+    }
+
+    async fn find_track(db: &Database, id: i64) -> entities::track::Model {
+        entities::track::Entity::find_by_id(id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_enrich_tracks_by_isrc_and_search() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let tokens = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let client = tokens.client(account.id).await.unwrap();
+
+        let by_isrc = insert_track(
+            &db,
+            local_track(
+                "Wild World (2020 Mix)",
+                "Cat Stevens",
+                "Remastered Hits",
+                201,
+                Some(r#"["GBAAN7000012"]"#),
+            ),
+        )
+        .await;
+        let by_search = insert_track(
+            &db,
+            local_track(
+                "Bohemian Rhapsody",
+                "Queen",
+                "A Night at the Opera",
+                354,
+                None,
+            ),
+        )
+        .await;
+        let unknown = insert_track(
+            &db,
+            local_track("Unreleased Demo", "Nobody", "Demos", 100, None),
+        )
+        .await;
+        // Tracks already linked from a Spotify playlist are left alone
+        SpotifySyncService::new(db.clone(), client.clone())
+            .sync_account_playlists(account.id)
+            .await
+            .unwrap();
+        let linked = insert_track(
+            &db,
+            local_track("Dreams", "Fleetwood Mac", "Rumours", 257, None),
+        )
+        .await;
+        let mut spotify_track: entities::spotify_track::ActiveModel =
+            entities::spotify_track::Entity::find_by_id("track-dreams")
+                .one(&db.conn)
+                .await
+                .unwrap()
+                .unwrap()
+                .into();
+        spotify_track.local_track_id = Set(Some(linked.id));
+        spotify_track.update(&db.conn).await.unwrap();
+
+        let tracks = unenriched_tracks()
+            .order_by_asc(entities::track::Column::Id)
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(tracks.len(), 3);
+        let counts = enrich_tracks(&db, &client, &MatcherConfig::default(), tracks, &mut 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            counts,
+            EnrichmentCounts {
+                enriched: 2,
+                not_found: 1,
+                failed: 0
+            }
+        );
+
+        let by_isrc = find_track(&db, by_isrc.id).await;
+        assert_eq!(by_isrc.spotify_id.as_deref(), Some("track-wild-world"));
+        assert_eq!(by_isrc.spotify_popularity, Some(71));
+        assert_eq!(by_isrc.musical_key, Some(0));
+
+        // The live version is passed over for the studio recording
+        let by_search = find_track(&db, by_search.id).await;
+        assert_eq!(
+            by_search.spotify_id.as_deref(),
+            Some("track-bohemian-rhapsody")
+        );
+        assert_eq!(by_search.tempo, Some(71.1));
+        assert_eq!(by_search.energy, Some(0.4));
+
+        let unknown = find_track(&db, unknown.id).await;
+        assert_eq!(unknown.spotify_id, None);
+        assert!(unknown.spotify_enriched_at.is_some());
+
+        assert!(
+            find_track(&db, linked.id)
+                .await
+                .spotify_enriched_at
+                .is_none()
+        );
+        assert_eq!(unenriched_tracks().count(&db.conn).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_enrich_tracks_without_audio_features() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        fake.forbid_audio_features();
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let tokens = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let client = tokens.client(account.id).await.unwrap();
+        let track = insert_track(
+            &db,
+            local_track(
+                "Wild World (2020 Mix)",
+                "Cat Stevens",
+                "Remastered Hits",
+                201,
+                Some(r#"["GBAAN7000012"]"#),
+            ),
+        )
+        .await;
+
+        let counts = enrich_tracks(
+            &db,
+            &client,
+            &MatcherConfig::default(),
+            vec![track.clone()],
+            &mut 0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(counts.enriched, 1);
+
+        let track = find_track(&db, track.id).await;
+        assert_eq!(track.spotify_id.as_deref(), Some("track-wild-world"));
+        assert_eq!(track.spotify_popularity, Some(71));
+        assert_eq!(track.tempo, None);
+        assert!(track.spotify_enriched_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_enrich_tracks_gives_up_after_failures_in_a_row() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let tokens = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let client = tokens.client(account.id).await.unwrap();
+        let mut tracks = Vec::new();
+        for index in 0..MAX_CONSECUTIVE_FAILURES + 1 {
+            let title = format!("Song {}", index);
+            tracks.push(insert_track(&db, local_track(&title, "Queen", "Jazz", 200, None)).await);
+        }
+        fake.revoke("household-1");
+
+        let mut consecutive_failures = 0;
+        let err = enrich_tracks(
+            &db,
+            &client,
+            &MatcherConfig::default(),
+            tracks,
+            &mut consecutive_failures,
+        )
+        .await
+        .unwrap_err();
+        assert!(format!("{:?}", err).contains("failed in a row"));
+        assert_eq!(consecutive_failures, MAX_CONSECUTIVE_FAILURES);
+        assert_eq!(
+            unenriched_tracks().count(&db.conn).await.unwrap(),
+            MAX_CONSECUTIVE_FAILURES as u64 + 1
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Offline evaluation of the matcher against past review decisions.
+//!
+//! Accepted match candidates are treated as true matches and dismissed ones as
+//! non-matches. Every labelled pair is re-scored with a `MatcherConfig`, so
+//! different weights and thresholds can be compared before changing the config.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre::{Result, WrapErr};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+
+use super::matcher::{
+    MatchConfidence, MatchResult, MatcherConfig, Track, normalize_track, score_candidate,
+};
+use super::similarity_filter::{db_track_to_track, spotify_track_to_track};
+use crate::{database::Database, entities};
+
+/// A reviewed candidate re-scored with the evaluated config
+#[derive(Debug, Clone)]
+pub struct EvaluatedCandidate {
+    /// Whether the candidate was accepted (true) or dismissed (false)
+    pub accepted: bool,
+    /// `None` when the matcher filters the pair out before scoring
+    pub result: Option<MatchResult>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatcherEvaluation {
+    pub candidates: Vec<EvaluatedCandidate>,
+    /// Reviewed candidates that could not be scored, e.g. a track without a duration
+    pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub false_negatives: usize,
+    pub true_negatives: usize,
+}
+
+impl ConfusionMatrix {
+    /// Share of predicted matches that were accepted, `None` without predictions
+    pub fn precision(&self) -> Option<f64> {
+        let predicted = self.true_positives + self.false_positives;
+        (predicted > 0).then(|| self.true_positives as f64 / predicted as f64)
+    }
+
+    /// Share of accepted candidates that were predicted, `None` without accepted candidates
+    pub fn recall(&self) -> Option<f64> {
+        let actual = self.true_positives + self.false_negatives;
+        (actual > 0).then(|| self.true_positives as f64 / actual as f64)
+    }
+}
+
+impl MatcherEvaluation {
+    /// Count outcomes when every candidate at or above `min_confidence` is
+    /// predicted to be a match
+    pub fn confusion_matrix(&self, min_confidence: MatchConfidence) -> ConfusionMatrix {
+        let mut matrix = ConfusionMatrix::default();
+
+        for candidate in &self.candidates {
+            let predicted = candidate
+                .result
+                .as_ref()
+                .is_some_and(|result| result.confidence.is_at_least(min_confidence));
+
+            match (predicted, candidate.accepted) {
+                (true, true) => matrix.true_positives += 1,
+                (true, false) => matrix.false_positives += 1,
+                (false, true) => matrix.false_negatives += 1,
+                (false, false) => matrix.true_negatives += 1,
+            }
+        }
+
+        matrix
+    }
+}
+
+impl std::fmt::Display for MatcherEvaluation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let accepted = self.candidates.iter().filter(|c| c.accepted).count();
+        writeln!(
+            f,
+            "Evaluated {} candidates ({} accepted, {} dismissed, {} skipped)",
+            self.candidates.len(),
+            accepted,
+            self.candidates.len() - accepted,
+            self.skipped
+        )?;
+
+        let format_ratio = |ratio: Option<f64>| {
+            ratio
+                .map(|ratio| format!("{:.3}", ratio))
+                .unwrap_or_else(|| "n/a".to_string())
+        };
+
+        for min_confidence in [
+            MatchConfidence::High,
+            MatchConfidence::Medium,
+            MatchConfidence::Low,
+        ] {
+            let matrix = self.confusion_matrix(min_confidence);
+            writeln!(
+                f,
+                ">= {:<6}  precision {}  recall {}  (tp {}, fp {}, fn {}, tn {})",
+                min_confidence,
+                format_ratio(matrix.precision()),
+                format_ratio(matrix.recall()),
+                matrix.true_positives,
+                matrix.false_positives,
+                matrix.false_negatives,
+                matrix.true_negatives
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Re-score every accepted and dismissed match candidate with `config`
+pub async fn evaluate_matcher(db: &Database, config: &MatcherConfig) -> Result<MatcherEvaluation> {
+    let candidates = entities::spotify_match_candidate::Entity::find()
+        .filter(entities::spotify_match_candidate::Column::Status.is_in([
+            entities::spotify_match_candidate::CandidateStatus::Accepted,
+            entities::spotify_match_candidate::CandidateStatus::Dismissed,
+        ]))
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch reviewed match candidates")?;
+
+    let spotify_tracks: HashMap<String, entities::spotify_track::Model> =
+        entities::spotify_track::Entity::find()
+            .filter(
+                entities::spotify_track::Column::SpotifyTrackId.is_in(
+                    candidates
+                        .iter()
+                        .map(|candidate| candidate.spotify_track_id.clone()),
+                ),
+            )
+            .all(&db.conn)
+            .await
+            .wrap_err("Failed to fetch spotify tracks")?
+            .into_iter()
+            .map(|track| (track.spotify_track_id.clone(), track))
+            .collect();
+
+    let local_tracks: HashMap<i64, entities::track::Model> = entities::track::Entity::find()
+        .filter(
+            entities::track::Column::Id
+                .is_in(candidates.iter().map(|candidate| candidate.local_track_id)),
+        )
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch local tracks")?
+        .into_iter()
+        .map(|track| (track.id, track))
+        .collect();
+
+    // Local tracks need several queries to convert, and usually appear in
+    // more than one candidate
+    let mut converted_local_tracks: HashMap<i64, Option<Track>> = HashMap::new();
+    let mut evaluated = Vec::with_capacity(candidates.len());
+    let mut skipped = 0;
+
+    for candidate in candidates {
+        let spotify_track = spotify_tracks
+            .get(&candidate.spotify_track_id)
+            .and_then(|track| spotify_track_to_track(track).ok());
+
+        let local_track = match converted_local_tracks.get(&candidate.local_track_id) {
+            Some(track) => track.clone(),
+            None => {
+                let track = match local_tracks.get(&candidate.local_track_id) {
+                    Some(track) => db_track_to_track(&db.conn, track).await.ok(),
+                    None => None,
+                };
+                converted_local_tracks.insert(candidate.local_track_id, track.clone());
+                track
+            }
+        };
+
+        let (Some(spotify_track), Some(local_track)) = (spotify_track, local_track) else {
+            tracing::debug!(
+                candidate_id = candidate.id,
+                "Skipping match candidate that cannot be scored"
+            );
+            skipped += 1;
+            continue;
+        };
+
+        let result = score_candidate(
+            &normalize_track(&spotify_track),
+            &normalize_track(&local_track),
+            config,
+        );
+
+        evaluated.push(EvaluatedCandidate {
+            accepted: candidate.status
+                == entities::spotify_match_candidate::CandidateStatus::Accepted,
+            result,
+        });
+    }
+
+    Ok(MatcherEvaluation {
+        candidates: evaluated,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait, Set};
+
+    fn local_track<'a>(title: &'a str, album: &'a str, duration: i32) -> TestTrack<'a> {
+        TestTrack {
+            album: Some(album),
+            album_artist: Some("Queen"),
+            duration: Some(duration),
+            ..TestTrack::new(title)
+        }
+    }
+
+    async fn insert_spotify_track(db: &Database, spotify_id: &str, title: &str, album: &str) {
+        entities::spotify_track::ActiveModel {
+            spotify_track_id: Set(spotify_id.into()),
+            title: Set(title.into()),
+            duration: Set(Some(354_000)),
+            artists: Set(entities::spotify_track::StringVec(vec!["Queen".into()])),
+            album: Set(album.into()),
+            ..entities::spotify_track::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_candidate(
+        db: &Database,
+        spotify_track_id: &str,
+        local_track_id: i64,
+        status: entities::spotify_match_candidate::CandidateStatus,
+    ) {
+        entities::spotify_match_candidate::ActiveModel {
+            spotify_track_id: Set(spotify_track_id.into()),
+            local_track_id: Set(local_track_id),
+            score: Set(0.8),
+            confidence: Set(entities::spotify_match_candidate::CandidateConfidence::Medium),
+            title_similarity: Set(0.8),
+            artist_similarity: Set(0.8),
+            album_similarity: Set(0.8),
+            duration_match: Set(entities::spotify_match_candidate::CandidateDurationMatch::Exact),
+            version_match: Set(entities::spotify_match_candidate::CandidateVersionMatch::Match),
+            status: Set(status),
+            ..entities::spotify_match_candidate::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+    }
+
+    async fn seed_reviewed_candidates(db: &Database) {
+        use entities::spotify_match_candidate::CandidateStatus;
+
+        let rhapsody = insert_track(
+            db,
+            local_track("Bohemian Rhapsody", "A Night at the Opera", 354),
+        )
+        .await
+        .id;
+        let rock_you = insert_track(
+            db,
+            local_track("We Will Rock You", "News of the World", 122),
+        )
+        .await
+        .id;
+
+        insert_spotify_track(db, "sp1", "Bohemian Rhapsody", "A Night at the Opera").await;
+        insert_spotify_track(db, "sp2", "Bohemian Rhapsody", "A Night at the Opera").await;
+
+        // Correct match
+        insert_candidate(db, "sp1", rhapsody, CandidateStatus::Accepted).await;
+        // Filtered out by duration, correctly dismissed
+        insert_candidate(db, "sp1", rock_you, CandidateStatus::Dismissed).await;
+        // Identical metadata but dismissed during review
+        insert_candidate(db, "sp2", rhapsody, CandidateStatus::Dismissed).await;
+        // Pending candidates are not labelled
+        insert_candidate(db, "sp2", rock_you, CandidateStatus::Pending).await;
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_matcher_default_config() {
+        let db = test_db().await;
+        seed_reviewed_candidates(&db).await;
+
+        let evaluation = evaluate_matcher(&db, &MatcherConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(evaluation.candidates.len(), 3);
+        assert_eq!(evaluation.skipped, 0);
+
+        let matrix = evaluation.confusion_matrix(MatchConfidence::High);
+        assert_eq!(
+            matrix,
+            ConfusionMatrix {
+                true_positives: 1,
+                false_positives: 1,
+                false_negatives: 0,
+                true_negatives: 1,
+            }
+        );
+        assert_eq!(matrix.precision(), Some(0.5));
+        assert_eq!(matrix.recall(), Some(1.0));
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_matcher_uses_config() {
+        let db = test_db().await;
+        seed_reviewed_candidates(&db).await;
+
+        let strict = MatcherConfig {
+            high_threshold: 1.01,
+            ..MatcherConfig::default()
+        };
+        let evaluation = evaluate_matcher(&db, &strict).await.unwrap();
+
+        let high = evaluation.confusion_matrix(MatchConfidence::High);
+        assert_eq!(high.true_positives, 0);
+        assert_eq!(high.precision(), None);
+        assert_eq!(high.recall(), Some(0.0));
+
+        // Still predicted as a match one level down
+        let medium = evaluation.confusion_matrix(MatchConfidence::Medium);
+        assert_eq!(medium.true_positives, 1);
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_matcher_skips_unscorable_candidates() {
+        let db = test_db().await;
+        seed_reviewed_candidates(&db).await;
+
+        let orphan = insert_track(&db, TestTrack::new("Orphan")).await;
+        insert_candidate(
+            &db,
+            "sp1",
+            orphan.id,
+            entities::spotify_match_candidate::CandidateStatus::Dismissed,
+        )
+        .await;
+
+        let evaluation = evaluate_matcher(&db, &MatcherConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(evaluation.candidates.len(), 3);
+        assert_eq!(evaluation.skipped, 1);
+    }
+}
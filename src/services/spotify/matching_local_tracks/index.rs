@@ -0,0 +1,575 @@
+//! Persistent index of normalized local tracks.
+//!
+//! Each indexed track stores its `NormalizedTrack` and a set of blocking keys
+//! (title and artist tokens). A Spotify track is only scored against local
+//! tracks that share a key with it and fall inside the loose duration
+//! tolerance, instead of against the whole library.
+
+use std::collections::{BTreeSet, HashMap};
+
+use color_eyre::eyre::{OptionExt, Result, WrapErr};
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{
+    ColumnTrait, EntityTrait, QueryFilter, QuerySelect, QueryTrait, Set, TransactionTrait,
+};
+
+use super::matcher::{
+    MatchConfidence, MatcherConfig, NormalizedTrack, Track, find_matches, normalize_track,
+};
+use super::similarity_filter::db_track_to_track;
+use crate::{database::Database, entities};
+
+/// Words too common to narrow down candidates on their own
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "de", "el", "i", "in", "it", "la", "le", "me", "my", "of", "on", "the", "to",
+    "you",
+];
+
+fn insert_tokens(keys: &mut BTreeSet<String>, prefix: &str, text: &str) {
+    let tokens = text.split_whitespace().collect::<Vec<_>>();
+    let meaningful = tokens
+        .iter()
+        .copied()
+        .filter(|token| !STOPWORDS.contains(token))
+        .collect::<Vec<_>>();
+
+    // Text made only of stopwords still needs keys
+    let tokens = if meaningful.is_empty() {
+        tokens
+    } else {
+        meaningful
+    };
+
+    for token in tokens {
+        keys.insert(format!("{}:{}", prefix, token));
+    }
+}
+
+/// Blocking keys of a normalized track. Title and artist tokens are prefixed
+/// so a title word only ever matches another title word.
+pub fn blocking_keys(track: &NormalizedTrack) -> Vec<String> {
+    let mut keys = BTreeSet::new();
+    insert_tokens(&mut keys, "t", &track.title);
+    for artist in &track.all_artists {
+        insert_tokens(&mut keys, "a", artist);
+    }
+    keys.into_iter().collect()
+}
+
+fn normalized_from_index(entry: entities::track_match_index::Model) -> NormalizedTrack {
+    NormalizedTrack {
+        title: entry.title,
+        original_title: entry.original_title,
+        primary_artist: entry.primary_artist,
+        all_artists: entry.artists.0.into_iter().collect(),
+        album: entry.album,
+        duration_ms: entry.duration_ms as u32,
+        version_indicator: entry.version_indicator,
+    }
+}
+
+/// Normalize a local track and replace its index entry and blocking keys.
+pub async fn index_track(db: &Database, track_id: i64) -> Result<()> {
+    let track = entities::track::Entity::find_by_id(track_id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch track")?
+        .ok_or_eyre("Track not found")?;
+    let normalized = normalize_track(&db_track_to_track(&db.conn, &track).await?);
+    let keys = blocking_keys(&normalized);
+
+    let txn = db
+        .conn
+        .begin()
+        .await
+        .wrap_err("Failed to begin transaction")?;
+
+    entities::track_match_index::Entity::delete_by_id(track_id)
+        .exec(&txn)
+        .await
+        .wrap_err("Failed to remove old track index entry")?;
+    entities::track_match_key::Entity::delete_many()
+        .filter(entities::track_match_key::Column::TrackId.eq(track_id))
+        .exec(&txn)
+        .await
+        .wrap_err("Failed to remove old track index keys")?;
+    entities::track_match_index_failure::Entity::delete_by_id(track_id)
+        .exec(&txn)
+        .await
+        .wrap_err("Failed to remove track index failure")?;
+
+    let mut artists = normalized.all_artists.into_iter().collect::<Vec<_>>();
+    artists.sort();
+
+    entities::track_match_index::Entity::insert(entities::track_match_index::ActiveModel {
+        track_id: Set(track_id),
+        title: Set(normalized.title),
+        original_title: Set(normalized.original_title),
+        primary_artist: Set(normalized.primary_artist),
+        artists: Set(entities::spotify_track::StringVec(artists)),
+        album: Set(normalized.album),
+        duration_ms: Set(normalized.duration_ms as i64),
+        version_indicator: Set(normalized.version_indicator),
+        indexed_at: Set(chrono::Utc::now().timestamp()),
+    })
+    .exec(&txn)
+    .await
+    .wrap_err("Failed to insert track index entry")?;
+
+    if !keys.is_empty() {
+        entities::track_match_key::Entity::insert_many(keys.into_iter().map(|key| {
+            entities::track_match_key::ActiveModel {
+                track_id: Set(track_id),
+                key: Set(key),
+            }
+        }))
+        .exec(&txn)
+        .await
+        .wrap_err("Failed to insert track index keys")?;
+    }
+
+    txn.commit()
+        .await
+        .wrap_err("Failed to commit transaction")?;
+
+    Ok(())
+}
+
+/// Index tracks that are missing from the index or changed since they were
+/// indexed. A track changes with its album, its track and album artists and
+/// their aliases. Tracks that cannot be matched at all (e.g. without an
+/// artist) are recorded as failures and only retried once they change again.
+/// Tracks changed in the same second as they were last checked are checked
+/// again. Returns the number of indexed tracks.
+pub async fn refresh_index(db: &Database) -> Result<usize> {
+    let mut checked_at: HashMap<i64, i64> = entities::track_match_index::Entity::find()
+        .select_only()
+        .column(entities::track_match_index::Column::TrackId)
+        .column(entities::track_match_index::Column::IndexedAt)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch track index")?
+        .into_iter()
+        .collect();
+    let failures = entities::track_match_index_failure::Entity::find()
+        .select_only()
+        .column(entities::track_match_index_failure::Column::TrackId)
+        .column(entities::track_match_index_failure::Column::FailedAt)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch track index failures")?;
+    for (track_id, failed_at) in failures {
+        keep_latest(&mut checked_at, track_id, failed_at);
+    }
+
+    let mut indexed = 0;
+    for (track_id, changed_at) in track_changed_at(db).await? {
+        // Timestamps are in seconds: a change in the second of the last
+        // check may have come after it
+        if checked_at
+            .get(&track_id)
+            .is_some_and(|checked_at| *checked_at > changed_at)
+        {
+            continue;
+        }
+
+        match index_track(db, track_id).await {
+            Ok(()) => indexed += 1,
+            Err(e) => {
+                tracing::debug!(error = ?e, track_id, "Skipping track in match index");
+                record_failure(db, track_id, &e).await?;
+            }
+        }
+    }
+
+    Ok(indexed)
+}
+
+/// Last change of each track and of everything its index entry is built from
+async fn track_changed_at(db: &Database) -> Result<HashMap<i64, i64>> {
+    let mut artist_changed_at: HashMap<i64, i64> = entities::artist::Entity::find()
+        .select_only()
+        .column(entities::artist::Column::Id)
+        .column(entities::artist::Column::UpdatedAt)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch artists")?
+        .into_iter()
+        .collect();
+    let aliases = entities::artist_alias::Entity::find()
+        .select_only()
+        .column(entities::artist_alias::Column::ArtistId)
+        .column(entities::artist_alias::Column::CreatedAt)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch artist aliases")?;
+    for (artist_id, created_at) in aliases {
+        keep_latest(&mut artist_changed_at, artist_id, created_at);
+    }
+
+    let mut album_changed_at: HashMap<i64, i64> = entities::album::Entity::find()
+        .select_only()
+        .column(entities::album::Column::Id)
+        .column(entities::album::Column::UpdatedAt)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch albums")?
+        .into_iter()
+        .collect();
+    let album_artists = entities::album_artist::Entity::find()
+        .select_only()
+        .column(entities::album_artist::Column::AlbumId)
+        .column(entities::album_artist::Column::ArtistId)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch album artists")?;
+    for (album_id, artist_id) in album_artists {
+        if let Some(changed_at) = artist_changed_at.get(&artist_id) {
+            keep_latest(&mut album_changed_at, album_id, *changed_at);
+        }
+    }
+
+    let tracks = entities::track::Entity::find()
+        .select_only()
+        .column(entities::track::Column::Id)
+        .column(entities::track::Column::AlbumId)
+        .column(entities::track::Column::UpdatedAt)
+        .into_tuple::<(i64, i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch tracks")?;
+    let mut track_changed_at = HashMap::with_capacity(tracks.len());
+    for (track_id, album_id, updated_at) in tracks {
+        let album_changed_at = album_changed_at.get(&album_id).copied().unwrap_or(0);
+        track_changed_at.insert(track_id, updated_at.max(album_changed_at));
+    }
+    let track_artists = entities::track_artist::Entity::find()
+        .select_only()
+        .column(entities::track_artist::Column::TrackId)
+        .column(entities::track_artist::Column::ArtistId)
+        .into_tuple::<(i64, i64)>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch track artists")?;
+    for (track_id, artist_id) in track_artists {
+        if let Some(changed_at) = artist_changed_at.get(&artist_id) {
+            keep_latest(&mut track_changed_at, track_id, *changed_at);
+        }
+    }
+
+    Ok(track_changed_at)
+}
+
+fn keep_latest(timestamps: &mut HashMap<i64, i64>, id: i64, timestamp: i64) {
+    let entry = timestamps.entry(id).or_insert(timestamp);
+    *entry = (*entry).max(timestamp);
+}
+
+async fn record_failure(db: &Database, track_id: i64, error: &color_eyre::Report) -> Result<()> {
+    entities::track_match_index_failure::Entity::insert(
+        entities::track_match_index_failure::ActiveModel {
+            track_id: Set(track_id),
+            error: Set(error.to_string()),
+            failed_at: Set(chrono::Utc::now().timestamp()),
+        },
+    )
+    .on_conflict(
+        OnConflict::column(entities::track_match_index_failure::Column::TrackId)
+            .update_columns([
+                entities::track_match_index_failure::Column::Error,
+                entities::track_match_index_failure::Column::FailedAt,
+            ])
+            .to_owned(),
+    )
+    .exec(&db.conn)
+    .await
+    .wrap_err("Failed to record track index failure")?;
+
+    Ok(())
+}
+
+/// Indexed local tracks that share a blocking key with `spotify_track` and
+/// are within the loose duration tolerance of it.
+pub async fn find_candidates(
+    db: &Database,
+    spotify_track: &NormalizedTrack,
+    config: &MatcherConfig,
+) -> Result<Vec<(i64, NormalizedTrack)>> {
+    let keys = blocking_keys(spotify_track);
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // The tolerance is based on the shorter track, so using the Spotify
+    // duration gives a window at least as wide as the one the matcher applies
+    let duration_ms = spotify_track.duration_ms as i64;
+    let tolerance_ms = config
+        .loose_tolerance_ms
+        .max((spotify_track.duration_ms as f64 * config.loose_tolerance_ratio) as u32)
+        as i64;
+
+    let entries = entities::track_match_index::Entity::find()
+        .filter(
+            entities::track_match_index::Column::TrackId.in_subquery(
+                entities::track_match_key::Entity::find()
+                    .select_only()
+                    .column(entities::track_match_key::Column::TrackId)
+                    .filter(entities::track_match_key::Column::Key.is_in(keys))
+                    .into_query(),
+            ),
+        )
+        .filter(
+            entities::track_match_index::Column::DurationMs
+                .between(duration_ms - tolerance_ms, duration_ms + tolerance_ms),
+        )
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch match candidates from track index")?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.track_id, normalized_from_index(entry)))
+        .collect())
+}
+
+/// The indexed local track that is the best match for `track`, if it is a
+/// high confidence one. The index should be refreshed beforehand.
+pub async fn find_local_track(
+    db: &Database,
+    track: &Track,
+    config: &MatcherConfig,
+) -> Result<Option<i64>> {
+    let normalized = normalize_track(track);
+    let (track_ids, candidates): (Vec<i64>, Vec<NormalizedTrack>) =
+        find_candidates(db, &normalized, config)
+            .await?
+            .into_iter()
+            .unzip();
+
+    Ok(find_matches(&normalized, &candidates, config)
+        .into_iter()
+        .next()
+        .filter(|(_, result)| matches!(result.confidence, MatchConfidence::High))
+        .map(|(index, _)| track_ids[index]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestTrack, insert_track, test_db};
+    use sea_orm::ActiveModelTrait;
+    use sea_orm::sea_query::Expr;
+
+    fn local_track<'a>(title: &'a str, artist: &'a str, duration: i32) -> TestTrack<'a> {
+        TestTrack {
+            album_artist: Some(artist),
+            duration: Some(duration),
+            ..TestTrack::new(title)
+        }
+    }
+
+    /// Dates the tracks, albums and artists long before any indexing, so
+    /// refreshes within the same second see them as unchanged.
+    async fn imported_long_ago(db: &Database) {
+        let long_ago = 1_700_000_000_i64;
+        entities::track::Entity::update_many()
+            .col_expr(entities::track::Column::UpdatedAt, Expr::value(long_ago))
+            .exec(&db.conn)
+            .await
+            .unwrap();
+        entities::album::Entity::update_many()
+            .col_expr(entities::album::Column::UpdatedAt, Expr::value(long_ago))
+            .exec(&db.conn)
+            .await
+            .unwrap();
+        entities::artist::Entity::update_many()
+            .col_expr(entities::artist::Column::UpdatedAt, Expr::value(long_ago))
+            .exec(&db.conn)
+            .await
+            .unwrap();
+    }
+
+    fn spotify(title: &str, artist: &str, duration_ms: u32) -> NormalizedTrack {
+        normalize_track(&Track {
+            title: title.to_string(),
+            primary_artist: artist.to_string(),
+            secondary_artists: vec![],
+            album: "Album".to_string(),
+            duration_ms,
+        })
+    }
+
+    #[test]
+    fn test_blocking_keys() {
+        let keys = blocking_keys(&spotify("The Show Must Go On", "Queen", 0));
+        assert_eq!(
+            keys,
+            vec!["a:queen", "t:go", "t:must", "t:show"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        );
+
+        // Only stopwords: keep them rather than having no keys
+        let keys = blocking_keys(&spotify("You & I", "Queen", 0));
+        assert!(keys.contains(&"t:you".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_find_candidates_uses_keys_and_duration() {
+        let db = test_db().await;
+        let rhapsody = insert_track(&db, local_track("Bohemian Rhapsody", "Queen", 354))
+            .await
+            .id;
+        let other_song = insert_track(&db, local_track("Killer Queen", "Queen", 180))
+            .await
+            .id;
+        let unrelated = insert_track(&db, local_track("Yellow Submarine", "The Beatles", 358))
+            .await
+            .id;
+
+        assert_eq!(refresh_index(&db).await.unwrap(), 3);
+
+        let candidates = find_candidates(
+            &db,
+            &spotify("Bohemian Rhapsody", "Queen", 355_000),
+            &MatcherConfig::default(),
+        )
+        .await
+        .unwrap();
+        let ids = candidates.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+
+        assert_eq!(ids, vec![rhapsody]);
+        assert!(!ids.contains(&other_song));
+        assert!(!ids.contains(&unrelated));
+        assert_eq!(candidates[0].1.title, "bohemian rhapsody");
+        assert_eq!(candidates[0].1.duration_ms, 354_000);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_index_only_indexes_stale_tracks() {
+        let db = test_db().await;
+        let rhapsody = insert_track(&db, local_track("Bohemian Rhapsody", "Queen", 354))
+            .await
+            .id;
+        imported_long_ago(&db).await;
+
+        assert_eq!(refresh_index(&db).await.unwrap(), 1);
+        assert_eq!(refresh_index(&db).await.unwrap(), 0);
+
+        let added = insert_track(&db, local_track("Killer Queen", "Queen", 180))
+            .await
+            .id;
+        imported_long_ago(&db).await;
+        assert_eq!(refresh_index(&db).await.unwrap(), 1);
+
+        let keys = entities::track_match_key::Entity::find()
+            .filter(entities::track_match_key::Column::TrackId.eq(added))
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert!(keys.iter().any(|key| key.key == "t:killer"));
+
+        // A new alias of the album artist makes both Queen tracks stale, even
+        // in the same second as the last run
+        let queen = db.get_artist_id_by_name("Queen").await.unwrap().unwrap();
+        db.add_artist_aliases(queen, &["Kween".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(refresh_index(&db).await.unwrap(), 2);
+        let entry = entities::track_match_index::Entity::find_by_id(rhapsody)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(entry.artists.0.contains(&"kween".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_index_records_unmatchable_tracks() {
+        let db = test_db().await;
+        let orphan = insert_track(&db, TestTrack::new("Orphan")).await;
+        imported_long_ago(&db).await;
+
+        assert_eq!(refresh_index(&db).await.unwrap(), 0);
+        let failure = entities::track_match_index_failure::Entity::find_by_id(orphan.id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(failure.error.contains("No artist"));
+
+        // Retried only once the track gets an artist
+        assert_eq!(refresh_index(&db).await.unwrap(), 0);
+        let artist_id = db.upsert_artist("Queen", None).await.unwrap();
+        db.add_album_artist(orphan.album_id, artist_id, true)
+            .await
+            .unwrap();
+        assert_eq!(refresh_index(&db).await.unwrap(), 1);
+        assert!(
+            entities::track_match_index_failure::Entity::find_by_id(orphan.id)
+                .one(&db.conn)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_index_uses_track_artists_and_aliases() {
+        let db = test_db().await;
+        let track_id = insert_track(&db, local_track("Father and Son", "Various Artists", 221))
+            .await
+            .id;
+        let now = chrono::Utc::now().timestamp();
+
+        let artist = entities::artist::ActiveModel {
+            name: Set("Yusuf Islam".into()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        entities::track_artist::Entity::insert(entities::track_artist::ActiveModel {
+            track_id: Set(track_id),
+            artist_id: Set(artist.id),
+            is_primary: Set(1),
+        })
+        .exec(&db.conn)
+        .await
+        .unwrap();
+        db.add_artist_aliases(artist.id, &["Cat Stevens".to_string()])
+            .await
+            .unwrap();
+
+        index_track(&db, track_id).await.unwrap();
+
+        let entry = entities::track_match_index::Entity::find_by_id(track_id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.primary_artist, "yusuf islam");
+        assert_eq!(
+            entry.artists.0,
+            vec!["cat stevens", "various artists", "yusuf islam"]
+        );
+
+        let candidates = find_candidates(
+            &db,
+            &spotify("Father and Son", "Cat Stevens", 221_000),
+            &MatcherConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(candidates.len(), 1);
+    }
+}
@@ -4,7 +4,7 @@
 //! track information, handling edge cases like Japanese characters, version
 //! indicators, featuring artists, and varying metadata quality.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use unicode_normalization::UnicodeNormalization;
 
@@ -18,6 +18,76 @@ pub struct Track {
     pub duration_ms: u32,
 }
 
+/// Weights, factors and cutoffs used when scoring candidates
+///
+/// Loaded from the `[matcher]` section of the config file. Every field is
+/// optional and falls back to the default tuning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatcherConfig {
+    /// Weight of title similarity in the overall score
+    pub title_weight: f64,
+    /// Weight of artist similarity in the overall score
+    pub artist_weight: f64,
+    /// Weight of album similarity, only applied above `album_boost_threshold`
+    pub album_weight: f64,
+    pub album_boost_threshold: f64,
+    /// Share of the artist similarity coming from the primary artist
+    pub primary_artist_weight: f64,
+    /// Share of the artist similarity coming from the overlap of all artists
+    pub artist_overlap_weight: f64,
+    /// Minimum primary artist similarity for a track to be considered at all
+    pub min_artist_similarity: f64,
+    pub duration_close_factor: f64,
+    pub duration_mismatch_factor: f64,
+    pub version_ambiguous_factor: f64,
+    pub version_mismatch_factor: f64,
+    /// Tight duration tolerance: max(`tight_tolerance_ms`, `tight_tolerance_ratio` of the shorter track)
+    pub tight_tolerance_ms: u32,
+    pub tight_tolerance_ratio: f64,
+    /// Loose duration tolerance: max(`loose_tolerance_ms`, `loose_tolerance_ratio` of the shorter track)
+    pub loose_tolerance_ms: u32,
+    pub loose_tolerance_ratio: f64,
+    /// Minimum score for a high confidence match
+    pub high_threshold: f64,
+    /// Minimum score for a high confidence match with an ambiguous version
+    pub high_ambiguous_version_threshold: f64,
+    pub medium_threshold: f64,
+    pub low_threshold: f64,
+    /// Link Spotify tracks without review when the best candidate is high confidence
+    pub auto_accept: bool,
+    /// Minimum score lead of the best candidate over the runner-up for auto-accepting
+    pub auto_accept_margin: f64,
+}
+
+impl Default for MatcherConfig {
+    fn default() -> Self {
+        Self {
+            title_weight: 0.45,
+            artist_weight: 0.40,
+            album_weight: 0.10,
+            album_boost_threshold: 0.8,
+            primary_artist_weight: 0.7,
+            artist_overlap_weight: 0.3,
+            min_artist_similarity: 0.5,
+            duration_close_factor: 0.85,
+            duration_mismatch_factor: 0.5,
+            version_ambiguous_factor: 0.9,
+            version_mismatch_factor: 0.6,
+            tight_tolerance_ms: 5000,
+            tight_tolerance_ratio: 0.03,
+            loose_tolerance_ms: 15000,
+            loose_tolerance_ratio: 0.08,
+            high_threshold: 0.85,
+            high_ambiguous_version_threshold: 0.92,
+            medium_threshold: 0.70,
+            low_threshold: 0.50,
+            auto_accept: false,
+            auto_accept_margin: 0.05,
+        }
+    }
+}
+
 /// Normalized track data after preprocessing
 #[derive(Debug, Clone)]
 pub struct NormalizedTrack {
@@ -63,6 +133,22 @@ pub enum MatchConfidence {
     NoMatch,
 }
 
+impl MatchConfidence {
+    fn rank(self) -> u8 {
+        match self {
+            MatchConfidence::High => 3,
+            MatchConfidence::Medium => 2,
+            MatchConfidence::Low => 1,
+            MatchConfidence::NoMatch => 0,
+        }
+    }
+
+    /// Whether this confidence is the same as or better than `other`
+    pub fn is_at_least(self, other: MatchConfidence) -> bool {
+        self.rank() >= other.rank()
+    }
+}
+
 impl std::fmt::Display for MatchConfidence {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -189,7 +275,7 @@ pub fn normalize_string(s: &str) -> String {
 }
 
 /// Extract content from parentheses and brackets
-fn extract_parenthetical_content(s: &str) -> (String, Vec<String>) {
+pub fn extract_parenthetical_content(s: &str) -> (String, Vec<String>) {
     let mut result = String::new();
     let mut extracted = Vec::new();
     let mut depth = 0;
@@ -244,7 +330,7 @@ fn extract_parenthetical_content(s: &str) -> (String, Vec<String>) {
 }
 
 /// Extract version indicator from parenthetical content
-fn extract_version_indicator(parentheticals: &[String]) -> Option<String> {
+pub fn extract_version_indicator(parentheticals: &[String]) -> Option<String> {
     for content in parentheticals {
         let lower = content.to_lowercase();
         for indicator in VERSION_INDICATORS {
@@ -471,15 +557,19 @@ pub fn combined_string_similarity(s1: &str, s2: &str) -> f64 {
 /// Check if durations match within tolerance
 ///
 /// Uses percentage-based tolerance with a minimum floor
-pub fn check_duration_match(dur1_ms: u32, dur2_ms: u32) -> DurationMatch {
+pub fn check_duration_match(dur1_ms: u32, dur2_ms: u32, config: &MatcherConfig) -> DurationMatch {
     let shorter = dur1_ms.min(dur2_ms);
     let diff_ms = (dur1_ms as i64 - dur2_ms as i64).unsigned_abs() as u32;
 
-    // Tight tolerance: max(5 seconds, 3% of shorter)
-    let tight_tolerance_ms = (5000_u32).max((shorter as f64 * 0.03) as u32);
+    // Tight tolerance: max(5 seconds, 3% of shorter) by default
+    let tight_tolerance_ms = config
+        .tight_tolerance_ms
+        .max((shorter as f64 * config.tight_tolerance_ratio) as u32);
 
-    // Loose tolerance: max(15 seconds, 8% of shorter)
-    let loose_tolerance_ms = (15000_u32).max((shorter as f64 * 0.08) as u32);
+    // Loose tolerance: max(15 seconds, 8% of shorter) by default
+    let loose_tolerance_ms = config
+        .loose_tolerance_ms
+        .max((shorter as f64 * config.loose_tolerance_ratio) as u32);
 
     if diff_ms <= tight_tolerance_ms {
         DurationMatch::Exact
@@ -494,10 +584,33 @@ pub fn check_duration_match(dur1_ms: u32, dur2_ms: u32) -> DurationMatch {
 // Artist Matching
 // =============================================================================
 
+/// Similarity of the primary artists of two tracks
+///
+/// A primary artist also counts as matching when it is credited anywhere on
+/// the other track, so compilations (album artist "Various Artists"),
+/// collaborations listed in a different order and known aliases still match.
+pub fn primary_artist_similarity(a: &NormalizedTrack, b: &NormalizedTrack) -> f64 {
+    let best_credited = |primary: &str, track: &NormalizedTrack| {
+        track
+            .all_artists
+            .iter()
+            .map(|artist| jaro_winkler_similarity(primary, artist))
+            .fold(0.0_f64, |a, b| a.max(b))
+    };
+
+    jaro_winkler_similarity(&a.primary_artist, &b.primary_artist)
+        .max(best_credited(&a.primary_artist, b))
+        .max(best_credited(&b.primary_artist, a))
+}
+
 /// Compare artists between two tracks
-pub fn compare_artists(local: &NormalizedTrack, spotify: &NormalizedTrack) -> f64 {
+pub fn compare_artists(
+    local: &NormalizedTrack,
+    spotify: &NormalizedTrack,
+    config: &MatcherConfig,
+) -> f64 {
     // Primary artist match is most important
-    let primary_sim = jaro_winkler_similarity(&local.primary_artist, &spotify.primary_artist);
+    let primary_sim = primary_artist_similarity(local, spotify);
 
     // Check overlap of all artists
     let overlap_score = if local.all_artists.is_empty() || spotify.all_artists.is_empty() {
@@ -516,7 +629,7 @@ pub fn compare_artists(local: &NormalizedTrack, spotify: &NormalizedTrack) -> f6
     };
 
     // Weight primary artist heavily
-    primary_sim * 0.7 + overlap_score * 0.3
+    primary_sim * config.primary_artist_weight + overlap_score * config.artist_overlap_weight
 }
 
 // =============================================================================
@@ -541,50 +654,56 @@ pub fn compare_version_indicators(
 // =============================================================================
 
 /// Compare two normalized tracks and return a match result
-pub fn compare_tracks(local: &NormalizedTrack, spotify: &NormalizedTrack) -> MatchResult {
+pub fn compare_tracks(
+    local: &NormalizedTrack,
+    spotify: &NormalizedTrack,
+    config: &MatcherConfig,
+) -> MatchResult {
     let title_similarity = combined_string_similarity(&local.title, &spotify.title);
-    let artist_similarity = compare_artists(local, spotify);
+    let artist_similarity = compare_artists(local, spotify, config);
     let album_similarity = combined_string_similarity(&local.album, &spotify.album);
-    let duration_match = check_duration_match(local.duration_ms, spotify.duration_ms);
+    let duration_match = check_duration_match(local.duration_ms, spotify.duration_ms, config);
     let version_match = compare_version_indicators(local, spotify);
 
     // Calculate overall score
-    let mut score = title_similarity * 0.45 + artist_similarity * 0.40;
+    let mut score =
+        title_similarity * config.title_weight + artist_similarity * config.artist_weight;
 
     // Album boosts but doesn't penalize
-    if album_similarity > 0.8 {
-        score += 0.10 * album_similarity;
+    if album_similarity > config.album_boost_threshold {
+        score += config.album_weight * album_similarity;
     }
 
     // Duration affects confidence
     let duration_factor = match duration_match {
         DurationMatch::Exact => 1.0,
-        DurationMatch::Close => 0.85,
-        DurationMatch::Mismatch => 0.5,
+        DurationMatch::Close => config.duration_close_factor,
+        DurationMatch::Mismatch => config.duration_mismatch_factor,
     };
     score *= duration_factor;
 
     // Version indicator can penalize
     let version_factor = match version_match {
         VersionMatch::Match => 1.0,
-        VersionMatch::Ambiguous => 0.9,
-        VersionMatch::Mismatch => 0.6,
+        VersionMatch::Ambiguous => config.version_ambiguous_factor,
+        VersionMatch::Mismatch => config.version_mismatch_factor,
     };
     score *= version_factor;
 
     // Determine confidence level
-    let confidence = if score >= 0.85
+    let confidence = if score >= config.high_threshold
         && matches!(duration_match, DurationMatch::Exact)
         && (matches!(version_match, VersionMatch::Match)
-            || (score >= 0.92 && matches!(version_match, VersionMatch::Ambiguous)))
+            || (score >= config.high_ambiguous_version_threshold
+                && matches!(version_match, VersionMatch::Ambiguous)))
     {
         MatchConfidence::High
-    } else if score >= 0.70
+    } else if score >= config.medium_threshold
         && !matches!(duration_match, DurationMatch::Mismatch)
         && !matches!(version_match, VersionMatch::Mismatch)
     {
         MatchConfidence::Medium
-    } else if score >= 0.50 {
+    } else if score >= config.low_threshold {
         MatchConfidence::Low
     } else {
         MatchConfidence::NoMatch
@@ -601,48 +720,52 @@ pub fn compare_tracks(local: &NormalizedTrack, spotify: &NormalizedTrack) -> Mat
     }
 }
 
-/// Find the best matching Spotify track for a local track
+/// Score a single candidate the same way `find_matches` does
 ///
-/// Returns candidates sorted by score, filtered to plausible matches
-pub fn find_matches(
-    spotify_track: &Track,
-    local_tracks: &[Track],
-    min_artist_threshold: f64,
-) -> Vec<(usize, NormalizedTrack, MatchResult)> {
-    let local_normalized = normalize_track(spotify_track);
+/// Returns `None` when the candidate is filtered out before scoring (primary
+/// artist too different or duration mismatch).
+pub fn score_candidate(
+    spotify: &NormalizedTrack,
+    local: &NormalizedTrack,
+    config: &MatcherConfig,
+) -> Option<MatchResult> {
+    // First pass: filter by artist similarity
+    if primary_artist_similarity(spotify, local) < config.min_artist_similarity {
+        return None;
+    }
 
-    let mut results: Vec<(usize, NormalizedTrack, MatchResult)> = local_tracks
+    // Second pass: filter by duration (loose tolerance)
+    if matches!(
+        check_duration_match(spotify.duration_ms, local.duration_ms, config),
+        DurationMatch::Mismatch
+    ) {
+        return None;
+    }
+
+    Some(compare_tracks(spotify, local, config))
+}
+
+/// Find the best matching local tracks for a Spotify track
+///
+/// Returns the indices of `local_tracks` with their results, sorted by score
+/// and filtered to plausible matches
+pub fn find_matches(
+    spotify_track: &NormalizedTrack,
+    local_tracks: &[NormalizedTrack],
+    config: &MatcherConfig,
+) -> Vec<(usize, MatchResult)> {
+    let mut results: Vec<(usize, MatchResult)> = local_tracks
         .iter()
         .enumerate()
-        .map(|(idx, spotify)| {
-            let spotify_normalized = normalize_track(spotify);
-            (idx, spotify_normalized)
-        })
-        // First pass: filter by artist similarity
-        .filter(|(_, spotify_normalized)| {
-            jaro_winkler_similarity(
-                &local_normalized.primary_artist,
-                &spotify_normalized.primary_artist,
-            ) >= min_artist_threshold
-        })
-        // Second pass: filter by duration (loose tolerance)
-        .filter(|(_, spotify_normalized)| {
-            !matches!(
-                check_duration_match(local_normalized.duration_ms, spotify_normalized.duration_ms),
-                DurationMatch::Mismatch
-            )
-        })
-        // Score remaining candidates
-        .map(|(idx, spotify_normalized)| {
-            let result = compare_tracks(&local_normalized, &spotify_normalized);
-            (idx, spotify_normalized, result)
+        .filter_map(|(idx, local)| {
+            score_candidate(spotify_track, local, config).map(|result| (idx, result))
         })
         // Filter out no-matches
-        .filter(|(_, _, result)| !matches!(result.confidence, MatchConfidence::NoMatch))
+        .filter(|(_, result)| !matches!(result.confidence, MatchConfidence::NoMatch))
         .collect();
 
     // Sort by score descending
-    results.sort_by(|a, b| b.2.score.partial_cmp(&a.2.score).unwrap());
+    results.sort_by(|a, b| b.1.score.partial_cmp(&a.1.score).unwrap());
 
     results
 }
@@ -713,27 +836,29 @@ mod tests {
 
     #[test]
     fn test_duration_match() {
+        let config = MatcherConfig::default();
+
         // Same duration
         assert!(matches!(
-            check_duration_match(180000, 180000),
+            check_duration_match(180000, 180000, &config),
             DurationMatch::Exact
         ));
 
         // Within 5 seconds
         assert!(matches!(
-            check_duration_match(180000, 183000),
+            check_duration_match(180000, 183000, &config),
             DurationMatch::Exact
         ));
 
         // Within loose tolerance
         assert!(matches!(
-            check_duration_match(180000, 190000),
+            check_duration_match(180000, 190000, &config),
             DurationMatch::Close
         ));
 
         // Outside tolerance
         assert!(matches!(
-            check_duration_match(180000, 220000),
+            check_duration_match(180000, 220000, &config),
             DurationMatch::Mismatch
         ));
     }
@@ -758,7 +883,7 @@ mod tests {
 
         let local_norm = normalize_track(&local);
         let spotify_norm = normalize_track(&spotify);
-        let result = compare_tracks(&local_norm, &spotify_norm);
+        let result = compare_tracks(&local_norm, &spotify_norm, &MatcherConfig::default());
 
         // Should be a strong match despite version differences
         assert!(result.score > 0.7);
@@ -785,10 +910,184 @@ mod tests {
 
         let original_norm = normalize_track(&original);
         let live_norm = normalize_track(&live);
-        let result = compare_tracks(&original_norm, &live_norm);
+        let result = compare_tracks(&original_norm, &live_norm, &MatcherConfig::default());
 
         // Should flag as different versions
         assert!(matches!(result.version_match, VersionMatch::Ambiguous));
         assert!(matches!(result.duration_match, DurationMatch::Mismatch));
     }
+
+    #[test]
+    fn test_config_changes_confidence() {
+        let local = Track {
+            title: "Song".to_string(),
+            primary_artist: "Artist".to_string(),
+            secondary_artists: vec![],
+            album: "Album".to_string(),
+            duration_ms: 180000,
+        };
+        let spotify = Track {
+            duration_ms: 190000,
+            ..local.clone()
+        };
+
+        let local_norm = normalize_track(&local);
+        let spotify_norm = normalize_track(&spotify);
+
+        let default_result = compare_tracks(&local_norm, &spotify_norm, &MatcherConfig::default());
+        assert_eq!(default_result.duration_match, DurationMatch::Close);
+        assert_eq!(default_result.confidence, MatchConfidence::Medium);
+
+        let lenient = MatcherConfig {
+            tight_tolerance_ms: 10000,
+            ..MatcherConfig::default()
+        };
+        let lenient_result = compare_tracks(&local_norm, &spotify_norm, &lenient);
+        assert_eq!(lenient_result.duration_match, DurationMatch::Exact);
+        assert_eq!(lenient_result.confidence, MatchConfidence::High);
+    }
+
+    #[test]
+    fn test_config_from_partial_toml() {
+        let config: MatcherConfig = toml::from_str("high_threshold = 0.9").unwrap();
+        assert_eq!(config.high_threshold, 0.9);
+        assert_eq!(config.title_weight, MatcherConfig::default().title_weight);
+    }
+
+    #[test]
+    fn test_find_matches_filters_artist() {
+        let spotify = Track {
+            title: "Song".to_string(),
+            primary_artist: "Artist".to_string(),
+            secondary_artists: vec![],
+            album: "Album".to_string(),
+            duration_ms: 180000,
+        };
+        let other_artist = Track {
+            primary_artist: "Zzyzx Quartet".to_string(),
+            ..spotify.clone()
+        };
+
+        let matches = find_matches(
+            &normalize_track(&spotify),
+            &[normalize_track(&other_artist), normalize_track(&spotify)],
+            &MatcherConfig::default(),
+        );
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, 1);
+    }
+
+    #[test]
+    fn test_compilation_matches_track_artist() {
+        let spotify = Track {
+            title: "Get Lucky".to_string(),
+            primary_artist: "Daft Punk".to_string(),
+            secondary_artists: vec![],
+            album: "Random Access Memories".to_string(),
+            duration_ms: 248000,
+        };
+        // Track artist first, album artist of the compilation after it
+        let compilation = Track {
+            primary_artist: "Daft Punk".to_string(),
+            secondary_artists: vec!["Various Artists".to_string()],
+            album: "Now That's What I Call Music! 85".to_string(),
+            ..spotify.clone()
+        };
+        // What the matcher used to see: only the album artist
+        let album_artist_only = Track {
+            primary_artist: "Various Artists".to_string(),
+            secondary_artists: vec![],
+            ..compilation.clone()
+        };
+
+        let config = MatcherConfig::default();
+        let spotify_norm = normalize_track(&spotify);
+
+        let result =
+            score_candidate(&spotify_norm, &normalize_track(&compilation), &config).unwrap();
+        assert!((result.artist_similarity - 1.0).abs() < 0.001);
+        assert!(result.confidence.is_at_least(MatchConfidence::Medium));
+
+        assert!(
+            score_candidate(&spotify_norm, &normalize_track(&album_artist_only), &config).is_none()
+        );
+    }
+
+    #[test]
+    fn test_featured_artist_in_title() {
+        let spotify = Track {
+            title: "Get Lucky (feat. Pharrell Williams)".to_string(),
+            primary_artist: "Daft Punk".to_string(),
+            secondary_artists: vec![],
+            album: "Random Access Memories".to_string(),
+            duration_ms: 248000,
+        };
+        let local = Track {
+            title: "Get Lucky".to_string(),
+            secondary_artists: vec!["Pharrell Williams".to_string(), "Nile Rodgers".to_string()],
+            ..spotify.clone()
+        };
+
+        let result = score_candidate(
+            &normalize_track(&spotify),
+            &normalize_track(&local),
+            &MatcherConfig::default(),
+        )
+        .unwrap();
+        assert!((result.artist_similarity - 1.0).abs() < 0.001);
+        assert_eq!(result.confidence, MatchConfidence::High);
+    }
+
+    #[test]
+    fn test_collaboration_in_different_order() {
+        let spotify = Track {
+            title: "Otis".to_string(),
+            primary_artist: "JAY-Z".to_string(),
+            secondary_artists: vec!["Kanye West".to_string()],
+            album: "Watch The Throne".to_string(),
+            duration_ms: 178000,
+        };
+        let local = Track {
+            primary_artist: "Kanye West".to_string(),
+            secondary_artists: vec!["JAY-Z".to_string()],
+            ..spotify.clone()
+        };
+
+        let result = score_candidate(
+            &normalize_track(&spotify),
+            &normalize_track(&local),
+            &MatcherConfig::default(),
+        )
+        .unwrap();
+        assert!((result.artist_similarity - 1.0).abs() < 0.001);
+        assert_eq!(result.confidence, MatchConfidence::High);
+    }
+
+    #[test]
+    fn test_artist_alias() {
+        let spotify = Track {
+            title: "Father and Son".to_string(),
+            primary_artist: "Cat Stevens".to_string(),
+            secondary_artists: vec![],
+            album: "Tea for the Tillerman".to_string(),
+            duration_ms: 221000,
+        };
+        // Aliases from MusicBrainz are listed after the credited artists
+        let local = Track {
+            primary_artist: "Yusuf Islam".to_string(),
+            secondary_artists: vec!["Cat Stevens".to_string(), "Yusuf".to_string()],
+            ..spotify.clone()
+        };
+        let without_alias = Track {
+            secondary_artists: vec![],
+            ..local.clone()
+        };
+
+        let config = MatcherConfig::default();
+        let spotify_norm = normalize_track(&spotify);
+        assert!(score_candidate(&spotify_norm, &normalize_track(&local), &config).is_some());
+        assert!(
+            score_candidate(&spotify_norm, &normalize_track(&without_alias), &config).is_none()
+        );
+    }
 }
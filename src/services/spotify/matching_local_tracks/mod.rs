@@ -1,6 +1,15 @@
+mod enrichment;
+mod evaluation;
+mod index;
 mod matcher;
 mod similarity_filter;
 mod task;
-mod task_db;
 
-pub use task::match_existing_spotify_tracks_with_local_task;
+pub use enrichment::{SpotifyEnrichmentTaskHandler, enrich_local_tracks_from_spotify_task};
+pub use evaluation::evaluate_matcher;
+pub use index::{find_local_track, index_track, refresh_index};
+pub use matcher::{
+    MatchConfidence, MatcherConfig, Track, extract_parenthetical_content,
+    extract_version_indicator, normalize_track, score_candidate,
+};
+pub use task::{SpotifyToLocalMatcherTaskHandler, match_existing_spotify_tracks_with_local_task};
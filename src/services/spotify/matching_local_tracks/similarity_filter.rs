@@ -1,9 +1,14 @@
-use super::matcher::{MatchResult, Track, find_matches};
+use std::collections::{HashMap, HashSet};
+
+use super::index::find_candidates;
+use super::matcher::{
+    MatchResult, MatcherConfig, NormalizedTrack, Track, find_matches, normalize_track,
+};
 use crate::{database::Database, entities};
 use color_eyre::eyre::{OptionExt, Result};
 use rayon::prelude::*;
 use sea_orm::QueryFilter;
-use sea_orm::{ColumnTrait, EntityTrait};
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait};
 
 pub fn spotify_track_to_track(spotify_track: &entities::spotify_track::Model) -> Result<Track> {
     Ok(Track {
@@ -22,100 +27,140 @@ pub fn spotify_track_to_track(spotify_track: &entities::spotify_track::Model) ->
     })
 }
 
-pub async fn db_track_to_track(db: &Database, track: &entities::track::Model) -> Result<Track> {
+/// Build the matcher input for a local track
+///
+/// The primary artist is the primary track artist, falling back to the primary
+/// album artist for tracks imported without track artists. The remaining track
+/// artists, the album artists and the MusicBrainz aliases of all of them are
+/// used as secondary artists.
+pub async fn db_track_to_track<C: ConnectionTrait>(
+    conn: &C,
+    track: &entities::track::Model,
+) -> Result<Track> {
     let album = entities::album::Entity::find_by_id(track.album_id)
-        .one(&db.conn)
+        .one(conn)
         .await?
         .ok_or_eyre("No album found for track")?;
-    let primary_artist = entities::album_artist::Entity::find()
-        .filter(entities::album_artist::Column::AlbumId.eq(track.album_id))
-        .filter(entities::album_artist::Column::IsPrimary.eq(1))
+    let track_artists = entities::track_artist::Entity::find()
+        .filter(entities::track_artist::Column::TrackId.eq(track.id))
         .find_also_related(entities::artist::Entity)
-        .one(&db.conn)
+        .all(conn)
         .await?
-        .ok_or_eyre("No primary artist found for album")?
-        .1
-        .ok_or_eyre("No artist found for primary artist link")?
-        .name;
-    let secondary_artists = entities::album_artist::Entity::find()
+        .into_iter()
+        .filter_map(|(link, artist)| artist.map(|a| (a, link.is_primary == 1)));
+    let album_artists = entities::album_artist::Entity::find()
         .filter(entities::album_artist::Column::AlbumId.eq(track.album_id))
-        .filter(entities::album_artist::Column::IsPrimary.eq(0))
         .find_also_related(entities::artist::Entity)
-        .all(&db.conn)
+        .all(conn)
         .await?
         .into_iter()
-        .filter_map(|(_, artist)| artist.map(|a| a.name))
+        .filter_map(|(link, artist)| artist.map(|a| (a, link.is_primary == 1)));
+
+    // Primary track artists first, then the other track artists, then the
+    // album artists in the same order
+    let mut artists = track_artists.collect::<Vec<_>>();
+    artists.sort_by_key(|(_, is_primary)| !is_primary);
+    let mut album_artists = album_artists.collect::<Vec<_>>();
+    album_artists.sort_by_key(|(_, is_primary)| !is_primary);
+    artists.extend(album_artists);
+
+    let mut seen = HashSet::new();
+    let artists = artists
+        .into_iter()
+        .map(|(artist, _)| artist)
+        .filter(|artist| seen.insert(artist.id))
         .collect::<Vec<_>>();
+    let (primary_artist, other_artists) = artists
+        .split_first()
+        .ok_or_eyre("No artist found for track")?;
+
+    let aliases = entities::artist_alias::Entity::find()
+        .filter(
+            entities::artist_alias::Column::ArtistId.is_in(artists.iter().map(|artist| artist.id)),
+        )
+        .all(conn)
+        .await?;
+
+    let mut secondary_artists = Vec::new();
+    for name in other_artists
+        .iter()
+        .map(|artist| artist.name.clone())
+        .chain(aliases.into_iter().map(|alias| alias.name))
+    {
+        if name != primary_artist.name && !secondary_artists.contains(&name) {
+            secondary_artists.push(name);
+        }
+    }
 
     Ok(Track {
         title: track.title.clone(),
-        primary_artist,
+        primary_artist: primary_artist.name.clone(),
         secondary_artists,
         album: album.title.clone(),
         duration_ms: track.duration.ok_or_eyre("No duration found for track")? as u32 * 1000,
     })
 }
 
+/// Score every Spotify track against the indexed local tracks that share a
+/// blocking key with it. The index should be refreshed beforehand.
 pub async fn match_spotify_track_to_local_track<'a>(
     db: &Database,
     spotify_tracks: &'a [entities::spotify_track::Model],
-    local_tracks: &[entities::track::Model],
+    config: &MatcherConfig,
 ) -> Result<
     Vec<(
         &'a entities::spotify_track::Model,
         Vec<(entities::track::Model, MatchResult)>,
     )>,
 > {
-    let spotify_tracks_tracks = spotify_tracks
-        .iter()
-        .map(spotify_track_to_track)
-        .collect::<Result<Vec<_>>>()?;
-    let local_tracks_tracks = {
-        let mut local_tracks_tracks = Vec::new();
-        for local_track in local_tracks.iter() {
-            local_tracks_tracks.push(db_track_to_track(db, local_track).await?);
-        }
-        local_tracks_tracks
-    };
+    let mut spotify_candidates = Vec::with_capacity(spotify_tracks.len());
+    for spotify_track in spotify_tracks {
+        let spotify_normalized = normalize_track(&spotify_track_to_track(spotify_track)?);
+        let candidates = find_candidates(db, &spotify_normalized, config).await?;
+        spotify_candidates.push((spotify_track, spotify_normalized, candidates));
+    }
 
-    let match_results = spotify_tracks_tracks
-        .into_iter()
-        .zip(spotify_tracks.iter())
-        .collect::<Vec<_>>()
+    let match_results = spotify_candidates
         .into_par_iter()
-        .map(|(spotify_track_track, spotify_track)| {
-            (
-                spotify_track,
-                find_matches(&spotify_track_track, &local_tracks_tracks, 0.5),
-            )
+        .map(|(spotify_track, spotify_normalized, candidates)| {
+            let (track_ids, normalized): (Vec<i64>, Vec<NormalizedTrack>) =
+                candidates.into_iter().unzip();
+            let matches = find_matches(&spotify_normalized, &normalized, config)
+                .into_iter()
+                .map(|(index, match_result)| (track_ids[index], match_result))
+                .collect::<Vec<_>>();
+            (spotify_track, matches)
         })
         .collect::<Vec<_>>();
 
-    let match_results = match_results
+    let matched_track_ids = match_results
+        .iter()
+        .flat_map(|(_, matches)| matches.iter().map(|(track_id, _)| *track_id))
+        .collect::<HashSet<_>>();
+    let local_tracks: HashMap<i64, entities::track::Model> = entities::track::Entity::find()
+        .filter(entities::track::Column::Id.is_in(matched_track_ids))
+        .all(&db.conn)
+        .await?
         .into_iter()
-        .map(
-            |(spotify_track, matches)| -> Result<(
-                &entities::spotify_track::Model,
-                Vec<(entities::track::Model, MatchResult)>,
-            )> {
-                Ok((
-                    spotify_track,
-                    matches
-                        .into_iter()
-                        .map(|(index, _, match_result)| -> Result<(entities::track::Model, MatchResult)> {
-                            Ok((
-                                local_tracks
-                                    .get(index)
-                                    .ok_or_eyre("No local track found for index")?
-                                    .clone(),
-                                match_result,
-                            ))
-                        })
-                        .collect::<Result<Vec<_>>>()?,
-                ))
-            },
-        )
-        .collect::<Result<Vec<_>>>()?;
+        .map(|track| (track.id, track))
+        .collect();
 
-    Ok(match_results)
+    match_results
+        .into_iter()
+        .map(|(spotify_track, matches)| {
+            let matches = matches
+                .into_iter()
+                .map(|(track_id, match_result)| {
+                    Ok((
+                        local_tracks
+                            .get(&track_id)
+                            .ok_or_eyre("No local track found for index")?
+                            .clone(),
+                        match_result,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((spotify_track, matches))
+        })
+        .collect()
 }
@@ -1,30 +1,50 @@
+use std::collections::HashSet;
 use std::sync::Arc;
-use tracing::{self, Instrument, instrument};
+use tracing::{self, instrument};
 
+use crate::entities::background_task::TaskKind;
+use crate::services::spotify::matching::record_decision;
+use crate::services::spotify::matching_local_tracks::index::refresh_index;
 use crate::services::spotify::matching_local_tracks::matcher::{
-    DurationMatch, MatchConfidence, VersionMatch,
+    DurationMatch, MatchConfidence, MatchResult, MatcherConfig, VersionMatch,
 };
 use crate::services::spotify::matching_local_tracks::similarity_filter::match_spotify_track_to_local_track;
-use crate::services::spotify::matching_local_tracks::task_db::{
-    create_spotify_to_local_matcher_task, mark_spotify_to_local_matcher_task_as_completed,
-    mark_spotify_to_local_matcher_task_as_failed,
-    mark_spotify_to_local_matcher_task_as_in_progress, update_spotify_to_local_matcher_task,
-};
+use crate::services::tasks::{TaskContext, TaskHandler, TaskManager};
 use crate::{database::Database, entities};
-use color_eyre::eyre::{OptionExt, Result};
+use async_trait::async_trait;
+use color_eyre::eyre::{Result, WrapErr};
 use sea_orm::ActiveModelBehavior;
 use sea_orm::ActiveModelTrait;
-use sea_orm::{ColumnTrait, EntityTrait};
+use sea_orm::{ColumnTrait, EntityTrait, QuerySelect, TransactionTrait};
 use sea_orm::{QueryFilter, Set};
+use serde::{Deserialize, Serialize};
 
 fn is_spotify_track_already_matched(spotify_track: &entities::spotify_track::Model) -> bool {
     spotify_track.local_track_id.is_some()
 }
 
-async fn update_database_spotify_track_with_local_track(
+/// Whether the best candidate is good enough to link without review: it must
+/// be high confidence and lead the runner-up by at least `margin`.
+fn should_auto_accept<T>(candidates: &[(T, MatchResult)], margin: f64) -> bool {
+    let Some((_, best)) = candidates.first() else {
+        return false;
+    };
+    if !matches!(best.confidence, MatchConfidence::High) {
+        return false;
+    }
+
+    candidates
+        .get(1)
+        .is_none_or(|(_, runner_up)| best.score - runner_up.score >= margin)
+}
+
+/// Link the Spotify track to the candidate's local track and mark the
+/// candidate as auto-accepted. Other pending candidates are left untouched so
+/// reverting restores the track's review queue as it was.
+async fn auto_accept_candidate(
     db: &Database,
     spotify_track: &entities::spotify_track::Model,
-    local_track: &entities::track::Model,
+    candidate: entities::spotify_match_candidate::Model,
 ) -> Result<()> {
     if spotify_track.local_track_id.is_some() {
         // This should not happen, but if it does, we should log a failure.
@@ -32,9 +52,43 @@ async fn update_database_spotify_track_with_local_track(
             "Spotify track already has a local track"
         ));
     }
+
+    let txn = db
+        .conn
+        .begin()
+        .await
+        .wrap_err("Failed to begin transaction")?;
+
     let mut spotify_track: entities::spotify_track::ActiveModel = spotify_track.clone().into();
-    spotify_track.local_track_id = Set(Some(local_track.id));
-    spotify_track.update(&db.conn).await?;
+    spotify_track.local_track_id = Set(Some(candidate.local_track_id));
+    spotify_track
+        .update(&txn)
+        .await
+        .wrap_err("Failed to link spotify track")?;
+
+    let decision = entities::spotify_match_decision::ActiveModel {
+        spotify_track_id: Set(candidate.spotify_track_id.clone()),
+        action: Set(entities::spotify_match_decision::MatchDecisionAction::Link),
+        actor: Set(entities::spotify_match_decision::MatchDecisionActor::Matcher),
+        candidate_id: Set(Some(candidate.id)),
+        previous_candidate_status: Set(Some(candidate.status.clone())),
+        local_track_id: Set(Some(candidate.local_track_id)),
+        ..entities::spotify_match_decision::ActiveModel::new()
+    };
+
+    let mut candidate: entities::spotify_match_candidate::ActiveModel = candidate.into();
+    candidate.status = Set(entities::spotify_match_candidate::CandidateStatus::AutoAccepted);
+    candidate
+        .update(&txn)
+        .await
+        .wrap_err("Failed to mark candidate as auto-accepted")?;
+
+    record_decision(&txn, decision).await?;
+
+    txn.commit()
+        .await
+        .wrap_err("Failed to commit transaction")?;
+
     Ok(())
 }
 
@@ -77,14 +131,38 @@ fn version_match_to_candidate(
     }
 }
 
+/// Local tracks that were already accepted or dismissed for this Spotify track.
+/// They are never proposed again, so a person's decision is not overridden.
+async fn reviewed_local_track_ids(
+    db: &Database,
+    spotify_track: &entities::spotify_track::Model,
+) -> Result<HashSet<i64>> {
+    Ok(entities::spotify_match_candidate::Entity::find()
+        .select_only()
+        .column(entities::spotify_match_candidate::Column::LocalTrackId)
+        .filter(
+            entities::spotify_match_candidate::Column::SpotifyTrackId
+                .eq(&spotify_track.spotify_track_id),
+        )
+        .filter(
+            entities::spotify_match_candidate::Column::Status
+                .ne(entities::spotify_match_candidate::CandidateStatus::Pending),
+        )
+        .into_tuple::<i64>()
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch reviewed candidates")?
+        .into_iter()
+        .collect())
+}
+
+/// Replace the pending candidates of a Spotify track, returning the stored
+/// candidates in the same order as `candidates`.
 async fn store_match_candidates(
     db: &Database,
     spotify_track: &entities::spotify_track::Model,
-    candidates: &[(
-        entities::track::Model,
-        crate::services::spotify::matching_local_tracks::matcher::MatchResult,
-    )],
-) -> Result<()> {
+    candidates: &[(entities::track::Model, MatchResult)],
+) -> Result<Vec<entities::spotify_match_candidate::Model>> {
     // Delete existing pending candidates for this spotify track
     entities::spotify_match_candidate::Entity::delete_many()
         .filter(
@@ -99,6 +177,7 @@ async fn store_match_candidates(
         .await?;
 
     // Store top 5 candidates
+    let mut stored = Vec::new();
     for (local_track, match_result) in candidates.iter().take(5) {
         let candidate_confidence = match confidence_to_candidate(&match_result.confidence) {
             Some(c) => c,
@@ -118,115 +197,453 @@ async fn store_match_candidates(
             ..entities::spotify_match_candidate::ActiveModel::new()
         };
 
-        candidate.insert(&db.conn).await?;
+        stored.push(candidate.insert(&db.conn).await?);
     }
 
-    Ok(())
+    Ok(stored)
 }
 
-#[instrument(skip(db, task, spotify_tracks), fields(num_spotify_tracks = ?spotify_tracks.len()))]
-async fn match_existing_spotify_tracks_with_local(
+/// Match Spotify tracks against the local library: store candidates for
+/// review and auto-accept clear winners when enabled. Returns the number of
+/// auto-accepted and of unmatched tracks. The match index should be refreshed
+/// beforehand.
+#[instrument(skip(db, config, spotify_tracks), fields(num_spotify_tracks = ?spotify_tracks.len()))]
+async fn match_spotify_tracks(
     db: &Database,
-    task: &entities::spotify_to_local_matcher_tasks::Model,
-    spotify_tracks: Vec<entities::spotify_track::Model>,
-) -> Result<()> {
+    config: &MatcherConfig,
+    spotify_tracks: &[entities::spotify_track::Model],
+) -> Result<(i64, i64)> {
     let unmatched_spotify_tracks = spotify_tracks
-        .into_iter()
+        .iter()
         .filter(|spotify_track| !is_spotify_track_already_matched(spotify_track))
+        .cloned()
         .collect::<Vec<_>>();
-    let local_tracks = entities::track::Entity::find().all(&db.conn).await?;
 
     let matches =
-        match_spotify_track_to_local_track(db, &unmatched_spotify_tracks[..], &local_tracks[..])
-            .await?;
+        match_spotify_track_to_local_track(db, &unmatched_spotify_tracks[..], config).await?;
     tracing::debug!("Best local matches: {:?}", matches);
     let mut matched_tracks = 0;
     let mut failed_tracks = 0;
     for (spotify_track, matches) in matches {
-        let best_local_match = matches.first();
+        let reviewed = reviewed_local_track_ids(db, spotify_track).await?;
+        let matches = matches
+            .into_iter()
+            .filter(|(local_track, _)| !reviewed.contains(&local_track.id))
+            .collect::<Vec<_>>();
+
+        let stored = if matches.is_empty() {
+            Vec::new()
+        } else {
+            match store_match_candidates(db, spotify_track, &matches).await {
+                Ok(stored) => stored,
+                Err(e) => {
+                    tracing::error!(error = ?e, "Failed to store match candidates");
+                    Vec::new()
+                }
+            }
+        };
 
-        if let Some(best_local_match) = best_local_match
-            && matches!(best_local_match.1.confidence, MatchConfidence::High)
-        {
-            let best_local_match = entities::track::Entity::find()
-                .filter(entities::track::Column::Id.eq(best_local_match.0.id))
-                .one(&db.conn)
-                .await?
-                .ok_or_eyre("No local track found for best local match")?;
+        let auto_accept = config.auto_accept
+            && should_auto_accept(&matches, config.auto_accept_margin)
+            && stored
+                .first()
+                .is_some_and(|candidate| candidate.local_track_id == matches[0].0.id);
 
+        if auto_accept && let Some(best_candidate) = stored.into_iter().next() {
             tracing::info!(
-                "Best local match found for spotify track: {:?}",
-                best_local_match
+                spotify_track_id = %spotify_track.spotify_track_id,
+                local_track_id = best_candidate.local_track_id,
+                score = best_candidate.score,
+                "Auto-accepting high-confidence match for spotify track",
             );
 
-            update_database_spotify_track_with_local_track(db, spotify_track, &best_local_match)
-                .await?;
-            matched_tracks += 1;
+            match auto_accept_candidate(db, spotify_track, best_candidate).await {
+                Ok(()) => matched_tracks += 1,
+                Err(e) => {
+                    tracing::error!(error = ?e, "Failed to auto-accept match candidate");
+                    failed_tracks += 1;
+                }
+            }
         } else {
             tracing::warn!(
                 spotify_track = ?spotify_track,
-                best_local_match = ?best_local_match,
-                "No high-confidence match found for spotify track, storing candidates",
+                best_local_match = ?matches.first(),
+                "No auto-accepted match for spotify track, candidates stored for review",
             );
 
-            // Store non-high-confidence candidates for manual review
-            if !matches.is_empty()
-                && let Err(e) = store_match_candidates(db, spotify_track, &matches).await
-            {
-                tracing::error!(error = ?e, "Failed to store match candidates");
-            }
-
             failed_tracks += 1;
         }
-        if let Err(e) =
-            update_spotify_to_local_matcher_task(db, task, matched_tracks, failed_tracks).await
-        {
-            tracing::error!(error = ?e, "Failed to update spotify to local matcher task");
-        }
     }
 
-    Ok(())
+    Ok((matched_tracks, failed_tracks))
+}
+
+/// Number of Spotify tracks matched between two checkpoints
+const MATCHER_CHUNK_SIZE: usize = 50;
+
+#[derive(Serialize, Deserialize)]
+struct MatcherTaskPayload {
+    spotify_track_ids: Vec<String>,
+    config: MatcherConfig,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct MatcherTaskCheckpoint {
+    /// Number of `spotify_track_ids` already processed
+    processed: usize,
+    matched_tracks: i64,
+    failed_tracks: i64,
 }
 
-#[instrument(skip(db, spotify_tracks), fields(num_spotify_tracks = ?spotify_tracks.len()))]
+/// Runs [`TaskKind::SpotifyToLocalMatcher`] tasks in chunks, checkpointing
+/// after each one so a restarted task skips the tracks it already matched.
+pub struct SpotifyToLocalMatcherTaskHandler;
+
+#[async_trait]
+impl TaskHandler for SpotifyToLocalMatcherTaskHandler {
+    fn kind(&self) -> TaskKind {
+        TaskKind::SpotifyToLocalMatcher
+    }
+
+    async fn run(&self, ctx: &TaskContext) -> Result<()> {
+        let db = ctx.db();
+        let payload: MatcherTaskPayload = ctx.payload()?;
+        let mut checkpoint = ctx
+            .checkpoint::<MatcherTaskCheckpoint>()?
+            .unwrap_or_default();
+        let total_tracks = payload.spotify_track_ids.len() as i64;
+        ctx.report_progress(checkpoint.processed as i64, Some(total_tracks))
+            .await?;
+
+        let indexed_tracks = refresh_index(db).await?;
+        tracing::debug!(indexed_tracks, "Refreshed track match index");
+
+        let remaining = payload
+            .spotify_track_ids
+            .get(checkpoint.processed..)
+            .unwrap_or_default();
+        for chunk in remaining.chunks(MATCHER_CHUNK_SIZE) {
+            if ctx.is_cancelled() {
+                break;
+            }
+
+            // Tracks matched by hand since the task was submitted are skipped
+            let spotify_tracks = entities::spotify_track::Entity::find()
+                .filter(entities::spotify_track::Column::SpotifyTrackId.is_in(chunk.to_vec()))
+                .all(&db.conn)
+                .await
+                .wrap_err("Failed to fetch spotify tracks")?;
+            let (matched_tracks, failed_tracks) =
+                match_spotify_tracks(db, &payload.config, &spotify_tracks).await?;
+
+            checkpoint.processed += chunk.len();
+            checkpoint.matched_tracks += matched_tracks;
+            checkpoint.failed_tracks += failed_tracks;
+            ctx.save_checkpoint(&checkpoint, checkpoint.processed as i64)
+                .await?;
+        }
+
+        tracing::info!(
+            matched_tracks = checkpoint.matched_tracks,
+            failed_tracks = checkpoint.failed_tracks,
+            "Matched existing spotify tracks with local"
+        );
+        Ok(())
+    }
+}
+
+/// Start a background task matching the unmatched tracks among
+/// `spotify_tracks` against the local library.
+#[instrument(skip(tasks, config, spotify_tracks), fields(num_spotify_tracks = ?spotify_tracks.len()))]
 pub async fn match_existing_spotify_tracks_with_local_task(
-    db: Arc<Database>,
+    tasks: &Arc<TaskManager>,
+    config: MatcherConfig,
     spotify_tracks: Vec<entities::spotify_track::Model>,
-) -> Result<entities::spotify_to_local_matcher_tasks::Model> {
-    let unmatched_spotify_tracks_count = spotify_tracks
-        .iter()
-        .filter(|&spotify_track| !is_spotify_track_already_matched(spotify_track))
-        .count() as i64;
-
-    let task = create_spotify_to_local_matcher_task(&db, unmatched_spotify_tracks_count).await?;
-    let task_clone = task.clone();
-    tokio::task::spawn(async move {
-        if let Err(e) = mark_spotify_to_local_matcher_task_as_in_progress(&db, &task_clone).await {
-            tracing::error!(error = ?e, "Failed to mark spotify to local matcher task as in progress");
+) -> Result<entities::background_task::Model> {
+    let spotify_track_ids = spotify_tracks
+        .into_iter()
+        .filter(|spotify_track| !is_spotify_track_already_matched(spotify_track))
+        .map(|spotify_track| spotify_track.spotify_track_id)
+        .collect();
+
+    tasks
+        .submit(
+            TaskKind::SpotifyToLocalMatcher,
+            &MatcherTaskPayload {
+                spotify_track_ids,
+                config,
+            },
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::spotify::matching::SpotifyMatchingService;
+    use crate::services::spotify::sync::SpotifySyncService;
+    use crate::services::spotify::token_manager::SpotifyTokenManager;
+    use crate::test_utils::fake_spotify::FakeSpotify;
+    use crate::test_utils::{TestTrack, insert_spotify_account, insert_track, test_db};
+
+    fn result(confidence: MatchConfidence, score: f64) -> MatchResult {
+        MatchResult {
+            confidence,
+            title_similarity: score,
+            artist_similarity: score,
+            album_similarity: score,
+            duration_match: DurationMatch::Exact,
+            version_match: VersionMatch::Match,
+            score,
         }
+    }
 
-        match match_existing_spotify_tracks_with_local(&db, &task_clone, spotify_tracks).await {
-            Ok(()) => {
-                tracing::info!("Successfully matched existing spotify tracks with local");
-                if let Err(e) =
-                    mark_spotify_to_local_matcher_task_as_completed(&db, &task_clone).await
-                {
-                    tracing::error!(error = ?e, "Failed to mark spotify to local matcher task as completed");
-                }
-            }
-            Err(e) => {
-                if let Err(e) =
-                    mark_spotify_to_local_matcher_task_as_failed(&db, &task_clone, e.to_string())
-                        .await
-                {
-                    tracing::error!(error = ?e, "Failed to mark spotify to local matcher task as failed");
-                }
-                tracing::error!(
-                    "Failed to match existing spotify tracks with local: {:?}",
-                    e
-                );
-            }
+    #[test]
+    fn test_should_auto_accept() {
+        assert!(!should_auto_accept::<i64>(&[], 0.05));
+        assert!(should_auto_accept(
+            &[(1, result(MatchConfidence::High, 0.95))],
+            0.05
+        ));
+        assert!(!should_auto_accept(
+            &[(1, result(MatchConfidence::Medium, 0.8))],
+            0.05
+        ));
+        assert!(should_auto_accept(
+            &[
+                (1, result(MatchConfidence::High, 0.95)),
+                (2, result(MatchConfidence::Medium, 0.8)),
+            ],
+            0.05
+        ));
+        // Runner-up too close to tell apart
+        assert!(!should_auto_accept(
+            &[
+                (1, result(MatchConfidence::High, 0.95)),
+                (2, result(MatchConfidence::High, 0.93)),
+            ],
+            0.05
+        ));
+    }
+
+    fn local_track(title: &str) -> TestTrack<'_> {
+        local_track_by(title, "Queen", "A Night at the Opera", 354)
+    }
+
+    fn local_track_by<'a>(
+        title: &'a str,
+        artist: &'a str,
+        album: &'a str,
+        duration: i32,
+    ) -> TestTrack<'a> {
+        TestTrack {
+            album: Some(album),
+            album_artist: Some(artist),
+            duration: Some(duration),
+            ..TestTrack::new(title)
+        }
+    }
+
+    async fn insert_spotify_track(db: &Database) -> entities::spotify_track::Model {
+        entities::spotify_track::ActiveModel {
+            spotify_track_id: Set("sp1".into()),
+            title: Set("Bohemian Rhapsody".into()),
+            duration: Set(Some(354_000)),
+            artists: Set(entities::spotify_track::StringVec(vec!["Queen".into()])),
+            album: Set("A Night at the Opera".into()),
+            ..entities::spotify_track::ActiveModel::new()
         }
-    }.in_current_span());
-    Ok(task)
+        .insert(&db.conn)
+        .await
+        .unwrap()
+    }
+
+    async fn run_matcher(db: &Database, config: &MatcherConfig) {
+        let spotify_tracks = entities::spotify_track::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap();
+        refresh_index(db).await.unwrap();
+        match_spotify_tracks(db, config, &spotify_tracks)
+            .await
+            .unwrap();
+    }
+
+    async fn candidate_statuses(
+        db: &Database,
+    ) -> Vec<(i64, entities::spotify_match_candidate::CandidateStatus)> {
+        entities::spotify_match_candidate::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|candidate| (candidate.local_track_id, candidate.status))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_high_confidence_stays_pending_by_default() {
+        let db = test_db().await;
+        let local_track_id = insert_track(&db, local_track("Bohemian Rhapsody")).await.id;
+        insert_spotify_track(&db).await;
+
+        run_matcher(&db, &MatcherConfig::default()).await;
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id("sp1")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spotify_track.local_track_id, None);
+        assert_eq!(
+            candidate_statuses(&db).await,
+            vec![(
+                local_track_id,
+                entities::spotify_match_candidate::CandidateStatus::Pending
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_auto_accept_links_best_candidate() {
+        let db = test_db().await;
+        let local_track_id = insert_track(&db, local_track("Bohemian Rhapsody")).await.id;
+        insert_spotify_track(&db).await;
+
+        let config = MatcherConfig {
+            auto_accept: true,
+            ..MatcherConfig::default()
+        };
+        run_matcher(&db, &config).await;
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id("sp1")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spotify_track.local_track_id, Some(local_track_id));
+        assert_eq!(
+            candidate_statuses(&db).await,
+            vec![(
+                local_track_id,
+                entities::spotify_match_candidate::CandidateStatus::AutoAccepted
+            )]
+        );
+
+        let decision = entities::spotify_match_decision::Entity::find()
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            decision.actor,
+            entities::spotify_match_decision::MatchDecisionActor::Matcher
+        );
+        assert_eq!(decision.local_track_id, Some(local_track_id));
+    }
+
+    #[tokio::test]
+    async fn test_auto_accept_skips_dismissed_candidates() {
+        let db = test_db().await;
+        let local_track_id = insert_track(&db, local_track("Bohemian Rhapsody")).await.id;
+        insert_spotify_track(&db).await;
+
+        run_matcher(&db, &MatcherConfig::default()).await;
+        let candidate = entities::spotify_match_candidate::Entity::find()
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut candidate: entities::spotify_match_candidate::ActiveModel = candidate.into();
+        candidate.status = Set(entities::spotify_match_candidate::CandidateStatus::Dismissed);
+        candidate.update(&db.conn).await.unwrap();
+
+        let config = MatcherConfig {
+            auto_accept: true,
+            ..MatcherConfig::default()
+        };
+        run_matcher(&db, &config).await;
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id("sp1")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spotify_track.local_track_id, None);
+        assert_eq!(
+            candidate_statuses(&db).await,
+            vec![(
+                local_track_id,
+                entities::spotify_match_candidate::CandidateStatus::Dismissed
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reverted_auto_accept_is_not_linked_again() {
+        let db = test_db().await;
+        let local_track_id = insert_track(&db, local_track("Bohemian Rhapsody")).await.id;
+        insert_spotify_track(&db).await;
+        let config = MatcherConfig {
+            auto_accept: true,
+            ..MatcherConfig::default()
+        };
+        run_matcher(&db, &config).await;
+
+        SpotifyMatchingService::new(db.clone())
+            .revert_auto_accepted(None)
+            .await
+            .unwrap();
+        run_matcher(&db, &config).await;
+
+        let spotify_track = entities::spotify_track::Entity::find_by_id("sp1")
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(spotify_track.local_track_id, None);
+        assert_eq!(
+            candidate_statuses(&db).await,
+            vec![(
+                local_track_id,
+                entities::spotify_match_candidate::CandidateStatus::Dismissed
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_matches_tracks_synced_from_spotify() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let local_track_id = insert_track(
+            &db,
+            local_track_by("Dreams", "Fleetwood Mac", "Rumours", 257),
+        )
+        .await
+        .id;
+
+        let tokens = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let client = tokens.client(account.id).await.unwrap();
+        SpotifySyncService::new(db.clone(), client)
+            .sync_account_playlists(account.id)
+            .await
+            .unwrap();
+        run_matcher(&db, &MatcherConfig::default()).await;
+
+        let candidates = entities::spotify_match_candidate::Entity::find()
+            .filter(entities::spotify_match_candidate::Column::LocalTrackId.eq(local_track_id))
+            .all(&db.conn)
+            .await
+            .unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].spotify_track_id, "track-dreams");
+        assert_eq!(
+            candidates[0].confidence,
+            entities::spotify_match_candidate::CandidateConfidence::High
+        );
+    }
 }
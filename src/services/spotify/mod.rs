@@ -1,7 +1,9 @@
 pub mod account;
 pub mod client;
 pub mod download_best_match_for_spotify_track;
+pub mod download_failures;
 pub mod matching;
 pub mod matching_local_tracks;
 pub mod sync;
 pub mod sync_spotify_playlist_to_local_library;
+pub mod token_manager;
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::sync::Arc;
 
 use color_eyre::eyre::{Result, WrapErr};
@@ -88,9 +89,14 @@ impl<C: SpotifyClient> SpotifySyncService<C> {
                 .playlist_tracks(&saved_playlist.spotify_id)
                 .await?;
 
-            for track in tracks {
-                let track_id = self.upsert_track(&txn, &track).await?;
-                self.link_track_to_playlist(&txn, &track_id, saved_playlist.id)
+            // A track listed more than once on Spotify keeps its first position
+            let mut seen_track_ids = HashSet::new();
+            for (position, track) in tracks.iter().enumerate() {
+                if !seen_track_ids.insert(track.id.clone()) {
+                    continue;
+                }
+                let track_id = self.upsert_track(&txn, track).await?;
+                self.link_track_to_playlist(&txn, &track_id, saved_playlist.id, position as i32)
                     .await?;
             }
         }
@@ -199,21 +205,30 @@ impl<C: SpotifyClient> SpotifySyncService<C> {
         txn: &impl sea_orm::ConnectionTrait,
         track_id: &str,
         playlist_id: i64,
+        position: i32,
     ) -> Result<()> {
-        if entities::spotify_track_playlist::Entity::find()
+        if let Some(existing) = entities::spotify_track_playlist::Entity::find()
             .filter(entities::spotify_track_playlist::Column::SpotifyTrackId.eq(track_id))
             .filter(entities::spotify_track_playlist::Column::SpotifyPlaylistId.eq(playlist_id))
             .one(txn)
             .await
             .wrap_err("Failed to fetch saved spotify track playlist")?
-            .is_some()
         {
+            if existing.position != position {
+                let mut model: entities::spotify_track_playlist::ActiveModel = existing.into();
+                model.position = Set(position);
+                entities::spotify_track_playlist::Entity::update(model)
+                    .exec(txn)
+                    .await
+                    .wrap_err("Failed to update spotify track playlist position")?;
+            }
             return Ok(());
         }
 
         let model = entities::spotify_track_playlist::ActiveModel {
             spotify_track_id: Set(track_id.to_string()),
             spotify_playlist_id: Set(playlist_id),
+            position: Set(position),
         };
         entities::spotify_track_playlist::Entity::insert(model)
             .exec(txn)
@@ -230,7 +245,7 @@ mod tests {
     use super::*;
     use crate::ports::spotify::{MockSpotifyClient, SpotifyApiPlaylist, SpotifyApiTrack};
     use crate::test_utils::test_db;
-    use sea_orm::{ActiveModelBehavior, ActiveModelTrait};
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait, QueryOrder};
 
     async fn insert_account(db: &Database) -> entities::spotify_account::Model {
         let account = entities::spotify_account::ActiveModel {
@@ -379,6 +394,7 @@ mod tests {
                 album_name: "Album X".into(),
                 isrc: None,
                 upc: None,
+                popularity: None,
             },
             SpotifyApiTrack {
                 id: "t2".into(),
@@ -388,6 +404,7 @@ mod tests {
                 album_name: "Album Y".into(),
                 isrc: Some("USRC1234".into()),
                 upc: None,
+                popularity: None,
             },
         ];
 
@@ -408,6 +425,16 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(saved_tracks.len(), 2);
+
+        // Verify playlist order was recorded
+        let links = entities::spotify_track_playlist::Entity::find()
+            .order_by_asc(entities::spotify_track_playlist::Column::Position)
+            .all(&db.conn)
+            .await
+            .unwrap();
+        let ordered_ids: Vec<&str> = links.iter().map(|l| l.spotify_track_id.as_str()).collect();
+        assert_eq!(ordered_ids, vec!["t1", "t2"]);
+        assert_eq!(links[1].position, 1);
     }
 
     #[tokio::test]
@@ -430,6 +457,7 @@ mod tests {
             album_name: "Album X".into(),
             isrc: None,
             upc: None,
+            popularity: None,
         }];
 
         // Sync twice
@@ -1,20 +1,27 @@
+use std::collections::HashSet;
+
 use crate::database::Database;
 use crate::entities;
+use crate::services::playlist::{ordered_entries, renumber_entries};
 use color_eyre::eyre::Result;
 use color_eyre::eyre::WrapErr;
 use sea_orm::ColumnTrait;
 use sea_orm::QueryFilter;
-use sea_orm::{EntityTrait, Set};
+use sea_orm::QuerySelect;
+use sea_orm::TransactionTrait;
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
 
-/// Adds tracks to a local playlist, skipping tracks that are already in the playlist.
+/// Brings a local playlist in line with the order of its Spotify playlist.
 ///
 /// This function:
-/// 1. Fetches all local tracks by their IDs
-/// 2. Checks which tracks are already in the playlist (to avoid duplicates)
-/// 3. Inserts only the new tracks
+/// 1. Drops track IDs that no longer exist locally
+/// 2. Reuses existing playlist entries for tracks already in the playlist and
+///    inserts entries for the rest
+/// 3. Renumbers the playlist so the synced tracks come first, in `local_track_ids`
+///    order, followed by any entries that were added to the playlist by hand
 ///
-/// Note: Currently uses N+1 queries for checking existing playlist tracks.
-/// This could be optimized with a single query using a LEFT JOIN or NOT EXISTS.
+/// Everything happens in a single transaction so a failed sync never leaves the
+/// playlist half reordered.
 pub async fn add_tracks_to_local_playlist(
     db: &Database,
     local_playlist: &entities::playlist::Model,
@@ -24,40 +31,58 @@ pub async fn add_tracks_to_local_playlist(
         return Ok(());
     }
 
-    // Fetch all local tracks that we want to add
-    let local_playlist_tracks = entities::track::Entity::find()
-        .filter(entities::track::Column::Id.is_in(local_track_ids))
+    let existing_track_ids: HashSet<i64> = entities::track::Entity::find()
+        .select_only()
+        .column(entities::track::Column::Id)
+        .filter(entities::track::Column::Id.is_in(local_track_ids.clone()))
+        .into_tuple::<i64>()
         .all(&db.conn)
         .await
-        .wrap_err("Failed to fetch local tracks")?;
-
-    // Check which tracks are already in the playlist and only add new ones
-    // TODO: Optimize this to use a single query instead of N+1 queries
-    // Could use: SELECT track_id FROM playlist_track WHERE playlist_id = ? AND track_id IN (?)
-    // Then filter out those IDs before inserting
-    for local_track in local_playlist_tracks {
-        let has_existing_playlist_track = entities::playlist_track::Entity::find()
-            .filter(entities::playlist_track::Column::PlaylistId.eq(local_playlist.id))
-            .filter(entities::playlist_track::Column::TrackId.eq(local_track.id))
-            .one(&db.conn)
-            .await
-            .wrap_err("Failed to check if track is already in playlist")?;
-
-        // Skip tracks that are already in the playlist
-        if has_existing_playlist_track.is_some() {
+        .wrap_err("Failed to fetch local tracks")?
+        .into_iter()
+        .collect();
+
+    let txn = db
+        .conn
+        .begin()
+        .await
+        .wrap_err("Failed to begin transaction")?;
+
+    let mut remaining = ordered_entries(&txn, local_playlist.id).await?;
+    let mut ordered = Vec::with_capacity(local_track_ids.len() + remaining.len());
+
+    for track_id in local_track_ids {
+        if !existing_track_ids.contains(&track_id) {
             continue;
         }
 
-        // Add the track to the playlist
-        let local_playlist_track = entities::playlist_track::ActiveModel {
+        if let Some(index) = remaining
+            .iter()
+            .position(|entry| entry.track_id == track_id)
+        {
+            ordered.push(remaining.remove(index));
+            continue;
+        }
+
+        let entry = entities::playlist_track::ActiveModel {
             playlist_id: Set(local_playlist.id),
-            track_id: Set(local_track.id),
+            track_id: Set(track_id),
+            position: Set(ordered.len() as i32),
             ..Default::default()
-        };
-        entities::playlist_track::Entity::insert(local_playlist_track)
-            .exec(&db.conn)
-            .await?;
+        }
+        .insert(&txn)
+        .await
+        .wrap_err("Failed to add track to playlist")?;
+        ordered.push(entry);
     }
 
+    // Entries that are not part of the Spotify playlist keep their relative order
+    ordered.extend(remaining);
+    renumber_entries(&txn, &ordered).await?;
+
+    txn.commit()
+        .await
+        .wrap_err("Failed to commit transaction")?;
+
     Ok(())
 }
@@ -11,7 +11,7 @@ use sea_orm::{EntityTrait, Set};
 /// - Which Spotify playlist is being synced
 /// - Which local playlist it's being synced to
 /// - Progress counters (tracks downloaded, tracks failed)
-/// - Sync status (pending, in_progress, completed, error)
+/// - Sync status (pending, in_progress, completed, error, cancelled, interrupted)
 pub async fn create_sync_state(
     db: &Database,
     spotify_playlist: &crate::entities::spotify_playlist::Model,
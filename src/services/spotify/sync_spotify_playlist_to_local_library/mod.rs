@@ -4,12 +4,19 @@
 //! 1. Creating sync state to track progress
 //! 2. Processing each Spotify track (downloading/matching)
 //! 3. Adding successfully processed tracks to the local playlist
-//! 4. Updating sync state throughout the process
+//! 4. Updating sync state and per-track progress throughout the process, so
+//!    an interrupted sync can be resumed without redoing completed tracks
 
 mod add_tracks_to_playlist;
 mod create_sync_state;
 mod process_track;
+mod sync_config;
+mod sync_progress;
 mod sync_task;
 mod task;
 
-pub use task::sync_spotify_playlist_to_local_library_task;
+pub use sync_config::PlaylistSyncConfig;
+pub use task::{
+    SpotifyPlaylistSyncTaskHandler, mark_stale_syncs, resume_latest_playlist_sync,
+    resume_spotify_playlist_sync_task, sync_spotify_playlist_to_local_library_task,
+};
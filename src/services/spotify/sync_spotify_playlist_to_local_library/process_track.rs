@@ -1,12 +1,20 @@
 use crate::database::Database;
 use crate::entities;
 use crate::import_track::import_track;
-use crate::services::spotify::download_best_match_for_spotify_track::download_best_match_for_spotify_track;
+use crate::services::spotify::download_best_match_for_spotify_track::{
+    download_match, search_best_match_for_spotify_track,
+};
+use crate::services::spotify::download_failures::record_download_failure;
+use crate::services::tasks::TaskContext;
 use crate::soulseek::SoulSeekClientContext;
 use color_eyre::eyre::Result;
 use sea_orm::{EntityTrait, Set};
+use std::future::Future;
+use tokio::sync::Semaphore;
 use tracing;
 
+use super::sync_config::PlaylistSyncConfig;
+
 /// Result of processing a single Spotify track.
 #[derive(Debug)]
 pub struct ProcessTrackResult {
@@ -16,15 +24,66 @@ pub struct ProcessTrackResult {
     pub success: bool,
 }
 
+/// Step of processing a track that isn't in the local library yet
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyncStage {
+    Search,
+    Download,
+    Import,
+}
+
+/// Limits on how many tracks can be in each stage of processing at once, so
+/// that e.g. slow downloads don't hold up searching for the next tracks.
+pub struct SyncStages {
+    search: Semaphore,
+    download: Semaphore,
+    import: Semaphore,
+}
+
+impl SyncStages {
+    pub fn new(config: &PlaylistSyncConfig) -> Self {
+        Self {
+            search: Semaphore::new(config.search_concurrency.max(1)),
+            download: Semaphore::new(config.download_concurrency.max(1)),
+            import: Semaphore::new(config.import_concurrency.max(1)),
+        }
+    }
+
+    /// Runs `step` once `stage` has a free slot, holding the slot until the
+    /// step finishes. Returns `None` without running the step if
+    /// `is_cancelled` is set by the time a slot frees up.
+    pub async fn run<T>(
+        &self,
+        stage: SyncStage,
+        is_cancelled: impl Fn() -> bool,
+        step: impl Future<Output = T>,
+    ) -> Result<Option<T>> {
+        let semaphore = match stage {
+            SyncStage::Search => &self.search,
+            SyncStage::Download => &self.download,
+            SyncStage::Import => &self.import,
+        };
+        let _permit = semaphore.acquire().await?;
+        if is_cancelled() {
+            return Ok(None);
+        }
+
+        Ok(Some(step.await))
+    }
+}
+
 /// Processes a single Spotify track by either:
 /// 1. Using the existing local track if already matched
-/// 2. Downloading and matching the track if not yet in local library
+/// 2. Searching, downloading and importing the track if not yet in local library,
+///    waiting for a free slot in `stages` before each step
 ///
-/// Returns the local track ID if successful, None if the track couldn't be processed.
+/// Returns `None` if the task was cancelled before the track finished, in
+/// which case nothing was recorded for it.
 ///
 /// # Note
 /// The `spotify_track` parameter should be a `ModelEx` with the `local_track` relation loaded.
 /// This is typically obtained from `Entity::load().with(...)`.
+#[allow(clippy::too_many_arguments)]
 pub async fn process_spotify_track(
     db: &Database,
     soulseek_context: &SoulSeekClientContext,
@@ -32,17 +91,19 @@ pub async fn process_spotify_track(
     config: &crate::config::Config,
     spotify_playlist_id: i64,
     spotify_track: entities::spotify_track::ModelEx,
-) -> Result<ProcessTrackResult> {
+    stages: &SyncStages,
+    ctx: &TaskContext,
+) -> Result<Option<ProcessTrackResult>> {
     // Check if track is already matched to a local track
     // The local_track relation is loaded via Entity::load().with()
     let local_track = spotify_track.local_track.clone().into_option();
 
     if let Some(local_track) = local_track {
         tracing::info!("Track already exists in local library: {:?}", &local_track);
-        return Ok(ProcessTrackResult {
+        return Ok(Some(ProcessTrackResult {
             local_track_id: Some(local_track.id),
             success: true,
-        });
+        }));
     }
 
     // Track doesn't exist in local library - need to download and match it
@@ -50,91 +111,255 @@ pub async fn process_spotify_track(
         "Downloading and matching spotify track: {:?}",
         &spotify_track
     );
+    let spotify_track_model: entities::spotify_track::Model = spotify_track.into();
 
-    let output =
-        download_best_match_for_spotify_track(soulseek_context, spotify_track.clone().into()).await;
-
-    match output {
-        Ok(Some((_temp_dir, temp_file))) => {
-            tracing::info!("Found best match for spotify track: {:?}", &spotify_track);
-
-            // Import the downloaded track into the local library
-            let local_track = import_track(&temp_file, api_key, config, db).await?;
-
-            // Link the Spotify track to the newly imported local track
-            // Convert ModelEx to Model, then to ActiveModel
-            let spotify_track_model: entities::spotify_track::Model = spotify_track.into();
-            let mut spotify_track_active: entities::spotify_track::ActiveModel =
-                spotify_track_model.into();
-            spotify_track_active.local_track_id = Set(Some(local_track.id));
-            entities::spotify_track::Entity::update(spotify_track_active)
-                .exec(&db.conn)
-                .await?;
+    let Some(search_result) = stages
+        .run(
+            SyncStage::Search,
+            || ctx.is_cancelled(),
+            search_best_match_for_spotify_track(soulseek_context, &spotify_track_model),
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
 
-            Ok(ProcessTrackResult {
-                local_track_id: Some(local_track.id),
-                success: true,
-            })
-        }
+    let best_match = match search_result {
+        Ok(Some(best_match)) => best_match,
         Ok(None) => {
             tracing::info!(
                 "No best match found for spotify track: {:?}",
-                &spotify_track
+                &spotify_track_model
             );
             // Record the failure for tracking purposes
             record_download_failure(
                 db,
                 spotify_playlist_id,
-                &spotify_track,
+                &spotify_track_model,
                 "No match found".to_string(),
             )
             .await?;
 
-            Ok(ProcessTrackResult {
+            return Ok(Some(ProcessTrackResult {
                 local_track_id: None,
                 success: false,
-            })
+            }));
         }
         Err(e) => {
-            tracing::error!(
-                "Failed to download best match for spotify track: {:?}: {}",
-                &spotify_track,
-                e
-            );
-            // Record the failure with the error message
-            record_download_failure(db, spotify_playlist_id, &spotify_track, e.to_string()).await?;
+            return failed_download(db, spotify_playlist_id, &spotify_track_model, e)
+                .await
+                .map(Some);
+        }
+    };
 
-            Ok(ProcessTrackResult {
-                local_track_id: None,
-                success: false,
-            })
+    let Some(download_result) = stages
+        .run(
+            SyncStage::Download,
+            || ctx.is_cancelled(),
+            download_match(soulseek_context, &best_match),
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    // The temporary directory is removed once `_temp_dir` is dropped, after the import
+    let (_temp_dir, temp_file) = match download_result {
+        Ok(download) => download,
+        Err(e) => {
+            return failed_download(db, spotify_playlist_id, &spotify_track_model, e)
+                .await
+                .map(Some);
         }
-    }
-}
+    };
+    tracing::info!(
+        "Downloaded best match for spotify track: {:?}",
+        &spotify_track_model
+    );
 
-/// Records a download failure in the database for tracking and debugging purposes.
-async fn record_download_failure(
-    db: &Database,
-    spotify_playlist_id: i64,
-    spotify_track: &entities::spotify_track::ModelEx,
-    reason: String,
-) -> Result<()> {
-    // Convert ModelEx to Model to access fields
-    let spotify_track_model: entities::spotify_track::Model = spotify_track.clone().into();
-    let spotify_track_download_failure = entities::spotify_track_download_failure::ActiveModel {
-        spotify_playlist_id: Set(spotify_playlist_id),
-        spotify_track_id: Set(spotify_track_model.spotify_track_id),
-        track_name: Set(spotify_track_model.title),
-        artist_name: Set(spotify_track_model.artists.0.join(", ")),
-        album_name: Set(Some(spotify_track_model.album)),
-        isrc: Set(spotify_track_model.isrc),
-        reason: Set(reason),
-        ..Default::default()
+    // Import the downloaded track into the local library. This goes ahead
+    // after a cancellation, so the finished download isn't wasted.
+    let Some(imported) = stages
+        .run(
+            SyncStage::Import,
+            || false,
+            import_track(&temp_file, api_key, config, db),
+        )
+        .await?
+    else {
+        return Ok(None);
     };
+    let local_track = imported?;
 
-    entities::spotify_track_download_failure::Entity::insert(spotify_track_download_failure)
+    // Link the Spotify track to the newly imported local track
+    let mut spotify_track_active: entities::spotify_track::ActiveModel = spotify_track_model.into();
+    spotify_track_active.local_track_id = Set(Some(local_track.id));
+    entities::spotify_track::Entity::update(spotify_track_active)
         .exec(&db.conn)
         .await?;
 
-    Ok(())
+    Ok(Some(ProcessTrackResult {
+        local_track_id: Some(local_track.id),
+        success: true,
+    }))
+}
+
+/// Records a failed search or download and returns the failed result.
+async fn failed_download(
+    db: &Database,
+    spotify_playlist_id: i64,
+    spotify_track: &entities::spotify_track::Model,
+    error: color_eyre::Report,
+) -> Result<ProcessTrackResult> {
+    tracing::error!(
+        "Failed to download best match for spotify track: {:?}: {}",
+        spotify_track,
+        error
+    );
+    // Record the failure with the error message
+    record_download_failure(db, spotify_playlist_id, spotify_track, error.to_string()).await?;
+
+    Ok(ProcessTrackResult {
+        local_track_id: None,
+        success: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{StreamExt, stream};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    const STAGES: [SyncStage; 3] = [SyncStage::Search, SyncStage::Download, SyncStage::Import];
+
+    /// Records the steps run by the fake pipeline and how many of each stage
+    /// ran at once
+    #[derive(Default)]
+    struct StageLog {
+        steps: Mutex<Vec<(usize, SyncStage)>>,
+        running: Mutex<HashMap<SyncStage, usize>>,
+        max_running: Mutex<HashMap<SyncStage, usize>>,
+    }
+
+    impl StageLog {
+        async fn step(&self, track: usize, stage: SyncStage) {
+            self.steps.lock().unwrap().push((track, stage));
+            {
+                let mut running = self.running.lock().unwrap();
+                let count = running.entry(stage).or_default();
+                *count += 1;
+                let mut max_running = self.max_running.lock().unwrap();
+                let max = max_running.entry(stage).or_default();
+                *max = (*max).max(*count);
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            *self.running.lock().unwrap().get_mut(&stage).unwrap() -= 1;
+        }
+
+        fn max_running(&self, stage: SyncStage) -> usize {
+            self.max_running.lock().unwrap()[&stage]
+        }
+    }
+
+    /// Runs the stages of `process_spotify_track` with fake steps
+    async fn process(
+        stages: &SyncStages,
+        log: &StageLog,
+        cancelled: &AtomicBool,
+        track: usize,
+    ) -> Result<Option<()>> {
+        for stage in STAGES {
+            let step = log.step(track, stage);
+            if stages
+                .run(stage, || cancelled.load(Ordering::SeqCst), step)
+                .await?
+                .is_none()
+            {
+                return Ok(None);
+            }
+        }
+        Ok(Some(()))
+    }
+
+    fn sync_config(search: usize, download: usize, import: usize) -> PlaylistSyncConfig {
+        PlaylistSyncConfig {
+            search_concurrency: search,
+            download_concurrency: download,
+            import_concurrency: import,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stages_run_in_order_within_their_limits() {
+        let config = sync_config(2, 3, 1);
+        let stages = SyncStages::new(&config);
+        let log = StageLog::default();
+        let cancelled = AtomicBool::new(false);
+
+        let results = stream::iter(0..8)
+            .map(|track| process(&stages, &log, &cancelled, track))
+            .buffer_unordered(config.max_tracks_in_flight())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(results.len(), 8);
+        assert!(results.iter().all(|result| matches!(result, Ok(Some(())))));
+
+        let steps = log.steps.lock().unwrap().clone();
+        for track in 0..8 {
+            let track_steps = steps
+                .iter()
+                .filter(|(step_track, _)| *step_track == track)
+                .map(|(_, stage)| *stage)
+                .collect::<Vec<_>>();
+            assert_eq!(track_steps, STAGES);
+        }
+        assert_eq!(log.max_running(SyncStage::Search), 2);
+        assert!(log.max_running(SyncStage::Download) <= 3);
+        assert_eq!(log.max_running(SyncStage::Import), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_stops_tracks_at_their_next_stage() {
+        let stages = SyncStages::new(&sync_config(1, 1, 1));
+        let log = StageLog::default();
+        let cancelled = AtomicBool::new(false);
+        let started = Notify::new();
+        let release = Notify::new();
+
+        // The first track holds the only search slot until released
+        let first = async {
+            let step = async {
+                log.steps.lock().unwrap().push((0, SyncStage::Search));
+                started.notify_one();
+                release.notified().await;
+            };
+            stages
+                .run(SyncStage::Search, || cancelled.load(Ordering::SeqCst), step)
+                .await?;
+            let step = log.step(0, SyncStage::Download);
+            stages
+                .run(
+                    SyncStage::Download,
+                    || cancelled.load(Ordering::SeqCst),
+                    step,
+                )
+                .await
+        };
+        let second = process(&stages, &log, &cancelled, 1);
+        let cancel = async {
+            started.notified().await;
+            cancelled.store(true, Ordering::SeqCst);
+            release.notify_one();
+        };
+
+        let (first, second, ()) = tokio::join!(first, second, cancel);
+        assert!(first.unwrap().is_none());
+        assert!(second.unwrap().is_none());
+        assert_eq!(*log.steps.lock().unwrap(), vec![(0, SyncStage::Search)]);
+    }
 }
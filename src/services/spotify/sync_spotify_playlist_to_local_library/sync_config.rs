@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// Concurrency limits for syncing a Spotify playlist to the local library.
+///
+/// Loaded from the `[playlist_sync]` section of the config file. Every field
+/// is optional and falls back to the default. Searches are additionally
+/// throttled by the SoulSeek rate limiter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaylistSyncConfig {
+    /// Tracks searched for on SoulSeek at the same time
+    pub search_concurrency: usize,
+    /// Tracks downloaded from SoulSeek at the same time
+    pub download_concurrency: usize,
+    /// Downloaded tracks imported into the library at the same time
+    pub import_concurrency: usize,
+}
+
+impl Default for PlaylistSyncConfig {
+    fn default() -> Self {
+        Self {
+            search_concurrency: 2,
+            download_concurrency: 3,
+            import_concurrency: 1,
+        }
+    }
+}
+
+impl PlaylistSyncConfig {
+    /// Tracks in flight across all stages, enough to keep every stage busy
+    pub fn max_tracks_in_flight(&self) -> usize {
+        self.search_concurrency.max(1)
+            + self.download_concurrency.max(1)
+            + self.import_concurrency.max(1)
+    }
+}
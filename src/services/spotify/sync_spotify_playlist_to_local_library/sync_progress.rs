@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use crate::database::Database;
+use crate::entities;
+use crate::entities::spotify_playlist_sync_track::SyncTrackStatus;
+use color_eyre::eyre::Result;
+use color_eyre::eyre::WrapErr;
+use sea_orm::prelude::Expr;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+
+/// Loads the per-track progress of a sync, adding pending entries for tracks
+/// the sync hasn't seen yet. Returns the progress keyed by Spotify track ID.
+pub async fn load_sync_progress(
+    db: &Database,
+    sync_state_id: i64,
+    spotify_track_ids: &[String],
+) -> Result<HashMap<String, entities::spotify_playlist_sync_track::Model>> {
+    if !spotify_track_ids.is_empty() {
+        let models = spotify_track_ids.iter().map(|spotify_track_id| {
+            entities::spotify_playlist_sync_track::ActiveModel {
+                sync_state_id: Set(sync_state_id),
+                spotify_track_id: Set(spotify_track_id.clone()),
+                ..Default::default()
+            }
+        });
+
+        entities::spotify_playlist_sync_track::Entity::insert_many(models)
+            .on_conflict(
+                OnConflict::columns([
+                    entities::spotify_playlist_sync_track::Column::SyncStateId,
+                    entities::spotify_playlist_sync_track::Column::SpotifyTrackId,
+                ])
+                .do_nothing()
+                .to_owned(),
+            )
+            .do_nothing()
+            .exec(&db.conn)
+            .await
+            .wrap_err("Failed to create sync track progress")?;
+    }
+
+    let progress = entities::spotify_playlist_sync_track::Entity::find()
+        .filter(entities::spotify_playlist_sync_track::Column::SyncStateId.eq(sync_state_id))
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch sync track progress")?
+        .into_iter()
+        .map(|track| (track.spotify_track_id.clone(), track))
+        .collect();
+
+    Ok(progress)
+}
+
+/// Records the outcome of syncing a single track.
+pub async fn record_track_progress(
+    db: &Database,
+    sync_state_id: i64,
+    spotify_track_id: &str,
+    status: SyncTrackStatus,
+    local_track_id: Option<i64>,
+) -> Result<()> {
+    entities::spotify_playlist_sync_track::Entity::update_many()
+        .col_expr(
+            entities::spotify_playlist_sync_track::Column::Status,
+            Expr::value(status),
+        )
+        .col_expr(
+            entities::spotify_playlist_sync_track::Column::LocalTrackId,
+            Expr::value(local_track_id),
+        )
+        .col_expr(
+            entities::spotify_playlist_sync_track::Column::UpdatedAt,
+            Expr::value(chrono::Utc::now().timestamp()),
+        )
+        .filter(entities::spotify_playlist_sync_track::Column::SyncStateId.eq(sync_state_id))
+        .filter(entities::spotify_playlist_sync_track::Column::SpotifyTrackId.eq(spotify_track_id))
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to update sync track progress")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{insert_spotify_account, test_db};
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait};
+
+    async fn insert_sync_state(db: &Database) -> entities::spotify_playlist_sync_state::Model {
+        let account = insert_spotify_account(db, "test_user", "rt").await;
+        let playlist = entities::spotify_playlist::ActiveModel {
+            account_id: Set(account.id),
+            spotify_id: Set("playlist".into()),
+            name: Set("Playlist".into()),
+            snapshot_id: Set("snap1".into()),
+            track_count: Set(2),
+            ..entities::spotify_playlist::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        for id in ["sp1", "sp2"] {
+            entities::spotify_track::ActiveModel {
+                spotify_track_id: Set(id.into()),
+                title: Set(id.into()),
+                artists: Set(entities::spotify_track::StringVec(vec!["Artist".into()])),
+                album: Set("Album".into()),
+                ..entities::spotify_track::ActiveModel::new()
+            }
+            .insert(&db.conn)
+            .await
+            .unwrap();
+        }
+        entities::spotify_playlist_sync_state::ActiveModel {
+            spotify_playlist_id: Set(playlist.id),
+            ..entities::spotify_playlist_sync_state::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_progress_survives_reload() {
+        let db = test_db().await;
+        let sync_state = insert_sync_state(&db).await;
+        let ids = vec!["sp1".to_string(), "sp2".to_string()];
+
+        let progress = load_sync_progress(&db, sync_state.id, &ids).await.unwrap();
+        assert_eq!(progress.len(), 2);
+        assert!(
+            progress
+                .values()
+                .all(|track| track.status == SyncTrackStatus::Pending)
+        );
+
+        record_track_progress(&db, sync_state.id, "sp1", SyncTrackStatus::Failed, None)
+            .await
+            .unwrap();
+
+        // Loading again keeps recorded progress instead of resetting it
+        let progress = load_sync_progress(&db, sync_state.id, &ids).await.unwrap();
+        assert_eq!(progress["sp1"].status, SyncTrackStatus::Failed);
+        assert_eq!(progress["sp2"].status, SyncTrackStatus::Pending);
+    }
+}
@@ -1,30 +1,74 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::config::Config;
 use crate::database::Database;
 use crate::entities;
+use crate::entities::spotify_playlist_sync_track::SyncTrackStatus;
+use crate::services::spotify::download_failures::{
+    clear_download_failure, clear_removed_track_failures, tracks_not_due_for_retry,
+};
+use crate::services::tasks::TaskContext;
 use crate::soulseek::SoulSeekClientContext;
 use color_eyre::eyre::OptionExt;
 use color_eyre::eyre::Result;
 use color_eyre::eyre::WrapErr;
+use futures::{StreamExt, stream};
 use sea_orm::ColumnTrait;
 use sea_orm::QueryFilter;
 use sea_orm::{EntityTrait, Set};
 use tracing;
 
 use super::add_tracks_to_playlist::add_tracks_to_local_playlist;
-use super::process_track::process_spotify_track;
+use super::process_track::{ProcessTrackResult, SyncStages, process_spotify_track};
+use super::sync_progress::{load_sync_progress, record_track_progress};
+
+/// How a track of the playlist was handled in this run of the sync.
+enum TrackOutcome {
+    /// Completed by an earlier run of the sync
+    AlreadySynced(i64),
+    /// Failed before and not due for another attempt yet
+    NotDue,
+    Processed(ProcessTrackResult),
+}
+
+/// Counts of the tracks handled so far, shown on the sync state and the task
+#[derive(Debug, Default, PartialEq)]
+struct SyncCounters {
+    tracks_downloaded: i32,
+    tracks_failed: i32,
+    processed: i64,
+}
+
+impl SyncCounters {
+    fn record(&mut self, outcome: &TrackOutcome) {
+        match outcome {
+            TrackOutcome::AlreadySynced(_) => self.tracks_downloaded += 1,
+            TrackOutcome::NotDue => self.tracks_failed += 1,
+            TrackOutcome::Processed(result) if result.success => self.tracks_downloaded += 1,
+            TrackOutcome::Processed(_) => self.tracks_failed += 1,
+        }
+        self.processed += 1;
+    }
+}
 
 /// Main sync function that orchestrates the process of syncing a Spotify playlist
 /// to the local music library.
 ///
 /// This function:
 /// 1. Loads the Spotify playlist with all its tracks
-/// 2. Processes each track (downloads/matches if needed)
-/// 3. Updates sync state progress after each track
-/// 4. Adds all successfully processed tracks to the local playlist
-/// 5. Marks the sync as completed
+/// 2. Processes the tracks concurrently (downloads/matches if needed), skipping
+///    tracks that an earlier run of the same sync already completed and failed
+///    tracks that aren't due for a retry
+/// 3. Updates sync state and per-track progress after each track
+/// 4. Adds all successfully processed tracks to the local playlist in Spotify order
+/// 5. Deletes download failures of tracks removed from the Spotify playlist
+/// 6. Marks the sync as completed
 ///
 /// The sync state is updated incrementally throughout the process so that
-/// progress can be monitored even if the sync is interrupted.
+/// progress can be monitored and the sync resumed if it is interrupted. When
+/// the task is cancelled no new tracks are started, tracks in flight stop at
+/// their next stage, and the local playlist is left untouched.
+#[allow(clippy::too_many_arguments)]
 pub async fn sync_spotify_playlist_to_local_library(
     db: &Database,
     soulseek_context: &SoulSeekClientContext,
@@ -33,13 +77,20 @@ pub async fn sync_spotify_playlist_to_local_library(
     sync_state: entities::spotify_playlist_sync_state::Model,
     spotify_playlist: entities::spotify_playlist::Model,
     local_playlist: entities::playlist::Model,
+    ctx: &TaskContext,
 ) -> Result<()> {
     tracing::info!(
         "Starting sync of spotify playlist to local library: {:?}",
         &spotify_playlist
     );
 
+    let sync_state_id = sync_state.id;
     let mut sync_state: entities::spotify_playlist_sync_state::ActiveModel = sync_state.into();
+    sync_state.sync_status = Set("in_progress".to_string());
+    sync_state.error_log = Set(None);
+    entities::spotify_playlist_sync_state::Entity::update(sync_state.clone())
+        .exec(&db.conn)
+        .await?;
 
     // Load the Spotify playlist with all its tracks and their local track relationships
     let spotify_playlist_with_tracks = entities::spotify_playlist::Entity::load()
@@ -54,49 +105,180 @@ pub async fn sync_spotify_playlist_to_local_library(
         .wrap_err("Failed to fetch spotify tracks for spotify playlist")?
         .ok_or_eyre("Spotify playlist not found")?;
 
+    // Process tracks in playlist order so the local playlist mirrors Spotify
+    let positions: HashMap<String, i32> = entities::spotify_track_playlist::Entity::find()
+        .filter(entities::spotify_track_playlist::Column::SpotifyPlaylistId.eq(spotify_playlist.id))
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch spotify playlist track positions")?
+        .into_iter()
+        .map(|link| (link.spotify_track_id, link.position))
+        .collect();
+    let mut spotify_tracks: Vec<_> = spotify_playlist_with_tracks
+        .spotify_tracks
+        .into_iter()
+        .collect();
+    spotify_tracks.sort_by_key(|track| {
+        positions
+            .get(&track.spotify_track_id)
+            .copied()
+            .unwrap_or(i32::MAX)
+    });
+
     tracing::info!(
         "Found {} tracks in spotify playlist: {:?}",
-        spotify_playlist_with_tracks.spotify_tracks.len(),
+        spotify_tracks.len(),
         &spotify_playlist
     );
 
-    // Process each track and collect successfully processed track IDs
-    let mut local_tracks_for_local_playlist = Vec::new();
-    let mut tracks_downloaded = 0;
-    let mut tracks_failed = 0;
-
-    for spotify_track in spotify_playlist_with_tracks.spotify_tracks {
-        let result = process_spotify_track(
-            db,
-            soulseek_context,
-            &api_key,
-            &config,
-            spotify_playlist.id,
-            spotify_track,
-        )
-        .await?;
+    // Per-track progress from earlier runs of this sync, so an interrupted
+    // sync only retries tracks that didn't complete
+    let spotify_track_ids: Vec<String> = spotify_tracks
+        .iter()
+        .map(|track| track.spotify_track_id.clone())
+        .collect();
+    let progress = load_sync_progress(db, sync_state_id, &spotify_track_ids).await?;
+    // Tracks that failed before and are still backing off or marked "won't find"
+    let not_due_for_retry: HashSet<String> = tracks_not_due_for_retry(db, spotify_playlist.id)
+        .await?
+        .into_iter()
+        .collect();
+
+    // Tracks go through the search, download and import stages concurrently,
+    // each stage with its own limit. Results are handled here one at a time,
+    // so the sync state counters are only ever updated from this loop.
+    let sync_config = config.playlist_sync().clone();
+    let stages = SyncStages::new(&sync_config);
+    let api_key = api_key.as_str();
+    let config = &config;
+    let stages = &stages;
+    let progress = &progress;
+    let not_due_for_retry = &not_due_for_retry;
+    let mut results = stream::iter(spotify_tracks.into_iter().enumerate())
+        .map(|(position, spotify_track)| async move {
+            let spotify_track_id = spotify_track.spotify_track_id.clone();
+            let completed_local_track_id = progress
+                .get(&spotify_track_id)
+                .filter(|track| track.status == SyncTrackStatus::Completed)
+                .and_then(|track| track.local_track_id);
+
+            if let Some(local_track_id) = completed_local_track_id {
+                tracing::debug!(
+                    "Skipping spotify track already synced: {}",
+                    &spotify_track_id
+                );
+                return (
+                    position,
+                    spotify_track_id,
+                    Ok(Some(TrackOutcome::AlreadySynced(local_track_id))),
+                );
+            }
+            if not_due_for_retry.contains(&spotify_track_id) {
+                tracing::debug!(
+                    "Skipping spotify track not due for retry: {}",
+                    &spotify_track_id
+                );
+                return (position, spotify_track_id, Ok(Some(TrackOutcome::NotDue)));
+            }
+
+            let result = process_spotify_track(
+                db,
+                soulseek_context,
+                api_key,
+                config,
+                spotify_playlist.id,
+                spotify_track,
+                stages,
+                ctx,
+            )
+            .await
+            .map(|result| result.map(TrackOutcome::Processed));
+            (position, spotify_track_id, result)
+        })
+        .buffer_unordered(sync_config.max_tracks_in_flight());
 
-        if result.success {
-            if let Some(local_track_id) = result.local_track_id {
-                local_tracks_for_local_playlist.push(local_track_id);
+    // Successfully processed local track IDs keyed by playlist position
+    let mut local_tracks_by_position = Vec::new();
+    let mut counters = SyncCounters::default();
+
+    let total_tracks = spotify_track_ids.len() as i64;
+    while let Some((position, spotify_track_id, result)) = results.next().await {
+        // Cancelled before the track finished; it stays pending
+        let Some(outcome) = result? else {
+            continue;
+        };
+        counters.record(&outcome);
+        match outcome {
+            TrackOutcome::AlreadySynced(local_track_id) => {
+                local_tracks_by_position.push((position, local_track_id));
+            }
+            TrackOutcome::NotDue => {
+                record_track_progress(
+                    db,
+                    sync_state_id,
+                    &spotify_track_id,
+                    SyncTrackStatus::Failed,
+                    None,
+                )
+                .await?;
+            }
+            TrackOutcome::Processed(result) => {
+                let status = if result.success {
+                    if let Some(local_track_id) = result.local_track_id {
+                        local_tracks_by_position.push((position, local_track_id));
+                    }
+                    clear_download_failure(db, spotify_playlist.id, &spotify_track_id).await?;
+                    SyncTrackStatus::Completed
+                } else {
+                    SyncTrackStatus::Failed
+                };
+                record_track_progress(
+                    db,
+                    sync_state_id,
+                    &spotify_track_id,
+                    status,
+                    result.local_track_id,
+                )
+                .await?;
             }
-            tracks_downloaded += 1;
-        } else {
-            tracks_failed += 1;
         }
 
         // Update sync state progress after each track so progress is visible
         // even if the sync is interrupted
-        sync_state.tracks_downloaded = Set(tracks_downloaded);
-        sync_state.tracks_failed = Set(tracks_failed);
+        sync_state.tracks_downloaded = Set(counters.tracks_downloaded);
+        sync_state.tracks_failed = Set(counters.tracks_failed);
         entities::spotify_playlist_sync_state::Entity::update(sync_state.clone())
             .exec(&db.conn)
             .await?;
+        ctx.report_progress(counters.processed, Some(total_tracks))
+            .await?;
+    }
+
+    if ctx.is_cancelled() {
+        tracing::info!(
+            "Cancelled sync of spotify playlist to local library: {:?}",
+            &spotify_playlist
+        );
+        sync_state.sync_status = Set("cancelled".to_string());
+        entities::spotify_playlist_sync_state::Entity::update(sync_state)
+            .exec(&db.conn)
+            .await?;
+        return Ok(());
     }
 
+    // Restore Spotify order, as tracks finish out of order
+    local_tracks_by_position.sort_by_key(|(position, _)| *position);
+    let local_tracks_for_local_playlist = local_tracks_by_position
+        .into_iter()
+        .map(|(_, local_track_id)| local_track_id)
+        .collect();
+
     // Add all successfully processed tracks to the local playlist
     add_tracks_to_local_playlist(db, &local_playlist, local_tracks_for_local_playlist).await?;
 
+    // Failures of removed tracks would keep resuming the sync
+    clear_removed_track_failures(db, spotify_playlist.id).await?;
+
     // Mark sync as completed
     tracing::info!(
         "Completed sync of spotify playlist to local library: {:?}",
@@ -109,3 +291,54 @@ pub async fn sync_spotify_playlist_to_local_library(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn processed(success: bool) -> TrackOutcome {
+        TrackOutcome::Processed(ProcessTrackResult {
+            local_track_id: success.then_some(1),
+            success,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_counters_add_up_when_tracks_finish_out_of_order() {
+        // Later tracks finish first; cancelled tracks (`None`) aren't counted
+        let outcomes = (0..12u64).map(|position| {
+            let outcome = match position % 4 {
+                0 => Some(TrackOutcome::AlreadySynced(position as i64)),
+                1 => Some(processed(true)),
+                2 => Some(processed(false)),
+                _ if position == 11 => None,
+                _ => Some(TrackOutcome::NotDue),
+            };
+            async move {
+                tokio::time::sleep(Duration::from_millis(12 - position)).await;
+                (position, outcome)
+            }
+        });
+        let mut results = stream::iter(outcomes).buffer_unordered(6);
+
+        let mut counters = SyncCounters::default();
+        let mut finished = Vec::new();
+        while let Some((position, outcome)) = results.next().await {
+            finished.push(position);
+            if let Some(outcome) = outcome {
+                counters.record(&outcome);
+            }
+        }
+
+        assert_ne!(finished, (0..12).collect::<Vec<_>>());
+        assert_eq!(
+            counters,
+            SyncCounters {
+                tracks_downloaded: 6,
+                tracks_failed: 5,
+                processed: 11,
+            }
+        );
+    }
+}
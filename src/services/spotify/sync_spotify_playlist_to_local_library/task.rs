@@ -4,41 +4,49 @@ use tracing;
 use crate::config::Config;
 use crate::database::Database;
 use crate::entities;
+use crate::entities::background_task::{TaskKind, TaskStatus};
+use crate::services::tasks::{TaskContext, TaskHandler, TaskManager};
 use crate::soulseek::SoulSeekClientContext;
+use async_trait::async_trait;
 use color_eyre::eyre::OptionExt;
 use color_eyre::eyre::Result;
 use color_eyre::eyre::WrapErr;
+use color_eyre::eyre::eyre;
 use sea_orm::ColumnTrait;
 use sea_orm::QueryFilter;
+use sea_orm::QueryOrder;
+use sea_orm::prelude::Expr;
 use sea_orm::{EntityTrait, Set};
+use serde::{Deserialize, Serialize};
 
 use super::create_sync_state::create_sync_state;
 use super::sync_task::sync_spotify_playlist_to_local_library;
 
+#[derive(Serialize, Deserialize)]
+struct PlaylistSyncTaskPayload {
+    sync_state_id: i64,
+}
+
 /// Public entry point for syncing a Spotify playlist to the local music library.
 ///
 /// This function:
 /// 1. Validates that the Spotify playlist exists
 /// 2. Finds or creates the local playlist
 /// 3. Creates a sync state to track progress
-/// 4. Spawns a background task to perform the actual sync
+/// 4. Submits a background task to perform the actual sync
 ///
 /// The sync runs asynchronously in the background, and the sync state can be
 /// queried to check progress. The function returns immediately with the sync state.
 ///
 /// # Arguments
 /// * `db` - Database connection
-/// * `soulseek_context` - SoulSeek client context for downloading tracks
-/// * `api_key` - API key for track import
-/// * `config` - Application configuration
+/// * `tasks` - Task manager running the sync
 /// * `spotify_account_id` - ID of the Spotify account that owns the playlist
 /// * `spotify_playlist_id` - ID of the Spotify playlist to sync
 /// * `local_playlist_name` - Name of the local playlist (created if it doesn't exist)
 pub async fn sync_spotify_playlist_to_local_library_task(
-    db: Arc<Database>,
-    soulseek_context: Arc<SoulSeekClientContext>,
-    api_key: &str,
-    config: &Config,
+    db: &Database,
+    tasks: &Arc<TaskManager>,
     spotify_account_id: i64,
     spotify_playlist_id: i64,
     local_playlist_name: String,
@@ -75,50 +83,319 @@ pub async fn sync_spotify_playlist_to_local_library_task(
     };
 
     // Create sync state to track progress
-    let sync_state = create_sync_state(&db, &spotify_playlist, &local_playlist).await?;
+    let sync_state = create_sync_state(db, &spotify_playlist, &local_playlist).await?;
+
+    tasks
+        .submit(
+            TaskKind::SpotifyPlaylistSync,
+            &PlaylistSyncTaskPayload {
+                sync_state_id: sync_state.id,
+            },
+        )
+        .await?;
+
+    Ok(sync_state)
+}
+
+/// Statuses of a sync that is queued or running.
+const ACTIVE_SYNC_STATUSES: [&str; 2] = ["pending", "in_progress"];
+
+/// Resumes a sync that was interrupted, failed or cancelled. Tracks the sync
+/// already completed are skipped; pending and failed ones are retried.
+pub async fn resume_spotify_playlist_sync_task(
+    db: &Database,
+    tasks: &Arc<TaskManager>,
+    sync_state_id: i64,
+) -> Result<entities::spotify_playlist_sync_state::Model> {
+    let sync_state = entities::spotify_playlist_sync_state::Entity::find_by_id(sync_state_id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch sync state")?
+        .ok_or_eyre("Sync state not found")?;
+
+    if ACTIVE_SYNC_STATUSES.contains(&sync_state.sync_status.as_str()) {
+        return Err(eyre!("Sync {} is already running", sync_state_id));
+    }
+
+    let mut sync_state: entities::spotify_playlist_sync_state::ActiveModel = sync_state.into();
+    sync_state.sync_status = Set("pending".to_string());
+    let sync_state = entities::spotify_playlist_sync_state::Entity::update(sync_state)
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to update sync state")?;
+
+    tasks
+        .submit(
+            TaskKind::SpotifyPlaylistSync,
+            &PlaylistSyncTaskPayload { sync_state_id },
+        )
+        .await?;
+
+    Ok(sync_state)
+}
+
+/// Resumes the most recent sync of a Spotify playlist, unless it is already
+/// running. Returns the resumed sync, or None if there was nothing to resume.
+pub async fn resume_latest_playlist_sync(
+    db: &Database,
+    tasks: &Arc<TaskManager>,
+    spotify_playlist_id: i64,
+) -> Result<Option<entities::spotify_playlist_sync_state::Model>> {
+    let sync_state = entities::spotify_playlist_sync_state::Entity::find()
+        .filter(
+            entities::spotify_playlist_sync_state::Column::SpotifyPlaylistId
+                .eq(spotify_playlist_id),
+        )
+        .order_by_desc(entities::spotify_playlist_sync_state::Column::Id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch sync state")?;
+
+    match sync_state {
+        Some(sync_state) if !ACTIVE_SYNC_STATUSES.contains(&sync_state.sync_status.as_str()) => {
+            resume_spotify_playlist_sync_task(db, tasks, sync_state.id)
+                .await
+                .map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Marks syncs that claim to be pending or in progress but have no unfinished
+/// task behind them as `interrupted`, e.g. syncs started before the task that
+/// ran them was lost. Syncs whose task is still unfinished are left alone, as
+/// the task manager resumes them. Returns the number of syncs marked.
+pub async fn mark_stale_syncs(db: &Database) -> Result<u64> {
+    let active_sync_state_ids: Vec<i64> = entities::background_task::Entity::find()
+        .filter(entities::background_task::Column::Kind.eq(TaskKind::SpotifyPlaylistSync))
+        .filter(
+            entities::background_task::Column::Status
+                .is_in([TaskStatus::Pending, TaskStatus::Running]),
+        )
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch playlist sync tasks")?
+        .into_iter()
+        .filter_map(|task| {
+            serde_json::from_value::<PlaylistSyncTaskPayload>(task.payload)
+                .ok()
+                .map(|payload| payload.sync_state_id)
+        })
+        .collect();
+
+    let result = entities::spotify_playlist_sync_state::Entity::update_many()
+        .col_expr(
+            entities::spotify_playlist_sync_state::Column::SyncStatus,
+            Expr::value("interrupted"),
+        )
+        .col_expr(
+            entities::spotify_playlist_sync_state::Column::UpdatedAt,
+            Expr::value(chrono::Utc::now().timestamp()),
+        )
+        .filter(
+            entities::spotify_playlist_sync_state::Column::SyncStatus.is_in(ACTIVE_SYNC_STATUSES),
+        )
+        .filter(entities::spotify_playlist_sync_state::Column::Id.is_not_in(active_sync_state_ids))
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to mark stale playlist syncs")?;
+
+    Ok(result.rows_affected)
+}
+
+/// Runs [`TaskKind::SpotifyPlaylistSync`] tasks. A resumed sync skips the
+/// tracks it already completed.
+pub struct SpotifyPlaylistSyncTaskHandler {
+    pub soulseek_context: Arc<SoulSeekClientContext>,
+    pub api_key: String,
+    pub config: Config,
+}
+
+#[async_trait]
+impl TaskHandler for SpotifyPlaylistSyncTaskHandler {
+    fn kind(&self) -> TaskKind {
+        TaskKind::SpotifyPlaylistSync
+    }
+
+    async fn run(&self, ctx: &TaskContext) -> Result<()> {
+        let db = ctx.db();
+        let payload: PlaylistSyncTaskPayload = ctx.payload()?;
 
-    // Spawn background task to perform the sync
-    // Clone all necessary data for the async task
-    let api_key_clone = api_key.to_string();
-    let config_clone = config.clone();
-    let sync_state_clone = sync_state.clone();
-    let local_playlist_clone = local_playlist.clone();
-    let spotify_playlist_clone = spotify_playlist.clone();
+        let sync_state =
+            entities::spotify_playlist_sync_state::Entity::find_by_id(payload.sync_state_id)
+                .one(&db.conn)
+                .await
+                .wrap_err("Failed to fetch sync state")?
+                .ok_or_eyre("Sync state not found")?;
+        let spotify_playlist =
+            entities::spotify_playlist::Entity::find_by_id(sync_state.spotify_playlist_id)
+                .one(&db.conn)
+                .await
+                .wrap_err("Failed to fetch spotify playlist")?
+                .ok_or_eyre("Spotify playlist not found")?;
+        let local_playlist = entities::playlist::Entity::find_by_id(
+            sync_state
+                .local_playlist_id
+                .ok_or_eyre("Sync state has no local playlist")?,
+        )
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch local playlist")?
+        .ok_or_eyre("Local playlist not found")?;
 
-    tokio::spawn(async move {
         tracing::info!(
             "Syncing spotify playlist to local library: {:?}",
-            spotify_playlist_clone
+            spotify_playlist
         );
 
-        match sync_spotify_playlist_to_local_library(
-            &db,
-            &soulseek_context,
-            api_key_clone,
-            config_clone,
-            sync_state_clone.clone(),
-            spotify_playlist_clone,
-            local_playlist_clone,
+        let result = sync_spotify_playlist_to_local_library(
+            db,
+            &self.soulseek_context,
+            self.api_key.clone(),
+            self.config.clone(),
+            sync_state.clone(),
+            spotify_playlist,
+            local_playlist,
+            ctx,
+        )
+        .await;
+
+        if let Err(e) = &result {
+            tracing::error!("Failed to sync spotify playlist to local library: {:?}", e);
+            // Update sync state to mark it as failed
+            let mut sync_state: entities::spotify_playlist_sync_state::ActiveModel =
+                sync_state.into();
+            sync_state.sync_status = Set("error".to_string());
+            sync_state.error_log = Set(Some(e.to_string()));
+            // Ignore errors when updating failed state - the task records the error
+            let _ = entities::spotify_playlist_sync_state::Entity::update(sync_state)
+                .exec(&db.conn)
+                .await;
+        }
+
+        result
+    }
+
+    async fn cancelled_before_start(&self, ctx: &TaskContext) -> Result<()> {
+        let payload: PlaylistSyncTaskPayload = ctx.payload()?;
+        mark_cancelled_before_start(ctx.db(), payload.sync_state_id).await
+    }
+}
+
+/// Marks a sync whose task was cancelled before it started as `cancelled`,
+/// so it can be resumed later.
+async fn mark_cancelled_before_start(db: &Database, sync_state_id: i64) -> Result<()> {
+    entities::spotify_playlist_sync_state::Entity::update_many()
+        .col_expr(
+            entities::spotify_playlist_sync_state::Column::SyncStatus,
+            Expr::value("cancelled"),
+        )
+        .col_expr(
+            entities::spotify_playlist_sync_state::Column::UpdatedAt,
+            Expr::value(chrono::Utc::now().timestamp()),
+        )
+        .filter(entities::spotify_playlist_sync_state::Column::Id.eq(sync_state_id))
+        .filter(
+            entities::spotify_playlist_sync_state::Column::SyncStatus.is_in(ACTIVE_SYNC_STATUSES),
         )
+        .exec(&db.conn)
         .await
-        {
-            Ok(()) => {
-                tracing::info!("Successfully completed sync");
-            }
-            Err(e) => {
-                tracing::error!("Failed to sync spotify playlist to local library: {:?}", e);
-                // Update sync state to mark it as failed
-                let mut sync_state: entities::spotify_playlist_sync_state::ActiveModel =
-                    sync_state_clone.into();
-                sync_state.sync_status = Set("error".to_string());
-                sync_state.error_log = Set(Some(e.to_string()));
-                // Ignore errors when updating failed state - we've already logged the error
-                let _ = entities::spotify_playlist_sync_state::Entity::update(sync_state)
-                    .exec(&db.conn)
-                    .await;
+        .wrap_err("Failed to mark sync as cancelled")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{insert_spotify_account, test_db};
+    use sea_orm::{ActiveModelBehavior, ActiveModelTrait};
+
+    #[tokio::test]
+    async fn test_mark_stale_syncs() {
+        let db = test_db().await;
+        let account = insert_spotify_account(&db, "test_user", "rt").await;
+        let playlist = entities::spotify_playlist::ActiveModel {
+            account_id: Set(account.id),
+            spotify_id: Set("playlist".into()),
+            name: Set("Playlist".into()),
+            snapshot_id: Set("snap1".into()),
+            track_count: Set(0),
+            ..entities::spotify_playlist::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let mut sync_states = Vec::new();
+        for status in ["in_progress", "in_progress", "completed"] {
+            let sync_state = entities::spotify_playlist_sync_state::ActiveModel {
+                spotify_playlist_id: Set(playlist.id),
+                sync_status: Set(status.to_string()),
+                ..entities::spotify_playlist_sync_state::ActiveModel::new()
             }
+            .insert(&db.conn)
+            .await
+            .unwrap();
+            sync_states.push(sync_state);
         }
-    });
 
-    Ok(sync_state)
+        // Only the first sync still has a task that will resume it
+        entities::background_task::ActiveModel {
+            kind: Set(TaskKind::SpotifyPlaylistSync),
+            status: Set(TaskStatus::Running),
+            payload: Set(serde_json::json!({ "sync_state_id": sync_states[0].id })),
+            ..entities::background_task::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        assert_eq!(mark_stale_syncs(&db).await.unwrap(), 1);
+
+        let statuses: Vec<String> = entities::spotify_playlist_sync_state::Entity::find()
+            .all(&db.conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|sync_state| sync_state.sync_status)
+            .collect();
+        assert_eq!(statuses, vec!["in_progress", "interrupted", "completed"]);
+    }
+
+    #[tokio::test]
+    async fn test_mark_cancelled_before_start() {
+        let db = test_db().await;
+        let account = insert_spotify_account(&db, "test_user", "rt").await;
+        let playlist = entities::spotify_playlist::ActiveModel {
+            account_id: Set(account.id),
+            spotify_id: Set("playlist".into()),
+            name: Set("Playlist".into()),
+            snapshot_id: Set("snap1".into()),
+            track_count: Set(0),
+            ..entities::spotify_playlist::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+        let sync_state = entities::spotify_playlist_sync_state::ActiveModel {
+            spotify_playlist_id: Set(playlist.id),
+            sync_status: Set("pending".to_string()),
+            ..entities::spotify_playlist_sync_state::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        mark_cancelled_before_start(&db, sync_state.id)
+            .await
+            .unwrap();
+
+        let sync_state = entities::spotify_playlist_sync_state::Entity::find_by_id(sync_state.id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(sync_state.sync_status, "cancelled");
+        assert!(!ACTIVE_SYNC_STATUSES.contains(&sync_state.sync_status.as_str()));
+    }
 }
@@ -0,0 +1,392 @@
+//! Spotify API clients per account.
+//!
+//! Each account gets its own cached client and lock, so accounts refresh and
+//! sync independently. Refreshed tokens are written back to `spotify_account`,
+//! including refresh tokens rotated by Spotify, and an account whose refresh
+//! token was revoked is marked as needing re-authorization instead of being
+//! retried.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set};
+use tokio::sync::Mutex;
+
+use crate::database::Database;
+use crate::entities;
+use crate::entities::spotify_account::AccountStatus;
+use crate::ports::spotify::{
+    SpotifyApiAudioFeatures, SpotifyApiPlaylist, SpotifyApiTrack, SpotifyClient,
+};
+use crate::services::spotify::client::{
+    RefreshedToken, SpotifyApiCredentials, SpotifyWebApiClient, refresh_access_token,
+};
+
+/// Clients are rebuilt this long before their token expires
+const REFRESH_MARGIN_SECS: i64 = 5 * 60;
+
+struct CachedClient {
+    client: Arc<dyn SpotifyClient>,
+    expires_at: i64,
+}
+
+type AccountSlot = Arc<Mutex<Option<CachedClient>>>;
+
+pub struct SpotifyTokenManager {
+    db: Arc<Database>,
+    credentials: Option<SpotifyApiCredentials>,
+    http: reqwest::Client,
+    accounts: Mutex<HashMap<i64, AccountSlot>>,
+}
+
+impl SpotifyTokenManager {
+    pub fn new(db: Arc<Database>, credentials: Option<SpotifyApiCredentials>) -> Self {
+        Self {
+            db,
+            credentials,
+            http: reqwest::Client::new(),
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// A client for the account. It checks out the account's current client
+    /// for every request, so it keeps working in long tasks after the access
+    /// token it started with expired.
+    pub async fn client(self: &Arc<Self>, account_id: i64) -> Result<Arc<dyn SpotifyClient>> {
+        // Fails early when the account can't be used
+        self.checkout(account_id).await?;
+        Ok(Arc::new(AccountSpotifyClient {
+            tokens: self.clone(),
+            account_id,
+        }))
+    }
+
+    /// The cached client of the account, refreshing its access token if it is
+    /// about to expire.
+    async fn checkout(&self, account_id: i64) -> Result<Arc<dyn SpotifyClient>> {
+        let slot = self
+            .accounts
+            .lock()
+            .await
+            .entry(account_id)
+            .or_default()
+            .clone();
+        // Held across the refresh so concurrent callers wait for one refresh
+        // instead of racing to rotate the refresh token
+        let mut cached = slot.lock().await;
+
+        let now = chrono::Utc::now().timestamp();
+        if let Some(cached) = cached.as_ref()
+            && cached.expires_at - REFRESH_MARGIN_SECS > now
+        {
+            return Ok(cached.client.clone());
+        }
+
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_eyre("Spotify credentials not found")?;
+        let account = entities::spotify_account::Entity::find_by_id(account_id)
+            .one(&self.db.conn)
+            .await
+            .wrap_err("Failed to fetch spotify account")?
+            .ok_or_eyre("Spotify account not found")?;
+
+        if account.status == AccountStatus::NeedsReauth {
+            return Err(eyre!(
+                "Spotify account {} needs to be authorized again",
+                account.user_id
+            ));
+        }
+
+        let (client, expires_at) = self.refresh(credentials, account).await?;
+        // The account was forgotten during the refresh, e.g. because it was
+        // re-authorized; its next checkout starts over with a new slot
+        let forgotten = !self
+            .accounts
+            .lock()
+            .await
+            .get(&account_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &slot));
+        if !forgotten {
+            *cached = Some(CachedClient {
+                client: client.clone(),
+                expires_at,
+            });
+        }
+        Ok(client)
+    }
+
+    /// Drops the cached client, e.g. after the account was re-authorized or
+    /// deleted.
+    pub async fn forget(&self, account_id: i64) {
+        self.accounts.lock().await.remove(&account_id);
+    }
+
+    /// Refreshes the account's access token, returning a client using it and
+    /// its expiry.
+    async fn refresh(
+        &self,
+        credentials: &SpotifyApiCredentials,
+        account: entities::spotify_account::Model,
+    ) -> Result<(Arc<dyn SpotifyClient>, i64)> {
+        tracing::debug!("Refreshing spotify token for account {}", account.user_id);
+        match self.connect(credentials, &account.refresh_token).await {
+            Ok((client, token)) => {
+                let expires_at = record_refresh_success(&self.db, account, &token).await?;
+                Ok((client, expires_at))
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to refresh spotify token for account {}: {:?}",
+                    account.user_id,
+                    e
+                );
+                record_refresh_failure(&self.db, account, &e).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Exchanges the refresh token for a client using the new access token.
+    async fn connect(
+        &self,
+        credentials: &SpotifyApiCredentials,
+        refresh_token: &str,
+    ) -> Result<(Arc<dyn SpotifyClient>, RefreshedToken)> {
+        let token = refresh_access_token(&self.http, credentials, refresh_token).await?;
+        let client = SpotifyWebApiClient::new(
+            self.http.clone(),
+            credentials.api_base_url().clone(),
+            token.access_token.clone(),
+        );
+        Ok((Arc::new(client), token))
+    }
+}
+
+/// Client returned by [`SpotifyTokenManager::client`].
+struct AccountSpotifyClient {
+    tokens: Arc<SpotifyTokenManager>,
+    account_id: i64,
+}
+
+#[async_trait::async_trait]
+impl SpotifyClient for AccountSpotifyClient {
+    async fn current_user_playlists(&self) -> Result<Vec<SpotifyApiPlaylist>> {
+        let client = self.tokens.checkout(self.account_id).await?;
+        client.current_user_playlists().await
+    }
+
+    async fn playlist_tracks(&self, playlist_id: &str) -> Result<Vec<SpotifyApiTrack>> {
+        let client = self.tokens.checkout(self.account_id).await?;
+        client.playlist_tracks(playlist_id).await
+    }
+
+    async fn search_tracks(&self, query: &str) -> Result<Vec<SpotifyApiTrack>> {
+        let client = self.tokens.checkout(self.account_id).await?;
+        client.search_tracks(query).await
+    }
+
+    async fn audio_features(&self, track_ids: &[String]) -> Result<Vec<SpotifyApiAudioFeatures>> {
+        let client = self.tokens.checkout(self.account_id).await?;
+        client.audio_features(track_ids).await
+    }
+}
+
+/// Whether a refresh failed because Spotify no longer accepts the refresh
+/// token, as opposed to e.g. a network error worth retrying. Spotify answers
+/// revoked or expired refresh tokens with the OAuth `invalid_grant` error.
+fn is_refresh_token_revoked(error: &color_eyre::Report) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.to_string().contains("invalid_grant"))
+}
+
+/// Saves the refreshed tokens, returning when the access token expires.
+async fn record_refresh_success(
+    db: &Database,
+    account: entities::spotify_account::Model,
+    token: &RefreshedToken,
+) -> Result<i64> {
+    let now = chrono::Utc::now().timestamp();
+    let expires_at = now + token.expires_in;
+    let mut account: entities::spotify_account::ActiveModel = account.into();
+    account.access_token = Set(token.access_token.clone());
+    // Spotify only returns a refresh token when it rotates it
+    if let Some(refresh_token) = &token.refresh_token {
+        account.refresh_token = Set(refresh_token.clone());
+    }
+    account.token_expiry = Set(expires_at);
+    account.status = Set(AccountStatus::Active);
+    account.last_error = Set(None);
+    account.last_refreshed_at = Set(Some(now));
+    account
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save refreshed spotify tokens")?;
+    Ok(expires_at)
+}
+
+async fn record_refresh_failure(
+    db: &Database,
+    account: entities::spotify_account::Model,
+    error: &color_eyre::Report,
+) -> Result<()> {
+    let mut account: entities::spotify_account::ActiveModel = account.into();
+    if is_refresh_token_revoked(error) {
+        account.status = Set(AccountStatus::NeedsReauth);
+    }
+    account.last_error = Set(Some(format!("{:#}", error)));
+    account
+        .update(&db.conn)
+        .await
+        .wrap_err("Failed to save spotify account refresh error")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::fake_spotify::FakeSpotify;
+    use crate::test_utils::{insert_spotify_account, test_db};
+
+    async fn find_account(db: &Database, id: i64) -> entities::spotify_account::Model {
+        entities::spotify_account::Entity::find_by_id(id)
+            .one(&db.conn)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_refresh_success_persists_rotated_token() {
+        let db = test_db().await;
+        let account = insert_spotify_account(&db, "test_user", "rt").await;
+
+        let token = RefreshedToken {
+            access_token: "at2".into(),
+            refresh_token: Some("rt2".into()),
+            expires_in: 3600,
+        };
+        record_refresh_success(&db, account.clone(), &token)
+            .await
+            .unwrap();
+        let refreshed = find_account(&db, account.id).await;
+        assert_eq!(refreshed.access_token, "at2");
+        assert_eq!(refreshed.refresh_token, "rt2");
+        assert!(refreshed.token_expiry > 0);
+        assert!(refreshed.last_refreshed_at.is_some());
+
+        // Without a rotated refresh token the stored one is kept
+        let token = RefreshedToken {
+            access_token: "at3".into(),
+            refresh_token: None,
+            expires_in: 3600,
+        };
+        record_refresh_success(&db, refreshed, &token)
+            .await
+            .unwrap();
+        let refreshed = find_account(&db, account.id).await;
+        assert_eq!(refreshed.access_token, "at3");
+        assert_eq!(refreshed.refresh_token, "rt2");
+    }
+
+    #[tokio::test]
+    async fn test_revoked_refresh_token_needs_reauth() {
+        let db = test_db().await;
+        let account = insert_spotify_account(&db, "test_user", "rt").await;
+
+        let network_error = eyre!("connection reset").wrap_err("Failed to create spotify client");
+        record_refresh_failure(&db, account.clone(), &network_error)
+            .await
+            .unwrap();
+        let failed = find_account(&db, account.id).await;
+        assert_eq!(failed.status, AccountStatus::Active);
+        assert!(failed.last_error.unwrap().contains("connection reset"));
+
+        let revoked_error = eyre!("invalid_grant: Refresh token revoked")
+            .wrap_err("Failed to create spotify client");
+        record_refresh_failure(&db, account.clone(), &revoked_error)
+            .await
+            .unwrap();
+        let revoked = find_account(&db, account.id).await;
+        assert_eq!(revoked.status, AccountStatus::NeedsReauth);
+
+        // The manager refuses to refresh until the account is re-authorized
+        let fake = FakeSpotify::start().await;
+        let manager = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let error = manager.client(account.id).await.err().unwrap();
+        assert!(error.to_string().contains("needs to be authorized again"));
+    }
+
+    #[tokio::test]
+    async fn test_client_refreshes_and_caches_token() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let manager = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+
+        let client = manager.client(account.id).await.unwrap();
+        let playlists = client.current_user_playlists().await.unwrap();
+        assert_eq!(playlists.len(), 2);
+
+        // The rotated refresh token was saved
+        let refreshed = find_account(&db, account.id).await;
+        assert_ne!(refreshed.refresh_token, "refresh-household-1");
+        assert_eq!(refreshed.access_token.split('-').next(), Some("access"));
+        assert!(refreshed.token_expiry > chrono::Utc::now().timestamp());
+
+        // A fresh token is reused instead of refreshing again
+        manager.client(account.id).await.unwrap();
+        assert_eq!(
+            find_account(&db, account.id).await.refresh_token,
+            refreshed.refresh_token
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_outlives_its_access_token() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-1", "refresh-household-1").await;
+        let manager = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+        let client = manager.client(account.id).await.unwrap();
+        client.current_user_playlists().await.unwrap();
+
+        // The access token expires while a long task still uses the client
+        fake.expire_access_tokens();
+        let slot = manager.accounts.lock().await[&account.id].clone();
+        slot.lock().await.as_mut().unwrap().expires_at = 0;
+
+        assert_eq!(client.current_user_playlists().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_marks_revoked_account() {
+        let db = test_db().await;
+        let fake = FakeSpotify::start().await;
+        let account = insert_spotify_account(&db, "household-2", "refresh-household-2").await;
+        fake.revoke("household-2");
+        let manager = Arc::new(SpotifyTokenManager::new(
+            db.clone(),
+            Some(fake.credentials()),
+        ));
+
+        let error = manager.client(account.id).await.err().unwrap();
+        assert!(format!("{:#}", error).contains("invalid_grant"));
+
+        let revoked = find_account(&db, account.id).await;
+        assert_eq!(revoked.status, AccountStatus::NeedsReauth);
+        assert!(revoked.last_error.unwrap().contains("invalid_grant"));
+    }
+}
@@ -0,0 +1,654 @@
+//! Background task subsystem.
+//!
+//! Long-running jobs are stored in the `background_task` table and run by the
+//! [`TaskManager`] through the [`TaskHandler`] registered for their kind. Each
+//! kind has its own concurrency limit, tasks can be cancelled while pending or
+//! running, and unfinished tasks are resumed on startup from the last
+//! checkpoint their handler saved.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
+use sea_orm::prelude::Expr;
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+use crate::database::Database;
+use crate::entities;
+use crate::entities::background_task::{TaskKind, TaskStatus};
+use crate::services::track::PaginatedResult;
+
+/// Handle passed to a running task to read its input and report back.
+pub struct TaskContext {
+    task_id: i64,
+    db: Arc<Database>,
+    payload: serde_json::Value,
+    checkpoint: Option<serde_json::Value>,
+    cancellation: CancellationToken,
+}
+
+impl TaskContext {
+    pub fn db(&self) -> &Arc<Database> {
+        &self.db
+    }
+
+    pub fn payload<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_value(self.payload.clone()).wrap_err("Failed to parse task payload")
+    }
+
+    /// The checkpoint saved by a previous run of this task, if any
+    pub fn checkpoint<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        self.checkpoint
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .wrap_err("Failed to parse task checkpoint")
+    }
+
+    /// Whether the task was asked to stop. Handlers should check this between
+    /// units of work and return early once it is set.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    pub async fn report_progress(&self, current: i64, total: Option<i64>) -> Result<()> {
+        entities::background_task::Entity::update_many()
+            .col_expr(
+                entities::background_task::Column::ProgressCurrent,
+                Expr::value(current),
+            )
+            .col_expr(
+                entities::background_task::Column::ProgressTotal,
+                Expr::value(total),
+            )
+            .col_expr(
+                entities::background_task::Column::UpdatedAt,
+                Expr::value(chrono::Utc::now().timestamp()),
+            )
+            .filter(entities::background_task::Column::Id.eq(self.task_id))
+            .exec(&self.db.conn)
+            .await
+            .wrap_err("Failed to report task progress")?;
+        Ok(())
+    }
+
+    /// Save the point a restarted task should resume from, along with its
+    /// progress.
+    pub async fn save_checkpoint<T: Serialize>(&self, checkpoint: &T, current: i64) -> Result<()> {
+        let checkpoint =
+            serde_json::to_value(checkpoint).wrap_err("Failed to serialize task checkpoint")?;
+        entities::background_task::Entity::update_many()
+            .col_expr(
+                entities::background_task::Column::Checkpoint,
+                Expr::value(Some(checkpoint)),
+            )
+            .col_expr(
+                entities::background_task::Column::ProgressCurrent,
+                Expr::value(current),
+            )
+            .col_expr(
+                entities::background_task::Column::UpdatedAt,
+                Expr::value(chrono::Utc::now().timestamp()),
+            )
+            .filter(entities::background_task::Column::Id.eq(self.task_id))
+            .exec(&self.db.conn)
+            .await
+            .wrap_err("Failed to save task checkpoint")?;
+        Ok(())
+    }
+}
+
+/// Runs the tasks of one kind.
+#[async_trait]
+pub trait TaskHandler: Send + Sync {
+    fn kind(&self) -> TaskKind;
+
+    /// Maximum number of tasks of this kind running at once
+    fn max_concurrency(&self) -> usize {
+        1
+    }
+
+    /// Run the task to completion. Called again with the saved checkpoint when
+    /// the task is resumed after a restart.
+    async fn run(&self, ctx: &TaskContext) -> Result<()>;
+
+    /// Called instead of `run` when the task is cancelled while still queued,
+    /// to undo what was set up for it when it was submitted.
+    async fn cancelled_before_start(&self, _ctx: &TaskContext) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct RegisteredHandler {
+    handler: Arc<dyn TaskHandler>,
+    permits: Arc<Semaphore>,
+}
+
+pub struct TaskManager {
+    db: Arc<Database>,
+    handlers: HashMap<TaskKind, RegisteredHandler>,
+    /// Cancellation tokens of tasks spawned by this process
+    running: Mutex<HashMap<i64, CancellationToken>>,
+}
+
+impl TaskManager {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            handlers: HashMap::new(),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&mut self, handler: impl TaskHandler + 'static) {
+        let permits = Arc::new(Semaphore::new(handler.max_concurrency().max(1)));
+        self.handlers.insert(
+            handler.kind(),
+            RegisteredHandler {
+                handler: Arc::new(handler),
+                permits,
+            },
+        );
+    }
+
+    /// Store a new task and start it in the background once a slot of its
+    /// kind is free.
+    pub async fn submit<T: Serialize>(
+        self: &Arc<Self>,
+        kind: TaskKind,
+        payload: &T,
+    ) -> Result<entities::background_task::Model> {
+        if !self.handlers.contains_key(&kind) {
+            return Err(eyre!("No handler registered for {:?} tasks", kind));
+        }
+
+        let payload = serde_json::to_value(payload).wrap_err("Failed to serialize task payload")?;
+        let task = entities::background_task::ActiveModel {
+            kind: Set(kind),
+            payload: Set(payload),
+            ..entities::background_task::ActiveModel::new()
+        }
+        .insert(&self.db.conn)
+        .await
+        .wrap_err("Failed to create background task")?;
+
+        self.spawn(task.clone());
+        Ok(task)
+    }
+
+    /// Ask a task to stop. Returns `false` if the task had already finished.
+    pub async fn cancel(&self, task_id: i64) -> Result<bool> {
+        let task = get_task(&self.db, task_id).await?;
+        if task.status.is_finished() {
+            return Ok(false);
+        }
+
+        let token = self
+            .running
+            .lock()
+            .expect("task registry lock poisoned")
+            .get(&task_id)
+            .cloned();
+        match token {
+            // The task itself records the cancellation once it stops
+            Some(token) => token.cancel(),
+            None => {
+                let token = CancellationToken::new();
+                token.cancel();
+                self.cancel_queued(task, token).await?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Restart tasks that were pending or running when the process stopped.
+    /// Returns the number of resumed tasks.
+    pub async fn resume_unfinished(self: &Arc<Self>) -> Result<usize> {
+        let tasks = entities::background_task::Entity::find()
+            .filter(
+                entities::background_task::Column::Status
+                    .is_in([TaskStatus::Pending, TaskStatus::Running]),
+            )
+            .order_by_asc(entities::background_task::Column::Id)
+            .all(&self.db.conn)
+            .await
+            .wrap_err("Failed to fetch unfinished background tasks")?;
+
+        let mut resumed = 0;
+        for task in tasks {
+            if !self.handlers.contains_key(&task.kind) {
+                tracing::warn!(task_id = task.id, kind = ?task.kind, "No handler for unfinished task");
+                finish_task(
+                    &self.db,
+                    task.id,
+                    TaskStatus::Failed,
+                    Some("No handler registered to resume the task".to_string()),
+                )
+                .await?;
+                continue;
+            }
+
+            tracing::info!(task_id = task.id, kind = ?task.kind, "Resuming background task");
+            self.spawn(task);
+            resumed += 1;
+        }
+
+        Ok(resumed)
+    }
+
+    fn spawn(self: &Arc<Self>, task: entities::background_task::Model) {
+        let token = CancellationToken::new();
+        self.running
+            .lock()
+            .expect("task registry lock poisoned")
+            .insert(task.id, token.clone());
+
+        let manager = self.clone();
+        let span = tracing::info_span!("background_task", task_id = task.id, kind = ?task.kind);
+        tokio::spawn(
+            async move {
+                let task_id = task.id;
+                if let Err(e) = manager.run(task, token).await {
+                    tracing::error!(error = ?e, "Failed to run background task");
+                }
+                manager
+                    .running
+                    .lock()
+                    .expect("task registry lock poisoned")
+                    .remove(&task_id);
+            }
+            .instrument(span),
+        );
+    }
+
+    async fn run(
+        &self,
+        task: entities::background_task::Model,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let registered = self
+            .handlers
+            .get(&task.kind)
+            .ok_or_eyre("No handler registered for task kind")?;
+
+        // Wait for a free slot, unless the task is cancelled first
+        let _permit = tokio::select! {
+            permit = registered.permits.clone().acquire_owned() => {
+                permit.wrap_err("Task semaphore closed")?
+            }
+            _ = token.cancelled() => {
+                return self.cancel_queued(task, token).await;
+            }
+        };
+
+        let mut running: entities::background_task::ActiveModel = task.clone().into();
+        running.status = Set(TaskStatus::Running);
+        running.started_at = Set(Some(chrono::Utc::now().timestamp()));
+        running
+            .update(&self.db.conn)
+            .await
+            .wrap_err("Failed to mark background task as running")?;
+
+        let ctx = self.context(task, token.clone());
+        match registered.handler.run(&ctx).await {
+            Ok(()) if token.is_cancelled() => {
+                tracing::info!("Background task cancelled");
+                finish_task(&self.db, task.id, TaskStatus::Cancelled, None).await
+            }
+            Ok(()) => {
+                tracing::info!("Background task completed");
+                finish_task(&self.db, task.id, TaskStatus::Completed, None).await
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "Background task failed");
+                finish_task(&self.db, task.id, TaskStatus::Failed, Some(e.to_string())).await
+            }
+        }
+    }
+
+    /// Record a task cancelled before it started, letting its handler undo
+    /// what was set up for it.
+    async fn cancel_queued(
+        &self,
+        task: entities::background_task::Model,
+        token: CancellationToken,
+    ) -> Result<()> {
+        let task_id = task.id;
+        if let Some(registered) = self.handlers.get(&task.kind) {
+            let ctx = self.context(task, token);
+            if let Err(e) = registered.handler.cancelled_before_start(&ctx).await {
+                tracing::error!(error = ?e, "Failed to clean up cancelled background task");
+            }
+        }
+        tracing::info!("Background task cancelled before it started");
+        finish_task(&self.db, task_id, TaskStatus::Cancelled, None).await
+    }
+
+    fn context(
+        &self,
+        task: entities::background_task::Model,
+        token: CancellationToken,
+    ) -> TaskContext {
+        TaskContext {
+            task_id: task.id,
+            db: self.db.clone(),
+            payload: task.payload,
+            checkpoint: task.checkpoint,
+            cancellation: token,
+        }
+    }
+}
+
+async fn finish_task(
+    db: &Database,
+    task_id: i64,
+    status: TaskStatus,
+    error_message: Option<String>,
+) -> Result<()> {
+    let now = chrono::Utc::now().timestamp();
+    entities::background_task::Entity::update_many()
+        .col_expr(
+            entities::background_task::Column::Status,
+            Expr::value(status),
+        )
+        .col_expr(
+            entities::background_task::Column::ErrorMessage,
+            Expr::value(error_message),
+        )
+        .col_expr(
+            entities::background_task::Column::FinishedAt,
+            Expr::value(Some(now)),
+        )
+        .col_expr(
+            entities::background_task::Column::UpdatedAt,
+            Expr::value(now),
+        )
+        .filter(entities::background_task::Column::Id.eq(task_id))
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to update background task status")?;
+    Ok(())
+}
+
+pub async fn get_task(db: &Database, task_id: i64) -> Result<entities::background_task::Model> {
+    entities::background_task::Entity::find_by_id(task_id)
+        .one(&db.conn)
+        .await
+        .wrap_err("Failed to fetch background task")?
+        .ok_or_eyre("Background task not found")
+}
+
+/// Tasks, most recent first, optionally filtered by kind and status.
+pub async fn list_tasks(
+    db: &Database,
+    kind: Option<TaskKind>,
+    status: Option<TaskStatus>,
+    page: usize,
+    page_size: usize,
+) -> Result<PaginatedResult<entities::background_task::Model>> {
+    let mut query = entities::background_task::Entity::find();
+    if let Some(kind) = kind {
+        query = query.filter(entities::background_task::Column::Kind.eq(kind));
+    }
+    if let Some(status) = status {
+        query = query.filter(entities::background_task::Column::Status.eq(status));
+    }
+
+    let total_count = query
+        .clone()
+        .count(&db.conn)
+        .await
+        .wrap_err("Failed to count background tasks")?;
+
+    let offset = (page.saturating_sub(1)) * page_size;
+    let items = query
+        .order_by_desc(entities::background_task::Column::Id)
+        .limit(page_size as u64)
+        .offset(offset as u64)
+        .all(&db.conn)
+        .await
+        .wrap_err("Failed to fetch background tasks")?;
+
+    Ok(PaginatedResult {
+        items,
+        total_count,
+        page,
+        page_size,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{test_db, wait_for_status};
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    #[derive(Serialize, Deserialize)]
+    struct CountPayload {
+        steps: i64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct CountCheckpoint {
+        next_step: i64,
+    }
+
+    /// Counts to `steps`, checkpointing each step and waiting for `release`
+    /// before the last one.
+    struct CountHandler {
+        release: Arc<tokio::sync::Notify>,
+        started_at_step: Arc<Mutex<Vec<i64>>>,
+        cancelled_before_start: Arc<Mutex<Vec<i64>>>,
+    }
+
+    #[async_trait]
+    impl TaskHandler for CountHandler {
+        fn kind(&self) -> TaskKind {
+            TaskKind::SpotifyToLocalMatcher
+        }
+
+        async fn run(&self, ctx: &TaskContext) -> Result<()> {
+            let payload: CountPayload = ctx.payload()?;
+            let start = ctx
+                .checkpoint::<CountCheckpoint>()?
+                .map_or(0, |checkpoint| checkpoint.next_step);
+            self.started_at_step.lock().unwrap().push(start);
+
+            for step in start..payload.steps {
+                if step == payload.steps - 1 {
+                    loop {
+                        if ctx.is_cancelled() {
+                            return Ok(());
+                        }
+                        tokio::select! {
+                            _ = self.release.notified() => break,
+                            _ = tokio::time::sleep(Duration::from_millis(10)) => {}
+                        }
+                    }
+                }
+                ctx.save_checkpoint(
+                    &CountCheckpoint {
+                        next_step: step + 1,
+                    },
+                    step + 1,
+                )
+                .await?;
+            }
+            Ok(())
+        }
+
+        async fn cancelled_before_start(&self, ctx: &TaskContext) -> Result<()> {
+            let payload: CountPayload = ctx.payload()?;
+            self.cancelled_before_start
+                .lock()
+                .unwrap()
+                .push(payload.steps);
+            Ok(())
+        }
+    }
+
+    fn manager(
+        db: Arc<Database>,
+    ) -> (
+        Arc<TaskManager>,
+        Arc<tokio::sync::Notify>,
+        Arc<Mutex<Vec<i64>>>,
+        Arc<Mutex<Vec<i64>>>,
+    ) {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let started_at_step = Arc::new(Mutex::new(Vec::new()));
+        let cancelled_before_start = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = TaskManager::new(db);
+        manager.register(CountHandler {
+            release: release.clone(),
+            started_at_step: started_at_step.clone(),
+            cancelled_before_start: cancelled_before_start.clone(),
+        });
+        (
+            Arc::new(manager),
+            release,
+            started_at_step,
+            cancelled_before_start,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_task_runs_to_completion() {
+        let db = test_db().await;
+        let (manager, release, _, _) = manager(db.clone());
+
+        let task = manager
+            .submit(TaskKind::SpotifyToLocalMatcher, &CountPayload { steps: 3 })
+            .await
+            .unwrap();
+        release.notify_one();
+
+        let task = wait_for_status(&db, task.id, TaskStatus::Completed).await;
+        assert_eq!(task.progress_current, 3);
+        assert!(task.finished_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_task() {
+        let db = test_db().await;
+        let (manager, _release, _, _) = manager(db.clone());
+
+        let task = manager
+            .submit(TaskKind::SpotifyToLocalMatcher, &CountPayload { steps: 3 })
+            .await
+            .unwrap();
+        wait_for_status(&db, task.id, TaskStatus::Running).await;
+
+        assert!(manager.cancel(task.id).await.unwrap());
+        let task = wait_for_status(&db, task.id, TaskStatus::Cancelled).await;
+        assert_eq!(task.progress_current, 2);
+        assert!(!manager.cancel(task.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_checkpoint() {
+        let db = test_db().await;
+        // A task interrupted by a restart after two steps
+        let task = entities::background_task::ActiveModel {
+            kind: Set(TaskKind::SpotifyToLocalMatcher),
+            status: Set(TaskStatus::Running),
+            payload: Set(serde_json::json!({ "steps": 3 })),
+            checkpoint: Set(Some(serde_json::json!({ "next_step": 2 }))),
+            ..entities::background_task::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let (manager, release, started_at_step, _) = manager(db.clone());
+        assert_eq!(manager.resume_unfinished().await.unwrap(), 1);
+        release.notify_one();
+
+        let task = wait_for_status(&db, task.id, TaskStatus::Completed).await;
+        assert_eq!(task.progress_current, 3);
+        assert_eq!(*started_at_step.lock().unwrap(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_resume_without_handler_fails_task() {
+        let db = test_db().await;
+        let task = entities::background_task::ActiveModel {
+            kind: Set(TaskKind::SpotifyPlaylistSync),
+            payload: Set(serde_json::json!({})),
+            ..entities::background_task::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let (manager, _, _, _) = manager(db.clone());
+        assert_eq!(manager.resume_unfinished().await.unwrap(), 0);
+        assert_eq!(
+            get_task(&db, task.id).await.unwrap().status,
+            TaskStatus::Failed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_queues_tasks() {
+        let db = test_db().await;
+        let (manager, release, started_at_step, cancelled_before_start) = manager(db.clone());
+
+        let first = manager
+            .submit(TaskKind::SpotifyToLocalMatcher, &CountPayload { steps: 1 })
+            .await
+            .unwrap();
+        let second = manager
+            .submit(TaskKind::SpotifyToLocalMatcher, &CountPayload { steps: 2 })
+            .await
+            .unwrap();
+
+        wait_for_status(&db, first.id, TaskStatus::Running).await;
+        assert_eq!(
+            get_task(&db, second.id).await.unwrap().status,
+            TaskStatus::Pending
+        );
+
+        // Cancelling a queued task does not wait for a slot
+        manager.cancel(second.id).await.unwrap();
+        wait_for_status(&db, second.id, TaskStatus::Cancelled).await;
+        assert_eq!(*cancelled_before_start.lock().unwrap(), vec![2]);
+
+        release.notify_one();
+        wait_for_status(&db, first.id, TaskStatus::Completed).await;
+        assert_eq!(*started_at_step.lock().unwrap(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_task_not_resumed_yet() {
+        let db = test_db().await;
+        let task = entities::background_task::ActiveModel {
+            kind: Set(TaskKind::SpotifyToLocalMatcher),
+            payload: Set(serde_json::json!({ "steps": 3 })),
+            ..entities::background_task::ActiveModel::new()
+        }
+        .insert(&db.conn)
+        .await
+        .unwrap();
+
+        let (manager, _, started_at_step, cancelled_before_start) = manager(db.clone());
+        assert!(manager.cancel(task.id).await.unwrap());
+        assert_eq!(
+            get_task(&db, task.id).await.unwrap().status,
+            TaskStatus::Cancelled
+        );
+        assert_eq!(*cancelled_before_start.lock().unwrap(), vec![3]);
+        assert!(started_at_step.lock().unwrap().is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use sea_orm::{
@@ -10,8 +11,9 @@ use crate::http_server::graphql::query_builder::{
     SortInput, SortableField, TrackSortField, apply_multi_column_text_search, apply_pagination,
     apply_sort,
 };
+use crate::services::smart_playlist;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TrackWithRelations {
     pub track: entities::track::Model,
     pub album: entities::album::Model,
@@ -19,6 +21,16 @@ pub struct TrackWithRelations {
     pub artists: Vec<(database::Artist, bool)>,
 }
 
+/// A single positioned entry of a playlist with its hydrated track
+#[derive(Debug)]
+pub struct PlaylistEntryWithTrack {
+    /// `playlist_tracks` row ID; `None` for smart playlists, whose entries
+    /// are computed from rules
+    pub entry_id: Option<i64>,
+    pub position: i32,
+    pub track: TrackWithRelations,
+}
+
 pub struct PaginatedResult<T> {
     pub items: Vec<T>,
     pub total_count: u64,
@@ -141,6 +153,27 @@ impl TrackService {
         page: Option<i32>,
         page_size: Option<i32>,
     ) -> color_eyre::Result<PaginatedResult<TrackWithRelations>> {
+        let result = self
+            .list_playlist_entries(playlist_id, page, page_size)
+            .await?;
+
+        Ok(PaginatedResult {
+            items: result.items.into_iter().map(|entry| entry.track).collect(),
+            total_count: result.total_count,
+            page: result.page,
+            page_size: result.page_size,
+        })
+    }
+
+    /// Lists the entries of a playlist in playlist order. A track that appears
+    /// several times in the playlist is returned once per entry. Smart playlists
+    /// are evaluated on demand.
+    pub async fn list_playlist_entries(
+        &self,
+        playlist_id: i64,
+        page: Option<i32>,
+        page_size: Option<i32>,
+    ) -> color_eyre::Result<PaginatedResult<PlaylistEntryWithTrack>> {
         // Verify playlist exists
         let playlist = entities::playlist::Entity::find_by_id(playlist_id)
             .one(&self.db.conn)
@@ -153,24 +186,53 @@ impl TrackService {
 
         let page_val = page.unwrap_or(1).max(1) as usize;
         let page_size_val = page_size.unwrap_or(25).clamp(1, 100) as usize;
-
-        let playlist_track_models = entities::playlist_track::Entity::find()
-            .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
-            .all(&self.db.conn)
-            .await
-            .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch playlist tracks: {}", e))?;
-
-        let track_ids: Vec<i64> = playlist_track_models.iter().map(|pt| pt.track_id).collect();
-        let total_count = track_ids.len() as u64;
-
         let offset = (page_val.saturating_sub(1)) * page_size_val;
-        let paginated_track_ids: Vec<i64> = track_ids
-            .into_iter()
-            .skip(offset)
-            .take(page_size_val)
-            .collect();
 
-        if paginated_track_ids.is_empty() {
+        // (entry_id, position, track_id) for the requested page
+        let (total_count, entries): (u64, Vec<(Option<i64>, i32, i64)>) =
+            match smart_playlist::find_rules(&self.db.conn, playlist_id).await? {
+                Some(rules) => {
+                    let total_count =
+                        smart_playlist::count_matching_tracks(&self.db.conn, &rules).await?;
+                    let entries = smart_playlist::matching_track_ids_page(
+                        &self.db.conn,
+                        &rules,
+                        offset as u64,
+                        page_size_val as u64,
+                    )
+                    .await?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, track_id)| (None, (offset + index) as i32, track_id))
+                    .collect();
+                    (total_count, entries)
+                }
+                None => {
+                    let query = entities::playlist_track::Entity::find()
+                        .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id));
+
+                    let total_count = query.clone().count(&self.db.conn).await.map_err(|e| {
+                        color_eyre::eyre::eyre!("Failed to count playlist tracks: {}", e)
+                    })?;
+
+                    let entries = query
+                        .order_by_asc(entities::playlist_track::Column::Position)
+                        .order_by_asc(entities::playlist_track::Column::Id)
+                        .limit(page_size_val as u64)
+                        .offset(offset as u64)
+                        .all(&self.db.conn)
+                        .await
+                        .map_err(|e| {
+                            color_eyre::eyre::eyre!("Failed to fetch playlist tracks: {}", e)
+                        })?
+                        .into_iter()
+                        .map(|entry| (Some(entry.id), entry.position, entry.track_id))
+                        .collect();
+                    (total_count, entries)
+                }
+            };
+
+        if entries.is_empty() {
             return Ok(PaginatedResult {
                 items: Vec::new(),
                 total_count,
@@ -179,14 +241,34 @@ impl TrackService {
             });
         }
 
+        let track_ids: Vec<i64> = entries.iter().map(|(_, _, track_id)| *track_id).collect();
         let track_album_pairs = entities::track::Entity::find()
-            .filter(entities::track::Column::Id.is_in(paginated_track_ids))
+            .filter(entities::track::Column::Id.is_in(track_ids))
             .find_also_related(entities::album::Entity)
             .all(&self.db.conn)
             .await
             .map_err(|e| color_eyre::eyre::eyre!("Failed to fetch tracks: {}", e))?;
 
-        let items = self.hydrate_tracks(track_album_pairs).await?;
+        let tracks_by_id: HashMap<i64, TrackWithRelations> = self
+            .hydrate_tracks(track_album_pairs)
+            .await?
+            .into_iter()
+            .map(|twr| (twr.track.id, twr))
+            .collect();
+
+        let items = entries
+            .into_iter()
+            .map(|(entry_id, position, track_id)| {
+                let track = tracks_by_id.get(&track_id).cloned().ok_or_else(|| {
+                    color_eyre::eyre::eyre!("Track {} not found for playlist entry", track_id)
+                })?;
+                Ok(PlaylistEntryWithTrack {
+                    entry_id,
+                    position,
+                    track,
+                })
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
 
         Ok(PaginatedResult {
             items,
@@ -261,13 +343,7 @@ impl TrackService {
 
         let mut items = Vec::new();
         for playlist_model in playlist_models {
-            let track_count = entities::playlist_track::Entity::find()
-                .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_model.id))
-                .count(&self.db.conn)
-                .await
-                .map_err(|e| {
-                    color_eyre::eyre::eyre!("Failed to count tracks for playlist: {}", e)
-                })?;
+            let track_count = self.playlist_track_count(playlist_model.id).await?;
             items.push((playlist_model, track_count));
         }
 
@@ -289,19 +365,24 @@ impl TrackService {
             .map_err(|e| color_eyre::eyre::eyre!("Failed to find playlist: {}", e))?;
 
         if let Some(playlist_model) = playlist_model {
-            let track_count = entities::playlist_track::Entity::find()
-                .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_model.id))
-                .count(&self.db.conn)
-                .await
-                .map_err(|e| {
-                    color_eyre::eyre::eyre!("Failed to count tracks for playlist: {}", e)
-                })?;
+            let track_count = self.playlist_track_count(playlist_model.id).await?;
             Ok(Some((playlist_model, track_count)))
         } else {
             Ok(None)
         }
     }
 
+    async fn playlist_track_count(&self, playlist_id: i64) -> color_eyre::Result<u64> {
+        match smart_playlist::find_rules(&self.db.conn, playlist_id).await? {
+            Some(rules) => smart_playlist::count_matching_tracks(&self.db.conn, &rules).await,
+            None => entities::playlist_track::Entity::find()
+                .filter(entities::playlist_track::Column::PlaylistId.eq(playlist_id))
+                .count(&self.db.conn)
+                .await
+                .map_err(|e| color_eyre::eyre::eyre!("Failed to count tracks for playlist: {}", e)),
+        }
+    }
+
     async fn hydrate_tracks(
         &self,
         track_album_pairs: Vec<(entities::track::Model, Option<entities::album::Model>)>,
@@ -480,4 +561,41 @@ mod tests {
                 .all(|t| t.track.title.contains("Bohemian"))
         );
     }
+
+    #[tokio::test]
+    async fn test_list_playlist_entries_keeps_order_and_duplicates() {
+        let db = test_db().await;
+        let album_id = insert_album(&db).await;
+        let artist_id = insert_artist(&db, "Artist").await;
+        let t1 = insert_track(&db, album_id, "First", "/music/first.flac").await;
+        let t2 = insert_track(&db, album_id, "Second", "/music/second.flac").await;
+        for tid in [t1, t2] {
+            insert_track_artist(&db, tid, artist_id).await;
+        }
+
+        let playlists = crate::services::playlist::PlaylistService::new(db.clone());
+        let playlist = playlists.create("Mix".into(), None).await.unwrap();
+        playlists.add_track(playlist.id, t2).await.unwrap();
+        playlists.add_track(playlist.id, t1).await.unwrap();
+        playlists.add_track(playlist.id, t2).await.unwrap();
+
+        let service = TrackService::new(db);
+        let result = service
+            .list_playlist_entries(playlist.id, None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(result.total_count, 3);
+        let track_ids: Vec<i64> = result.items.iter().map(|e| e.track.track.id).collect();
+        assert_eq!(track_ids, vec![t2, t1, t2]);
+        let positions: Vec<i32> = result.items.iter().map(|e| e.position).collect();
+        assert_eq!(positions, vec![0, 1, 2]);
+
+        let page = service
+            .list_playlist_tracks(playlist.id, Some(2), Some(2))
+            .await
+            .unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].track.id, t2);
+    }
 }
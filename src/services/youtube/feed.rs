@@ -1,4 +1,6 @@
 use super::types::Feed;
+use crate::services::feeds::{self, FeedItem, FeedParser, ParsedFeed};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{Context, Result};
 use html_parser::{Dom, Node};
 use reqwest::Client;
@@ -64,20 +66,41 @@ fn get_feed_url(channel_id: &str) -> String {
 }
 
 #[instrument]
-pub async fn fetch_feed(channel_id: &str) -> Result<Feed> {
+pub async fn fetch_feed(channel_id: &str) -> Result<ParsedFeed> {
     let feed_url = get_feed_url(channel_id);
-    let response = Client::new()
-        .get(feed_url)
-        .timeout(Duration::from_secs(10))
-        .send()
-        .await?
-        .error_for_status()?;
-    let body = response.text().await?;
-    tracing::info!(body = ?body, "Fetched feed");
-    let feed = serde_xml_rs::SerdeXml::new()
-        // https://docs.rs/serde-xml-rs/latest/serde_xml_rs/config/struct.SerdeXml.html#method.overlapping_sequences
-        .overlapping_sequences(true)
-        .from_str(&body)
-        .wrap_err("Failed to parse feed")?;
-    Ok(feed)
+    let body = feeds::fetch(&feed_url).await?;
+    YoutubeFeedParser.parse(&body)
+}
+
+/// Parses the Atom feed of a YouTube channel, with its `yt:` and `media:` fields.
+pub struct YoutubeFeedParser;
+
+impl FeedParser for YoutubeFeedParser {
+    fn parse(&self, body: &str) -> Result<ParsedFeed> {
+        let feed: Feed = serde_xml_rs::SerdeXml::new()
+            // https://docs.rs/serde-xml-rs/latest/serde_xml_rs/config/struct.SerdeXml.html#method.overlapping_sequences
+            .overlapping_sequences(true)
+            .from_str(body)
+            .wrap_err("Failed to parse feed")?;
+        Ok(ParsedFeed {
+            title: feed.title,
+            items: feed
+                .entries
+                .into_iter()
+                // YouTube always dates its videos, entries without a valid
+                // date aren't videos worth adding
+                .filter_map(|entry| {
+                    let published_at = entry.published.parse::<DateTime<Utc>>().ok()?;
+                    Some(FeedItem {
+                        id: entry.id,
+                        title: entry.title,
+                        author: Some(entry.author.name),
+                        published_at: Some(published_at),
+                        url: entry.link.href,
+                        thumbnail_url: Some(entry.media_group.thumbnail.url),
+                    })
+                })
+                .collect(),
+        })
+    }
 }
@@ -0,0 +1,497 @@
+//! Finding the song of a YouTube video and downloading it like a Spotify
+//! track: the artist and title are read from the video title, looked up on
+//! MusicBrainz, then searched on SoulSeek and imported.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{OptionExt, Result, WrapErr, eyre};
+use musicbrainz_rs::entity::recording::Recording;
+use sea_orm::{EntityTrait, Set};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::entities;
+use crate::entities::background_task::TaskKind;
+use crate::import_track::import_track;
+use crate::musicbrainz;
+use crate::services::spotify::download_best_match_for_spotify_track::{
+    download_match, search_best_match,
+};
+use crate::services::spotify::matching_local_tracks::{
+    MatchConfidence, MatcherConfig, Track, extract_parenthetical_content,
+    extract_version_indicator, find_local_track, normalize_track, refresh_index, score_candidate,
+};
+use crate::services::tasks::{TaskContext, TaskHandler, TaskManager};
+use crate::soulseek::{self, SoulSeekClientContext};
+
+/* ---------- Video titles ---------- */
+
+/// Artist and title of the song in a video, read from the video title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoSong {
+    pub artist: String,
+    /// Title with the details of the song kept, e.g. `Song (Live) (feat. X)`,
+    /// but not those of the video, e.g. `(Official Video)`
+    pub title: String,
+}
+
+/// Separators between the artist and the title, e.g. `Artist - Title`
+const ARTIST_TITLE_SEPARATORS: &[&str] = &[" - ", " – ", " — ", " -- ", " ~ "];
+
+/// Labels of the video rather than the song, when not in brackets
+const VIDEO_LABELS: &[&str] = &[
+    "official music video",
+    "official lyric video",
+    "official video",
+    "official audio",
+    "music video",
+    "lyric video",
+    "visualizer",
+    "lyrics",
+];
+
+/// Featured artists credited after the artist, e.g. `Artist ft. Other - Title`
+const ARTIST_FEATURING_PATTERNS: &[&str] = &[" featuring ", " feat. ", " feat ", " ft. ", " ft "];
+
+/// Whether bracketed content credits other artists, e.g. `(feat. Other)`
+fn is_featuring(content: &str) -> bool {
+    let lower = content.to_ascii_lowercase();
+    ["feat", "ft.", "ft ", "with "]
+        .iter()
+        .any(|pattern| lower.starts_with(pattern))
+}
+
+/// `s` without the (ASCII) `suffix`, ignoring case
+fn strip_suffix_ignore_case<'a>(s: &'a str, suffix: &str) -> Option<&'a str> {
+    let index = s.len().checked_sub(suffix.len())?;
+    (s.is_char_boundary(index) && s[index..].eq_ignore_ascii_case(suffix)).then(|| &s[..index])
+}
+
+fn strip_video_labels(s: &str) -> &str {
+    let mut s = s.trim();
+    while let Some(stripped) = VIDEO_LABELS
+        .iter()
+        .find_map(|label| strip_suffix_ignore_case(s, label))
+    {
+        s = stripped.trim_end_matches([' ', '-', '–', '—', ':']);
+    }
+    s
+}
+
+/// Artist and title of `Artist - Title` or `Artist "Title"`
+fn split_artist_title(s: &str) -> Option<(&str, &str)> {
+    if let Some((artist, title)) = ARTIST_TITLE_SEPARATORS
+        .iter()
+        .filter_map(|separator| s.split_once(separator))
+        .min_by_key(|(artist, _)| artist.len())
+    {
+        return Some((artist, title));
+    }
+
+    let (artist, rest) = s.split_once(['"', '“'])?;
+    let (title, _) = rest.split_once(['"', '”'])?;
+    Some((artist, title))
+}
+
+/// Artist of an auto-generated (`Artist - Topic`) or VEVO channel
+fn artist_from_channel(channel_name: &str) -> Option<&str> {
+    channel_name
+        .strip_suffix(" - Topic")
+        .or_else(|| channel_name.strip_suffix("VEVO"))
+        .map(str::trim)
+}
+
+/// Reads the artist and title of the song from a video title such as
+/// `Artist - Title (Official Video)`. Videos of `Artist - Topic` and VEVO
+/// channels are only titled with the song, so their artist is the channel.
+///
+/// Bracketed details of the song, its version (see the matcher's version
+/// indicators) and featured artists, are kept in the title; everything else in
+/// brackets or after a `|` is about the video and dropped.
+pub fn parse_video_title(title: &str, channel_name: &str) -> Option<VideoSong> {
+    let title = title.split(" | ").next().unwrap_or_default();
+    let (rest, parentheticals) = extract_parenthetical_content(title);
+    let rest = rest.split_whitespace().collect::<Vec<_>>().join(" ");
+    let rest = strip_video_labels(&rest);
+
+    let (artist, song_title) = match split_artist_title(rest) {
+        Some((artist, song_title)) => (artist, song_title),
+        None => (artist_from_channel(channel_name)?, rest),
+    };
+    let artist = artist.trim();
+    let song_title = song_title.trim();
+
+    let mut details: Vec<String> = parentheticals
+        .into_iter()
+        .filter(|content| {
+            extract_version_indicator(std::slice::from_ref(content)).is_some()
+                || is_featuring(content)
+        })
+        .collect();
+
+    // `Artist ft. Other` is credited like `Title (feat. Other)`
+    let lower = artist.to_ascii_lowercase();
+    let artist = match ARTIST_FEATURING_PATTERNS
+        .iter()
+        .filter_map(|pattern| lower.find(pattern).map(|index| (index, pattern.len())))
+        .min()
+    {
+        Some((index, len)) => {
+            details.push(format!("feat. {}", artist[index + len..].trim()));
+            artist[..index].trim()
+        }
+        None => artist,
+    };
+
+    if artist.is_empty() || song_title.is_empty() {
+        return None;
+    }
+
+    let mut title = song_title.to_string();
+    for detail in details {
+        title.push_str(&format!(" ({})", detail));
+    }
+    Some(VideoSong {
+        artist: artist.to_string(),
+        title,
+    })
+}
+
+/* ---------- MusicBrainz recordings ---------- */
+
+/// A MusicBrainz recording that could be the song of a video.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SongCandidate {
+    pub title: String,
+    /// e.g. `live, 1985` for versions of a recording with the same title
+    pub disambiguation: Option<String>,
+    /// Credited artists, primary artist first
+    pub artists: Vec<String>,
+    /// Title of the first release the recording is on
+    pub album: Option<String>,
+    pub length_ms: Option<u32>,
+}
+
+impl From<&Recording> for SongCandidate {
+    fn from(recording: &Recording) -> Self {
+        Self {
+            title: recording.title.clone(),
+            disambiguation: recording
+                .disambiguation
+                .clone()
+                .filter(|disambiguation| !disambiguation.is_empty()),
+            artists: recording
+                .artist_credit
+                .iter()
+                .flatten()
+                .map(|credit| credit.name.clone())
+                .collect(),
+            album: recording
+                .releases
+                .iter()
+                .flatten()
+                .next()
+                .map(|release| release.title.clone()),
+            length_ms: recording.length,
+        }
+    }
+}
+
+impl SongCandidate {
+    /// The recording as a matcher track. The video has no album or duration,
+    /// so neither is compared.
+    fn matcher_track(&self) -> Track {
+        let title = match &self.disambiguation {
+            Some(disambiguation) => format!("{} ({})", self.title, disambiguation),
+            None => self.title.clone(),
+        };
+        Track {
+            title,
+            primary_artist: self.artists.first().cloned().unwrap_or_default(),
+            secondary_artists: self.artists.iter().skip(1).cloned().collect(),
+            album: String::new(),
+            duration_ms: 0,
+        }
+    }
+
+    /// The recording as a local library track, to check whether the song is
+    /// already there.
+    fn local_track(&self) -> Option<Track> {
+        Some(Track {
+            album: self.album.clone().unwrap_or_default(),
+            duration_ms: self.length_ms?,
+            ..self.matcher_track()
+        })
+    }
+
+    fn soulseek_track(&self) -> soulseek::Track {
+        soulseek::Track {
+            title: self.title.clone(),
+            album: self.album.clone().unwrap_or_default(),
+            artists: self.artists.clone(),
+            length: self.length_ms,
+        }
+    }
+}
+
+/// The candidate most likely to be the song, if any is at least a medium
+/// confidence match. Earlier candidates win ties, as MusicBrainz returns the
+/// best matches of its search first.
+pub fn best_candidate<'a>(
+    song: &VideoSong,
+    candidates: &'a [SongCandidate],
+    config: &MatcherConfig,
+) -> Option<&'a SongCandidate> {
+    // Without an album on the video, the album can only inflate scores
+    let config = MatcherConfig {
+        album_weight: 0.0,
+        ..config.clone()
+    };
+    let song = normalize_track(&Track {
+        title: song.title.clone(),
+        primary_artist: song.artist.clone(),
+        secondary_artists: Vec::new(),
+        album: String::new(),
+        duration_ms: 0,
+    });
+
+    let mut best: Option<(&SongCandidate, f64)> = None;
+    for candidate in candidates {
+        let Some(result) =
+            score_candidate(&song, &normalize_track(&candidate.matcher_track()), &config)
+        else {
+            continue;
+        };
+        if !result.confidence.is_at_least(MatchConfidence::Medium) {
+            continue;
+        }
+        if best.is_none_or(|(_, score)| result.score > score) {
+            best = Some((candidate, result.score));
+        }
+    }
+    best.map(|(candidate, _)| candidate)
+}
+
+/* ---------- Background task ---------- */
+
+#[derive(Serialize, Deserialize)]
+struct FindSongTaskPayload {
+    youtube_video_id: i64,
+    config: MatcherConfig,
+}
+
+/// Runs [`TaskKind::YoutubeSongDownload`] tasks: finds the song of a video on
+/// MusicBrainz, then links the local track of the song, downloading and
+/// importing it from SoulSeek unless it is already in the library.
+pub struct YoutubeSongDownloadTaskHandler {
+    pub soulseek_context: Arc<SoulSeekClientContext>,
+    pub api_key: String,
+    pub config: Config,
+}
+
+#[async_trait]
+impl TaskHandler for YoutubeSongDownloadTaskHandler {
+    fn kind(&self) -> TaskKind {
+        TaskKind::YoutubeSongDownload
+    }
+
+    async fn run(&self, ctx: &TaskContext) -> Result<()> {
+        let db = ctx.db();
+        let payload: FindSongTaskPayload = ctx.payload()?;
+        let video = entities::youtube_video::Entity::find_by_id(payload.youtube_video_id)
+            .one(&db.conn)
+            .await
+            .wrap_err("Failed to fetch youtube video")?
+            .ok_or_eyre("Youtube video not found")?;
+        if video.local_track_id.is_some() {
+            tracing::info!("Song of youtube video already found: {:?}", video);
+            return Ok(());
+        }
+        ctx.report_progress(0, Some(3)).await?;
+
+        let song = parse_video_title(&video.title, &video.channel_name).ok_or_else(|| {
+            eyre!(
+                "Failed to find the artist and title in video title '{}'",
+                video.title
+            )
+        })?;
+        let (search_title, _) = extract_parenthetical_content(&song.title);
+        let candidates: Vec<SongCandidate> =
+            musicbrainz::search_recordings(&song.artist, &search_title)
+                .await?
+                .iter()
+                .map(SongCandidate::from)
+                .collect();
+        let candidate = best_candidate(&song, &candidates, &payload.config).ok_or_else(|| {
+            eyre!(
+                "No MusicBrainz recording of '{}' by '{}'",
+                song.title,
+                song.artist
+            )
+        })?;
+        tracing::info!(
+            "Found song of youtube video '{}': {:?}",
+            video.title,
+            candidate
+        );
+        ctx.report_progress(1, Some(3)).await?;
+
+        if let Some(local_track) = candidate.local_track() {
+            refresh_index(db).await?;
+            if let Some(local_track_id) =
+                find_local_track(db, &local_track, &payload.config).await?
+            {
+                tracing::info!("Song of youtube video already in local library");
+                ctx.report_progress(3, Some(3)).await?;
+                return set_local_track(db, video, local_track_id).await;
+            }
+        }
+        if ctx.is_cancelled() {
+            return Ok(());
+        }
+
+        let best_match = search_best_match(&self.soulseek_context, &candidate.soulseek_track())
+            .await?
+            .ok_or_else(|| {
+                eyre!(
+                    "No SoulSeek match for '{}' by '{}'",
+                    candidate.title,
+                    candidate.artists.join(", ")
+                )
+            })?;
+        if ctx.is_cancelled() {
+            return Ok(());
+        }
+        // The temporary directory is removed once `_temp_dir` is dropped, after the import
+        let (_temp_dir, temp_file) = download_match(&self.soulseek_context, &best_match).await?;
+        ctx.report_progress(2, Some(3)).await?;
+
+        let local_track = import_track(&temp_file, &self.api_key, &self.config, db).await?;
+        ctx.report_progress(3, Some(3)).await?;
+        set_local_track(db, video, local_track.id).await
+    }
+}
+
+async fn set_local_track(
+    db: &Database,
+    video: entities::youtube_video::Model,
+    local_track_id: i64,
+) -> Result<()> {
+    let mut video: entities::youtube_video::ActiveModel = video.into();
+    video.local_track_id = Set(Some(local_track_id));
+    entities::youtube_video::Entity::update(video)
+        .exec(&db.conn)
+        .await
+        .wrap_err("Failed to link youtube video to local track")?;
+    Ok(())
+}
+
+/// Start a background task finding the song of a video and downloading it.
+pub async fn find_youtube_video_song_task(
+    tasks: &Arc<TaskManager>,
+    youtube_video_id: i64,
+    config: MatcherConfig,
+) -> Result<entities::background_task::Model> {
+    tasks
+        .submit(
+            TaskKind::YoutubeSongDownload,
+            &FindSongTaskPayload {
+                youtube_video_id,
+                config,
+            },
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(artist: &str, title: &str) -> Option<VideoSong> {
+        Some(VideoSong {
+            artist: artist.into(),
+            title: title.into(),
+        })
+    }
+
+    #[test]
+    fn test_parses_video_titles() {
+        assert_eq!(
+            parse_video_title(
+                "Queen - Bohemian Rhapsody (Remastered 2011) [Official Video]",
+                "Queen Official"
+            ),
+            song("Queen", "Bohemian Rhapsody (Remastered 2011)")
+        );
+        assert_eq!(
+            parse_video_title(
+                "Daft Punk – Get Lucky [Official Audio] (feat. Pharrell Williams)",
+                "DaftPunkVEVO"
+            ),
+            song("Daft Punk", "Get Lucky (feat. Pharrell Williams)")
+        );
+        assert_eq!(
+            parse_video_title(
+                "Nirvana - Lithium (Live at Reading 1992) | Lyrics",
+                "Nirvana"
+            ),
+            song("Nirvana", "Lithium (Live at Reading 1992)")
+        );
+        assert_eq!(
+            parse_video_title(
+                "Calvin Harris ft. Rihanna - This Is What You Came For Official Video",
+                "CalvinHarrisVEVO"
+            ),
+            song("Calvin Harris", "This Is What You Came For (feat. Rihanna)")
+        );
+        assert_eq!(
+            parse_video_title("Radiohead \"Creep\" HD", "Radiohead"),
+            song("Radiohead", "Creep")
+        );
+        // Auto-generated channels only title videos with the song
+        assert_eq!(
+            parse_video_title("Under Pressure (Remastered 2011)", "Queen - Topic"),
+            song("Queen", "Under Pressure (Remastered 2011)")
+        );
+        assert_eq!(parse_video_title("Under Pressure", "Queen"), None);
+    }
+
+    fn candidate(title: &str, disambiguation: Option<&str>, artists: &[&str]) -> SongCandidate {
+        SongCandidate {
+            title: title.into(),
+            disambiguation: disambiguation.map(Into::into),
+            artists: artists.iter().map(|artist| artist.to_string()).collect(),
+            album: None,
+            length_ms: Some(200_000),
+        }
+    }
+
+    #[test]
+    fn test_picks_recording_of_the_same_version() {
+        let config = MatcherConfig::default();
+        let candidates = vec![
+            candidate("Lithium", None, &["Nirvana"]),
+            candidate(
+                "Lithium",
+                Some("live, 1992-08-30: Reading Festival"),
+                &["Nirvana"],
+            ),
+            candidate("Lithium", None, &["Evanescence"]),
+        ];
+
+        let studio = song("Nirvana", "Lithium").unwrap();
+        assert_eq!(
+            best_candidate(&studio, &candidates, &config),
+            Some(&candidates[0])
+        );
+        let live = song("Nirvana", "Lithium (Live at Reading 1992)").unwrap();
+        assert_eq!(
+            best_candidate(&live, &candidates, &config),
+            Some(&candidates[1])
+        );
+        let unknown = song("Nirvana", "Smells Like Teen Spirit").unwrap();
+        assert_eq!(best_candidate(&unknown, &candidates, &config), None);
+    }
+}
@@ -1,3 +1,4 @@
 pub mod feed;
+pub mod find_song;
 pub mod service;
 pub mod types;
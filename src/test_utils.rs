@@ -1,8 +1,17 @@
+pub mod fake_plex;
+pub mod fake_spotify;
+
 use std::sync::Arc;
+use std::time::Duration;
 
-use sea_orm::{ConnectionTrait, Database as SeaDatabase};
+use sea_orm::{
+    ActiveModelBehavior, ActiveModelTrait, ConnectionTrait, Database as SeaDatabase, Set,
+};
 
 use crate::database::Database;
+use crate::entities;
+use crate::entities::background_task::TaskStatus;
+use crate::services::tasks::get_task;
 
 pub async fn test_db() -> Arc<Database> {
     let conn = SeaDatabase::connect("sqlite::memory:?mode=rwc")
@@ -38,5 +47,120 @@ pub async fn test_db() -> Arc<Database> {
         }
     }
 
-    Arc::new(Database { conn })
+    Arc::new(Database::from_connection(conn))
+}
+
+/// Insert a Spotify account whose access token has already expired
+pub async fn insert_spotify_account(
+    db: &Database,
+    user_id: &str,
+    refresh_token: &str,
+) -> entities::spotify_account::Model {
+    entities::spotify_account::ActiveModel {
+        user_id: Set(user_id.into()),
+        display_name: Set(None),
+        access_token: Set("at".into()),
+        refresh_token: Set(refresh_token.into()),
+        token_expiry: Set(0),
+        ..entities::spotify_account::ActiveModel::new()
+    }
+    .insert(&db.conn)
+    .await
+    .unwrap()
+}
+
+/// A local track for [`insert_track`]
+///
+/// Only the title is required. Every track gets its own album, and the file
+/// path and hash are derived from the title unless given.
+#[derive(Default)]
+pub struct TestTrack<'a> {
+    pub title: &'a str,
+    /// Album title, `"Album"` unless given
+    pub album: Option<&'a str>,
+    /// Primary album artist
+    pub album_artist: Option<&'a str>,
+    /// Primary track artist
+    pub artist: Option<&'a str>,
+    pub duration: Option<i32>,
+    pub file_path: Option<&'a str>,
+    pub musicbrainz_id: Option<&'a str>,
+    /// JSON array of ISRCs
+    pub isrcs: Option<&'a str>,
+    /// Creation and update time, now unless given
+    pub created_at: Option<i64>,
+}
+
+impl<'a> TestTrack<'a> {
+    pub fn new(title: &'a str) -> Self {
+        Self {
+            title,
+            ..Default::default()
+        }
+    }
+}
+
+/// Insert a local track with its album and artists
+pub async fn insert_track(db: &Database, track: TestTrack<'_>) -> entities::track::Model {
+    let now = chrono::Utc::now().timestamp();
+    let created_at = track.created_at.unwrap_or(now);
+    let album = entities::album::ActiveModel {
+        title: Set(track.album.unwrap_or("Album").into()),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&db.conn)
+    .await
+    .unwrap();
+    if let Some(artist) = track.album_artist {
+        let artist_id = db.upsert_artist(artist, None).await.unwrap();
+        db.add_album_artist(album.id, artist_id, true)
+            .await
+            .unwrap();
+    }
+
+    let file_path = track
+        .file_path
+        .map(String::from)
+        .unwrap_or_else(|| format!("/music/{}.flac", track.title));
+    let model = entities::track::ActiveModel {
+        album_id: Set(album.id),
+        title: Set(track.title.into()),
+        duration: Set(track.duration),
+        musicbrainz_id: Set(track.musicbrainz_id.map(String::from)),
+        isrcs: Set(track.isrcs.map(String::from)),
+        sha256: Set(format!("sha256_{}", file_path)),
+        file_path: Set(file_path),
+        created_at: Set(created_at),
+        updated_at: Set(created_at),
+        ..Default::default()
+    }
+    .insert(&db.conn)
+    .await
+    .unwrap();
+    if let Some(artist) = track.artist {
+        let artist_id = db.upsert_artist(artist, None).await.unwrap();
+        db.add_track_artist(model.id, artist_id, true)
+            .await
+            .unwrap();
+    }
+
+    model
+}
+
+/// Wait up to two seconds for a background task to reach `status`.
+pub async fn wait_for_status(
+    db: &Database,
+    task_id: i64,
+    status: TaskStatus,
+) -> entities::background_task::Model {
+    for _ in 0..200 {
+        let task = get_task(db, task_id).await.unwrap();
+        if task.status == status {
+            return task;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Task {} never reached {:?}", task_id, status);
 }
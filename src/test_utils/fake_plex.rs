@@ -0,0 +1,539 @@
+//! In-process stand-in for a Plex Media Server.
+//!
+//! Serves the library section, track, playlist and activity endpoints the app
+//! uses, seeded from `fixtures/plex.json`, so tests can exercise the real
+//! HTTP adapter, playlist sync and scan polling without a Plex server.
+//! Playlists keep their edits, and a section refresh shows up as a scan
+//! activity for the fixture's number of `/activities` polls.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use url::Url;
+
+const FIXTURE: &str = include_str!("fixtures/plex.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    machine_identifier: String,
+    token: String,
+    /// `/activities` polls a scan stays visible for
+    scan_polls: usize,
+    sections: Vec<Value>,
+    /// Track metadata as returned by `GET /library/sections/{id}/all`, with
+    /// the `librarySectionID` it belongs to
+    tracks: Vec<Value>,
+    playlists: Vec<FixturePlaylist>,
+}
+
+#[derive(Deserialize)]
+struct FixturePlaylist {
+    #[serde(rename = "ratingKey")]
+    rating_key: String,
+    title: String,
+    /// Rating keys of the playlist's tracks
+    items: Vec<String>,
+}
+
+struct FakePlaylist {
+    rating_key: String,
+    title: String,
+    /// (playlist item ID, track rating key)
+    items: Vec<(u64, String)>,
+}
+
+struct FakeScan {
+    section_id: String,
+    polls_left: usize,
+}
+
+struct FakeState {
+    machine_identifier: String,
+    token: String,
+    scan_polls: usize,
+    sections: Vec<Value>,
+    tracks: Vec<Value>,
+    playlists: Vec<FakePlaylist>,
+    scans: Vec<FakeScan>,
+    /// (section ID, folder) of every refresh, `None` for a whole section
+    refreshes: Vec<(String, Option<String>)>,
+    /// Last rating key or playlist item ID handed out
+    last_id: u64,
+}
+
+impl FakeState {
+    fn next_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn playlist_mut(&mut self, rating_key: &str) -> Option<&mut FakePlaylist> {
+        self.playlists
+            .iter_mut()
+            .find(|playlist| playlist.rating_key == rating_key)
+    }
+
+    fn track(&self, rating_key: &str) -> Option<&Value> {
+        self.tracks
+            .iter()
+            .find(|track| track["ratingKey"] == rating_key)
+    }
+}
+
+type SharedState = Arc<Mutex<FakeState>>;
+
+pub struct FakePlex {
+    base_url: Url,
+    state: SharedState,
+}
+
+impl FakePlex {
+    /// Starts the server on a random local port.
+    pub async fn start() -> Self {
+        let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let mut state = FakeState {
+            machine_identifier: fixture.machine_identifier,
+            token: fixture.token,
+            scan_polls: fixture.scan_polls,
+            sections: fixture.sections,
+            tracks: fixture.tracks,
+            playlists: Vec::new(),
+            scans: Vec::new(),
+            refreshes: Vec::new(),
+            last_id: 1000,
+        };
+        for playlist in fixture.playlists {
+            let items = playlist
+                .items
+                .into_iter()
+                .map(|rating_key| (state.next_id(), rating_key))
+                .collect();
+            state.playlists.push(FakePlaylist {
+                rating_key: playlist.rating_key,
+                title: playlist.title,
+                items,
+            });
+        }
+        let state = Arc::new(Mutex::new(state));
+
+        let router = Router::new()
+            .route("/identity", get(identity))
+            .route("/library/sections", get(sections))
+            .route("/library/sections/{id}/all", get(section_tracks))
+            .route("/library/sections/{id}/refresh", get(refresh_section))
+            .route("/activities", get(activities))
+            .route("/playlists", get(playlists).post(create_playlist))
+            .route(
+                "/playlists/{id}/items",
+                get(playlist_items).put(add_playlist_item),
+            )
+            .route(
+                "/playlists/{id}/items/{item_id}",
+                delete(remove_playlist_item),
+            )
+            .route(
+                "/playlists/{id}/items/{item_id}/move",
+                put(move_playlist_item),
+            )
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { base_url, state }
+    }
+
+    pub fn url(&self) -> Url {
+        self.base_url.clone()
+    }
+
+    /// The only access token the server accepts.
+    pub fn token(&self) -> String {
+        self.state.lock().unwrap().token.clone()
+    }
+
+    /// Rating keys of a playlist's tracks in order, `None` if there is no
+    /// playlist with that title.
+    pub fn playlist_tracks(&self, title: &str) -> Option<Vec<String>> {
+        let state = self.state.lock().unwrap();
+        state
+            .playlists
+            .iter()
+            .find(|playlist| playlist.title == title)
+            .map(|playlist| {
+                playlist
+                    .items
+                    .iter()
+                    .map(|(_, rating_key)| rating_key.clone())
+                    .collect()
+            })
+    }
+
+    /// Replaces the tracks of a playlist, as if edited in a Plex app.
+    pub fn set_playlist_tracks(&self, title: &str, rating_keys: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        let items = rating_keys
+            .iter()
+            .map(|rating_key| (state.next_id(), rating_key.to_string()))
+            .collect();
+        let playlist = state
+            .playlists
+            .iter_mut()
+            .find(|playlist| playlist.title == title)
+            .unwrap();
+        playlist.items = items;
+    }
+
+    /// Deletes a track from the library, as if its file was removed and the
+    /// trash emptied.
+    pub fn delete_track(&self, rating_key: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .tracks
+            .retain(|track| track["ratingKey"] != rating_key);
+    }
+
+    /// Every refresh requested so far, as (section ID, folder).
+    pub fn refreshes(&self) -> Vec<(String, Option<String>)> {
+        self.state.lock().unwrap().refreshes.clone()
+    }
+
+    /// Whether a scan of the section would still be reported.
+    pub fn is_scanning(&self, section_id: &str) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .scans
+            .iter()
+            .any(|scan| scan.section_id == section_id)
+    }
+}
+
+fn authorized(state: &FakeState, headers: &HeaderMap, query: &HashMap<String, String>) -> bool {
+    let token = headers
+        .get("X-Plex-Token")
+        .and_then(|token| token.to_str().ok())
+        .or(query.get("X-Plex-Token").map(String::as_str));
+    token == Some(state.token.as_str())
+}
+
+fn unauthorized() -> Response {
+    StatusCode::UNAUTHORIZED.into_response()
+}
+
+fn media_container(metadata: Vec<Value>) -> Json<Value> {
+    Json(json!({
+        "MediaContainer": { "size": metadata.len(), "Metadata": metadata }
+    }))
+}
+
+fn playlist_json(playlist: &FakePlaylist) -> Value {
+    json!({
+        "ratingKey": playlist.rating_key,
+        "key": format!("/playlists/{}/items", playlist.rating_key),
+        "title": playlist.title,
+        "playlistType": "audio",
+        "smart": false,
+        "leafCount": playlist.items.len(),
+        "summary": "",
+    })
+}
+
+async fn identity(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    Json(json!({
+        "MediaContainer": { "machineIdentifier": state.machine_identifier }
+    }))
+    .into_response()
+}
+
+async fn sections(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    Json(json!({
+        "MediaContainer": { "size": state.sections.len(), "Directory": state.sections }
+    }))
+    .into_response()
+}
+
+/// Tracks of a section, filtered by `updatedAt>>` or `lastViewedAt>>` and
+/// paged with the `X-Plex-Container-*` headers.
+async fn section_tracks(
+    State(state): State<SharedState>,
+    Path(section_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let since = |field: &str| {
+        query
+            .get(&format!("{}>>", field))
+            .and_then(|timestamp| timestamp.parse::<i64>().ok())
+    };
+    let updated_since = since("updatedAt");
+    let viewed_since = since("lastViewedAt");
+    let tracks: Vec<Value> = state
+        .tracks
+        .iter()
+        .filter(|track| track["librarySectionID"] == section_id.as_str())
+        .filter(|track| {
+            updated_since.is_none_or(|since| track["updatedAt"].as_i64() >= Some(since))
+        })
+        .filter(|track| {
+            viewed_since.is_none_or(|since| track["lastViewedAt"].as_i64() >= Some(since))
+        })
+        .cloned()
+        .collect();
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<usize>().ok())
+    };
+    let start = header("X-Plex-Container-Start").unwrap_or(0);
+    let size = header("X-Plex-Container-Size").unwrap_or(tracks.len());
+    let total = tracks.len();
+    let page: Vec<Value> = tracks.into_iter().skip(start).take(size).collect();
+    Json(json!({
+        "MediaContainer": {
+            "size": page.len(),
+            "totalSize": total,
+            "offset": start,
+            "Metadata": page,
+        }
+    }))
+    .into_response()
+}
+
+async fn refresh_section(
+    State(state): State<SharedState>,
+    Path(section_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    if !state
+        .sections
+        .iter()
+        .any(|section| section["key"] == section_id.as_str())
+    {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    state
+        .refreshes
+        .push((section_id.clone(), query.get("path").cloned()));
+    let polls_left = state.scan_polls;
+    state.scans.retain(|scan| scan.section_id != section_id);
+    state.scans.push(FakeScan {
+        section_id,
+        polls_left,
+    });
+    StatusCode::OK.into_response()
+}
+
+/// Running scans; every poll brings them closer to finishing.
+async fn activities(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let activities: Vec<Value> = state
+        .scans
+        .iter()
+        .map(|scan| {
+            json!({
+                "uuid": format!("scan-{}", scan.section_id),
+                "type": "library.update.section",
+                "title": "Scanning",
+                "progress": 50.0,
+                "Context": { "librarySectionID": scan.section_id },
+            })
+        })
+        .collect();
+    for scan in &mut state.scans {
+        scan.polls_left = scan.polls_left.saturating_sub(1);
+    }
+    state.scans.retain(|scan| scan.polls_left > 0);
+    Json(json!({ "MediaContainer": { "Activity": activities } })).into_response()
+}
+
+async fn playlists(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    media_container(state.playlists.iter().map(playlist_json).collect()).into_response()
+}
+
+/// Rating key of the track a `server://` item URI points at, if it is on
+/// this server.
+fn track_of_uri(state: &FakeState, uri: &str) -> Option<String> {
+    let prefix = format!(
+        "server://{}/com.plexapp.plugins.library/library/metadata/",
+        state.machine_identifier
+    );
+    let rating_key = uri.strip_prefix(&prefix)?;
+    state.track(rating_key)?;
+    Some(rating_key.to_string())
+}
+
+async fn create_playlist(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let Some(title) = query.get("title") else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let mut items = Vec::new();
+    if let Some(uri) = query.get("uri") {
+        let Some(rating_key) = track_of_uri(&state, uri) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        items.push((state.next_id(), rating_key));
+    }
+    let playlist = FakePlaylist {
+        rating_key: state.next_id().to_string(),
+        title: title.clone(),
+        items,
+    };
+    let response = media_container(vec![playlist_json(&playlist)]);
+    state.playlists.push(playlist);
+    response.into_response()
+}
+
+async fn playlist_items(
+    State(state): State<SharedState>,
+    Path(playlist_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let Some(playlist) = state.playlist_mut(&playlist_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let items = playlist.items.clone();
+    let metadata = items
+        .iter()
+        .filter_map(|(item_id, rating_key)| {
+            let mut track = state.track(rating_key)?.clone();
+            track["playlistItemID"] = json!(item_id);
+            Some(track)
+        })
+        .collect();
+    media_container(metadata).into_response()
+}
+
+async fn add_playlist_item(
+    State(state): State<SharedState>,
+    Path(playlist_id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let Some(rating_key) = query.get("uri").and_then(|uri| track_of_uri(&state, uri)) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let item_id = state.next_id();
+    let Some(playlist) = state.playlist_mut(&playlist_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    playlist.items.push((item_id, rating_key));
+    StatusCode::OK.into_response()
+}
+
+async fn remove_playlist_item(
+    State(state): State<SharedState>,
+    Path((playlist_id, item_id)): Path<(String, u64)>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let Some(playlist) = state.playlist_mut(&playlist_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(index) = playlist.items.iter().position(|(id, _)| *id == item_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    playlist.items.remove(index);
+    StatusCode::OK.into_response()
+}
+
+async fn move_playlist_item(
+    State(state): State<SharedState>,
+    Path((playlist_id, item_id)): Path<(String, u64)>,
+    headers: HeaderMap,
+    Query(query): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers, &query) {
+        return unauthorized();
+    }
+    let Some(playlist) = state.playlist_mut(&playlist_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Some(index) = playlist.items.iter().position(|(id, _)| *id == item_id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let item = playlist.items.remove(index);
+    let to = match query.get("after").map(|after| after.parse::<u64>()) {
+        None => 0,
+        Some(Ok(after)) => match playlist.items.iter().position(|(id, _)| *id == after) {
+            Some(after_index) => after_index + 1,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+        Some(Err(_)) => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    playlist.items.insert(to, item);
+    StatusCode::OK.into_response()
+}
@@ -0,0 +1,406 @@
+//! In-process stand-in for the Spotify Web API and accounts service.
+//!
+//! Serves token refreshes and the playlist, search and audio features
+//! endpoints the app reads, seeded from `fixtures/spotify.json`, so tests can
+//! exercise the real HTTP client, token refresh and sync code without network
+//! access. Responses are paged with `next` links like Spotify's, using the
+//! fixture's small page size.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Form, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use spotify_rs::RedirectUrl;
+use url::Url;
+
+use crate::services::spotify::client::SpotifyApiCredentials;
+
+const FIXTURE: &str = include_str!("fixtures/spotify.json");
+
+#[derive(Deserialize)]
+struct Fixture {
+    page_size: usize,
+    accounts: Vec<FixtureAccount>,
+    /// Searchable tracks that aren't in any playlist
+    catalog: Vec<Value>,
+    audio_features: Vec<Value>,
+    playlists: Vec<FixturePlaylist>,
+}
+
+#[derive(Deserialize)]
+struct FixtureAccount {
+    user_id: String,
+    refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct FixturePlaylist {
+    owner: String,
+    /// Playlist object as returned by `GET /me/playlists`
+    playlist: Value,
+    /// Raw playlist items as returned by `GET /playlists/{id}/tracks`
+    items: Vec<Value>,
+}
+
+struct FakeState {
+    base_url: Url,
+    page_size: usize,
+    catalog: Vec<Value>,
+    audio_features: Vec<Value>,
+    playlists: Vec<FixturePlaylist>,
+    /// Valid refresh tokens, mapped to their user
+    refresh_tokens: HashMap<String, String>,
+    /// Issued access tokens, mapped to their user
+    access_tokens: HashMap<String, String>,
+    issued: usize,
+    /// Answer audio features requests with 403, as Spotify does for apps
+    /// created after the endpoint was deprecated
+    audio_features_forbidden: bool,
+}
+
+type SharedState = Arc<Mutex<FakeState>>;
+
+pub struct FakeSpotify {
+    base_url: Url,
+    state: SharedState,
+}
+
+impl FakeSpotify {
+    /// Starts the server on a random local port.
+    pub async fn start() -> Self {
+        let fixture: Fixture = serde_json::from_str(FIXTURE).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+        let state = Arc::new(Mutex::new(FakeState {
+            base_url: base_url.clone(),
+            page_size: fixture.page_size,
+            catalog: fixture.catalog,
+            audio_features: fixture.audio_features,
+            playlists: fixture.playlists,
+            refresh_tokens: fixture
+                .accounts
+                .into_iter()
+                .map(|account| (account.refresh_token, account.user_id))
+                .collect(),
+            access_tokens: HashMap::new(),
+            issued: 0,
+            audio_features_forbidden: false,
+        }));
+
+        let router = Router::new()
+            .route("/api/token", post(token))
+            .route("/v1/me/playlists", get(current_user_playlists))
+            .route("/v1/playlists/{id}/tracks", get(playlist_tracks))
+            .route("/v1/search", get(search))
+            .route("/v1/audio-features", get(audio_features))
+            .with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { base_url, state }
+    }
+
+    /// Credentials pointing token refreshes and API requests at this server.
+    pub fn credentials(&self) -> SpotifyApiCredentials {
+        SpotifyApiCredentials::new(
+            "client-id".into(),
+            "client-secret".into(),
+            RedirectUrl::new("http://localhost/callback".into()).unwrap(),
+        )
+        .with_base_urls(self.api_base_url(), self.base_url.clone())
+    }
+
+    pub fn api_base_url(&self) -> Url {
+        self.base_url.join("v1/").unwrap()
+    }
+
+    /// Revokes every refresh token of the user, as if they removed the app's
+    /// access from their Spotify account.
+    pub fn revoke(&self, user_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.refresh_tokens.retain(|_, user| user != user_id);
+        state.access_tokens.retain(|_, user| user != user_id);
+    }
+
+    /// Refuses audio features requests from now on.
+    pub fn forbid_audio_features(&self) {
+        self.state.lock().unwrap().audio_features_forbidden = true;
+    }
+
+    /// Expires every access token issued so far.
+    pub fn expire_access_tokens(&self) {
+        self.state.lock().unwrap().access_tokens.clear();
+    }
+}
+
+fn error(status: StatusCode, body: Value) -> Response {
+    (status, Json(body)).into_response()
+}
+
+async fn token(
+    State(state): State<SharedState>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if form.get("grant_type").map(String::as_str) != Some("refresh_token") {
+        return error(
+            StatusCode::BAD_REQUEST,
+            json!({ "error": "unsupported_grant_type" }),
+        );
+    }
+    let Some(user_id) = form
+        .get("refresh_token")
+        .and_then(|refresh_token| state.refresh_tokens.remove(refresh_token))
+    else {
+        return error(
+            StatusCode::BAD_REQUEST,
+            json!({ "error": "invalid_grant", "error_description": "Refresh token revoked" }),
+        );
+    };
+
+    // Rotate the refresh token on every refresh, which Spotify may do
+    state.issued += 1;
+    let access_token = format!("access-{}-{}", user_id, state.issued);
+    let refresh_token = format!("refresh-{}-{}", user_id, state.issued);
+    state
+        .access_tokens
+        .insert(access_token.clone(), user_id.clone());
+    state.refresh_tokens.insert(refresh_token.clone(), user_id);
+
+    Json(json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": 3600,
+        "refresh_token": refresh_token,
+    }))
+    .into_response()
+}
+
+fn authorized_user(state: &FakeState, headers: &HeaderMap) -> Option<String> {
+    let token = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?;
+    state.access_tokens.get(token).cloned()
+}
+
+fn unauthorized() -> Response {
+    error(
+        StatusCode::UNAUTHORIZED,
+        json!({ "error": { "status": 401, "message": "Invalid access token" } }),
+    )
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+/// A Spotify paging object for `items`, with a `next` link while more remain.
+fn page(state: &FakeState, path: &str, items: Vec<Value>, query: PageQuery) -> Value {
+    let limit = query
+        .limit
+        .unwrap_or(state.page_size)
+        .min(state.page_size)
+        .max(1);
+    let offset = query.offset.unwrap_or(0);
+    let total = items.len();
+    let next = (offset + limit < total).then(|| {
+        let mut next = state.base_url.join(path).unwrap();
+        next.query_pairs_mut()
+            .append_pair("limit", &limit.to_string())
+            .append_pair("offset", &(offset + limit).to_string());
+        next.to_string()
+    });
+
+    json!({
+        "items": items.into_iter().skip(offset).take(limit).collect::<Vec<_>>(),
+        "limit": limit,
+        "offset": offset,
+        "total": total,
+        "next": next,
+    })
+}
+
+async fn current_user_playlists(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<PageQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    let Some(user_id) = authorized_user(&state, &headers) else {
+        return unauthorized();
+    };
+    let playlists = state
+        .playlists
+        .iter()
+        .filter(|playlist| playlist.owner == user_id)
+        .map(|playlist| playlist.playlist.clone())
+        .collect();
+    Json(page(&state, "v1/me/playlists", playlists, query)).into_response()
+}
+
+async fn playlist_tracks(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<PageQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if authorized_user(&state, &headers).is_none() {
+        return unauthorized();
+    }
+    let Some(playlist) = state
+        .playlists
+        .iter()
+        .find(|playlist| playlist.playlist["id"] == id.as_str())
+    else {
+        return error(
+            StatusCode::NOT_FOUND,
+            json!({ "error": { "status": 404, "message": "Resource not found" } }),
+        );
+    };
+    let items = playlist.items.clone();
+    let path = format!("v1/playlists/{}/tracks", id);
+    Json(page(&state, &path, items, query)).into_response()
+}
+
+/// Every track in the catalog and in playlists, once each.
+fn searchable_tracks(state: &FakeState) -> Vec<&Value> {
+    let playlist_tracks = state
+        .playlists
+        .iter()
+        .flat_map(|playlist| &playlist.items)
+        .map(|item| &item["track"])
+        .filter(|track| track["type"] == "track" && track["id"].is_string());
+
+    let mut seen = Vec::new();
+    state
+        .catalog
+        .iter()
+        .chain(playlist_tracks)
+        .filter(|track| {
+            let new = !seen.contains(&&track["id"]);
+            seen.push(&track["id"]);
+            new
+        })
+        .collect()
+}
+
+/// Whether the track matches a query of `isrc:`, `track:` and `artist:`
+/// filters and free words. Quoted filter values may contain spaces.
+fn matches_query(track: &Value, query: &str) -> bool {
+    let contains = |value: &Value, needle: &str| {
+        value
+            .as_str()
+            .is_some_and(|value| value.to_lowercase().contains(&needle.to_lowercase()))
+    };
+    let any_artist = |needle: &str| {
+        track["artists"].as_array().is_some_and(|artists| {
+            artists
+                .iter()
+                .any(|artist| contains(&artist["name"], needle))
+        })
+    };
+
+    let mut rest = query.trim();
+    while !rest.is_empty() {
+        let (field, value, remaining) = match rest.split_once(':') {
+            Some((field, value)) if !field.contains(' ') => {
+                let (value, remaining) = match value.strip_prefix('"') {
+                    Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                    None => value.split_once(' ').unwrap_or((value, "")),
+                };
+                (Some(field), value, remaining)
+            }
+            _ => {
+                let (word, remaining) = rest.split_once(' ').unwrap_or((rest, ""));
+                (None, word, remaining)
+            }
+        };
+        let matched = match field {
+            Some("isrc") => track["external_ids"]["isrc"] == value,
+            Some("track") => contains(&track["name"], value),
+            Some("artist") => any_artist(value),
+            Some(_) => false,
+            None => contains(&track["name"], value) || any_artist(value),
+        };
+        if !matched {
+            return false;
+        }
+        rest = remaining.trim();
+    }
+    true
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    q: String,
+    limit: Option<usize>,
+}
+
+async fn search(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<SearchQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if authorized_user(&state, &headers).is_none() {
+        return unauthorized();
+    }
+    let tracks = searchable_tracks(&state)
+        .into_iter()
+        .filter(|track| matches_query(track, &query.q))
+        .cloned()
+        .collect();
+    // The app only reads the first page of search results
+    let query = PageQuery {
+        limit: query.limit,
+        offset: None,
+    };
+    Json(json!({ "tracks": page(&state, "v1/search", tracks, query) })).into_response()
+}
+
+#[derive(Deserialize)]
+struct AudioFeaturesQuery {
+    ids: String,
+}
+
+async fn audio_features(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<AudioFeaturesQuery>,
+) -> Response {
+    let state = state.lock().unwrap();
+    if authorized_user(&state, &headers).is_none() {
+        return unauthorized();
+    }
+    if state.audio_features_forbidden {
+        return error(
+            StatusCode::FORBIDDEN,
+            json!({ "error": { "status": 403, "message": "Forbidden" } }),
+        );
+    }
+    // Unknown IDs get null entries, like Spotify
+    let features: Vec<Value> = query
+        .ids
+        .split(',')
+        .map(|id| {
+            state
+                .audio_features
+                .iter()
+                .find(|features| features["id"] == id)
+                .cloned()
+                .unwrap_or(Value::Null)
+        })
+        .collect();
+    Json(json!({ "audio_features": features })).into_response()
+}